@@ -0,0 +1,343 @@
+# Window
+
+app-title = Slark
+window-title = { $name } - Slark
+
+# Menus
+
+menu-file = File
+menu-new-window = New Window
+menu-close-window = Close Window
+menu-exit = Exit
+menu-view = View
+menu-dark-theme = Dark Theme
+menu-arrange-tiles = Arrange tiles
+menu-arrange-grid = Arrange in a grid
+menu-restore-removed = Restore last removed
+menu-replace-source = Replace source…
+menu-export-crop = Export crop…
+menu-export-frames = Export frames…
+menu-expand-filmstrip = Expand into a filmstrip
+menu-collapse-filmstrip = Collapse the filmstrip
+menu-time-remap = Playback speed
+time-remap-linear = Steady
+time-remap-ease-in = Ease in, slow at first
+time-remap-ease-out = Ease out, slow at the end
+
+# Shared dialog buttons
+
+dialog-ok = OK
+dialog-cancel = Cancel
+dialog-close = Close
+
+# Unsaved changes
+
+unsaved-save = Save
+unsaved-discard = Discard changes
+unsaved-open = The current project has unsaved changes. Open { $path } anyway?
+unsaved-new = The current project has unsaved changes. Start a new project anyway?
+
+# Adding directories
+
+directory-confirm = { $path } contains { $count } images. Add them all?
+directory-add-all = Add all
+large-image-confirm = { $path } is { $width } × { $height } pixels, which takes a lot of memory to show in full.
+large-image-full = Load in full
+large-image-proxy = Load at { $percent }%
+
+# File dialogs
+
+file-add-images = Add images
+file-export-layout = Export layout
+file-apply-layout = Apply layout
+file-import-project = Import project
+file-relink-image = Relink image
+file-export-frame = Export frame
+file-export-crop = Export crop
+file-series-directory = Save the screenshots into
+file-frames-directory = Export the frames into
+
+# Welcome panel
+
+welcome-hint = Ctrl+I to add images, Ctrl+O to open a project, Ctrl+N for new
+welcome-add-images = Add images…
+welcome-open-project = Open project…
+welcome-new-project = New project
+welcome-reopen = Reopen { $name }
+welcome-reopen-session = Reopen the last session
+welcome-start-paused = Animations start out paused, press Space to play them all
+
+# Name dialog
+
+name-hint = Name (leave empty to use the file name)
+
+# Summary dialog
+
+summary-images = Images: { $count }
+summary-decoded-memory = Decoded memory: { $size }
+summary-size-on-disk = Size on disk: { $size }
+summary-missing-files = Missing files: { $count }
+summary-stale-files = Changed since loading: { $count }
+summary-unknown-resolution = unknown
+summary-image = { $name } - { $resolution }, { $frames ->
+        [one] { $frames } frame
+       *[other] { $frames } frames
+    }, { $decoded } decoded, { $disk } on disk
+summary-animation = { $decoded } of { $total } frames decoded, { $seconds } s per loop
+summary-missing-image = { $name } - missing { $path }
+summary-stale-image = { $path } has changed since it was loaded, reload it to see the changes
+summary-relink = Relink…
+summary-embedded = the pixels embedded in the project
+embedded-image = Embedded image { $id }
+
+# Canvas dialog
+
+canvas-fixed = Use a fixed canvas
+canvas-width = Width
+canvas-height = Height
+canvas-background = Background
+canvas-invalid-size = Width and height must be positive numbers.
+canvas-invalid-background = Background must be #RRGGBB or #RRGGBBAA.
+
+# Image defaults dialog
+
+image-defaults-scaling = Scaling
+image-defaults-scaling-setting = As in the settings
+image-defaults-smooth = Smooth
+image-defaults-nearest = Nearest neighbor
+image-defaults-zoom = Zoom %
+image-defaults-checkerboard = Checkerboard behind transparent pixels
+image-defaults-paused = Start paused
+image-defaults-invalid-zoom = Zoom must be a positive percentage.
+image-defaults-apply = Apply to the selected image
+
+# Toasts
+
+toast-saved = Saved { $path }
+toast-save-failed = Failed to save { $path }: { $error }
+toast-missing-file = Missing image file: { $path }
+toast-missing-embedded = Missing the pixels of the embedded image { $name }
+toast-restored = Restored { $name }
+toast-duplicate-added = { $name } was already in the project, so another copy was added next to it
+toast-trash-empty = There are no removed images to restore
+toast-proxy-export = { $name } is loaded at { $percent }%, so the export has that resolution too
+toast-unsupported-extension = Unsupported file extension: { $extension }
+toast-no-extension = Slark needs a proper file extension for format detection: { $path }
+toast-open-failed = Failed to open { $path }: { $error }
+toast-no-images-in-directory = No supported images in { $path }
+toast-directory-added = Added { $count ->
+        [one] { $count } image
+       *[other] { $count } images
+    } from { $path }, skipped { $skipped ->
+        [one] { $skipped } other file
+       *[other] { $skipped } other files
+    }
+toast-layout-skipped = Skipped { $count ->
+        [one] { $count } layout entry
+       *[other] { $count } layout entries
+    }: { $reasons }
+toast-layout-failed = Couldn't apply the layout. { $error }
+toast-decode-failed = Failed to decode { $path }: { $error }
+toast-unsupported-png-color = Unsupported PNG color type { $color_type } in { $path }
+toast-reloaded = Reloaded { $path }
+toast-decode-stalled = Gave up on decoding { $path }, it produced nothing new for { $seconds } seconds
+view-decoding-stalled = Decoding stalled
+view-decoding-failed = Decoding failed: { $error }
+view-open-failed = Couldn't open this image
+toast-upload-failed = Failed to show { $path }: { $error }
+view-upload-failed = Too large to show
+toast-frame-exported = Exported the frame to { $path }
+toast-frame-export-failed = Failed to export the frame to { $path }: { $error }
+
+# Filmstrips
+
+filmstrip-frame = Frame { $index }
+filmstrip-group = Filmstrip of { $name }
+toast-filmstrip-needs-animation = Only animations with more than one decoded frame can be expanded into a filmstrip
+
+# Exporting crops
+
+crop-export = Export the { $width } x { $height } pixels shown of the current frame?
+crop-save = Save to file…
+crop-copy = Copy to clipboard
+toast-crop-copied = Copied the crop to the clipboard
+toast-crop-copy-failed = Failed to copy the crop: { $error }
+
+# Exporting frames
+
+frames-template = File names of the frames:
+frames-template-hint = Use {"{name}"}, {"{index}"} counting from 1, and {"{time}"} in milliseconds. Numbers can be padded with zeros, e.g. {"{index:04}"}.
+frames-preview = The first file: { $file }
+toast-frames-exported = Exported { $count ->
+    [one] one frame
+   *[other] { $count } frames
+} to { $path }
+template-unclosed = A token is missing its closing brace
+template-stray-brace = A closing brace has no opening one, write two of them for a brace in the name
+template-illegal-char = File names can't contain { $char }
+template-unknown-token = Unknown token { $token }
+template-no-number = Include {"{index}"} or {"{time}"}, so that every frame gets a name of its own
+template-bad-extension = The file names must end with .png or .jpg
+
+# Help overlay
+
+category-images = Images
+category-project = Project
+category-layout = Layout
+category-window = Window
+action-remove-image = Remove the selected image
+action-restore-removed = Restore the most recently removed image
+action-rename-image = Rename the selected image
+action-reload-image = Reload the selected image from disk
+action-replace-source = Replace the file of the selected image
+action-export-frame = Export the current frame of the selected image
+action-raise-layer = Move the selected image up a layer
+action-lower-layer = Move the selected image down a layer
+action-raise-to-top = Move the selected image to the top of its group
+action-lower-to-bottom = Move the selected image to the bottom of its group
+action-toggle-pin = Pin or unpin the selected image
+action-toggle-pause = Pause or resume all animations
+action-previous-frame = Pause and step the selected image back a frame
+action-next-frame = Pause and step the selected image forward a frame
+action-toggle-diff = Show how each frame of the selected image differs from the previous one
+action-link-views = Play the selected animation in step with the one below it
+action-unlink-views = Let the animations linked with the selected one play on their own
+action-toggle-solo = Show only the selected image, or all of them again
+action-inspect-gif = Inspect the palette and frames of the selected GIF
+action-show-metadata = Show the EXIF, XMP and text metadata of the selected image
+action-toggle-labels = Show or hide the image names
+action-toggle-measure = Measure distances, Escape to stop
+action-eyedropper = Pick colors, Escape to stop
+action-pick-color-key = Click a color to make it transparent in that image
+action-toggle-crosshair = Show or hide the crosshair at the cursor
+action-toggle-compare = Compare the selected image with the one below it
+action-arrange-tiles = Arrange the images into tiles
+action-arrange-grid = Arrange the images into a grid of equal cells
+action-fit-width = Fit the selected image to the window width
+action-fit-height = Fit the selected image to the window height
+action-frame-selected = Zoom and pan to the selected image
+action-toggle-physical = Show the images at their physical size relative to each other
+action-undo-arrangement = Undo the last arrangement
+action-new-project = Start a new project
+action-open-project = Open a project
+action-save-project = Save the project
+action-import-project = Import another project
+action-add-images = Add images
+action-quick-open = Quickly add an image from the project directory
+action-capture-region = Capture a region of the screen into the project
+action-show-summary = Show the project summary
+action-canvas-settings = Canvas settings
+action-image-defaults = Defaults for new images
+action-export-layout = Export the layout
+action-apply-layout = Apply a layout
+action-toggle-help = Show this help
+action-toggle-console = Toggle the debug console
+action-toggle-timing = Log how accurately the animations keep their timing
+action-toggle-series = Start or stop saving a screenshot of the window every few seconds
+
+# Tile arrangement
+
+toast-arranged = Arranged by { $order }
+toast-arranged-grid = Arranged into { $columns ->
+        [one] { $columns } column
+       *[other] { $columns } columns
+    }
+sort-add-order = add order
+sort-name = file name
+sort-area = size, largest first
+sort-layer = layer, topmost first
+toast-physical-on = Showing the images at their physical size, by the pixel density of each file
+toast-physical-off = Showing the images pixel for pixel
+
+# Playback
+
+toast-paused = Animations paused
+toast-resumed = Animations resumed
+toast-diff-needs-pause = Pause the animations with Space to see the frame differences
+toast-timing-on = Logging the frame timing of every full loop to the debug console
+toast-timing-off = Stopped logging the frame timing
+toast-linked = { $count } animations now play in step
+toast-link-needs-two = Select an animation that has another visible animation below it to link them
+toast-unlinked = The linked animations play on their own again
+toast-not-linked = The selected image isn't linked with any other
+
+# Zooming
+
+toast-zoom-max = Zoomed in as far as it goes, { $percent }%
+toast-zoom-min = Zoomed out as far as it goes, { $percent }%
+
+# Soloing
+
+solo-badge = SOLO
+
+# Measuring
+
+toast-measure-on = Drag to measure distances, press Escape to stop
+toast-measure-off = Stopped measuring
+measure-length = { $length } px
+measure-length-native = { $length } px, { $native } px in the image
+
+# Eyedropper
+
+toast-eyedropper-on = Click to copy the color under the cursor, press Escape to stop
+toast-eyedropper-off = Stopped picking colors
+toast-color-copied = Copied { $color } to the clipboard
+
+# Color keying
+
+toast-keying-on = Click a color to make it transparent in that image, press Escape to cancel
+toast-keying-off = Stopped picking a color to make transparent
+toast-color-keyed = { $color } is now transparent, right click the image to change the tolerance
+menu-color-key = Transparent { $color }
+menu-key-tolerance = Tolerance { $tolerance }
+menu-remove-color-key = Show all colors again
+
+# Comparing
+
+toast-compare-on = Drag the divider to compare, press Escape to stop
+toast-compare-needs-two = Select an image that has another visible image below it to compare them
+
+# Capturing
+
+capture-hint = Drag to capture a region, press Escape to cancel
+toast-capture-failed = Failed to capture the screen: { $error }
+
+# Screenshot series
+
+series-badge = ● REC { $count }
+toast-series-started = Saving a screenshot every { $seconds ->
+        [one] second
+       *[other] { $seconds } seconds
+    } into { $path }, press Ctrl+F9 to stop
+toast-series-stopped = Saved { $count ->
+        [one] { $count } screenshot
+       *[other] { $count } screenshots
+    } into { $path }
+toast-series-failed = Failed to save the screenshot { $path }: { $error }
+
+# GIF inspector
+
+inspector-global-palette = Global palette of { $count } colors
+inspector-no-global-palette = No global palette
+inspector-hint = Click a frame to pause on it
+inspector-frame = { $index }. { $width } x { $height } at { $left }, { $top }, { $palette }, { $disposal }, { $delay } ms
+inspector-local-palette = local palette of { $count } colors
+inspector-shared-palette = global palette
+disposal-unspecified = no disposal
+disposal-keep = kept
+disposal-background = cleared to the background
+disposal-previous = restored to the previous frame
+toast-inspector-needs-gif = Only GIF images can be inspected
+
+# Metadata
+
+metadata-empty = This image has no metadata, or it hasn't been decoded yet.
+metadata-copy = Copy
+metadata-compressed = (compressed)
+
+# Quick open
+
+quick-open-prompt = Type to find an image in { $path }
+quick-open-searching = Looking for images…
+quick-open-no-matches = No images match
+toast-quick-open-needs-project = Save the project first, so that there's a directory to add images from
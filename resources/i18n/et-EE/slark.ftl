@@ -0,0 +1,340 @@
+# Aken
+
+app-title = Slark
+window-title = { $name } - Slark
+
+# Menüüd
+
+menu-file = Fail
+menu-new-window = Uus aken
+menu-close-window = Sulge aken
+menu-exit = Välju
+menu-view = Vaade
+menu-dark-theme = Tume teema
+menu-arrange-tiles = Paiguta kõrvuti
+menu-arrange-grid = Paiguta ruudustikku
+menu-restore-removed = Taasta viimati eemaldatud
+menu-replace-source = Asenda allikas…
+menu-export-crop = Ekspordi väljalõige…
+menu-export-frames = Ekspordi kaadrid…
+menu-expand-filmstrip = Laota filmilindiks
+menu-collapse-filmstrip = Eemalda filmilint
+menu-time-remap = Taasesituse kiirus
+time-remap-linear = Ühtlane
+time-remap-ease-in = Kiirenev, alguses aeglane
+time-remap-ease-out = Aeglustuv, lõpus aeglane
+
+# Dialoogide ühised nupud
+
+dialog-ok = OK
+dialog-cancel = Loobu
+dialog-close = Sulge
+
+# Salvestamata muudatused
+
+unsaved-save = Salvesta
+unsaved-discard = Loobu muudatustest
+unsaved-open = Praegusel projektil on salvestamata muudatusi. Kas avada { $path } ikkagi?
+unsaved-new = Praegusel projektil on salvestamata muudatusi. Kas alustada ikkagi uut projekti?
+
+# Kaustade lisamine
+
+directory-confirm = Kaustas { $path } on { $count } pilti. Kas lisada need kõik?
+directory-add-all = Lisa kõik
+large-image-confirm = { $path } on { $width } × { $height } pikslit, mille täielikuks näitamiseks kulub palju mälu.
+large-image-full = Laadi täielikult
+large-image-proxy = Laadi { $percent }% suurusena
+
+# Failidialoogid
+
+file-add-images = Lisa pilte
+file-export-layout = Ekspordi paigutus
+file-apply-layout = Rakenda paigutus
+file-import-project = Impordi projekt
+file-relink-image = Seo pilt uuesti
+file-export-frame = Ekspordi kaader
+file-export-crop = Ekspordi väljalõige
+file-series-directory = Salvesta ekraanipildid kausta
+file-frames-directory = Ekspordi kaadrid kausta
+
+# Tervituspaneel
+
+welcome-hint = Ctrl+I piltide lisamiseks, Ctrl+O projekti avamiseks, Ctrl+N uue jaoks
+welcome-add-images = Lisa pilte…
+welcome-open-project = Ava projekt…
+welcome-new-project = Uus projekt
+welcome-reopen = Ava uuesti { $name }
+welcome-reopen-session = Ava eelmine sessioon uuesti
+welcome-start-paused = Animatsioonid on alguses peatatud, nende kõigi käivitamiseks vajuta tühikut
+
+# Nimedialoog
+
+name-hint = Nimi (jäta tühjaks, et kasutada faili nime)
+
+# Kokkuvõtte dialoog
+
+summary-images = Pilte: { $count }
+summary-decoded-memory = Dekodeeritud mälu: { $size }
+summary-size-on-disk = Suurus kettal: { $size }
+summary-missing-files = Puuduvaid faile: { $count }
+summary-stale-files = Pärast laadimist muutunud: { $count }
+summary-unknown-resolution = teadmata
+summary-image = { $name } - { $resolution }, { $frames ->
+        [one] { $frames } kaader
+       *[other] { $frames } kaadrit
+    }, { $decoded } dekodeeritud, { $disk } kettal
+summary-animation = dekodeeritud { $decoded } kaadrit { $total }-st, üks ring kestab { $seconds } s
+summary-missing-image = { $name } - puudub { $path }
+summary-stale-image = { $path } on pärast laadimist muutunud, muudatuste nägemiseks laadi see uuesti
+summary-relink = Seo uuesti…
+summary-embedded = projekti manustatud pikslid
+embedded-image = Manustatud pilt { $id }
+
+# Lõuendi dialoog
+
+canvas-fixed = Kasuta fikseeritud lõuendit
+canvas-width = Laius
+canvas-height = Kõrgus
+canvas-background = Taust
+canvas-invalid-size = Laius ja kõrgus peavad olema positiivsed arvud.
+canvas-invalid-background = Taust peab olema kujul #RRGGBB või #RRGGBBAA.
+
+# Uute piltide vaikeväärtuste dialoog
+
+image-defaults-scaling = Skaleerimine
+image-defaults-scaling-setting = Nagu seadetes
+image-defaults-smooth = Sujuv
+image-defaults-nearest = Lähim naaber
+image-defaults-zoom = Suurendus %
+image-defaults-checkerboard = Malelaua muster läbipaistvate pikslite taga
+image-defaults-paused = Alusta peatatuna
+image-defaults-invalid-zoom = Suurendus peab olema positiivne protsent.
+image-defaults-apply = Rakenda valitud pildile
+
+# Teated
+
+toast-saved = Salvestatud { $path }
+toast-save-failed = Faili { $path } salvestamine ebaõnnestus: { $error }
+toast-missing-file = Pildifail puudub: { $path }
+toast-missing-embedded = Manustatud pildi { $name } pikslid puuduvad
+toast-restored = { $name } taastatud
+toast-duplicate-added = { $name } oli juba projektis, seega lisati selle kõrvale veel üks koopia
+toast-trash-empty = Eemaldatud pilte, mida taastada, pole
+toast-proxy-export = { $name } on laaditud { $percent }% suurusena, seega on eksport samuti selles lahutusvõimes
+toast-unsupported-extension = Toetamata faililaiend: { $extension }
+toast-no-extension = Slark vajab vormingu tuvastamiseks korralikku faililaiendit: { $path }
+toast-open-failed = Faili { $path } avamine ebaõnnestus: { $error }
+toast-no-images-in-directory = Kaustas { $path } pole toetatud pilte
+toast-directory-added = Kaustast { $path } lisati { $count ->
+        [one] { $count } pilt
+       *[other] { $count } pilti
+    }, vahele jäeti { $skipped ->
+        [one] { $skipped } muu fail
+       *[other] { $skipped } muud faili
+    }
+toast-layout-skipped = Vahele jäeti { $count ->
+        [one] { $count } paigutuse kirje
+       *[other] { $count } paigutuse kirjet
+    }: { $reasons }
+toast-layout-failed = Paigutust ei õnnestunud rakendada. { $error }
+toast-decode-failed = Faili { $path } dekodeerimine ebaõnnestus: { $error }
+toast-unsupported-png-color = Toetamata PNG värvitüüp { $color_type } failis { $path }
+toast-reloaded = Laaditi uuesti { $path }
+toast-decode-stalled = Faili { $path } dekodeerimisest loobuti, { $seconds } sekundi jooksul ei tulnud midagi uut
+view-decoding-stalled = Dekodeerimine takerdus
+view-decoding-failed = Dekodeerimine ebaõnnestus: { $error }
+view-open-failed = Seda pilti ei õnnestunud avada
+toast-upload-failed = Faili { $path } kuvamine ebaõnnestus: { $error }
+view-upload-failed = Kuvamiseks liiga suur
+toast-frame-exported = Kaader eksporditi faili { $path }
+toast-frame-export-failed = Kaadri eksportimine faili { $path } ebaõnnestus: { $error }
+
+# Filmilindid
+
+filmstrip-frame = Kaader { $index }
+filmstrip-group = { $name } filmilindina
+toast-filmstrip-needs-animation = Filmilindiks saab laotada ainult animatsioone, millel on dekodeeritud rohkem kui üks kaader
+
+# Väljalõigete eksportimine
+
+crop-export = Kas eksportida praeguse kaadri nähtavad { $width } x { $height } pikslit?
+crop-save = Salvesta faili…
+crop-copy = Kopeeri lõikelauale
+toast-crop-copied = Väljalõige kopeeriti lõikelauale
+toast-crop-copy-failed = Väljalõike kopeerimine ebaõnnestus: { $error }
+
+# Kaadrite eksportimine
+
+frames-template = Kaadrite failinimed:
+frames-template-hint = Kasuta {"{name}"}, ühest loendavat {"{index}"} ja millisekundites {"{time}"}. Arve saab nullidega täita, näiteks {"{index:04}"}.
+frames-preview = Esimene fail: { $file }
+toast-frames-exported = { $count ->
+    [one] Üks kaader eksporditi
+   *[other] { $count } kaadrit eksporditi
+} kausta { $path }
+template-unclosed = Märgendil puudub sulgev looksulg
+template-stray-brace = Sulgeval looksulul puudub avav pool, looksulu jaoks nimes kirjuta kaks
+template-illegal-char = Failinimes ei saa olla { $char }
+template-unknown-token = Tundmatu märgend { $token }
+template-no-number = Lisa {"{index}"} või {"{time}"}, et igal kaadril oleks oma nimi
+template-bad-extension = Failinimed peavad lõppema laiendiga .png või .jpg
+
+# Abi
+
+category-images = Pildid
+category-project = Projekt
+category-layout = Paigutus
+category-window = Aken
+action-remove-image = Eemalda valitud pilt
+action-restore-removed = Taasta viimati eemaldatud pilt
+action-rename-image = Nimeta valitud pilt ümber
+action-reload-image = Laadi valitud pilt kettalt uuesti
+action-replace-source = Asenda valitud pildi fail
+action-export-frame = Ekspordi valitud pildi praegune kaader
+action-raise-layer = Tõsta valitud pilt kihi võrra üles
+action-lower-layer = Langeta valitud pilt kihi võrra alla
+action-raise-to-top = Tõsta valitud pilt oma rühmas kõige üles
+action-lower-to-bottom = Langeta valitud pilt oma rühmas kõige alla
+action-toggle-pin = Kinnita või vabasta valitud pilt
+action-toggle-pause = Peata või jätka kõiki animatsioone
+action-previous-frame = Peata ja liigu valitud pildis kaader tagasi
+action-next-frame = Peata ja liigu valitud pildis kaader edasi
+action-toggle-diff = Näita, kuidas valitud pildi iga kaader eelmisest erineb
+action-link-views = Mängi valitud animatsiooni selle all olevaga samas taktis
+action-unlink-views = Lase valitud animatsiooniga seotud animatsioonidel omaette mängida
+action-toggle-solo = Näita ainult valitud pilti või jälle kõiki
+action-inspect-gif = Uuri valitud GIF-i paletti ja kaadreid
+action-show-metadata = Näita valitud pildi EXIF-, XMP- ja tekstimetaandmeid
+action-toggle-labels = Näita või peida piltide nimed
+action-toggle-measure = Mõõda kaugusi, Escape lõpetamiseks
+action-eyedropper = Vali värve, Escape lõpetamiseks
+action-pick-color-key = Klõpsa värvil, et muuta see selles pildis läbipaistvaks
+action-toggle-crosshair = Näita või peida kursori sihikut
+action-toggle-compare = Võrdle valitud pilti selle all olevaga
+action-arrange-tiles = Paiguta pildid kõrvuti
+action-arrange-grid = Paiguta pildid võrdsetesse lahtritesse
+action-fit-width = Sobita valitud pilt akna laiusega
+action-fit-height = Sobita valitud pilt akna kõrgusega
+action-frame-selected = Suumi ja liigu valitud pildini
+action-toggle-physical = Näita pilte nende füüsilises suuruses üksteise suhtes
+action-undo-arrangement = Võta viimane paigutus tagasi
+action-new-project = Alusta uut projekti
+action-open-project = Ava projekt
+action-save-project = Salvesta projekt
+action-import-project = Impordi teine projekt
+action-add-images = Lisa pilte
+action-quick-open = Lisa kiirelt pilt projekti kaustast
+action-capture-region = Jäädvusta ekraanilt ala projekti
+action-show-summary = Näita projekti kokkuvõtet
+action-canvas-settings = Lõuendi seaded
+action-image-defaults = Uute piltide vaikeväärtused
+action-export-layout = Ekspordi paigutus
+action-apply-layout = Rakenda paigutus
+action-toggle-help = Näita seda abi
+action-toggle-console = Lülita silumiskonsool
+action-toggle-timing = Logi, kui täpselt animatsioonid oma ajastust peavad
+action-toggle-series = Alusta või lõpeta akna ekraanipildi salvestamine iga paari sekundi tagant
+
+# Paigutamine
+
+toast-arranged = Paigutatud: { $order }
+toast-arranged-grid = Paigutatud ruudustikku, veerge: { $columns }
+sort-add-order = lisamise järjekord
+sort-name = faili nimi
+sort-area = suurus, suurimad ees
+sort-layer = kiht, pealmised ees
+toast-physical-on = Pilte näidatakse füüsilises suuruses, iga faili pikslitiheduse järgi
+toast-physical-off = Pilte näidatakse piksel piksli haaval
+
+# Taasesitus
+
+toast-paused = Animatsioonid peatatud
+toast-resumed = Animatsioonid jätkuvad
+toast-diff-needs-pause = Kaadrite erinevuste nägemiseks peata animatsioonid tühikuga
+toast-timing-on = Iga täisringi kaadrite ajastus logitakse silumiskonsooli
+toast-timing-off = Kaadrite ajastuse logimine lõpetatud
+toast-linked = { $count } animatsiooni mängivad nüüd samas taktis
+toast-link-needs-two = Sidumiseks vali animatsioon, mille all on mõni teine nähtav animatsioon
+toast-unlinked = Seotud animatsioonid mängivad jälle omaette
+toast-not-linked = Valitud pilt pole ühegi teisega seotud
+
+# Suumimine
+
+toast-zoom-max = Suurendatud nii palju kui võimalik, { $percent }%
+toast-zoom-min = Vähendatud nii palju kui võimalik, { $percent }%
+
+# Üksi näitamine
+
+solo-badge = ÜKSI
+
+# Mõõtmine
+
+toast-measure-on = Lohista kauguste mõõtmiseks, lõpetamiseks vajuta Escape
+toast-measure-off = Mõõtmine lõpetatud
+measure-length = { $length } px
+measure-length-native = { $length } px, pildis { $native } px
+
+# Pipett
+
+toast-eyedropper-on = Klõpsa, et kopeerida kursori all olev värv, lõpetamiseks vajuta Escape
+toast-eyedropper-off = Värvide valimine lõpetatud
+toast-color-copied = { $color } kopeeriti lõikelauale
+
+# Värvivõti
+
+toast-keying-on = Klõpsa värvil, et muuta see selles pildis läbipaistvaks, loobumiseks vajuta Escape
+toast-keying-off = Läbipaistvaks muudetava värvi valimine lõpetatud
+toast-color-keyed = { $color } on nüüd läbipaistev, tolerantsi muutmiseks tee pildil paremklõps
+menu-color-key = Läbipaistev { $color }
+menu-key-tolerance = Tolerants { $tolerance }
+menu-remove-color-key = Näita jälle kõiki värve
+
+# Võrdlemine
+
+toast-compare-on = Võrdlemiseks lohista eraldajat, lõpetamiseks vajuta Escape
+toast-compare-needs-two = Võrdlemiseks vali pilt, mille all on mõni teine nähtav pilt
+
+# Jäädvustamine
+
+capture-hint = Lohista jäädvustatava ala valimiseks, loobumiseks vajuta Escape
+toast-capture-failed = Ekraani jäädvustamine ebaõnnestus: { $error }
+
+# Ekraanipiltide seeria
+
+series-badge = ● SALVESTAB { $count }
+toast-series-started = Ekraanipilt salvestatakse { $seconds ->
+        [one] iga sekundi
+       *[other] iga { $seconds } sekundi
+    } tagant kausta { $path }, lõpetamiseks vajuta Ctrl+F9
+toast-series-stopped = Kausta { $path } salvestati { $count ->
+        [one] { $count } ekraanipilt
+       *[other] { $count } ekraanipilti
+    }
+toast-series-failed = Ekraanipildi { $path } salvestamine ebaõnnestus: { $error }
+
+# GIF-i uurija
+
+inspector-global-palette = Üldpalett, { $count } värvi
+inspector-no-global-palette = Üldpalett puudub
+inspector-hint = Klõpsa kaadril, et sellel peatuda
+inspector-frame = { $index }. { $width } x { $height } kohal { $left }, { $top }, { $palette }, { $disposal }, { $delay } ms
+inspector-local-palette = oma palett, { $count } värvi
+inspector-shared-palette = üldpalett
+disposal-unspecified = kõrvaldamine määramata
+disposal-keep = jäetakse alles
+disposal-background = tühjendatakse taustaks
+disposal-previous = taastatakse eelmine kaader
+toast-inspector-needs-gif = Uurida saab ainult GIF-pilte
+
+# Metaandmed
+
+metadata-empty = Sellel pildil pole metaandmeid või pole seda veel dekodeeritud.
+metadata-copy = Kopeeri
+metadata-compressed = (tihendatud)
+
+# Kiiravamine
+
+quick-open-prompt = Kirjuta, et leida pilt kaustast { $path }
+quick-open-searching = Pilte otsitakse…
+quick-open-no-matches = Ükski pilt ei sobi
+toast-quick-open-needs-project = Salvesta esmalt projekt, et oleks kaust, kust pilte lisada
@@ -0,0 +1,237 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Command line arguments.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::Parser;
+use log::warn;
+
+use crate::formats;
+use crate::i18n::tr_args;
+use crate::project::{self, Project};
+use crate::ui::{post, ToastMessage};
+
+/// Slark is a tool for viewing static and animated images.
+#[derive(Parser, Clone, Debug, Default)]
+#[command(version, about)]
+pub struct Args {
+    /// Images to open, directories to open all the images in, or a single .ark project.
+    /// Wildcards like shots/*.png are expanded even when the shell doesn't do it.
+    pub paths: Vec<String>,
+
+    /// Also open the images in the subdirectories of directories
+    #[arg(long, short)]
+    pub recursive: bool,
+
+    /// Open an independent window which neither hands its images over to a running instance
+    /// nor accepts images from later launches
+    #[arg(long)]
+    pub new_instance: bool,
+
+    /// Write the layout of the images to a JSON or CSV file and exit without opening a window
+    #[arg(long, value_name = "FILE", requires = "paths")]
+    pub export: Option<PathBuf>,
+
+    /// Start with all animations paused
+    #[arg(long)]
+    pub paused: bool,
+
+    /// Arrange the images to fit the window once it has opened
+    #[arg(long)]
+    pub fit: bool,
+
+    /// Write a downscaled PNG or JPEG of the first frame of INPUT to OUTPUT and exit without opening a window
+    #[arg(long, num_args = 2, value_names = ["INPUT", "OUTPUT"], conflicts_with_all = ["paths", "export"])]
+    pub poster: Option<Vec<PathBuf>>,
+
+    /// The maximum width and height of the poster
+    #[arg(long, value_name = "PIXELS", default_value_t = 256, requires = "poster")]
+    #[arg(value_parser = clap::value_parser!(u32).range(1..))]
+    pub size: u32,
+}
+
+impl Args {
+    /// Replace the paths that contain wildcards with the paths they match.
+    pub fn expand_patterns(&mut self) {
+        let mut paths = Vec::with_capacity(self.paths.len());
+        for path in self.paths.drain(..) {
+            if !path.contains(|ch| ch == '*' || ch == '?' || ch == '[') {
+                paths.push(path);
+                continue;
+            }
+            let mut matches: Vec<String> = match glob::glob(&path) {
+                Ok(matches) => matches
+                    .flatten()
+                    .map(|matched| matched.to_string_lossy().into_owned())
+                    .collect(),
+                Err(error) => {
+                    warn!("Invalid pattern {}: {}", path, error);
+                    continue;
+                }
+            };
+            if matches.is_empty() {
+                warn!("Nothing matches the pattern {}", path);
+            }
+            matches.sort_by(|a, b| formats::natural_cmp(a, b));
+            paths.append(&mut matches);
+        }
+        self.paths = paths;
+    }
+
+    /// Replace the directories among the paths with the images they contain.
+    pub fn expand_directories(&mut self) {
+        let mut paths = Vec::with_capacity(self.paths.len());
+        for path in self.paths.drain(..) {
+            if !Path::new(&path).is_dir() {
+                paths.push(path);
+                continue;
+            }
+            let images = formats::images_in(Path::new(&path), self.recursive);
+            if images.is_empty() {
+                post(ToastMessage::warning(tr_args(
+                    "toast-no-images-in-directory",
+                    &[("path", path.into())],
+                )));
+            }
+            paths.extend(images.into_iter().map(|image| image.to_string_lossy().into_owned()));
+        }
+        self.paths = paths;
+    }
+
+    /// What to open at startup, one entry per window.
+    pub fn launches(&self) -> Vec<Launch> {
+        classify(&self.paths)
+    }
+}
+
+/// The contents of a window opened at startup.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Launch {
+    pub project: Option<String>,
+    pub images: Vec<String>,
+}
+
+impl Launch {
+    /// Open the project, or create a new one, and add the images to it.
+    pub fn project(&self) -> Result<Project, String> {
+        match &self.project {
+            Some(path) => {
+                let mut project = Project::open(path.into()).map_err(|error| {
+                    tr_args(
+                        "toast-open-failed",
+                        &[("path", path.as_str().into()), ("error", error.into())],
+                    )
+                })?;
+                // Relative paths in a project are relative to the project file, not to us
+                for image in &self.images {
+                    project.add(fs::canonicalize(image).unwrap_or_else(|_| image.into()));
+                }
+                Ok(project)
+            }
+            None => {
+                let mut project = Project::new();
+                for image in &self.images {
+                    project.add(image.into());
+                }
+                Ok(project)
+            }
+        }
+    }
+}
+
+pub fn is_project(path: &str) -> bool {
+    project::is_project_path(Path::new(path))
+}
+
+/// Split the paths into windows.
+///
+/// Every project gets its own window, in the given order. The images are added to the first project,
+/// or to a new project when there are no project files among the paths.
+pub fn classify(paths: &[String]) -> Vec<Launch> {
+    let mut launches: Vec<Launch> = paths
+        .iter()
+        .filter(|path| is_project(path))
+        .map(|path| Launch {
+            project: Some(path.clone()),
+            images: Vec::new(),
+        })
+        .collect();
+    if launches.is_empty() {
+        launches.push(Launch::default());
+    }
+    launches[0].images = paths.iter().filter(|path| !is_project(path)).cloned().collect();
+    launches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(paths: &[&str]) -> Vec<String> {
+        paths.iter().map(|path| path.to_string()).collect()
+    }
+
+    fn launch(project: Option<&str>, images: &[&str]) -> Launch {
+        Launch {
+            project: project.map(String::from),
+            images: paths(images),
+        }
+    }
+
+    #[test]
+    fn nothing() {
+        assert_eq!(classify(&[]), vec![launch(None, &[])]);
+    }
+
+    #[test]
+    fn images_only() {
+        let launches = classify(&paths(&["a.gif", "b.png"]));
+        assert_eq!(launches, vec![launch(None, &["a.gif", "b.png"])]);
+    }
+
+    #[test]
+    fn project_only() {
+        assert_eq!(classify(&paths(&["a.ark"])), vec![launch(Some("a.ark"), &[])]);
+    }
+
+    #[test]
+    fn images_go_to_the_project() {
+        let expected = vec![launch(Some("a.ark"), &["b.gif", "c.png"])];
+        assert_eq!(classify(&paths(&["a.ark", "b.gif", "c.png"])), expected);
+        assert_eq!(classify(&paths(&["b.gif", "a.ark", "c.png"])), expected);
+    }
+
+    #[test]
+    fn window_per_project() {
+        let launches = classify(&paths(&["a.ark", "b.ark"]));
+        assert_eq!(launches, vec![launch(Some("a.ark"), &[]), launch(Some("b.ark"), &[])]);
+    }
+
+    #[test]
+    fn images_go_to_the_first_project() {
+        let launches = classify(&paths(&["c.gif", "a.ark", "b.ark", "d.png"]));
+        assert_eq!(
+            launches,
+            vec![launch(Some("a.ark"), &["c.gif", "d.png"]), launch(Some("b.ark"), &[])]
+        );
+    }
+}
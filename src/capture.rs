@@ -0,0 +1,145 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Grabbing the pixels of the whole screen, for capturing a region of it into the project.
+
+use druid::kurbo::{Point, Rect};
+use imgref::ImgVec;
+use rgb::RGBA8;
+
+/// The contents of every monitor at the moment of capture.
+pub struct Screenshot {
+    pub image: ImgVec<RGBA8>,
+    pub origin: Point, // The top left corner of the virtual screen in pixels, negative with monitors left or above
+}
+
+/// Capture all the monitors as one image.
+#[cfg(windows)]
+pub fn screen() -> Result<Screenshot, String> {
+    use std::io;
+    use std::mem;
+    use std::ptr;
+
+    use winapi::um::wingdi::{
+        BitBlt, CreateCompatibleBitmap, CreateCompatibleDC, DeleteDC, DeleteObject, GetDIBits, SelectObject,
+        BITMAPINFO, BITMAPINFOHEADER, BI_RGB, CAPTUREBLT, DIB_RGB_COLORS, SRCCOPY,
+    };
+    use winapi::um::winuser::{
+        GetDC, GetSystemMetrics, ReleaseDC, SM_CXVIRTUALSCREEN, SM_CYVIRTUALSCREEN, SM_XVIRTUALSCREEN,
+        SM_YVIRTUALSCREEN,
+    };
+
+    unsafe {
+        let x = GetSystemMetrics(SM_XVIRTUALSCREEN);
+        let y = GetSystemMetrics(SM_YVIRTUALSCREEN);
+        let width = GetSystemMetrics(SM_CXVIRTUALSCREEN);
+        let height = GetSystemMetrics(SM_CYVIRTUALSCREEN);
+        if width <= 0 || height <= 0 {
+            return Err(String::from("The screen has no size"));
+        }
+
+        let screen_dc = GetDC(ptr::null_mut());
+        if screen_dc.is_null() {
+            return Err(io::Error::last_os_error().to_string());
+        }
+        let memory_dc = CreateCompatibleDC(screen_dc);
+        let bitmap = CreateCompatibleBitmap(screen_dc, width, height);
+        let previous = SelectObject(memory_dc, bitmap as _);
+        let copied = BitBlt(memory_dc, 0, 0, width, height, screen_dc, x, y, SRCCOPY | CAPTUREBLT);
+        SelectObject(memory_dc, previous);
+
+        let mut info: BITMAPINFO = mem::zeroed();
+        info.bmiHeader.biSize = mem::size_of::<BITMAPINFOHEADER>() as u32;
+        info.bmiHeader.biWidth = width;
+        info.bmiHeader.biHeight = -height; // Negative for rows from the top down
+        info.bmiHeader.biPlanes = 1;
+        info.bmiHeader.biBitCount = 32;
+        info.bmiHeader.biCompression = BI_RGB;
+        let mut bgra = vec![0u8; width as usize * height as usize * 4];
+        let lines = GetDIBits(
+            memory_dc,
+            bitmap,
+            0,
+            height as u32,
+            bgra.as_mut_ptr() as _,
+            &mut info,
+            DIB_RGB_COLORS,
+        );
+        let error = io::Error::last_os_error();
+
+        DeleteObject(bitmap as _);
+        DeleteDC(memory_dc);
+        ReleaseDC(ptr::null_mut(), screen_dc);
+
+        if copied == 0 || lines == 0 {
+            return Err(error.to_string());
+        }
+        // The screen has no transparency, whatever the unused fourth byte says
+        let pixels = bgra
+            .chunks_exact(4)
+            .map(|bgra| RGBA8::new(bgra[2], bgra[1], bgra[0], 255))
+            .collect();
+        Ok(Screenshot {
+            image: ImgVec::new(pixels, width as usize, height as usize),
+            origin: Point::new(x as f64, y as f64),
+        })
+    }
+}
+
+/// Capture all the monitors as one image.
+#[cfg(not(windows))]
+pub fn screen() -> Result<Screenshot, String> {
+    Err(String::from("Screen capture is only supported on Windows so far"))
+}
+
+/// Copy the pixels of `image` that are within `rect`, which is clamped to the image.
+pub fn crop(image: &ImgVec<RGBA8>, rect: Rect) -> Option<ImgVec<RGBA8>> {
+    let bounds = Rect::new(0.0, 0.0, image.width() as f64, image.height() as f64);
+    let rect = rect.round().intersect(bounds);
+    if rect.width() < 1.0 || rect.height() < 1.0 {
+        return None;
+    }
+    let (x, y) = (rect.x0 as usize, rect.y0 as usize);
+    let (width, height) = (rect.width() as usize, rect.height() as usize);
+    let pixels = image.sub_image(x, y, width, height).pixels().collect();
+    Some(ImgVec::new(pixels, width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crop_inside() {
+        let pixels = (0..16).map(|i| RGBA8::new(i, 0, 0, 255)).collect();
+        let image = ImgVec::new(pixels, 4, 4);
+        let cropped = crop(&image, Rect::new(1.0, 2.0, 3.0, 4.0)).unwrap();
+        assert_eq!((cropped.width(), cropped.height()), (2, 2));
+        let reds: Vec<u8> = cropped.pixels().map(|pixel| pixel.r).collect();
+        assert_eq!(reds, vec![9, 10, 13, 14]);
+    }
+
+    #[test]
+    fn crop_is_clamped() {
+        let image = ImgVec::new(vec![RGBA8::default(); 16], 4, 4);
+        let cropped = crop(&image, Rect::new(-5.0, 3.0, 10.0, 10.0)).unwrap();
+        assert_eq!((cropped.width(), cropped.height()), (4, 1));
+        assert!(crop(&image, Rect::new(5.0, 5.0, 8.0, 8.0)).is_none());
+    }
+}
@@ -0,0 +1,178 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! CPU compositing of the views, for when the pixels matter more than the speed.
+
+use druid::kurbo::{Affine, Point, Rect};
+use imgref::ImgVec;
+use rgb::RGBA8;
+
+/// An image as placed on the surface.
+pub struct Placed<'a> {
+    pub image: &'a ImgVec<RGBA8>,
+    pub rect: Rect,        // The image is scaled to cover this whole rect
+    pub transform: Affine, // Moves the rect to where it's shown, e.g. turning it for rotated images
+}
+
+/// Returns the color at `point` after painting `layers` in order on top of `backdrop`.
+pub fn composite_at(backdrop: RGBA8, layers: &[Placed], point: Point) -> RGBA8 {
+    layers
+        .iter()
+        .filter_map(|placed| pixel_at(placed, point))
+        .fold(backdrop, over)
+}
+
+/// Returns `width` by `height` pixels of `layers` painted in order on top of `backdrop`, sampled at the pixel centers.
+pub fn composite(backdrop: RGBA8, layers: &[Placed], width: usize, height: usize) -> ImgVec<RGBA8> {
+    let mut buf = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            buf.push(composite_at(
+                backdrop,
+                layers,
+                Point::new(x as f64 + 0.5, y as f64 + 0.5),
+            ));
+        }
+    }
+    ImgVec::new(buf, width, height)
+}
+
+/// Returns the pixel of the image that covers `point`, if any.
+fn pixel_at(placed: &Placed, point: Point) -> Option<RGBA8> {
+    let (width, height) = (placed.image.width(), placed.image.height());
+    let point = placed.transform.inverse() * point;
+    if width == 0 || height == 0 || !placed.rect.contains(point) {
+        return None;
+    }
+    let x = ((point.x - placed.rect.x0) / placed.rect.width() * width as f64) as usize;
+    let y = ((point.y - placed.rect.y0) / placed.rect.height() * height as f64) as usize;
+    Some(placed.image[(x.min(width - 1), y.min(height - 1))])
+}
+
+/// Paint `above` on top of `below`, both with straight alpha.
+pub fn over(below: RGBA8, above: RGBA8) -> RGBA8 {
+    let above_alpha = above.a as f64 / 255.0;
+    let below_alpha = below.a as f64 / 255.0 * (1.0 - above_alpha);
+    let alpha = above_alpha + below_alpha;
+    if alpha == 0.0 {
+        return RGBA8::new(0, 0, 0, 0);
+    }
+    let mix = |above: u8, below: u8| ((above as f64 * above_alpha + below as f64 * below_alpha) / alpha).round() as u8;
+    RGBA8::new(
+        mix(above.r, below.r),
+        mix(above.g, below.g),
+        mix(above.b, below.b),
+        (alpha * 255.0).round() as u8,
+    )
+}
+
+/// Returns the color as `#RRGGBBAA`.
+pub fn hex(color: RGBA8) -> String {
+    format!("#{:02X}{:02X}{:02X}{:02X}", color.r, color.g, color.b, color.a)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::FRAC_PI_2;
+
+    const RED: RGBA8 = RGBA8::new(255, 0, 0, 255);
+    const HALF_BLUE: RGBA8 = RGBA8::new(0, 0, 255, 128);
+    const CLEAR: RGBA8 = RGBA8::new(0, 0, 0, 0);
+    const WHITE: RGBA8 = RGBA8::new(255, 255, 255, 255);
+
+    fn solid(color: RGBA8, width: usize, height: usize) -> ImgVec<RGBA8> {
+        ImgVec::new(vec![color; width * height], width, height)
+    }
+
+    #[test]
+    fn two_layers() {
+        // A red square zoomed to twice its size, partly covered by a half transparent blue square
+        let red = solid(RED, 2, 2);
+        let blue = solid(HALF_BLUE, 1, 1);
+        let layers = [
+            Placed {
+                image: &red,
+                rect: Rect::new(0.0, 0.0, 4.0, 4.0),
+                transform: Affine::IDENTITY,
+            },
+            Placed {
+                image: &blue,
+                rect: Rect::new(2.0, 2.0, 6.0, 6.0),
+                transform: Affine::IDENTITY,
+            },
+        ];
+        assert_eq!(composite_at(CLEAR, &layers, Point::new(1.0, 1.0)), RED);
+        assert_eq!(
+            composite_at(CLEAR, &layers, Point::new(3.0, 3.0)),
+            RGBA8::new(127, 0, 128, 255)
+        );
+        assert_eq!(composite_at(CLEAR, &layers, Point::new(5.0, 5.0)), HALF_BLUE);
+        assert_eq!(
+            composite_at(WHITE, &layers, Point::new(5.0, 5.0)),
+            RGBA8::new(127, 127, 255, 255)
+        );
+        assert_eq!(composite_at(WHITE, &layers, Point::new(8.0, 8.0)), WHITE);
+    }
+
+    #[test]
+    fn samples_the_covering_pixel() {
+        let image = ImgVec::new(vec![RED, HALF_BLUE, WHITE, CLEAR], 2, 2);
+        let layers = [Placed {
+            image: &image,
+            rect: Rect::new(10.0, 10.0, 30.0, 30.0),
+            transform: Affine::IDENTITY,
+        }];
+        assert_eq!(composite_at(CLEAR, &layers, Point::new(12.0, 12.0)), RED);
+        assert_eq!(composite_at(CLEAR, &layers, Point::new(25.0, 12.0)), HALF_BLUE);
+        assert_eq!(composite_at(CLEAR, &layers, Point::new(12.0, 25.0)), WHITE);
+        assert_eq!(composite_at(WHITE, &layers, Point::new(25.0, 25.0)), WHITE);
+    }
+
+    #[test]
+    fn whole_area() {
+        let red = solid(RED, 1, 1);
+        let layers = [Placed {
+            image: &red,
+            rect: Rect::new(1.0, 0.0, 3.0, 1.0),
+            transform: Affine::IDENTITY,
+        }];
+        let pixels = composite(WHITE, &layers, 4, 2);
+        assert_eq!(pixels.buf(), &vec![WHITE, RED, RED, WHITE, WHITE, WHITE, WHITE, WHITE]);
+    }
+
+    #[test]
+    fn turned_images_are_sampled_turned() {
+        // A quarter turn clockwise about the center brings the top left pixel to the top right
+        let image = ImgVec::new(vec![RED, WHITE, WHITE, WHITE], 2, 2);
+        let rect = Rect::new(0.0, 0.0, 2.0, 2.0);
+        let layers = [Placed {
+            image: &image,
+            rect: rect,
+            transform: Affine::translate((1.0, 1.0)) * Affine::rotate(FRAC_PI_2) * Affine::translate((-1.0, -1.0)),
+        }];
+        assert_eq!(composite_at(CLEAR, &layers, Point::new(1.5, 0.5)), RED);
+        assert_eq!(composite_at(CLEAR, &layers, Point::new(0.5, 0.5)), WHITE);
+    }
+
+    #[test]
+    fn hex_includes_alpha() {
+        assert_eq!(hex(RGBA8::new(1, 171, 255, 128)), "#01ABFF80");
+    }
+}
@@ -0,0 +1,84 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use druid::{AppDelegate, Application, Command, DelegateCtx, Env, Handled, Target, WindowDesc, WindowHandle, WindowId};
+
+use crate::pool::{WindowTracker, COMMAND_REGISTER_WINDOW};
+use crate::ui::COMMAND_SHOW_TOAST;
+
+pub struct Delegate {
+    windows: WindowTracker,
+    pending_windows: Vec<WindowDesc<u64>>, // opened once the application is running
+}
+
+impl Delegate {
+    pub fn new(windows: WindowTracker, pending_windows: Vec<WindowDesc<u64>>) -> Delegate {
+        Delegate {
+            windows,
+            pending_windows,
+        }
+    }
+}
+
+impl AppDelegate<u64> for Delegate {
+    fn command(
+        &mut self,
+        ctx: &mut DelegateCtx,
+        target: Target,
+        cmd: &Command,
+        _data: &mut u64,
+        _env: &Env,
+    ) -> Handled {
+        if let Some(&window_id) = cmd.get(COMMAND_REGISTER_WINDOW) {
+            // Deliver whatever arrived from other instances while there was no window
+            for command in self.windows.register(window_id) {
+                ctx.submit_command(command);
+            }
+            return Handled::Yes;
+        }
+        if let (Target::Global, Some(message)) = (target, cmd.get(COMMAND_SHOW_TOAST)) {
+            // Toasts from code without a window show up in the most recently used window
+            if let Some(window_id) = self.windows.current() {
+                ctx.submit_command(COMMAND_SHOW_TOAST.with(message.clone()).to(window_id));
+                return Handled::Yes;
+            }
+        }
+        Handled::No
+    }
+
+    fn window_added(
+        &mut self,
+        _id: WindowId,
+        _handle: WindowHandle,
+        _data: &mut u64,
+        _env: &Env,
+        ctx: &mut DelegateCtx,
+    ) {
+        for window in self.pending_windows.drain(..) {
+            ctx.new_window(window);
+        }
+    }
+
+    fn window_removed(&mut self, id: WindowId, _data: &mut u64, _env: &Env, _ctx: &mut DelegateCtx) {
+        // Dialogs are windows too, but only closing the last main window should exit
+        if self.windows.unregister(id) {
+            Application::global().quit();
+        }
+    }
+}
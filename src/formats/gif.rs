@@ -19,52 +19,104 @@
 
 use std::fs::File;
 use std::path::Path;
-use std::sync::mpsc::{channel, Receiver};
-use std::thread;
-use std::time::Instant;
+use std::sync::Arc;
 
 use druid::kurbo::Size;
 use gif_dispose::Screen;
 use imgref::ImgVec;
 use rgb::{RGB8, RGBA8};
 
-use crate::image::Frame;
+use crate::image::{Disposal, Frame, FrameStructure};
 
-pub fn open_async(path: &Path) -> (Receiver<Frame>, Size) {
-    let file = File::open(path).expect("Failed to open file");
-    let mut gif_opts = gif::DecodeOptions::new();
-    gif_opts.set_color_output(gif::ColorOutput::Indexed);
+/// Read just the dimensions from the header of the file at `path`.
+pub fn read_size(path: &Path) -> Result<Size, String> {
+    let file = File::open(path).map_err(|error| error.to_string())?;
+    let decoder = gif::DecodeOptions::new()
+        .read_info(file)
+        .map_err(|error| error.to_string())?;
+    Ok(Size::new(decoder.width() as f64, decoder.height() as f64))
+}
+
+/// Decodes the frames of a GIF one at a time, each composed onto the ones before it.
+pub struct Frames {
+    decoder: gif::Decoder<File>,
+    screen: Screen<RGBA8>,
+    global_swatches: Option<Arc<Vec<RGB8>>>, // Only reported with the first frame
+    first: bool,
+    done: bool,
+}
 
-    let mut decoder = gif_opts.read_info(file).expect("Failed to read info");
-    let width = decoder.width() as usize;
-    let height = decoder.height() as usize;
-    let global_palette = decoder.global_palette().map(convert_pixels);
+impl Frames {
+    /// Read the header of the file at `path`, leaving the frames to be decoded as they're asked for.
+    pub fn open(path: &Path) -> Result<Frames, String> {
+        let file = File::open(path).map_err(|error| error.to_string())?;
+        let mut gif_opts = gif::DecodeOptions::new();
+        gif_opts.set_color_output(gif::ColorOutput::Indexed);
 
-    let mut screen = Screen::new(width, height, RGBA8::default(), global_palette);
+        let decoder = gif_opts.read_info(file).map_err(|error| error.to_string())?;
+        let width = decoder.width() as usize;
+        let height = decoder.height() as usize;
+        let global_palette = decoder.global_palette().map(convert_pixels);
+        let global_swatches = decoder.global_palette().map(|bytes| Arc::new(convert_pixels(bytes)));
 
-    let (sender, receiver) = channel();
+        Ok(Frames {
+            decoder: decoder,
+            screen: Screen::new(width, height, RGBA8::default(), global_palette),
+            global_swatches: global_swatches,
+            first: true,
+            done: false,
+        })
+    }
 
-    let debug_filename = String::from(path.to_str().expect("GIF path is invalid UTF-8"));
+    pub fn size(&self) -> Size {
+        Size::new(self.decoder.width() as f64, self.decoder.height() as f64)
+    }
+}
 
-    thread::spawn(move || {
-        let start = Instant::now();
+impl Iterator for Frames {
+    type Item = Result<Frame, String>;
+
+    fn next(&mut self) -> Option<Result<Frame, String>> {
+        if self.done {
+            return None;
+        }
         // NOTE: The decoding/bliting is surprisingly slow, especially in debug builds
-        while let Some(frame) = decoder.read_next_frame().expect("Failed to read next frame") {
-            screen.blit_frame(frame).expect("Failed to blit frame");
-            let pixel_ref = screen.pixels.as_ref();
-            let (buf, width, height) = pixel_ref.to_contiguous_buf();
-            let image = ImgVec::<RGBA8>::new(Vec::from(buf), width, height);
-            sender
-                .send(Frame {
-                    image: image,
-                    delay: frame.delay as i64 * 10_000_000,
-                })
-                .expect("Failed to send frame source");
+        let frame = match self.decoder.read_next_frame() {
+            Ok(Some(frame)) => frame,
+            Ok(None) => {
+                self.done = true;
+                return None;
+            }
+            Err(error) => {
+                self.done = true;
+                return Some(Err(error.to_string()));
+            }
+        };
+        let structure = FrameStructure {
+            left: frame.left,
+            top: frame.top,
+            width: frame.width,
+            height: frame.height,
+            local_palette: frame.palette.as_ref().map(|palette| palette.len() / 3),
+            disposal: match frame.dispose {
+                gif::DisposalMethod::Any => Disposal::Unspecified,
+                gif::DisposalMethod::Keep => Disposal::Keep,
+                gif::DisposalMethod::Background => Disposal::Background,
+                gif::DisposalMethod::Previous => Disposal::Previous,
+            },
+            global_palette: if self.first { self.global_swatches.clone() } else { None },
+        };
+        if let Err(error) = self.screen.blit_frame(frame) {
+            self.done = true;
+            return Some(Err(error.to_string()));
         }
-        println!("Fully decoded {} in {:?}", debug_filename, start.elapsed());
-    });
-
-    (receiver, Size::new(width as f64, height as f64))
+        let pixel_ref = self.screen.pixels.as_ref();
+        let (buf, width, height) = pixel_ref.to_contiguous_buf();
+        let image = ImgVec::<RGBA8>::new(Vec::from(buf), width, height);
+        let delay = frame.delay as i64 * 10_000_000;
+        self.first = false;
+        Some(Ok(Frame::new(image, delay).with_structure(structure)))
+    }
 }
 
 #[rustfmt::skip]
@@ -17,36 +17,80 @@
     along with this program.  If not, see <http://www.gnu.org/licenses/>.
 */
 
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::BufReader;
-use std::path::Path;
-use std::sync::mpsc::{channel, Receiver};
-use std::thread;
-use std::time::Instant;
+use std::path::{Path, PathBuf};
 
 use druid::kurbo::Size;
 use imgref::ImgVec;
 use jpeg_decoder::Decoder;
-use rgb::RGBA8;
+use jpeg_encoder::{ColorType, Encoder};
+use rgb::{ComponentBytes, RGBA8};
 
+use crate::formats::{metadata, Format};
 use crate::image::Frame;
 
-pub fn open_async(path: &Path) -> (Receiver<Frame>, Size) {
-    let file = File::open(path).expect("Failed to open file");
+/// The quality used for encoding, from 1 to 100.
+const QUALITY: u8 = 90;
+
+/// Encode `image` as a JPEG file at `path`, dropping the alpha channel.
+pub fn save(path: &Path, image: &ImgVec<RGBA8>) -> Result<(), String> {
+    if image.width() > u16::MAX as usize || image.height() > u16::MAX as usize {
+        return Err(format!("{} x {} is too large for JPEG", image.width(), image.height()));
+    }
+    let encoder = Encoder::new_file(path, QUALITY).map_err(|error| error.to_string())?;
+    let (buf, width, height) = image.as_ref().to_contiguous_buf();
+    encoder
+        .encode(buf.as_bytes(), width as u16, height as u16, ColorType::Rgba)
+        .map_err(|error| error.to_string())
+}
 
-    let (sender, receiver) = channel();
+/// Read just the dimensions from the header of the file at `path`.
+pub fn read_size(path: &Path) -> Result<Size, String> {
+    let file = File::open(path).map_err(|error| error.to_string())?;
+    let mut decoder = Decoder::new(BufReader::new(file));
+    decoder.read_info().map_err(|error| error.to_string())?;
+    let info = decoder.info().unwrap();
+    Ok(Size::new(info.width as f64, info.height as f64))
+}
 
-    let debug_filename = String::from(path.to_str().expect("JPEG path is invalid UTF-8"));
+/// Decodes the single frame of a JPEG when it's asked for.
+pub struct Frames {
+    decoder: Option<Decoder<BufReader<File>>>, // Taken by the one frame there is
+    path: PathBuf,                             // Where the metadata is read from
+    width: u16,
+    height: u16,
+}
 
-    let mut decoder = Decoder::new(BufReader::new(file));
-    decoder.read_info().expect("Failed to read metadata");
-    let metadata = decoder.info().unwrap();
-    let size = Size::new(metadata.width as f64, metadata.height as f64);
+impl Frames {
+    /// Read the header of the file at `path`, leaving the frame to be decoded when it's asked for.
+    pub fn open(path: &Path) -> Result<Frames, String> {
+        let file = File::open(path).map_err(|error| error.to_string())?;
+        let mut decoder = Decoder::new(BufReader::new(file));
+        decoder.read_info().map_err(|error| error.to_string())?;
+        let info = decoder.info().unwrap();
+        Ok(Frames {
+            decoder: Some(decoder),
+            path: path.to_path_buf(),
+            width: info.width,
+            height: info.height,
+        })
+    }
+
+    pub fn size(&self) -> Size {
+        Size::new(self.width as f64, self.height as f64)
+    }
+}
 
-    thread::spawn(move || {
-        let start = Instant::now();
+impl Iterator for Frames {
+    type Item = Result<Frame, String>;
 
-        let pixels = decoder.decode().expect("Failed to decode JPEG image");
+    fn next(&mut self) -> Option<Result<Frame, String>> {
+        let mut decoder = self.decoder.take()?;
+        let pixels = match decoder.decode() {
+            Ok(pixels) => pixels,
+            Err(error) => return Some(Err(error.to_string())),
+        };
         // TODO: Look into metadata.pixel_format and whether we need to throw a match statement in here to handle differences.
         let pixels = pixels
             .chunks(3)
@@ -57,14 +101,13 @@ pub fn open_async(path: &Path) -> (Receiver<Frame>, Size) {
                 a: 255,
             })
             .collect();
-        let image = ImgVec::new(pixels, metadata.width as usize, metadata.height as usize);
-
-        sender
-            .send(Frame { image: image, delay: 0 })
-            .expect("Failed to send frame source");
-
-        println!("Fully decoded {} in {:?}", debug_filename, start.elapsed());
-    });
-
-    (receiver, size)
+        let image = ImgVec::new(pixels, self.width as usize, self.height as usize);
+
+        let bytes = fs::read(&self.path).unwrap_or_default();
+        let file_metadata = metadata::read(Format::Jpeg, &bytes);
+        let frame = Frame::new(image, 0)
+            .with_metadata(file_metadata)
+            .with_dpi(metadata::jfif_dpi(&bytes));
+        Some(Ok(frame))
+    }
 }
@@ -0,0 +1,485 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Reading the descriptive metadata of image files, like EXIF tags, XMP and PNG text chunks.
+//!
+//! The parsing is deliberately forgiving. Anything that doesn't make sense is skipped,
+//! as the metadata is only ever shown to the user.
+
+use std::convert::TryInto;
+use std::path::Path;
+
+use crate::formats::Format;
+use crate::i18n::tr;
+
+/// A metadata entry as a name and a value, both meant for showing to the user.
+pub type Entry = (String, String);
+
+/// Returns the metadata of the file at `path`, empty if it has none or can't be read.
+pub fn read_file(format: Format, path: &Path) -> Vec<Entry> {
+    match std::fs::read(path) {
+        Ok(bytes) => read(format, &bytes),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Returns the horizontal pixel density of a JPEG file in dots per inch, if its JFIF header has one.
+pub fn jfif_dpi(bytes: &[u8]) -> Option<f64> {
+    jpeg_segments(bytes)
+        .into_iter()
+        .find(|&(marker, _)| marker == 0xE0)
+        .and_then(|(_, data)| jfif_density(data))
+}
+
+/// Returns the metadata found in the contents of a file in `format`.
+pub fn read(format: Format, bytes: &[u8]) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    match format {
+        Format::Png => png(bytes, &mut entries),
+        Format::Jpeg => jpeg(bytes, &mut entries),
+        Format::Webp => webp(bytes, &mut entries),
+        Format::Gif => (),
+    }
+    entries
+}
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+const XMP_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+const XMP_KEYWORD: &str = "XML:com.adobe.xmp";
+const JFIF_HEADER: &[u8] = b"JFIF\0";
+const INCHES_PER_METER: f64 = 39.3701;
+const CENTIMETERS_PER_INCH: f64 = 2.54;
+
+fn png(bytes: &[u8], entries: &mut Vec<Entry>) {
+    if !bytes.starts_with(PNG_SIGNATURE) {
+        return;
+    }
+    let mut rest = &bytes[PNG_SIGNATURE.len()..];
+    while rest.len() >= 12 {
+        let length = u32::from_be_bytes(rest[0..4].try_into().unwrap()) as usize;
+        let kind = &rest[4..8];
+        let data = match rest.get(8..8 + length) {
+            Some(data) => data,
+            None => return,
+        };
+        match kind {
+            b"tEXt" => {
+                if let Some((keyword, text)) = split_nul(data) {
+                    entries.push((format!("tEXt {}", latin1(keyword)), latin1(text)));
+                }
+            }
+            b"zTXt" => {
+                if let Some((keyword, _)) = split_nul(data) {
+                    entries.push((format!("zTXt {}", latin1(keyword)), tr("metadata-compressed")));
+                }
+            }
+            b"iTXt" => itxt(data, entries),
+            b"tIME" if data.len() == 7 => {
+                let year = u16::from_be_bytes([data[0], data[1]]);
+                let time = format!(
+                    "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+                    year, data[2], data[3], data[4], data[5], data[6]
+                );
+                entries.push((String::from("tIME"), time));
+            }
+            b"eXIf" => exif(data, entries),
+            b"pHYs" if data.len() == 9 => {
+                let x = u32::from_be_bytes(data[0..4].try_into().unwrap());
+                let y = u32::from_be_bytes(data[4..8].try_into().unwrap());
+                let density = if data[8] == 1 {
+                    format!(
+                        "{:.0} x {:.0} dpi",
+                        x as f64 / INCHES_PER_METER,
+                        y as f64 / INCHES_PER_METER
+                    )
+                } else {
+                    format!("{} : {}", x, y)
+                };
+                entries.push((String::from("pHYs"), density));
+            }
+            b"IEND" => return,
+            _ => (),
+        }
+        // Skip the data and the checksum
+        rest = &rest[(12 + length).min(rest.len())..];
+    }
+}
+
+fn itxt(data: &[u8], entries: &mut Vec<Entry>) {
+    let (keyword, rest) = match split_nul(data) {
+        Some(split) => split,
+        None => return,
+    };
+    let compressed = rest.first() == Some(&1);
+    // Skip the compression flag and method, the language tag and the translated keyword
+    let text = rest
+        .get(2..)
+        .and_then(split_nul)
+        .and_then(|(_, rest)| split_nul(rest))
+        .map(|(_, text)| text);
+    let text = match text {
+        Some(_) if compressed => tr("metadata-compressed"),
+        Some(text) => String::from_utf8_lossy(text).into_owned(),
+        None => return,
+    };
+    let keyword = latin1(keyword);
+    if keyword == XMP_KEYWORD {
+        entries.push((String::from("XMP"), text));
+    } else {
+        entries.push((format!("iTXt {}", keyword), text));
+    }
+}
+
+fn jpeg(bytes: &[u8], entries: &mut Vec<Entry>) {
+    for (marker, data) in jpeg_segments(bytes) {
+        if marker == 0xE0 {
+            if let Some(dpi) = jfif_density(data) {
+                entries.push((String::from("JFIF density"), format!("{:.0} dpi", dpi)));
+            }
+        } else if marker == 0xE1 {
+            if let Some(tiff) = data.strip_prefix(EXIF_HEADER) {
+                exif(tiff, entries);
+            } else if let Some(xmp) = data.strip_prefix(XMP_HEADER) {
+                entries.push((String::from("XMP"), String::from_utf8_lossy(xmp).into_owned()));
+            }
+        }
+    }
+}
+
+/// The markers and contents of the segments before the image data.
+fn jpeg_segments(bytes: &[u8]) -> Vec<(u8, &[u8])> {
+    let mut segments = Vec::new();
+    if !bytes.starts_with(&[0xFF, 0xD8]) {
+        return segments;
+    }
+    let mut rest = &bytes[2..];
+    while rest.len() >= 4 && rest[0] == 0xFF {
+        let marker = rest[1];
+        // The image data follows the start of scan, there are no more metadata segments
+        if marker == 0xDA || marker == 0xD9 {
+            break;
+        }
+        let length = u16::from_be_bytes([rest[2], rest[3]]) as usize;
+        let data = match rest.get(4..2 + length) {
+            Some(data) if length >= 2 => data,
+            _ => break,
+        };
+        segments.push((marker, data));
+        rest = &rest[2 + length..];
+    }
+    segments
+}
+
+/// The horizontal density of a JFIF APP0 segment in dots per inch, unless it only gives the aspect ratio.
+fn jfif_density(data: &[u8]) -> Option<f64> {
+    let data = data.strip_prefix(JFIF_HEADER)?;
+    // Skip the version
+    let units = *data.get(2)?;
+    let x = u16::from_be_bytes([*data.get(3)?, *data.get(4)?]) as f64;
+    match units {
+        1 if x > 0.0 => Some(x),
+        2 if x > 0.0 => Some(x * CENTIMETERS_PER_INCH),
+        _ => None,
+    }
+}
+
+fn webp(bytes: &[u8], entries: &mut Vec<Entry>) {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WEBP" {
+        return;
+    }
+    let mut rest = &bytes[12..];
+    while rest.len() >= 8 {
+        let kind = &rest[0..4];
+        let length = u32::from_le_bytes(rest[4..8].try_into().unwrap()) as usize;
+        let data = match rest.get(8..8 + length) {
+            Some(data) => data,
+            None => return,
+        };
+        match kind {
+            b"EXIF" => exif(data.strip_prefix(EXIF_HEADER).unwrap_or(data), entries),
+            b"XMP " => entries.push((String::from("XMP"), String::from_utf8_lossy(data).into_owned())),
+            _ => (),
+        }
+        // Chunks are padded to an even length
+        rest = &rest[(8 + length + length % 2).min(rest.len())..];
+    }
+}
+
+/// The EXIF tags worth a readable name.
+const TAG_NAMES: &[(u16, &str)] = &[
+    (0x010E, "ImageDescription"),
+    (0x010F, "Make"),
+    (0x0110, "Model"),
+    (0x0112, "Orientation"),
+    (0x011A, "XResolution"),
+    (0x011B, "YResolution"),
+    (0x0128, "ResolutionUnit"),
+    (0x0131, "Software"),
+    (0x0132, "DateTime"),
+    (0x013B, "Artist"),
+    (0x8298, "Copyright"),
+    (0x829A, "ExposureTime"),
+    (0x829D, "FNumber"),
+    (0x8827, "ISOSpeedRatings"),
+    (0x9000, "ExifVersion"),
+    (0x9003, "DateTimeOriginal"),
+    (0x9004, "DateTimeDigitized"),
+    (0x920A, "FocalLength"),
+    (0x9286, "UserComment"),
+    (0xA001, "ColorSpace"),
+    (0xA002, "PixelXDimension"),
+    (0xA003, "PixelYDimension"),
+    (0xA434, "LensModel"),
+];
+
+/// Points to the sub-IFD with the camera specific tags.
+const EXIF_IFD_POINTER: u16 = 0x8769;
+/// Points to the sub-IFD with the location, which is not shown.
+const GPS_IFD_POINTER: u16 = 0x8825;
+
+/// Reads a TIFF structure with the EXIF tags, starting at its byte order mark.
+fn exif(tiff: &[u8], entries: &mut Vec<Entry>) {
+    let big_endian = match tiff.get(0..2) {
+        Some(b"MM") => true,
+        Some(b"II") => false,
+        _ => return,
+    };
+    let tiff = Tiff {
+        bytes: tiff,
+        big_endian,
+    };
+    if tiff.u16(2) != Some(42) {
+        return;
+    }
+    let first_ifd = match tiff.u32(4) {
+        Some(offset) => offset as usize,
+        None => return,
+    };
+    if let Some(exif_ifd) = tiff.ifd(first_ifd, entries) {
+        // The sub-IFD can't point to another one, so a broken file can't send us around in circles
+        tiff.ifd(exif_ifd, entries);
+    }
+}
+
+struct Tiff<'a> {
+    bytes: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    fn u16(&self, offset: usize) -> Option<u16> {
+        let bytes = self.bytes.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if self.big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        let bytes = self.bytes.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    /// Adds the tags of the IFD at `offset`, returning the offset of the EXIF sub-IFD if it points to one.
+    fn ifd(&self, offset: usize, entries: &mut Vec<Entry>) -> Option<usize> {
+        let count = self.u16(offset)? as usize;
+        let mut exif_ifd = None;
+        for i in 0..count {
+            let entry = offset + 2 + i * 12;
+            let tag = self.u16(entry)?;
+            match tag {
+                EXIF_IFD_POINTER => exif_ifd = Some(self.u32(entry + 8)? as usize),
+                GPS_IFD_POINTER => (),
+                _ => {
+                    if let Some(value) = self.value(entry) {
+                        let name = match TAG_NAMES.iter().find(|(known, _)| *known == tag) {
+                            Some((_, name)) => format!("EXIF {}", name),
+                            None => format!("EXIF 0x{:04X}", tag),
+                        };
+                        entries.push((name, value));
+                    }
+                }
+            }
+        }
+        exif_ifd
+    }
+
+    /// The value of the IFD entry at `entry` as text.
+    fn value(&self, entry: usize) -> Option<String> {
+        let kind = self.u16(entry + 2)?;
+        let count = self.u32(entry + 4)? as usize;
+        let unit = match kind {
+            1 | 2 | 6 | 7 => 1, // Bytes, ASCII and undefined
+            3 | 8 => 2,         // Shorts
+            4 | 9 => 4,         // Longs
+            5 | 10 => 8,        // Rationals
+            _ => return None,
+        };
+        let size = unit * count;
+        // Values that fit in four bytes are stored in place of the offset
+        let start = if size <= 4 {
+            entry + 8
+        } else {
+            self.u32(entry + 8)? as usize
+        };
+        let data = self.bytes.get(start..start + size)?;
+        let value = match kind {
+            2 => latin1(data).trim_end_matches('\0').to_string(),
+            3 | 8 => (0..count)
+                .filter_map(|i| self.u16(start + i * 2))
+                .map(|value| value.to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+            4 | 9 => (0..count)
+                .filter_map(|i| self.u32(start + i * 4))
+                .map(|value| value.to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+            5 | 10 => (0..count)
+                .filter_map(|i| Some(format!("{}/{}", self.u32(start + i * 8)?, self.u32(start + i * 8 + 4)?)))
+                .collect::<Vec<_>>()
+                .join(", "),
+            // Short undefined values like the EXIF version are often text, anything long is a blob
+            _ if size <= 16 => String::from_utf8_lossy(data).trim_end_matches('\0').to_string(),
+            _ => format!("{} bytes", size),
+        };
+        Some(value)
+    }
+}
+
+fn split_nul(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    let nul = data.iter().position(|&byte| byte == 0)?;
+    Some((&data[..nul], &data[nul + 1..]))
+}
+
+fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&byte| byte as char).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png_chunk(kind: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(kind);
+        chunk.extend_from_slice(data);
+        chunk.extend_from_slice(&[0, 0, 0, 0]); // The checksum isn't verified
+        chunk
+    }
+
+    /// A little endian TIFF with a single ASCII tag.
+    fn tiff(tag: u16, text: &[u8]) -> Vec<u8> {
+        let mut tiff = b"II".to_vec();
+        tiff.extend_from_slice(&42u16.to_le_bytes());
+        tiff.extend_from_slice(&8u32.to_le_bytes());
+        tiff.extend_from_slice(&1u16.to_le_bytes());
+        tiff.extend_from_slice(&tag.to_le_bytes());
+        tiff.extend_from_slice(&2u16.to_le_bytes());
+        tiff.extend_from_slice(&(text.len() as u32).to_le_bytes());
+        tiff.extend_from_slice(&26u32.to_le_bytes()); // Right after this IFD
+        tiff.extend_from_slice(&0u32.to_le_bytes());
+        tiff.extend_from_slice(text);
+        tiff
+    }
+
+    #[test]
+    fn png_text_and_time() {
+        let mut bytes = PNG_SIGNATURE.to_vec();
+        bytes.extend(png_chunk(b"tEXt", b"Software\0Slark"));
+        bytes.extend(png_chunk(b"tIME", &[0x07, 0xE6, 3, 14, 15, 9, 26]));
+        bytes.extend(png_chunk(b"iTXt", b"XML:com.adobe.xmp\0\0\0\0\0<x:xmpmeta/>"));
+        bytes.extend(png_chunk(b"IEND", b""));
+        let entries = read(Format::Png, &bytes);
+        assert_eq!(
+            entries,
+            vec![
+                (String::from("tEXt Software"), String::from("Slark")),
+                (String::from("tIME"), String::from("2022-03-14 15:09:26")),
+                (String::from("XMP"), String::from("<x:xmpmeta/>")),
+            ]
+        );
+    }
+
+    #[test]
+    fn jpeg_exif() {
+        let mut segment = EXIF_HEADER.to_vec();
+        segment.extend(tiff(0x010F, b"Camera\0"));
+        let mut bytes = vec![0xFF, 0xD8, 0xFF, 0xE1];
+        bytes.extend_from_slice(&(segment.len() as u16 + 2).to_be_bytes());
+        bytes.extend(segment);
+        bytes.extend_from_slice(&[0xFF, 0xDA, 0, 2]);
+        let entries = read(Format::Jpeg, &bytes);
+        assert_eq!(entries, vec![(String::from("EXIF Make"), String::from("Camera"))]);
+    }
+
+    #[test]
+    fn png_physical_size() {
+        let mut bytes = PNG_SIGNATURE.to_vec();
+        let mut phys = 11811u32.to_be_bytes().to_vec();
+        phys.extend_from_slice(&11811u32.to_be_bytes());
+        phys.push(1);
+        bytes.extend(png_chunk(b"pHYs", &phys));
+        let entries = read(Format::Png, &bytes);
+        assert_eq!(entries, vec![(String::from("pHYs"), String::from("300 x 300 dpi"))]);
+    }
+
+    #[test]
+    fn jpeg_jfif_density() {
+        let segment = |units: u8, density: u16| {
+            let mut bytes = vec![0xFF, 0xD8, 0xFF, 0xE0, 0, 16];
+            bytes.extend_from_slice(JFIF_HEADER);
+            bytes.extend_from_slice(&[1, 2, units]);
+            bytes.extend_from_slice(&density.to_be_bytes());
+            bytes.extend_from_slice(&density.to_be_bytes());
+            bytes.extend_from_slice(&[0, 0, 0xFF, 0xDA, 0, 2]);
+            bytes
+        };
+        assert_eq!(jfif_dpi(&segment(1, 150)), Some(150.0));
+        assert_eq!(jfif_dpi(&segment(2, 100)), Some(254.0));
+        assert_eq!(jfif_dpi(&segment(0, 1)), None);
+        assert_eq!(
+            read(Format::Jpeg, &segment(1, 72)),
+            vec![(String::from("JFIF density"), String::from("72 dpi"))]
+        );
+    }
+
+    #[test]
+    fn webp_xmp() {
+        let mut bytes = b"RIFF\0\0\0\0WEBP".to_vec();
+        bytes.extend_from_slice(b"XMP ");
+        bytes.extend_from_slice(&3u32.to_le_bytes());
+        bytes.extend_from_slice(b"<x>\0"); // Padded to an even length
+        let entries = read(Format::Webp, &bytes);
+        assert_eq!(entries, vec![(String::from("XMP"), String::from("<x>"))]);
+    }
+
+    #[test]
+    fn nothing_from_garbage() {
+        assert!(read(Format::Png, b"not a png").is_empty());
+        assert!(read(Format::Jpeg, &[0xFF, 0xD8, 0xFF, 0xE1, 0xFF, 0xFF]).is_empty());
+        assert!(read(Format::Webp, b"RIFF").is_empty());
+    }
+}
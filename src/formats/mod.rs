@@ -17,7 +17,512 @@
     along with this program.  If not, see <http://www.gnu.org/licenses/>.
 */
 
+use std::any::Any;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::iter::Peekable;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::str::Chars;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::Instant;
+
+use druid::kurbo::Size;
+use druid::{FileDialogOptions, FileSpec};
+use imgref::ImgVec;
+use log::{debug, warn};
+use rgb::RGBA8;
+
+use crate::image::{AnimationInfo, Frame};
+
 pub mod gif;
 pub mod jpeg;
+pub mod metadata;
 pub mod png;
 pub mod webp;
+
+/// File extensions of all the supported formats.
+pub const EXTENSIONS: &[&str] = &["gif", "webp", "jpg", "jpeg", "png"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Gif,
+    Webp,
+    Jpeg,
+    Png,
+}
+
+impl Format {
+    pub fn from_extension(ext: &OsStr) -> Option<Format> {
+        match ext.to_str()? {
+            "gif" => Some(Format::Gif),
+            "webp" => Some(Format::Webp),
+            "jpg" | "jpeg" => Some(Format::Jpeg),
+            "png" => Some(Format::Png),
+            _ => None,
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Option<Format> {
+        path.extension().and_then(Format::from_extension)
+    }
+
+    /// Read the size of the image at `path` from its header, without decoding it.
+    pub fn read_size(self, path: &Path) -> Result<Size, String> {
+        match self {
+            Format::Gif => gif::read_size(path),
+            Format::Webp => webp::read_size(path),
+            Format::Jpeg => jpeg::read_size(path),
+            Format::Png => png::read_size(path),
+        }
+    }
+
+    /// Open the image at `path` for iterating its frames on the calling thread, see [`open`].
+    ///
+    /// WebP is decoded in full before this returns, the other formats decode a frame per iteration.
+    pub fn open(self, path: &Path) -> Result<Frames, String> {
+        match self {
+            Format::Gif => gif::Frames::open(path).map(|frames| Frames::new(path, frames.size(), None, frames)),
+            Format::Webp => {
+                // The decoder borrows the contents of the file, so it has to be done with before they go
+                let buffer = fs::read(path).map_err(|error| error.to_string())?;
+                let frames = webp::Frames::new(&buffer)?;
+                let size = frames.size();
+                let decoded: Vec<Result<Frame, String>> = frames.collect();
+                Ok(Frames::new(
+                    path,
+                    size,
+                    Some(webp::animation_info(&buffer)),
+                    decoded.into_iter(),
+                ))
+            }
+            Format::Jpeg => jpeg::Frames::open(path).map(|frames| Frames::new(path, frames.size(), None, frames)),
+            Format::Png => png::Frames::open(path).map(|frames| Frames::new(path, frames.size(), None, frames)),
+        }
+    }
+
+    /// Start decoding the image at `path` on a thread of its own, returning the frames as they come and the size.
+    ///
+    /// Formats that tell the length of the animation up front return that too.
+    pub fn open_async(self, path: &Path) -> Result<(Receiver<Decoded>, Size, Option<AnimationInfo>), String> {
+        match self {
+            // The WebP decoder can't be sent to another thread, so it gets created on the decoder thread
+            Format::Webp => webp::open_async(path).map(|(receiver, size, info)| (receiver, size, Some(info))),
+            _ => {
+                let frames = self.open(path)?;
+                let (size, animation) = (frames.size(), frames.animation());
+                Ok((decode_async(frames), size, animation))
+            }
+        }
+    }
+}
+
+/// The frames of an image, iterated on the calling thread.
+///
+/// GIF, PNG and JPEG frames are decoded as they're iterated, WebP frames were all decoded up front.
+/// Decoding stops at the first error, which is the last item.
+pub struct Frames {
+    path: PathBuf, // Where the errors happened
+    size: Size,
+    animation: Option<AnimationInfo>,
+    decoded: Box<dyn Iterator<Item = Result<Frame, String>> + Send>,
+}
+
+impl Frames {
+    fn new(
+        path: &Path,
+        size: Size,
+        animation: Option<AnimationInfo>,
+        decoded: impl Iterator<Item = Result<Frame, String>> + Send + 'static,
+    ) -> Frames {
+        Frames {
+            path: path.to_path_buf(),
+            size: size,
+            animation: animation,
+            decoded: Box::new(decoded),
+        }
+    }
+
+    /// The size of the image, which every frame has.
+    pub fn size(&self) -> Size {
+        self.size
+    }
+
+    /// The length of the animation, for the formats that tell it up front.
+    pub fn animation(&self) -> Option<AnimationInfo> {
+        self.animation
+    }
+}
+
+impl Iterator for Frames {
+    type Item = Decoded;
+
+    fn next(&mut self) -> Option<Decoded> {
+        let path = &self.path;
+        self.decoded
+            .next()
+            .map(|decoded| decoded.map_err(|cause| DecodeError::new(path, cause)))
+    }
+}
+
+/// Open the image at `path` for iterating its frames on the calling thread.
+///
+/// The format is picked by the extension. The frames come with the metadata and the pixel density
+/// of the file attached to the first one, just like the ones that `Format::open_async` sends.
+/// Only the frames that are asked for get decoded, except for WebP, which is decoded in full right away.
+/// Use [`read_frame`] when only the first frame is needed.
+pub fn open(path: &Path) -> Result<Frames, String> {
+    let format = Format::from_path(path).ok_or_else(|| format!("Unsupported image format: {}", path.display()))?;
+    format.open(path)
+}
+
+/// Send the `frames` over as they're decoded on a thread of their own, until the receiver is dropped.
+fn decode_async(frames: Frames) -> Receiver<Decoded> {
+    let (sender, receiver) = channel();
+    let path = frames.path.clone();
+    spawn_decoder(sender, path.clone(), move |sender| {
+        let start = Instant::now();
+        for decoded in frames {
+            if sender.send(decoded).is_err() {
+                // Nobody wants the rest of the frames anymore
+                return;
+            }
+        }
+        debug!("Fully decoded {} in {:?}", path.display(), start.elapsed());
+    });
+    receiver
+}
+
+const PNG_FILE_TYPE: FileSpec = FileSpec::new("PNG", &["png"]);
+const JPEG_FILE_TYPE: FileSpec = FileSpec::new("JPEG", &["jpg", "jpeg"]);
+
+/// Dialog options for picking where to save a single frame.
+pub fn frame_dialog_options() -> FileDialogOptions {
+    FileDialogOptions::new()
+        .allowed_types(vec![PNG_FILE_TYPE, JPEG_FILE_TYPE])
+        .default_type(PNG_FILE_TYPE)
+}
+
+/// Encode a single frame to `path`, in the format that matches its extension.
+pub fn save_frame(path: &Path, image: &ImgVec<RGBA8>) -> Result<(), String> {
+    match Format::from_path(path) {
+        Some(Format::Png) => png::save(path, image),
+        Some(Format::Jpeg) => jpeg::save(path, image),
+        _ => Err(format!(
+            "Frames can only be saved as PNG or JPEG, not {}",
+            path.display()
+        )),
+    }
+}
+
+/// Decode only the first frame of the image at `path`, on the calling thread.
+pub fn read_frame(path: &Path) -> Result<ImgVec<RGBA8>, String> {
+    // A decoder that panics only fails this image, just like it would on a thread of its own
+    let first = panic::catch_unwind(|| first_frame(path)).map_err(|payload| panic_message(&*payload))??;
+    let frame = first.ok_or_else(|| format!("Failed to decode {}", path.display()))?;
+    frame
+        .map(|frame| frame.image)
+        .map_err(|cause| format!("{}: {}", path.display(), cause))
+}
+
+/// The first frame of the image at `path`, or `None` when it has no frames at all.
+fn first_frame(path: &Path) -> Result<Option<Result<Frame, String>>, String> {
+    match Format::from_path(path) {
+        // Opening a WebP decodes every frame, so the decoder is asked for the first one directly
+        Some(Format::Webp) => {
+            let buffer = fs::read(path).map_err(|error| error.to_string())?;
+            let first = webp::Frames::new(&buffer)?.next();
+            Ok(first)
+        }
+        _ => {
+            let mut frames = open(path)?;
+            Ok(frames.next().map(|decoded| decoded.map_err(|error| error.cause)))
+        }
+    }
+}
+
+/// All the supported images in `dir`, ordered by name with numbers compared by value.
+///
+/// With `recursive` set the subdirectories are searched as well.
+pub fn images_in(dir: &Path, recursive: bool) -> Vec<PathBuf> {
+    scan(dir, recursive).0
+}
+
+/// All the supported images in `dir` like `images_in`, along with the number of other files that were skipped.
+pub fn scan(dir: &Path, recursive: bool) -> (Vec<PathBuf>, usize) {
+    let mut images = Vec::new();
+    let mut skipped = 0;
+    collect_images(dir, recursive, &mut images, &mut skipped);
+    images.sort_by(|a, b| natural_cmp(&a.to_string_lossy(), &b.to_string_lossy()));
+    (images, skipped)
+}
+
+fn collect_images(dir: &Path, recursive: bool, images: &mut Vec<PathBuf>, skipped: &mut usize) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(error) => {
+            warn!("Failed to read directory {}: {}", dir.display(), error);
+            return;
+        }
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            if recursive {
+                collect_images(&path, recursive, images, skipped);
+            }
+        } else if Format::from_path(&path).is_some() {
+            images.push(path);
+        } else {
+            *skipped += 1;
+        }
+    }
+}
+
+/// Compare the strings so that `frame2` comes before `frame10`.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+    loop {
+        let ordering = match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x = take_number(&mut a);
+                let y = take_number(&mut b);
+                // Without leading zeros the longer number is the larger one
+                x.len().cmp(&y.len()).then_with(|| x.cmp(&y))
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+                a.next();
+                b.next();
+                ordering
+            }
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+fn take_number(chars: &mut Peekable<Chars>) -> String {
+    let mut number = String::new();
+    while let Some(ch) = chars.next_if(char::is_ascii_digit) {
+        number.push(ch);
+    }
+    number.trim_start_matches('0').to_string()
+}
+
+/// What the decoders send as they go: the frames in order, or why decoding stopped early.
+pub type Decoded = Result<Frame, DecodeError>;
+
+/// Why decoding an image stopped early, which can come after some frames were already decoded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodeError {
+    pub path: PathBuf,
+    pub cause: String, // Readable on its own, without the path
+}
+
+impl DecodeError {
+    pub fn new(path: &Path, cause: impl Into<String>) -> DecodeError {
+        DecodeError {
+            path: path.to_path_buf(),
+            cause: cause.into(),
+        }
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.cause)
+    }
+}
+
+thread_local! {
+    /// Where the decoder running on this thread sends its frames and which file it decodes,
+    /// so that a panic can be reported there.
+    static DECODER: RefCell<Option<(Sender<Decoded>, PathBuf)>> = RefCell::new(None);
+}
+
+/// Run `decode` on a thread of its own, sending what it decodes from `path` through `sender`.
+///
+/// Should the decoder panic, the panic arrives as an error at the other end, see `install_panic_hook`.
+fn spawn_decoder(sender: Sender<Decoded>, path: PathBuf, decode: impl FnOnce(Sender<Decoded>) + Send + 'static) {
+    thread::spawn(move || {
+        DECODER.with(|decoder| *decoder.borrow_mut() = Some((sender.clone(), path)));
+        decode(sender);
+    });
+}
+
+/// Turn panics on the decoder threads into errors for whoever is waiting for the frames,
+/// so that one bad file only fails its own view. Other panics are left to the previous hook alone.
+pub fn install_panic_hook() {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let _ = DECODER.try_with(|decoder| {
+            if let Some((sender, path)) = decoder.try_borrow().ok().as_ref().and_then(|decoder| decoder.as_ref()) {
+                let _ = sender.send(Err(DecodeError::new(path, panic_message(info.payload()))));
+            }
+        });
+        previous(info);
+    }));
+}
+
+/// The message that a panic was raised with.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+        (Some(message), _) => message.to_string(),
+        (_, Some(message)) => message.clone(),
+        _ => String::from("The decoder crashed"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rgb::ComponentBytes;
+
+    #[test]
+    fn every_format_iterates_its_frames() {
+        let dir = std::env::temp_dir().join("slark-frame-iterator");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let pixels = ImgVec::new(vec![RGBA8::new(0, 128, 255, 255); 4 * 3], 4, 3);
+        save_frame(&dir.join("still.png"), &pixels).unwrap();
+        save_frame(&dir.join("still.jpg"), &pixels).unwrap();
+        // Both animations have two frames, shown for 50 and 120 ms
+        let mut file = fs::File::create(dir.join("moving.gif")).unwrap();
+        {
+            let mut encoder = ::gif::Encoder::new(&mut file, 4, 3, &[]).unwrap();
+            for delay in &[5, 12] {
+                let mut rgba = pixels.buf().as_bytes().to_vec();
+                let mut frame = ::gif::Frame::from_rgba_speed(4, 3, &mut rgba, 10);
+                frame.delay = *delay;
+                encoder.write_frame(&frame).unwrap();
+            }
+        }
+        let mut encoder = webp_animation::Encoder::new((4, 3)).unwrap();
+        encoder.add_frame(pixels.buf().as_bytes(), 0).unwrap();
+        encoder.add_frame(pixels.buf().as_bytes(), 50).unwrap();
+        let webp = encoder.finalize(170).unwrap();
+        fs::write(dir.join("moving.webp"), &*webp).unwrap();
+
+        let expected: &[(&str, &[i64])] = &[
+            ("moving.gif", &[50, 120]),
+            ("moving.webp", &[50, 120]),
+            ("still.jpg", &[0]),
+            ("still.png", &[0]),
+        ];
+        for (name, delays) in expected {
+            let frames = open(&dir.join(name)).unwrap();
+            assert_eq!(frames.size(), Size::new(4.0, 3.0));
+            let frames: Vec<Frame> = frames.map(|decoded| decoded.unwrap()).collect();
+            let milliseconds: Vec<i64> = frames.iter().map(|frame| frame.delay / 1_000_000).collect();
+            assert_eq!(&milliseconds[..], *delays, "{}", name);
+            assert!(frames
+                .iter()
+                .all(|frame| frame.image.width() == 4 && frame.image.height() == 3));
+            // The metadata comes along with the first frame only
+            assert!(frames[1..].iter().all(|frame| frame.metadata.is_none()));
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn read_frame_takes_the_first_frame_of_an_animation() {
+        let dir = std::env::temp_dir().join(format!("slark-first-frame-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let first = ImgVec::new(vec![RGBA8::new(255, 0, 0, 255); 4 * 3], 4, 3);
+        let second = ImgVec::new(vec![RGBA8::new(0, 0, 255, 255); 4 * 3], 4, 3);
+        let mut encoder = webp_animation::Encoder::new((4, 3)).unwrap();
+        encoder.add_frame(first.buf().as_bytes(), 0).unwrap();
+        encoder.add_frame(second.buf().as_bytes(), 50).unwrap();
+        let webp = encoder.finalize(100).unwrap();
+        let path = dir.join("moving.webp");
+        fs::write(&path, &*webp).unwrap();
+
+        let image = read_frame(&path).unwrap();
+        assert_eq!((image.width(), image.height()), (4, 3));
+        // Lossy, so only roughly the same red
+        assert!(image.pixels().all(|pixel| pixel.r > 200 && pixel.b < 50));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_truncated_animation_keeps_the_frames_before_the_cut() {
+        let dir = std::env::temp_dir().join("slark-truncated-gif");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let pixels = ImgVec::new(vec![RGBA8::new(255, 128, 0, 255); 4 * 3], 4, 3);
+        let encode = |frame_count: usize| {
+            let mut gif = Vec::new();
+            {
+                let mut encoder = ::gif::Encoder::new(&mut gif, 4, 3, &[]).unwrap();
+                for _ in 0..frame_count {
+                    let mut rgba = pixels.buf().as_bytes().to_vec();
+                    let mut frame = ::gif::Frame::from_rgba_speed(4, 3, &mut rgba, 10);
+                    frame.delay = 5;
+                    encoder.write_frame(&frame).unwrap();
+                }
+            }
+            gif
+        };
+        // The encoding is the same up to the trailer, so this cuts a few bytes into the third frame
+        let cut = encode(2).len() - 1 + 4;
+        let path = dir.join("cut.gif");
+        fs::write(&path, &encode(3)[..cut]).unwrap();
+
+        let decoded: Vec<Decoded> = open(&path).unwrap().collect();
+        assert_eq!(decoded.iter().filter(|decoded| decoded.is_ok()).count(), 2);
+        match decoded.last() {
+            Some(Err(error)) => {
+                assert_eq!(error.path, path);
+                assert!(!error.cause.is_empty());
+            }
+            _ => panic!("The cut went unnoticed"),
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_corrupt_file_only_fails_itself() {
+        let dir = std::env::temp_dir().join("slark-corrupt-neighbor");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let pixels = ImgVec::new(vec![RGBA8::new(255, 0, 0, 255); 4 * 3], 4, 3);
+        save_frame(&dir.join("good.png"), &pixels).unwrap();
+        // A valid header with garbage where the image data should be
+        let mut corrupt = fs::read(dir.join("good.png")).unwrap();
+        let data_start = corrupt.len() / 2;
+        for byte in &mut corrupt[data_start..] {
+            *byte = 0xA5;
+        }
+        fs::write(dir.join("bad.png"), corrupt).unwrap();
+        fs::write(dir.join("worse.gif"), b"GIF89a\x04\x00\x03\x00garbage").unwrap();
+
+        install_panic_hook();
+        let mut good_frames = 0;
+        for path in images_in(&dir, false) {
+            let format = Format::from_path(&path).unwrap();
+            let received: Vec<Decoded> = match format.open_async(&path) {
+                Ok((receiver, _, _)) => receiver.iter().collect(),
+                Err(_) => Vec::new(),
+            };
+            let frames = received.iter().filter(|decoded| decoded.is_ok()).count();
+            if path.ends_with("good.png") {
+                good_frames = frames;
+            } else {
+                assert_eq!(frames, 0, "{} decoded", path.display());
+            }
+        }
+        assert_eq!(good_frames, 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
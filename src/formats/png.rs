@@ -18,158 +18,217 @@
 */
 
 use std::fs::File;
-use std::path::Path;
-use std::sync::mpsc::{channel, Receiver};
-use std::thread;
-use std::time::Instant;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
 
 use druid::kurbo::Size;
 use imgref::ImgVec;
-use png::ColorType;
-use rgb::RGBA8;
+use log::debug;
+use png::{BitDepth, ColorType, Encoder, Unit};
+use rgb::{ComponentBytes, RGBA8};
 
+use crate::formats::{metadata, Format};
+use crate::i18n::tr_args;
 use crate::image::Frame;
+use crate::ui::{post, ToastMessage};
 
-pub fn open_async(path: &Path) -> (Receiver<Frame>, Size) {
-    let file = File::open(path).expect("Failed to open file");
-
-    let (sender, receiver) = channel();
+/// Encode `image` as an RGBA PNG file at `path`.
+pub fn save(path: &Path, image: &ImgVec<RGBA8>) -> Result<(), String> {
+    let file = File::create(path).map_err(|error| error.to_string())?;
+    write(BufWriter::new(file), image)
+}
 
-    let debug_filename = String::from(path.to_str().expect("PNG path is invalid UTF-8"));
+/// Encode `image` as RGBA PNG data in memory.
+pub fn encode(image: &ImgVec<RGBA8>) -> Result<Vec<u8>, String> {
+    let mut data = Vec::new();
+    write(&mut data, image)?;
+    Ok(data)
+}
 
-    let decoder = png::Decoder::new(file);
-    let mut reader = decoder.read_info().unwrap();
+fn write(writer: impl Write, image: &ImgVec<RGBA8>) -> Result<(), String> {
+    let mut encoder = Encoder::new(writer, image.width() as u32, image.height() as u32);
+    encoder.set_color(ColorType::Rgba);
+    encoder.set_depth(BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|error| error.to_string())?;
+    let (buf, _, _) = image.as_ref().to_contiguous_buf();
+    writer
+        .write_image_data(buf.as_bytes())
+        .map_err(|error| error.to_string())
+}
 
+/// Read just the dimensions from the header of the file at `path`.
+pub fn read_size(path: &Path) -> Result<Size, String> {
+    let file = File::open(path).map_err(|error| error.to_string())?;
+    let reader = png::Decoder::new(file).read_info().map_err(|error| error.to_string())?;
     let info = reader.info();
-    println!("PNG tRNS: {:?}", info.trns);
-    println!("PNG palette: {:?}", info.palette);
+    Ok(Size::new(info.width as f64, info.height as f64))
+}
 
-    let size = Size::new(info.width as f64, info.height as f64);
+/// Decodes the frames of a PNG one at a time, of which an APNG may have several.
+pub struct Frames {
+    reader: png::Reader<File>,
+    buf: Vec<u8>,          // The output buffer, reused for every frame
+    trns: Option<Vec<u8>>, // The transparent color of RGB images
+    dpi: Option<f64>,
+    path: PathBuf, // Where the metadata is read from, along with the first frame
+    first: bool,
+    done: bool,
+}
 
-    let trns = if let Some(trns) = &info.trns {
-        let mut vec: Vec<u8> = Vec::new();
-        for b in trns.iter() {
-            vec.push(*b);
-        }
-        Some(vec)
-    } else {
-        None
-    };
+impl Frames {
+    /// Read the header of the file at `path`, leaving the frames to be decoded as they're asked for.
+    pub fn open(path: &Path) -> Result<Frames, String> {
+        let file = File::open(path).map_err(|error| error.to_string())?;
+        let decoder = png::Decoder::new(file);
+        let reader = decoder.read_info().map_err(|error| error.to_string())?;
+
+        let info = reader.info();
+        debug!("PNG tRNS: {:?}", info.trns);
+        debug!("PNG palette: {:?}", info.palette);
+
+        let dpi = match info.pixel_dims {
+            // The density is in pixels per meter, with an inch being 0.0254 meters
+            Some(dims) if dims.unit == Unit::Meter && dims.xppu > 0 => Some(dims.xppu as f64 * 0.0254),
+            _ => None,
+        };
+        let trns = info.trns.as_ref().map(|trns| trns.to_vec());
+
+        Ok(Frames {
+            buf: vec![0; reader.output_buffer_size()],
+            reader: reader,
+            trns: trns,
+            dpi: dpi,
+            path: path.to_path_buf(),
+            first: true,
+            done: false,
+        })
+    }
+
+    pub fn size(&self) -> Size {
+        let info = self.reader.info();
+        Size::new(info.width as f64, info.height as f64)
+    }
+}
 
-    thread::spawn(move || {
-        let start = Instant::now();
+impl Iterator for Frames {
+    type Item = Result<Frame, String>;
+
+    fn next(&mut self) -> Option<Result<Frame, String>> {
+        if self.done {
+            return None;
+        }
 
         // TODO: Implement a screen for properly drawing the image in vacuum.
 
         // TODO: Make sure that transparency works properly in APNG.
         // TODO: Figure out the issues with the walking APNG. Use the frame offsets correctly via a screen implementation.
 
-        // Allocate the output buffer.
-        let mut buf = vec![0; reader.output_buffer_size()];
         // Read the next frame. An APNG might contain multiple frames.
-        loop {
-            match reader.next_frame(&mut buf) {
-                Ok(info) => {
-                    let (mut width, mut height) = (info.width as usize, info.height as usize);
-
-                    // Grab the bytes of the image.
-                    let bytes = &buf[..info.buffer_size()];
-
-                    let mut delay = 0;
-                    // Inspect more details of the last read frame.
-                    if let Some(more_info) = reader.info().frame_control {
-                        let mut den = more_info.delay_den as u64;
-                        if den == 0 {
-                            den = 100;
-                        }
-                        delay = (1_000_000_000 * (more_info.delay_num as u64) / den) as i64;
-
-                        (width, height) = (more_info.width as usize, more_info.height as usize);
-
-                        if more_info.x_offset != 0 || more_info.y_offset != 0 {
-                            println!("Saw offsets: {} {}", more_info.x_offset, more_info.y_offset);
-                        }
-                    }
+        let info = match self.reader.next_frame(&mut self.buf) {
+            Ok(info) => info,
+            // Asking for another frame after the last one is how the end of the image shows up
+            Err(png::DecodingError::Parameter(_)) if !self.first => {
+                self.done = true;
+                return None;
+            }
+            Err(error) => {
+                self.done = true;
+                return Some(Err(error.to_string()));
+            }
+        };
+        let (mut width, mut height) = (info.width as usize, info.height as usize);
+
+        // Grab the bytes of the image.
+        let bytes = &self.buf[..info.buffer_size()];
+
+        let mut delay = 0;
+        // Inspect more details of the last read frame.
+        if let Some(more_info) = self.reader.info().frame_control {
+            let mut den = more_info.delay_den as u64;
+            if den == 0 {
+                den = 100;
+            }
+            delay = (1_000_000_000 * (more_info.delay_num as u64) / den) as i64;
 
-                    println!(
-                        "Found another PNG frame for {} which has {} bytes of {:?} and {} x {}",
-                        debug_filename,
-                        bytes.len(),
-                        info.color_type,
-                        info.width,
-                        info.height
-                    );
+            (width, height) = (more_info.width as usize, more_info.height as usize);
 
-                    let mut data = Vec::<u8>::with_capacity(info.width as usize * info.height as usize * 4);
+            if more_info.x_offset != 0 || more_info.y_offset != 0 {
+                debug!("Saw offsets: {} {}", more_info.x_offset, more_info.y_offset);
+            }
+        }
 
-                    match info.color_type {
-                        ColorType::Grayscale => {
-                            println!("Unimplemented color type {:?} for PNG.", info.color_type)
-                        }
-                        ColorType::GrayscaleAlpha => {
-                            println!("Unimplemented color type {:?} for PNG.", info.color_type)
-                        }
-                        ColorType::Indexed => {
-                            println!("Unimplemented color type {:?} for PNG.", info.color_type)
-                        }
-                        ColorType::Rgb => {
-                            let mut i = 0;
-                            for b in bytes.iter() {
-                                data.push(*b);
-                                i += 1;
-                                if i == 3 {
-                                    i = 0;
-                                    match &trns {
-                                        Some(trns) => {
-                                            let len = data.len();
-                                            if trns[0] == data[len - 3]
-                                                && trns[1] == data[len - 2]
-                                                && trns[2] == data[len - 1]
-                                            {
-                                                data.push(0);
-                                            } else {
-                                                data.push(255);
-                                            }
-                                        }
-                                        None => data.push(255),
-                                    }
+        let filename = self.path.display().to_string();
+        debug!(
+            "Found another PNG frame for {} which has {} bytes of {:?} and {} x {}",
+            filename,
+            bytes.len(),
+            info.color_type,
+            info.width,
+            info.height
+        );
+
+        let mut data = Vec::<u8>::with_capacity(info.width as usize * info.height as usize * 4);
+
+        match info.color_type {
+            ColorType::Grayscale => unsupported_color_type(&filename, info.color_type),
+            ColorType::GrayscaleAlpha => unsupported_color_type(&filename, info.color_type),
+            ColorType::Indexed => unsupported_color_type(&filename, info.color_type),
+            ColorType::Rgb => {
+                let mut i = 0;
+                for b in bytes.iter() {
+                    data.push(*b);
+                    i += 1;
+                    if i == 3 {
+                        i = 0;
+                        match &self.trns {
+                            Some(trns) => {
+                                let len = data.len();
+                                if trns[0] == data[len - 3] && trns[1] == data[len - 2] && trns[2] == data[len - 1] {
+                                    data.push(0);
+                                } else {
+                                    data.push(255);
                                 }
                             }
-                        }
-                        ColorType::Rgba => {
-                            for b in bytes.iter() {
-                                data.push(*b);
-                            }
+                            None => data.push(255),
                         }
                     }
-
-                    let pixels = data
-                        .chunks(4)
-                        .map(|bytes| RGBA8 {
-                            r: bytes[0],
-                            g: bytes[1],
-                            b: bytes[2],
-                            a: bytes[3],
-                        })
-                        .collect();
-                    let image = ImgVec::new(pixels, width as usize, height as usize);
-
-                    sender
-                        .send(Frame {
-                            image: image,
-                            delay: delay,
-                        })
-                        .expect("Failed to send frame source");
                 }
-                Err(error) => {
-                    println!("PNG reader error: {}", error);
-                    break;
+            }
+            ColorType::Rgba => {
+                for b in bytes.iter() {
+                    data.push(*b);
                 }
             }
         }
 
-        println!("Fully decoded {} in {:?}", debug_filename, start.elapsed());
-    });
+        let pixels = data
+            .chunks(4)
+            .map(|bytes| RGBA8 {
+                r: bytes[0],
+                g: bytes[1],
+                b: bytes[2],
+                a: bytes[3],
+            })
+            .collect();
+        let image = ImgVec::new(pixels, width as usize, height as usize);
+
+        let mut frame = Frame::new(image, delay);
+        if self.first {
+            frame = frame
+                .with_metadata(metadata::read_file(Format::Png, &self.path))
+                .with_dpi(self.dpi);
+        }
+        self.first = false;
+        Some(Ok(frame))
+    }
+}
 
-    (receiver, size)
+fn unsupported_color_type(filename: &str, color_type: ColorType) {
+    post(ToastMessage::warning(tr_args(
+        "toast-unsupported-png-color",
+        &[
+            ("path", filename.into()),
+            ("color_type", format!("{:?}", color_type).into()),
+        ],
+    )));
 }
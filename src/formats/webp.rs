@@ -17,85 +17,206 @@
     along with this program.  If not, see <http://www.gnu.org/licenses/>.
 */
 
+use std::convert::TryInto;
 use std::path::Path;
 use std::sync::mpsc::{channel, Receiver};
-use std::thread;
 use std::time::Instant;
 
 use druid::kurbo::Size;
 use imgref::ImgVec;
+use log::{debug, warn};
 use rgb::RGBA8;
-use webp_animation::{ColorMode, Decoder};
+use webp_animation::{ColorMode, Decoder, DecoderIterator};
 
-use crate::image::Frame;
+use crate::formats::{metadata, spawn_decoder, DecodeError, Decoded, Format};
+use crate::image::{AnimationInfo, Frame};
 
-pub fn open_async(path: &Path) -> (Receiver<Frame>, Size) {
-    let buffer = std::fs::read(path).unwrap();
+/// Read the dimensions of the file at `path`, without decoding any frames.
+pub fn read_size(path: &Path) -> Result<Size, String> {
+    let buffer = std::fs::read(path).map_err(|error| error.to_string())?;
+    read_size_from(&buffer)
+}
 
-    let (sender, receiver) = channel();
+fn read_size_from(buffer: &[u8]) -> Result<Size, String> {
+    let decoder = Decoder::new(buffer).map_err(|error| error.to_string())?;
+    let (width, height) = decoder.dimensions();
+    Ok(Size::new(width as f64, height as f64))
+}
 
-    let debug_filename = String::from(path.to_str().expect("WebP path is invalid UTF-8"));
+/// Decodes the frames of a WebP one at a time, from the contents of the file.
+///
+/// The decoder can't be sent to another thread and borrows the contents, so it's created where it's used.
+pub struct Frames<'a> {
+    frames: DecoderIterator<'a>,
+    width: u32,
+    height: u32,
+    metadata: Option<Vec<(String, String)>>, // Only sent with the first frame
+    prev_timestamp: i32,
+}
 
-    let decoder = Decoder::new(&buffer).unwrap();
-    let (width, height) = decoder.dimensions();
-    let size = Size::new(width as f64, height as f64);
+impl<'a> Frames<'a> {
+    /// Start decoding `buffer`, the contents of a WebP file.
+    pub fn new(buffer: &'a [u8]) -> Result<Frames<'a>, String> {
+        let decoder = Decoder::new(buffer).map_err(|error| error.to_string())?;
+        let (width, height) = decoder.dimensions();
+        Ok(Frames {
+            frames: decoder.into_iter(),
+            width: width,
+            height: height,
+            // The whole file is at hand anyway, so the metadata is read right away
+            metadata: Some(metadata::read(Format::Webp, buffer)),
+            prev_timestamp: 0,
+        })
+    }
 
-    // We need to drop & re-create the decoder because it doesn't implement Send.
-    std::mem::drop(decoder);
+    pub fn size(&self) -> Size {
+        Size::new(self.width as f64, self.height as f64)
+    }
+}
 
-    thread::spawn(move || {
-        let start = Instant::now();
-        let decoder = Decoder::new(&buffer).unwrap();
-        let mut prev_timestamp = 0;
-        for frame in decoder.into_iter() {
-            // The current implementation of webp_animation guarantees using the full image dimensions for every frame.
-            if frame.dimensions() != (width, height) {
-                println!(
-                    "Unexpected frame size for WebP decoding. Expected {} x {} but got {} x {}",
-                    width,
-                    height,
-                    frame.dimensions().0,
-                    frame.dimensions().1
-                );
-            }
-            println!(
-                "Calculated {} frame delay: {} ms",
-                debug_filename,
-                (frame.timestamp() - prev_timestamp)
+impl Iterator for Frames<'_> {
+    type Item = Result<Frame, String>;
+
+    fn next(&mut self) -> Option<Result<Frame, String>> {
+        let frame = self.frames.next()?;
+        // The current implementation of webp_animation guarantees using the full image dimensions for every frame.
+        if frame.dimensions() != (self.width, self.height) {
+            warn!(
+                "Unexpected frame size for WebP decoding. Expected {} x {} but got {} x {}",
+                self.width,
+                self.height,
+                frame.dimensions().0,
+                frame.dimensions().1
             );
-            let pixels = match frame.color_mode() {
-                ColorMode::Rgba => frame
-                    .data()
-                    .chunks(4)
-                    .map(|bytes| RGBA8 {
-                        r: bytes[0],
-                        g: bytes[1],
-                        b: bytes[2],
-                        a: bytes[3],
-                    })
-                    .collect(),
-                ColorMode::Bgra => frame
-                    .data()
-                    .chunks(4)
-                    .map(|bytes| RGBA8 {
-                        r: bytes[2],
-                        g: bytes[1],
-                        b: bytes[0],
-                        a: bytes[3],
-                    })
-                    .collect(),
-            };
-            let image = ImgVec::new(pixels, width as usize, height as usize);
-            sender
-                .send(Frame {
-                    image: image,
-                    delay: (frame.timestamp() - prev_timestamp) as i64 * 1_000_000,
+        }
+        debug!(
+            "Calculated frame delay: {} ms",
+            (frame.timestamp() - self.prev_timestamp)
+        );
+        let pixels = match frame.color_mode() {
+            ColorMode::Rgba => frame
+                .data()
+                .chunks(4)
+                .map(|bytes| RGBA8 {
+                    r: bytes[0],
+                    g: bytes[1],
+                    b: bytes[2],
+                    a: bytes[3],
+                })
+                .collect(),
+            ColorMode::Bgra => frame
+                .data()
+                .chunks(4)
+                .map(|bytes| RGBA8 {
+                    r: bytes[2],
+                    g: bytes[1],
+                    b: bytes[0],
+                    a: bytes[3],
                 })
-                .expect("Failed to send frame source");
-            prev_timestamp = frame.timestamp();
+                .collect(),
+        };
+        let image = ImgVec::new(pixels, self.width as usize, self.height as usize);
+        let delay = (frame.timestamp() - self.prev_timestamp) as i64 * 1_000_000;
+        let mut decoded = Frame::new(image, delay);
+        if let Some(metadata) = self.metadata.take() {
+            decoded = decoded.with_metadata(metadata);
         }
-        println!("Fully decoded {} in {:?}", debug_filename, start.elapsed());
+        self.prev_timestamp = frame.timestamp();
+        Some(Ok(decoded))
+    }
+}
+
+pub fn open_async(path: &Path) -> Result<(Receiver<Decoded>, Size, AnimationInfo), String> {
+    let buffer = std::fs::read(path).map_err(|error| error.to_string())?;
+    let info = animation_info(&buffer);
+    let size = read_size_from(&buffer)?;
+
+    let (sender, receiver) = channel();
+
+    let path = path.to_path_buf();
+
+    spawn_decoder(sender, path.clone(), move |sender| {
+        let start = Instant::now();
+        let frames = match Frames::new(&buffer) {
+            Ok(frames) => frames,
+            Err(cause) => {
+                let _ = sender.send(Err(DecodeError::new(&path, cause)));
+                return;
+            }
+        };
+        for decoded in frames {
+            let decoded = decoded.map_err(|cause| DecodeError::new(&path, cause));
+            if sender.send(decoded).is_err() {
+                // Nobody wants the rest of the frames anymore
+                return;
+            }
+        }
+        debug!("Fully decoded {} in {:?}", path.display(), start.elapsed());
     });
 
-    (receiver, size)
+    Ok((receiver, size, info))
+}
+
+/// Count the frames and add up their durations from the ANMF chunks, without decoding anything.
+///
+/// A still image without any ANMF chunks counts as a single frame with no duration.
+pub fn animation_info(buffer: &[u8]) -> AnimationInfo {
+    let mut info = AnimationInfo {
+        frame_count: 0,
+        duration: 0,
+    };
+    let mut rest = buffer.get(12..).unwrap_or_default();
+    while rest.len() >= 8 {
+        let length = u32::from_le_bytes(rest[4..8].try_into().unwrap()) as usize;
+        if &rest[0..4] == b"ANMF" {
+            // The duration in milliseconds follows the frame position and size
+            if let Some(duration) = rest.get(8 + 12..8 + 15) {
+                info.frame_count += 1;
+                let milliseconds = u32::from_le_bytes([duration[0], duration[1], duration[2], 0]);
+                info.duration += milliseconds as i64 * 1_000_000;
+            }
+        }
+        // Chunks are padded to an even length
+        rest = &rest[(8 + length + length % 2).min(rest.len())..];
+    }
+    info.frame_count = info.frame_count.max(1);
+    info
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn anmf(milliseconds: u32) -> Vec<u8> {
+        let mut chunk = b"ANMF".to_vec();
+        chunk.extend_from_slice(&16u32.to_le_bytes());
+        chunk.extend_from_slice(&[0; 12]);
+        chunk.extend_from_slice(&milliseconds.to_le_bytes()[..3]);
+        chunk.push(0); // Flags
+        chunk
+    }
+
+    #[test]
+    fn counts_the_anmf_chunks() {
+        let mut buffer = b"RIFF\0\0\0\0WEBP".to_vec();
+        buffer.extend_from_slice(b"ANIM");
+        buffer.extend_from_slice(&6u32.to_le_bytes());
+        buffer.extend_from_slice(&[0; 6]);
+        buffer.extend(anmf(100));
+        buffer.extend(anmf(250));
+        let info = animation_info(&buffer);
+        assert_eq!(info.frame_count, 2);
+        assert_eq!(info.duration, 350_000_000);
+    }
+
+    #[test]
+    fn still_image_is_one_frame() {
+        let mut buffer = b"RIFF\0\0\0\0WEBP".to_vec();
+        buffer.extend_from_slice(b"VP8 ");
+        buffer.extend_from_slice(&3u32.to_le_bytes());
+        buffer.extend_from_slice(&[0; 4]);
+        let info = animation_info(&buffer);
+        assert_eq!(info.frame_count, 1);
+        assert_eq!(info.duration, 0);
+    }
 }
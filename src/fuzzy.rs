@@ -0,0 +1,116 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Fuzzy matching of typed queries against names, for picking from long lists by typing a few of the letters.
+//!
+//! The query matches when all its characters appear in the name in the same order, ignoring case,
+//! e.g. `frwk` matches `fireworks.gif`.
+
+use std::cmp::Reverse;
+
+/// The bonus for a character that directly follows the previously matched one.
+const CONSECUTIVE_BONUS: u32 = 4;
+
+/// The bonus for a character that starts a word, e.g. after a separator or at a lowercase to uppercase change.
+const WORD_START_BONUS: u32 = 8;
+
+/// How well `candidate` matches `query`, or `None` when it doesn't match at all. Higher is better.
+///
+/// Runs of consecutive characters and the starts of words count for more. Whitespace in the query is ignored.
+pub fn score(query: &str, candidate: &str) -> Option<u32> {
+    let mut wanted = query
+        .chars()
+        .filter(|ch| !ch.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .peekable();
+    let mut score = 0;
+    let mut previous: Option<char> = None;
+    let mut previous_matched = false;
+    for ch in candidate.chars() {
+        let next = match wanted.peek() {
+            Some(&next) => next,
+            None => break,
+        };
+        let matched = ch.to_lowercase().next() == Some(next);
+        if matched {
+            wanted.next();
+            score += 1;
+            if previous_matched {
+                score += CONSECUTIVE_BONUS;
+            }
+            let word_start = match previous {
+                None => true,
+                Some(previous) => !previous.is_alphanumeric() || (previous.is_lowercase() && ch.is_uppercase()),
+            };
+            if word_start {
+                score += WORD_START_BONUS;
+            }
+        }
+        previous_matched = matched;
+        previous = Some(ch);
+    }
+    match wanted.peek() {
+        Some(_) => None,
+        None => Some(score),
+    }
+}
+
+/// The indices of the `candidates` that match `query`, best first.
+///
+/// Equally good matches go shortest first, then in their original order. An empty query keeps every candidate in order.
+pub fn rank<S: AsRef<str>>(query: &str, candidates: &[S]) -> Vec<usize> {
+    if query.trim().is_empty() {
+        return (0..candidates.len()).collect();
+    }
+    let mut matches: Vec<(usize, u32)> = candidates
+        .iter()
+        .enumerate()
+        .filter_map(|(index, candidate)| score(query, candidate.as_ref()).map(|score| (index, score)))
+        .collect();
+    matches.sort_by_key(|&(index, score)| (Reverse(score), candidates[index].as_ref().len(), index));
+    matches.into_iter().map(|(index, _)| index).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn characters_match_in_order() {
+        assert!(score("frwk", "fireworks.gif").is_some());
+        assert!(score("FIRE", "fireworks.gif").is_some());
+        assert!(score("fire gif", "fireworks.gif").is_some());
+        assert!(score("wf", "fireworks.gif").is_none());
+        assert!(score("fireworks.gifs", "fireworks.gif").is_none());
+        assert_eq!(score("", "fireworks.gif"), Some(0));
+    }
+
+    #[test]
+    fn word_starts_and_runs_rank_first() {
+        let candidates = [
+            "sprites/walking_unicorn.png",
+            "unicorn-space.webp",
+            "backgrounds/ruins.jpg",
+            "unicorn.jpg",
+        ];
+        assert_eq!(rank("uni", &candidates), vec![3, 1, 0, 2]);
+        assert_eq!(rank("wu", &candidates), vec![0]);
+        assert_eq!(rank("  ", &candidates), vec![0, 1, 2, 3]);
+    }
+}
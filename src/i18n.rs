@@ -0,0 +1,146 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Translations of the user-visible strings. The Fluent resources live in `resources/i18n`
+//! and are embedded into the binary, with `en-US` used for anything a translation is missing.
+
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use druid::Application;
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource, FluentValue};
+use log::{info, warn};
+use unic_langid::LanguageIdentifier;
+
+/// The language that has every key and is used for anything missing from the others.
+const FALLBACK: &str = "en-US";
+
+const RESOURCES: &[(&str, &str)] = &[
+    ("en-US", include_str!("../resources/i18n/en-US/slark.ftl")),
+    ("et-EE", include_str!("../resources/i18n/et-EE/slark.ftl")),
+];
+
+static LOCALIZATION: OnceLock<Localization> = OnceLock::new();
+
+struct Localization {
+    bundles: Vec<FluentBundle<FluentResource>>, // the chosen language first, then the fallback
+}
+
+impl Localization {
+    fn new(language: &str) -> Localization {
+        let mut bundles = vec![bundle(language)];
+        if language != FALLBACK {
+            bundles.push(bundle(FALLBACK));
+        }
+        Localization { bundles }
+    }
+}
+
+fn bundle(language: &str) -> FluentBundle<FluentResource> {
+    let (_, source) = RESOURCES.iter().find(|(name, _)| *name == language).unwrap();
+    let id = LanguageIdentifier::from_str(language).unwrap();
+    let resource = match FluentResource::try_new(source.to_string()) {
+        Ok(resource) => resource,
+        Err((resource, errors)) => {
+            debug_assert!(false, "Failed to parse {} resources: {:?}", language, errors);
+            warn!("Failed to parse {} resources: {:?}", language, errors);
+            resource
+        }
+    };
+    let mut bundle = FluentBundle::new_concurrent(vec![id]);
+    // Isolation marks around arguments show up as garbage in file paths
+    bundle.set_use_isolating(false);
+    if let Err(errors) = bundle.add_resource(resource) {
+        debug_assert!(false, "Failed to add {} resources: {:?}", language, errors);
+        warn!("Failed to add {} resources: {:?}", language, errors);
+    }
+    bundle
+}
+
+/// Find the available language that best matches `requested`, e.g. `et` matches `et-EE`.
+fn available(requested: &str) -> Option<&'static str> {
+    let requested = LanguageIdentifier::from_str(&requested.replace('_', "-")).ok()?;
+    let ids = RESOURCES
+        .iter()
+        .map(|(name, _)| (*name, LanguageIdentifier::from_str(name).unwrap()));
+    let mut same_language = None;
+    for (name, id) in ids {
+        if id == requested {
+            return Some(name);
+        }
+        if same_language.is_none() && id.language == requested.language {
+            same_language = Some(name);
+        }
+    }
+    same_language
+}
+
+/// Choose the language to use, either the requested one or the system language.
+///
+/// This needs to be called before any strings are translated, otherwise `en-US` will be used.
+pub fn init(language: Option<&str>) {
+    let language = match language {
+        Some(language) => available(language).unwrap_or_else(|| {
+            warn!("Language {} is not available, using {}", language, FALLBACK);
+            FALLBACK
+        }),
+        None => available(&Application::get_locale()).unwrap_or(FALLBACK),
+    };
+    info!("Using language {}", language);
+    if LOCALIZATION.set(Localization::new(language)).is_err() {
+        warn!("Localization was already initialized");
+    }
+}
+
+/// Translate the string with the given key.
+pub fn tr(key: &str) -> String {
+    format(key, None)
+}
+
+/// Translate the string with the given key, filling in the arguments.
+pub fn tr_args(key: &str, args: &[(&str, FluentValue)]) -> String {
+    let mut fluent_args = FluentArgs::new();
+    for (name, value) in args {
+        fluent_args.set(*name, value.clone());
+    }
+    format(key, Some(&fluent_args))
+}
+
+fn format(key: &str, args: Option<&FluentArgs>) -> String {
+    let localization = LOCALIZATION.get_or_init(|| Localization::new(FALLBACK));
+    for (i, bundle) in localization.bundles.iter().enumerate() {
+        if let Some(pattern) = bundle.get_message(key).and_then(|message| message.value()) {
+            if i > 0 {
+                warn!("Missing translation for {}", key);
+            }
+            let mut errors = Vec::new();
+            let text = bundle.format_pattern(pattern, args, &mut errors);
+            if !errors.is_empty() {
+                debug_assert!(false, "Failed to format {}: {:?}", key, errors);
+                warn!("Failed to format {}: {:?}", key, errors);
+            }
+            return text.into_owned();
+        }
+    }
+    // Every key must exist in the fallback language, so catch any mistakes early
+    debug_assert!(false, "Missing localization key {}", key);
+    warn!("Missing localization key {}", key);
+    key.to_string()
+}
@@ -17,11 +17,157 @@
     along with this program.  If not, see <http://www.gnu.org/licenses/>.
 */
 
+use std::sync::Arc;
+
 use imgref::ImgVec;
-use rgb::RGBA8;
+use rgb::{RGB8, RGBA8};
 
 /// Represents a single frame of a potentially multi-frame image.
 pub struct Frame {
     pub image: ImgVec<RGBA8>,
     pub delay: i64,
+    pub structure: Option<FrameStructure>,       // Only known for GIFs
+    pub metadata: Option<Vec<(String, String)>>, // Only sent with the first frame
+    pub dpi: Option<f64>,                        // Only sent with the first frame, if the file says
+}
+
+/// The length of an animation, known before all of its frames have been decoded.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct AnimationInfo {
+    pub frame_count: usize,
+    pub duration: i64, // In nanoseconds, like the frame delays
+}
+
+/// How a GIF frame is put together, kept around for inspecting it.
+#[derive(Clone)]
+pub struct FrameStructure {
+    pub left: u16,
+    pub top: u16,
+    pub width: u16,
+    pub height: u16,
+    pub local_palette: Option<usize>, // The number of colors, if the frame has a palette of its own
+    pub disposal: Disposal,
+    pub global_palette: Option<Arc<Vec<RGB8>>>, // Only sent with the first frame
+}
+
+/// What happens to the area of a GIF frame before the next frame is drawn.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Disposal {
+    Unspecified,
+    Keep,
+    Background,
+    Previous,
+}
+
+impl Frame {
+    pub fn new(image: ImgVec<RGBA8>, delay: i64) -> Frame {
+        Frame {
+            image: image,
+            delay: delay,
+            structure: None,
+            metadata: None,
+            dpi: None,
+        }
+    }
+
+    pub fn with_structure(mut self, structure: FrameStructure) -> Frame {
+        self.structure = Some(structure);
+        self
+    }
+
+    pub fn with_metadata(mut self, metadata: Vec<(String, String)>) -> Frame {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    pub fn with_dpi(mut self, dpi: Option<f64>) -> Frame {
+        self.dpi = dpi;
+        self
+    }
+}
+
+/// The size that `downscale` turns an image of `width` by `height` into.
+pub fn downscaled_size(width: usize, height: usize, max_size: usize) -> (usize, usize) {
+    let scale = (max_size as f64 / width.max(height) as f64).min(1.0);
+    (
+        ((width as f64 * scale).round() as usize).max(1),
+        ((height as f64 * scale).round() as usize).max(1),
+    )
+}
+
+/// Downscale the image to fit within `max_size` by averaging the covered pixels. Smaller images are kept as is.
+pub fn downscale(image: &ImgVec<RGBA8>, max_size: usize) -> ImgVec<RGBA8> {
+    let (width, height) = (image.width(), image.height());
+    if width == 0 || height == 0 {
+        return ImgVec::new(Vec::new(), 0, 0);
+    }
+    let (thumb_width, thumb_height) = downscaled_size(width, height, max_size);
+
+    let mut pixels = Vec::with_capacity(thumb_width * thumb_height);
+    for ty in 0..thumb_height {
+        let (y0, y1) = span(ty, thumb_height, height);
+        for tx in 0..thumb_width {
+            let (x0, x1) = span(tx, thumb_width, width);
+            let mut sum = [0u64; 4];
+            for row in image.rows().skip(y0).take(y1 - y0) {
+                for pixel in &row[x0..x1] {
+                    sum[0] += pixel.r as u64;
+                    sum[1] += pixel.g as u64;
+                    sum[2] += pixel.b as u64;
+                    sum[3] += pixel.a as u64;
+                }
+            }
+            let count = ((y1 - y0) * (x1 - x0)) as u64;
+            pixels.push(RGBA8 {
+                r: (sum[0] / count) as u8,
+                g: (sum[1] / count) as u8,
+                b: (sum[2] / count) as u8,
+                a: (sum[3] / count) as u8,
+            });
+        }
+    }
+    ImgVec::new(pixels, thumb_width, thumb_height)
+}
+
+/// How much the per-pixel differences are amplified, so that small changes still stand out.
+const DIFFERENCE_GAIN: u32 = 4;
+
+/// A heatmap of the per-pixel difference between two frames of the same size.
+///
+/// Identical pixels are black and the larger the change, the hotter the color, from red to yellow to white.
+/// Pixels that only exist in `current` count as completely changed.
+pub fn difference(previous: &ImgVec<RGBA8>, current: &ImgVec<RGBA8>) -> ImgVec<RGBA8> {
+    let (width, height) = (current.width(), current.height());
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let a = current[(x, y)];
+            let change = if x < previous.width() && y < previous.height() {
+                let b = previous[(x, y)];
+                let channels = [(a.r, b.r), (a.g, b.g), (a.b, b.b), (a.a, b.a)];
+                channels
+                    .iter()
+                    .map(|&(a, b)| (a as i32 - b as i32).abs() as u32)
+                    .max()
+                    .unwrap()
+            } else {
+                255
+            };
+            pixels.push(heat((change * DIFFERENCE_GAIN).min(255) as f64 / 255.0));
+        }
+    }
+    ImgVec::new(pixels, width, height)
+}
+
+/// Maps `t` from 0 to 1 onto black, red, yellow and finally white.
+fn heat(t: f64) -> RGBA8 {
+    let channel = |offset: f64| ((3.0 * t - offset).max(0.0).min(1.0) * 255.0).round() as u8;
+    RGBA8::new(channel(0.0), channel(1.0), channel(2.0), 255)
+}
+
+/// Returns the range of source pixels covered by the `index`th of `count` target pixels.
+fn span(index: usize, count: usize, total: usize) -> (usize, usize) {
+    let start = index * total / count;
+    let end = ((index + 1) * total / count).max(start + 1);
+    (start, end)
 }
@@ -0,0 +1,164 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! The protocol spoken between Slark instances over the local socket.
+//!
+//! Every message is framed as a version byte, a little-endian `u32` length, and that many bytes of JSON.
+
+use std::io::{self, prelude::*};
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// Bumped whenever `Message` changes in an incompatible way.
+pub const PROTOCOL_VERSION: u8 = 1;
+
+/// Messages larger than this are considered garbage.
+const MAX_MESSAGE_LEN: u32 = 16 * 1024 * 1024;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub enum Message {
+    AddImages(Vec<PathBuf>),
+    OpenProject(PathBuf),
+    Ping,
+    /// Reply to a successfully handled message.
+    Ack,
+    /// Reply to a message that could not be handled.
+    Error(String),
+}
+
+pub fn write_message(writer: &mut impl Write, message: &Message) -> io::Result<()> {
+    let json = serde_json::to_vec(message).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+    writer.write_all(&[PROTOCOL_VERSION])?;
+    writer.write_all(&(json.len() as u32).to_le_bytes())?;
+    writer.write_all(&json)?;
+    writer.flush()
+}
+
+/// Read the next message. Returns `Ok(None)` when the other side has cleanly closed the connection.
+pub fn read_message(reader: &mut impl Read) -> io::Result<Option<Message>> {
+    let mut version = [0u8; 1];
+    match reader.read_exact(&mut version) {
+        Ok(()) => (),
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(error) => return Err(error),
+    }
+    if version[0] != PROTOCOL_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Incompatible Slark instance, protocol version {} instead of {}",
+                version[0], PROTOCOL_VERSION
+            ),
+        ));
+    }
+
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)?;
+    let len = u32::from_le_bytes(len);
+    if len > MAX_MESSAGE_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Message is too large: {} bytes", len),
+        ));
+    }
+
+    let mut json = vec![0u8; len as usize];
+    reader.read_exact(&mut json)?;
+    serde_json::from_slice(&json)
+        .map(Some)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    use interprocess::local_socket::{LocalSocketListener, LocalSocketStream};
+
+    /// Both ends of a fresh local socket connection, the connecting one first.
+    fn socket_pair() -> (LocalSocketStream, LocalSocketStream) {
+        let name = format!("slark-ipc-{}.sock", std::process::id());
+        let pipe_name = if cfg!(windows) {
+            name
+        } else {
+            env::temp_dir().join(name).to_string_lossy().into_owned()
+        };
+        let listener = LocalSocketListener::bind(pipe_name.as_str()).unwrap();
+        let client = LocalSocketStream::connect(pipe_name.as_str()).unwrap();
+        let server = listener.accept().unwrap();
+        if !cfg!(windows) {
+            let _ = fs::remove_file(&pipe_name);
+        }
+        (client, server)
+    }
+
+    fn frame(version: u8, len: u32) -> Vec<u8> {
+        let mut bytes = vec![version];
+        bytes.extend_from_slice(&len.to_le_bytes());
+        bytes.extend_from_slice(b"\"Ping\"");
+        bytes
+    }
+
+    #[test]
+    fn every_message_survives_the_socket() {
+        let (mut client, mut server) = socket_pair();
+        let messages = vec![
+            Message::AddImages(vec![PathBuf::from("/images/a.gif"), PathBuf::from("/images/b c.png")]),
+            Message::OpenProject(PathBuf::from("/projects/scene.ark")),
+            Message::Ping,
+            Message::Ack,
+            Message::Error(String::from("No such file")),
+        ];
+        for message in messages {
+            write_message(&mut client, &message).unwrap();
+            assert_eq!(read_message(&mut server).unwrap(), Some(message));
+        }
+        // Replies go the other way over the same connection
+        write_message(&mut server, &Message::Ack).unwrap();
+        assert_eq!(read_message(&mut client).unwrap(), Some(Message::Ack));
+    }
+
+    #[test]
+    fn a_clean_end_is_not_an_error() {
+        assert_eq!(read_message(&mut io::empty()).unwrap(), None);
+        let mut bytes = Vec::new();
+        write_message(&mut bytes, &Message::Ping).unwrap();
+        let mut reader = &bytes[..];
+        assert_eq!(read_message(&mut reader).unwrap(), Some(Message::Ping));
+        assert_eq!(read_message(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn foreign_versions_and_huge_lengths_are_rejected() {
+        let wrong_version = frame(PROTOCOL_VERSION + 1, 6);
+        let error = read_message(&mut &wrong_version[..]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let too_long = frame(PROTOCOL_VERSION, MAX_MESSAGE_LEN + 1);
+        let error = read_message(&mut &too_long[..]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        // The same frame with an honest length is fine
+        let honest = frame(PROTOCOL_VERSION, 6);
+        assert_eq!(read_message(&mut &honest[..]).unwrap(), Some(Message::Ping));
+    }
+}
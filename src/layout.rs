@@ -0,0 +1,172 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Layout interchange files for scripts, in either JSON or CSV form.
+//!
+//! Unlike the project file these only contain placement information and are matched to images by path.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use druid::kurbo::Point;
+use druid::{FileDialogOptions, FileSpec};
+use serde::{Deserialize, Serialize};
+
+use crate::project::Project;
+use crate::ui::Zoom;
+
+const LAYOUT_JSON_FILE_TYPE: FileSpec = FileSpec::new("Layout JSON", &["json"]);
+const LAYOUT_CSV_FILE_TYPE: FileSpec = FileSpec::new("Layout CSV", &["csv"]);
+
+const CSV_HEADER: &str = "path,x,y,scale,layer";
+
+#[derive(Serialize, Deserialize)]
+pub struct LayoutEntry {
+    path: PathBuf,
+    x: f64,
+    y: f64,
+    scale: f64,
+    layer: usize,
+}
+
+pub fn file_dialog_options() -> FileDialogOptions {
+    FileDialogOptions::new()
+        .allowed_types(vec![LAYOUT_JSON_FILE_TYPE, LAYOUT_CSV_FILE_TYPE])
+        .default_type(LAYOUT_JSON_FILE_TYPE)
+}
+
+fn is_csv(path: &Path) -> bool {
+    path.extension().map_or(false, |ext| ext.eq_ignore_ascii_case("csv"))
+}
+
+/// The placement of every image that comes from a file, as embedded images have no path to match them by.
+pub fn entries(project: &Project) -> Vec<LayoutEntry> {
+    let layers = project.layers();
+    project
+        .images()
+        .iter()
+        .filter_map(|image| {
+            Some(LayoutEntry {
+                path: project.full_path(image)?,
+                x: image.origin().x,
+                y: image.origin().y,
+                scale: image.zoom().scale_factor(),
+                layer: layers.iter().position(|&id| id == image.id()).unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+pub fn export(project: &Project, path: &Path) -> Result<(), String> {
+    let entries = entries(project);
+    let contents = if is_csv(path) {
+        let mut csv = String::from(CSV_HEADER);
+        csv.push('\n');
+        for entry in &entries {
+            csv.push_str(&format!(
+                "\"{}\",{},{},{},{}\n",
+                entry.path.to_string_lossy().replace('"', "\"\""),
+                entry.x,
+                entry.y,
+                entry.scale,
+                entry.layer
+            ));
+        }
+        csv
+    } else {
+        serde_json::to_string_pretty(&entries).map_err(|error| error.to_string())?
+    };
+    fs::write(path, contents).map_err(|error| format!("Failed to write {}: {}", path.display(), error))
+}
+
+/// Apply the layout file at `path` to matching images.
+///
+/// Returns a description of every entry that could not be applied.
+pub fn apply(project: &mut Project, path: &Path) -> Result<Vec<String>, String> {
+    let contents = fs::read_to_string(path).map_err(|error| format!("Failed to read {}: {}", path.display(), error))?;
+    let mut problems = Vec::new();
+    let entries = if is_csv(path) {
+        let mut entries = Vec::new();
+        for (line_number, line) in contents.lines().enumerate().skip(1) {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match parse_csv_line(line) {
+                Some(entry) => entries.push(entry),
+                None => problems.push(format!("Line {} is malformed", line_number + 1)),
+            }
+        }
+        entries
+    } else {
+        serde_json::from_str::<Vec<LayoutEntry>>(&contents).map_err(|error| error.to_string())?
+    };
+
+    for entry in entries {
+        let image_id = project.find(&entry.path).or_else(|| {
+            project
+                .images()
+                .iter()
+                .find(|image| project.full_path(image).as_ref() == Some(&entry.path))
+                .map(|image| image.id())
+        });
+        match image_id {
+            Some(image_id) => {
+                project.set_origin(image_id, Point::new(entry.x, entry.y));
+                project.set_zoom(image_id, Zoom::from_scale_factor(entry.scale));
+            }
+            None => problems.push(format!("No image matches {}", entry.path.display())),
+        }
+    }
+    Ok(problems)
+}
+
+fn parse_csv_line(line: &str) -> Option<LayoutEntry> {
+    // The path is the only field that can contain commas, so it's quoted
+    let (path, rest) = if let Some(quoted) = line.strip_prefix('"') {
+        let mut path = String::new();
+        let mut chars = quoted.char_indices();
+        let mut end = None;
+        while let Some((i, ch)) = chars.next() {
+            if ch == '"' {
+                if quoted[i + 1..].starts_with('"') {
+                    path.push('"');
+                    chars.next();
+                } else {
+                    end = Some(i + 1);
+                    break;
+                }
+            } else {
+                path.push(ch);
+            }
+        }
+        (path, quoted[end?..].strip_prefix(',')?)
+    } else {
+        let (path, rest) = line.split_once(',')?;
+        (String::from(path), rest)
+    };
+    let mut fields = rest.split(',').map(|field| field.trim());
+    let entry = LayoutEntry {
+        path: PathBuf::from(path),
+        x: fields.next()?.parse().ok()?,
+        y: fields.next()?.parse().ok()?,
+        scale: fields.next()?.parse().ok()?,
+        layer: fields.next()?.parse().ok()?,
+    };
+    Some(entry)
+}
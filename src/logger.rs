@@ -0,0 +1,89 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! The logger writes to the console like `env_logger` does, but also keeps the most recent lines
+//! around so that they can be shown in the debug console inside the app.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use log::{Log, Metadata, Record};
+
+/// How many lines to keep for the debug console.
+const CAPACITY: usize = 500;
+
+static LINES: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+/// Total number of lines logged, used to detect new lines.
+static LINE_COUNT: AtomicU64 = AtomicU64::new(0);
+
+struct Logger {
+    console: env_logger::Logger,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.console.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        self.console.log(record);
+
+        let mut lines = LINES.lock().unwrap();
+        if lines.len() == CAPACITY {
+            lines.pop_front();
+        }
+        lines.push_back(format!("{:<5} {}", record.level(), record.args()));
+        LINE_COUNT.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn flush(&self) {
+        self.console.flush();
+    }
+}
+
+/// Install the logger. The filter can be configured with the `RUST_LOG` environment variable.
+pub fn init() {
+    let console = env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).build();
+    log::set_max_level(console.filter());
+    if let Err(error) = log::set_boxed_logger(Box::new(Logger { console })) {
+        eprintln!("Failed to install the logger: {}", error);
+    }
+}
+
+/// Returns the total number of lines logged so far, including the ones no longer kept.
+pub fn line_count() -> u64 {
+    LINE_COUNT.load(Ordering::Relaxed)
+}
+
+/// Returns up to `count` of the kept lines, ending `skip` lines before the most recent one.
+pub fn lines(count: usize, skip: usize) -> Vec<String> {
+    let lines = LINES.lock().unwrap();
+    let end = lines.len().saturating_sub(skip);
+    let start = end.saturating_sub(count);
+    lines.range(start..end).cloned().collect()
+}
+
+/// Returns the number of lines currently kept.
+pub fn kept_line_count() -> usize {
+    LINES.lock().unwrap().len()
+}
@@ -17,47 +17,117 @@
     along with this program.  If not, see <http://www.gnu.org/licenses/>.
 */
 
-use std::env;
+use std::process;
 use std::sync::mpsc;
 
-use druid::{AppLauncher, LocalizedString, WindowDesc};
+use clap::Parser;
+use druid::AppLauncher;
+use log::error;
 
+mod args;
+mod capture;
+mod composite;
+mod delegate;
 mod formats;
+mod fuzzy;
+mod i18n;
 mod image;
+mod ipc;
+mod layout;
+mod logger;
 
 mod ui;
-use ui::ui_root;
+use ui::main_window;
 
 mod pool;
 mod project;
+mod sequence;
+mod series;
+mod settings;
+mod thumbnails;
+mod watcher;
 
 fn main() {
-    let filenames: Vec<String> = env::args().skip(1).collect();
+    // Invalid arguments exit right here, before any window or socket work
+    let mut args = args::Args::parse();
+
+    logger::init();
+    formats::install_panic_hook();
+
+    let settings = settings::init();
+    i18n::init(settings.language.as_deref());
+    ui::theme::set_dark(settings.dark_theme);
+    ui::set_zoom_limits(settings.min_zoom, settings.max_zoom);
+
+    if let Some(poster) = &args.poster {
+        match thumbnails::poster(&poster[0], &poster[1], args.size as usize) {
+            Ok(()) => process::exit(0),
+            Err(error) => {
+                eprintln!("{}", error);
+                process::exit(1);
+            }
+        }
+    }
+
+    args.expand_patterns();
+    args.expand_directories();
+
+    let mut launches = args.launches();
+
+    if let Some(path) = &args.export {
+        if launches.len() > 1 {
+            eprintln!("--export takes a single project");
+            process::exit(2);
+        }
+        match launches[0].project().and_then(|project| layout::export(&project, path)) {
+            Ok(()) => process::exit(0),
+            Err(error) => {
+                eprintln!("{}", error);
+                process::exit(1);
+            }
+        }
+    }
 
     let (sender, receiver) = mpsc::channel();
+    let windows = pool::WindowTracker::default();
+
+    if let Some(exit_code) = pool::initialize(receiver, windows.clone(), &args) {
+        process::exit(exit_code);
+    }
 
-    let exit = pool::initialize(receiver, &filenames);
-    if exit {
-        println!("Pool initialization requested immediate application exit.");
-        return;
+    if args.paths.is_empty() && settings.reopen_last_session {
+        if let Some(path) = settings.last_session() {
+            launches = args::classify(&[path.to_string_lossy().into_owned()]);
+        }
     }
 
-    let window = WindowDesc::<u64>::new(ui_root(filenames))
-        .title(LocalizedString::new("app_title").with_placeholder("Slark".to_string()))
-        //.window_size((400.0, 300.0))
-        //.with_min_size((300.0, 200.0));
-        .window_size((1024.0, 768.0))
-        .with_min_size((320.0, 240.0));
-    let launcher = AppLauncher::with_window(window).log_to_console();
+    // Every project gets a window of its own
+    let mut main_windows = launches.iter().map(|launch| {
+        let project = launch.project().unwrap_or_else(|error| {
+            ui::post(ui::ToastMessage::error(error));
+            project::Project::new()
+        });
+        main_window(&args, project)
+    });
+    let first_window = main_windows.next().expect("there is always at least one launch");
+    let launcher = AppLauncher::with_window(first_window)
+        .delegate(delegate::Delegate::new(windows, main_windows.collect()))
+        .configure_env(|env, _data| ui::theme::current().apply(env));
 
     let event_sink = launcher.get_external_handle();
-
-    match sender.send(event_sink) {
-        Ok(_) => (),
-        Err(error) => {
-            eprintln!("Failed to send event sink: {}", error);
+    ui::set_toast_event_sink(event_sink.clone());
+
+    // An independent instance doesn't listen for other instances
+    if !args.new_instance {
+        match sender.send(event_sink) {
+            Ok(_) => (),
+            Err(error) => {
+                error!("Failed to send event sink: {}", error);
+            }
         }
     }
 
     launcher.launch(0).expect("launch failed");
+
+    pool::cleanup();
 }
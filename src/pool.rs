@@ -17,86 +17,539 @@
     along with this program.  If not, see <http://www.gnu.org/licenses/>.
 */
 
-use std::io::{self, prelude::*, BufReader};
-use std::sync::mpsc::Receiver;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::{Duration, Instant};
 
-use druid::ExtEventSink;
+use druid::{Command, ExtEventSink, Selector, Target, WindowId};
 use interprocess::local_socket::{LocalSocketListener, LocalSocketStream};
+use log::{error, info, warn};
+
+use crate::args::{is_project, Args};
+use crate::ipc::{read_message, write_message, Message};
 
 fn handle_error(conn: io::Result<LocalSocketStream>) -> Option<LocalSocketStream> {
     match conn {
         Ok(val) => Some(val),
         Err(error) => {
-            eprintln!("Incoming connection failed: {}", error);
+            error!("Incoming connection failed: {}", error);
             None
         }
     }
 }
 
-const PIPE_NAME: &str = "/tmp/slark.sock";
+/// Submitted by a window's surface so that IPC commands can be targeted at it.
+pub const COMMAND_REGISTER_WINDOW: Selector<WindowId> = Selector::new("slark.register_window");
 
-/// Application should exit when this function returns `true`.
-pub fn initialize(receiver: Receiver<ExtEventSink>, filenames: &[String]) -> bool {
-    // Attempt to connect to an existing Slark instance
-    let conn = LocalSocketStream::connect(PIPE_NAME);
+/// Tracks which window should receive the commands coming from other instances.
+///
+/// The most recently registered window is the target. Windows register again whenever they are
+/// interacted with, so this ends up being the most recently used window.
+/// Requests that arrive while there is no window are queued until one registers.
+#[derive(Clone, Default)]
+pub struct WindowTracker {
+    state: Arc<Mutex<WindowTrackerState>>,
+}
 
-    match conn {
-        Ok(mut conn) => {
-            if filenames.len() > 0 {
-                // TODO: Add support for more than one filename
-                conn.write_all(filenames[0].as_bytes())
-                    .expect("Couldn't write the filename");
-                conn.write_all(b"\n").expect("Couldn't write the newline");
-                /*
-                let mut conn = BufReader::new(conn);
-                let mut buffer = String::new();
-                conn.read_line(&mut buffer).expect("couldn't read");
-                println!("Server answered: {}", buffer);
-                */
-                return true;
-            }
-        }
-        Err(error) => match error.kind() {
-            io::ErrorKind::NotFound => {
+#[derive(Default)]
+struct WindowTrackerState {
+    windows: Vec<WindowId>, // The most recently used window is last
+    queue: Vec<Request>,
+}
+
+enum Request {
+    AddImages(Vec<PathBuf>),
+    OpenProject(PathBuf),
+    Raise,
+}
+
+impl Request {
+    fn into_command(self, window: WindowId) -> Command {
+        match self {
+            Request::AddImages(paths) => Command::new(crate::ui::COMMAND_ADD_IMAGE, paths, Target::Window(window)),
+            Request::OpenProject(path) => Command::new(crate::ui::COMMAND_OPEN_PROJECT, path, Target::Window(window)),
+            Request::Raise => Command::new(crate::ui::COMMAND_RAISE_WINDOW, (), Target::Window(window)),
+        }
+    }
+}
+
+impl WindowTracker {
+    /// Make `window` the target, returning any queued commands which should now be submitted to it.
+    pub fn register(&self, window: WindowId) -> Vec<Command> {
+        let mut state = self.state.lock().unwrap();
+        state.windows.retain(|&id| id != window);
+        state.windows.push(window);
+        state
+            .queue
+            .drain(..)
+            .map(|request| request.into_command(window))
+            .collect()
+    }
+
+    /// Forget about `window`, making the previously used window the target again.
+    ///
+    /// Returns `true` if `window` was the last registered window.
+    pub fn unregister(&self, window: WindowId) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let registered = state.windows.contains(&window);
+        state.windows.retain(|&id| id != window);
+        registered && state.windows.is_empty()
+    }
+
+    /// The most recently used window, if any.
+    pub fn current(&self) -> Option<WindowId> {
+        self.state.lock().unwrap().windows.last().copied()
+    }
+
+    fn submit(&self, event_sink: &ExtEventSink, request: Request) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        let window = match state.windows.last() {
+            Some(&window) => Target::Window(window),
+            None => {
+                state.queue.push(request);
+                return Ok(());
+            }
+        };
+        let result = match request {
+            Request::AddImages(paths) => event_sink.submit_command(crate::ui::COMMAND_ADD_IMAGE, paths, window),
+            Request::OpenProject(path) => event_sink.submit_command(crate::ui::COMMAND_OPEN_PROJECT, path, window),
+            Request::Raise => event_sink.submit_command(crate::ui::COMMAND_RAISE_WINDOW, (), window),
+        };
+        result.map_err(|error| error.to_string())
+    }
+}
+
+/// Whether this instance owns the socket.
+static PRIMARY: AtomicBool = AtomicBool::new(false);
+
+/// The per-user name of the socket that the primary instance listens on.
+fn pipe_name() -> String {
+    if cfg!(windows) {
+        pipe_name_for(None, env::var_os("USERNAME"))
+    } else {
+        pipe_name_for(env::var_os("XDG_RUNTIME_DIR"), env::var_os("USER"))
+    }
+}
+
+fn pipe_name_for(runtime_dir: Option<OsString>, user: Option<OsString>) -> String {
+    let user = user
+        .map(|user| user.to_string_lossy().into_owned())
+        .filter(|user| !user.is_empty());
+    if cfg!(windows) {
+        // Named pipes live in a namespace shared by all users
+        match user {
+            Some(user) => format!("slark-{}.sock", user),
+            None => String::from("slark.sock"),
+        }
+    } else {
+        match runtime_dir.filter(|dir| !dir.is_empty()) {
+            // The runtime directory is already private to the user
+            Some(dir) => PathBuf::from(dir).join("slark.sock").to_string_lossy().into_owned(),
+            None => match user {
+                Some(user) => format!("/tmp/slark-{}.sock", user),
+                None => String::from("/tmp/slark.sock"),
+            },
+        }
+    }
+}
+
+/// Remove the socket file if we're the primary instance. Call this on clean shutdown.
+pub fn cleanup() {
+    if PRIMARY.load(Ordering::SeqCst) && !cfg!(windows) {
+        let _ = fs::remove_file(pipe_name());
+    }
+}
+
+/// How long to wait for the primary instance to acknowledge our messages.
+const ACK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How many more times to look for the primary instance when another instance is just becoming it.
+const CONNECT_RETRIES: u32 = 5;
+
+/// How long to wait before the first retry, with every retry waiting a bit longer.
+const CONNECT_BACKOFF: Duration = Duration::from_millis(20);
+
+/// What this instance turned out to be after looking for the primary instance.
+enum Role {
+    /// Listening for the other instances.
+    Primary(LocalSocketListener),
+    /// Handed everything over to the primary instance and should exit with the code.
+    Forwarded(i32),
+    /// Running on its own, without listening for the other instances.
+    Standalone,
+}
+
+/// Application should exit with the returned exit code when this function returns `Some`.
+///
+/// With `--new-instance` this instance neither forwards its files nor listens for other instances.
+pub fn initialize(receiver: Receiver<ExtEventSink>, windows: WindowTracker, args: &Args) -> Option<i32> {
+    if args.new_instance {
+        return None;
+    }
+
+    match find_primary(&pipe_name(), &messages(&args.paths)) {
+        Role::Primary(listener) => {
+            claim_primacy(receiver, windows, listener);
+            None
+        }
+        Role::Forwarded(exit_code) => Some(exit_code),
+        Role::Standalone => None,
+    }
+}
+
+/// Connect to the primary instance and forward the `messages` to it, or become the primary instance.
+///
+/// Instances that start at the same time may all find no primary instance and race to become it.
+/// The losers of that race look for the winner again, giving it a moment to start listening.
+fn find_primary(pipe_name: &str, messages: &[Message]) -> Role {
+    for attempt in 0..=CONNECT_RETRIES {
+        if attempt > 0 {
+            thread::sleep(CONNECT_BACKOFF * attempt);
+        }
+
+        // Attempt to connect to an existing Slark instance
+        match LocalSocketStream::connect(pipe_name) {
+            Ok(conn) => {
+                if messages.is_empty() {
+                    return Role::Standalone;
+                }
+                match forward(conn, messages) {
+                    Some(exit_code) => return Role::Forwarded(exit_code),
+                    // The primary may have exited between our connect and write, but it may also just have
+                    // hung up on us. Only a fresh connection attempt can tell whether its role is free.
+                    None => continue,
+                }
+            }
+            Err(error) => match error.kind() {
                 // Not found? Let's be primary!
-                claim_primacy(receiver);
+                io::ErrorKind::NotFound => (),
+                // The socket file of an instance that is still starting up refuses connections for a moment
+                io::ErrorKind::ConnectionRefused if !cfg!(windows) && attempt == 0 => continue,
+                io::ErrorKind::ConnectionRefused if !cfg!(windows) => {
+                    // The socket file is a leftover from an instance that didn't exit cleanly
+                    info!("Removing stale socket {}", pipe_name);
+                    if let Err(error) = fs::remove_file(pipe_name) {
+                        error!("Failed to remove stale socket {}: {}", pipe_name, error);
+                        return Role::Standalone;
+                    }
+                }
+                _ => {
+                    error!("Failed to connect to the primary Slark instance. {}", error);
+                    return Role::Standalone;
+                }
+            },
+        }
+
+        match LocalSocketListener::bind(pipe_name) {
+            Ok(listener) => return Role::Primary(listener),
+            // Another instance got there first, a named pipe that already exists is reported as access denied
+            Err(error)
+                if error.kind() == io::ErrorKind::AddrInUse
+                    || (cfg!(windows) && error.kind() == io::ErrorKind::PermissionDenied) =>
+            {
+                info!("Another Slark instance is becoming the primary one, looking for it again");
             }
-            _ => {
-                eprintln!("Failed to connect to the primary Slark instance. {}", error);
+            Err(error) => {
+                error!("Failed to listen on {}: {}", pipe_name, error);
+                return Role::Standalone;
             }
-        },
+        }
     }
+    warn!("Couldn't reach the primary Slark instance, continuing on our own.");
+    Role::Standalone
+}
 
-    false
+/// Send the messages to the primary instance and wait for it to acknowledge them.
+///
+/// Returns the exit code for this instance, or `None` if the connection was closed
+/// before anything was handled, in which case the caller should look for the primary instance again.
+fn forward(conn: LocalSocketStream, messages: &[Message]) -> Option<i32> {
+    let mut conn = BufReader::new(conn);
+    for message in messages {
+        match write_message(conn.get_mut(), message) {
+            Ok(()) => (),
+            // Nothing the primary instance did, like a path that can't be encoded
+            Err(error) if error.kind() == io::ErrorKind::InvalidData => {
+                error!("Failed to encode the message for the primary Slark instance: {}", error);
+                return Some(1);
+            }
+            Err(error) => {
+                error!("Failed to send to the primary Slark instance: {}", error);
+                return None;
+            }
+        }
+    }
+
+    // Read the replies on a separate thread so that a stuck primary can't hang us
+    let expected = messages.len();
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        for _ in 0..expected {
+            if sender.send(read_message(&mut conn)).is_err() {
+                break;
+            }
+        }
+    });
+
+    let deadline = Instant::now() + ACK_TIMEOUT;
+    let mut exit_code = 0;
+    for received in 0..expected {
+        match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok(Ok(Some(Message::Ack))) => (),
+            Ok(Ok(Some(Message::Error(error)))) => {
+                error!("The primary Slark instance reported an error: {}", error);
+                exit_code = 1;
+            }
+            Ok(Ok(Some(message))) => {
+                error!("Unexpected reply from the primary Slark instance: {:?}", message);
+                exit_code = 1;
+            }
+            Ok(Ok(None)) if received == 0 => {
+                warn!("The primary Slark instance closed the connection, looking for it again.");
+                return None;
+            }
+            Ok(Ok(None)) => {
+                error!("The primary Slark instance closed the connection without replying.");
+                return Some(1);
+            }
+            Ok(Err(error)) => {
+                error!("Failed to read the reply of the primary Slark instance: {}", error);
+                return Some(1);
+            }
+            Err(_) => {
+                error!("Timed out waiting for the primary Slark instance to reply.");
+                return Some(1);
+            }
+        }
+    }
+    if exit_code == 0 {
+        info!("Handed over to the already running Slark instance.");
+    }
+    Some(exit_code)
 }
 
-fn claim_primacy(receiver: Receiver<ExtEventSink>) {
-    let listener = LocalSocketListener::bind(PIPE_NAME).expect("Couldn't bind");
+fn claim_primacy(receiver: Receiver<ExtEventSink>, windows: WindowTracker, listener: LocalSocketListener) {
+    PRIMARY.store(true, Ordering::SeqCst);
 
     thread::spawn(move || {
         match receiver.recv() {
             Ok(event_sink) => {
                 for conn in listener.incoming().filter_map(handle_error) {
-                    //conn.write_all(b"Hello from server!\n").expect("Couldn't write");
+                    // Read messages until the other side closes the connection
                     let mut conn = BufReader::new(conn);
-                    let mut buffer = String::new();
-                    match conn.read_line(&mut buffer) {
-                        Ok(line_len) => {
-                            let filename = String::from(buffer.trim());
-                            event_sink
-                                .submit_command(crate::ui::COMMAND_ADD_IMAGE, filename, druid::Target::Global)
-                                .expect("Couldn't submit command");
-                        }
-                        Err(error) => {
-                            eprintln!("Couldn't read line: {}", error);
+                    loop {
+                        match read_message(&mut conn) {
+                            Ok(Some(message)) => {
+                                let reply = match handle_message(&event_sink, &windows, message) {
+                                    Ok(()) => Message::Ack,
+                                    Err(error) => Message::Error(error),
+                                };
+                                if let Err(error) = write_message(conn.get_mut(), &reply) {
+                                    error!("Couldn't write reply: {}", error);
+                                    break;
+                                }
+                            }
+                            Ok(None) => break,
+                            Err(error) => {
+                                error!("Couldn't read message: {}", error);
+                                // Tell the other side why, so that it doesn't mistake us for gone
+                                if error.kind() == io::ErrorKind::InvalidData {
+                                    let _ = write_message(conn.get_mut(), &Message::Error(error.to_string()));
+                                }
+                                break;
+                            }
                         }
                     }
                 }
             }
             Err(error) => {
-                eprintln!("Failed to get event sink: {}", error);
+                error!("Failed to get event sink: {}", error);
             }
         }
     });
 }
+
+/// Convert the command line filenames into messages for the primary instance.
+///
+/// The paths are made absolute because the primary instance has a different working directory.
+fn messages(filenames: &[String]) -> Vec<Message> {
+    let mut images = Vec::new();
+    let mut messages = Vec::new();
+    for filename in filenames {
+        if is_project(filename) {
+            messages.push(Message::OpenProject(absolute(filename)));
+        } else {
+            images.push(absolute(filename));
+        }
+    }
+    if !images.is_empty() {
+        messages.push(Message::AddImages(images));
+    }
+    messages
+}
+
+fn absolute(filename: &str) -> PathBuf {
+    let path = PathBuf::from(filename);
+    match fs::canonicalize(&path) {
+        Ok(path) => path,
+        // Let the primary instance report the missing file, but against the right directory
+        Err(_) => match env::current_dir() {
+            Ok(dir) => dir.join(path),
+            Err(_) => path,
+        },
+    }
+}
+
+/// Check that a path received from another instance can be used as is.
+fn resolvable(path: &Path) -> Result<(), String> {
+    if !path.is_absolute() {
+        Err(format!("Path is not absolute: {}", path.display()))
+    } else if !path.exists() {
+        Err(format!("File not found: {}", path.display()))
+    } else {
+        Ok(())
+    }
+}
+
+fn handle_message(event_sink: &ExtEventSink, windows: &WindowTracker, message: Message) -> Result<(), String> {
+    match message {
+        Message::AddImages(paths) => {
+            // Add what we can and report the rest
+            let mut errors = Vec::new();
+            let mut images = Vec::with_capacity(paths.len());
+            for path in paths {
+                match resolvable(&path) {
+                    Ok(()) => images.push(path),
+                    Err(error) => errors.push(error),
+                }
+            }
+            if !images.is_empty() {
+                windows.submit(event_sink, Request::AddImages(images))?;
+            }
+            windows.submit(event_sink, Request::Raise)?;
+            if errors.is_empty() {
+                Ok(())
+            } else {
+                Err(errors.join("\n"))
+            }
+        }
+        Message::OpenProject(path) => {
+            resolvable(&path)?;
+            windows.submit(event_sink, Request::OpenProject(path))?;
+            windows.submit(event_sink, Request::Raise)
+        }
+        Message::Ping => Ok(()),
+        Message::Ack | Message::Error(_) => Err(String::from("Replies are not valid requests")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn racing_instances_elect_one_primary() {
+        let name = format!("slark-race-{}.sock", std::process::id());
+        let pipe_name = if cfg!(windows) {
+            name
+        } else {
+            env::temp_dir().join(name).to_string_lossy().into_owned()
+        };
+        let racers: Vec<_> = (0..2)
+            .map(|_| {
+                let pipe_name = pipe_name.clone();
+                thread::spawn(move || find_primary(&pipe_name, &[]))
+            })
+            .collect();
+        // Keep the listener alive until both have finished, so that the loser can find it
+        let roles: Vec<Role> = racers.into_iter().map(|racer| racer.join().unwrap()).collect();
+        let primaries = roles.iter().filter(|role| matches!(role, Role::Primary(_))).count();
+        let standalones = roles.iter().filter(|role| matches!(role, Role::Standalone)).count();
+        assert_eq!((primaries, standalones), (1, 1));
+        if !cfg!(windows) {
+            let _ = fs::remove_file(&pipe_name);
+        }
+    }
+
+    #[test]
+    fn pipe_names_are_per_user() {
+        let some = |value: &str| Some(OsString::from(value));
+        if cfg!(windows) {
+            assert_eq!(pipe_name_for(None, some("kaur")), "slark-kaur.sock");
+            assert_eq!(pipe_name_for(None, some("")), "slark.sock");
+            assert_eq!(pipe_name_for(None, None), "slark.sock");
+        } else {
+            let in_runtime_dir = PathBuf::from("/run/user/1000").join("slark.sock");
+            assert_eq!(
+                pipe_name_for(some("/run/user/1000"), some("kaur")),
+                in_runtime_dir.to_string_lossy()
+            );
+            assert_eq!(
+                pipe_name_for(some("/run/user/1000"), None),
+                in_runtime_dir.to_string_lossy()
+            );
+            assert_eq!(pipe_name_for(None, some("kaur")), "/tmp/slark-kaur.sock");
+            assert_eq!(pipe_name_for(some(""), some("kaur")), "/tmp/slark-kaur.sock");
+            assert_eq!(pipe_name_for(some(""), some("")), "/tmp/slark.sock");
+            assert_eq!(pipe_name_for(None, None), "/tmp/slark.sock");
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_stale_socket_is_replaced() {
+        let dir = env::temp_dir().join(format!("slark-stale-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let pipe_name = dir.join("slark.sock").to_string_lossy().into_owned();
+        // A socket file that nobody listens on anymore, like after a crash
+        drop(std::os::unix::net::UnixListener::bind(&pipe_name).unwrap());
+        assert!(Path::new(&pipe_name).exists());
+
+        let role = find_primary(&pipe_name, &[]);
+        assert!(matches!(role, Role::Primary(_)));
+        drop(role);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// A primary instance that hangs up on every connection without replying.
+    #[cfg(unix)]
+    fn rude_primary(dir: &Path) -> String {
+        fs::create_dir_all(dir).unwrap();
+        let pipe_name = dir.join("slark.sock").to_string_lossy().into_owned();
+        let listener = LocalSocketListener::bind(pipe_name.as_str()).unwrap();
+        thread::spawn(move || for _ in listener.incoming() {});
+        pipe_name
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_primary_that_hangs_up_keeps_its_socket() {
+        let dir = env::temp_dir().join(format!("slark-hang-up-{}", std::process::id()));
+        let pipe_name = rude_primary(&dir);
+        let role = find_primary(&pipe_name, &[Message::Ping]);
+        assert!(matches!(role, Role::Standalone));
+        assert!(Path::new(&pipe_name).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unencodable_paths_fail_locally() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = env::temp_dir().join(format!("slark-unencodable-{}", std::process::id()));
+        let pipe_name = rude_primary(&dir);
+        let path = PathBuf::from(std::ffi::OsStr::from_bytes(b"/tmp/\xff.png"));
+        let role = find_primary(&pipe_name, &[Message::AddImages(vec![path])]);
+        assert!(matches!(role, Role::Forwarded(1)));
+        assert!(Path::new(&pipe_name).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -17,23 +17,54 @@
     along with this program.  If not, see <http://www.gnu.org/licenses/>.
 */
 
-use std::fs::File;
+//! The project model: which images are in a project, how they're placed, and how it's saved.
+//!
+//! Every image has an id, which is always its index in [`Project::images`].
+//! Removing an image moves the last image into the freed id, so ids are only stable
+//! while no images are removed. The paint order is kept separately in the layer tree,
+//! which refers to the images by id, so an id says nothing about where the image is painted.
+//!
+//! Images that never came from a file are embedded into the project. Their pixels are kept
+//! in memory and saved as PNG files into a directory next to the project file, see [`Source`].
+
+use std::collections::HashMap;
+use std::fs::{self, File};
 use std::io::prelude::*;
-use std::io::BufReader;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-use druid::kurbo::Point;
+use druid::kurbo::{Point, Rect, Size, Vec2};
+use druid::piet::Color;
 use druid::{FileDialogOptions, FileSpec};
+use imgref::ImgVec;
+use log::warn;
+use rgb::RGBA8;
 use serde::{Deserialize, Serialize};
 
-use crate::ui::Zoom;
+use crate::formats;
+use crate::i18n::tr_args;
+use crate::ui::{ColorKey, Tileize, TimeRemap, Zoom};
 
 const PROJECT_FILE_TYPE: FileSpec = FileSpec::new("Slark project", &["ark"]);
+const COMPACT_PROJECT_FILE_TYPE: FileSpec = FileSpec::new("Compact Slark project", &["arkb"]);
+const IMAGE_FILE_TYPE: FileSpec = FileSpec::new("Image", formats::EXTENSIONS);
 
 #[derive(Serialize, Deserialize)]
 pub struct Project {
     images: Vec<Image>,
-    layers: Vec<usize>,
+    layers: Vec<Layer>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    canvas: Option<Canvas>,
+    #[serde(default)]
+    duplicate_policy: DuplicatePolicy,
+    #[serde(default, skip_serializing_if = "TileSpacing::is_default")]
+    tile_spacing: TileSpacing,
+    #[serde(default, skip_serializing_if = "is_pixel_scale")]
+    physical_scale: bool,
+    #[serde(default, skip_serializing_if = "ImageDefaults::is_default")]
+    image_defaults: ImageDefaults,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    trash: Vec<Trashed>,
     #[serde(skip)]
     state: State,
 }
@@ -43,26 +74,58 @@ impl Project {
         Project {
             images: Vec::new(),
             layers: Vec::new(),
+            canvas: None,
+            duplicate_policy: DuplicatePolicy::default(),
+            tile_spacing: TileSpacing::default(),
+            physical_scale: false,
+            image_defaults: ImageDefaults::default(),
+            trash: Vec::new(),
             state: State::default(),
         }
     }
 
-    pub fn open(path: PathBuf) -> Project {
-        let file = File::open(&path).expect("Failed to open file");
-        let reader = BufReader::new(file);
-        let mut project: Project = serde_json::from_reader(reader).expect("Failed to read file");
+    /// Read the project file at `path`, which also becomes the base for relative image paths.
+    pub fn open(path: PathBuf) -> Result<Project, String> {
+        let bytes = fs::read(&path).map_err(|error| error.to_string())?;
+        let mut project = Project::decode(&bytes)?;
+        // A missing embedded image is reported by its view, just like a missing file
+        let dir = embedded_dir(&path);
+        for embedded_id in project.embedded_ids() {
+            match formats::read_frame(&embedded_path(&dir, embedded_id)) {
+                Ok(pixels) => {
+                    project.state.embedded.insert(embedded_id, Arc::new(pixels));
+                }
+                Err(error) => warn!("Failed to read embedded image {}: {}", embedded_id, error),
+            }
+        }
         project.state.path = Some(path);
-        project
+        Ok(project)
     }
 
+    /// All the images, indexed by their id.
     pub fn images(&self) -> &Vec<Image> {
         &self.images
     }
 
-    pub fn layers(&self) -> &Vec<usize> {
+    pub fn image(&self, image_id: usize) -> Option<&Image> {
+        self.images.get(image_id)
+    }
+
+    /// Image ids in paint order, with the layer tree flattened depth-first.
+    pub fn layers(&self) -> Vec<usize> {
+        let mut order = Vec::with_capacity(self.images.len());
+        flatten(&self.layers, &mut order);
+        order
+    }
+
+    pub fn layer_tree(&self) -> &[Layer] {
         &self.layers
     }
 
+    pub fn canvas(&self) -> Option<&Canvas> {
+        self.canvas.as_ref()
+    }
+
     pub fn dirty(&self) -> bool {
         self.state.dirty
     }
@@ -74,58 +137,354 @@ impl Project {
         }
     }
 
+    /// Picking the compact file type when saving writes the project in the compact encoding.
     pub fn file_dialog_options(&self) -> FileDialogOptions {
         FileDialogOptions::new()
-            .allowed_types(vec![PROJECT_FILE_TYPE])
+            .allowed_types(vec![PROJECT_FILE_TYPE, COMPACT_PROJECT_FILE_TYPE])
             .default_type(PROJECT_FILE_TYPE)
     }
 
-    pub fn save(&mut self, path: &Path) {
-        if let Ok(json) = serde_json::to_string(self) {
-            let mut file = File::create(path).expect("Failed to create file");
-            file.write_all(json.as_bytes()).expect("Failed to write file");
-            file.sync_all().expect("Failed to sync file");
-            self.state.dirty = false;
-            let path_changed = if let Some(current_path) = &self.state.path {
-                path != current_path
-            } else {
-                true
+    pub fn image_dialog_options(&self) -> FileDialogOptions {
+        FileDialogOptions::new()
+            .allowed_types(vec![IMAGE_FILE_TYPE])
+            .default_type(IMAGE_FILE_TYPE)
+    }
+
+    /// Write the project to `path`, in the encoding that the extension of `path` calls for.
+    pub fn save(&mut self, path: &Path) -> Result<(), String> {
+        // The embedded images go first, so that the project file never refers to ones that weren't written
+        self.save_embedded(path)?;
+        let bytes = self.encode(Encoding::from_path(path))?;
+        let mut file = File::create(path).map_err(|error| error.to_string())?;
+        file.write_all(&bytes).map_err(|error| error.to_string())?;
+        file.sync_all().map_err(|error| error.to_string())?;
+        self.state.dirty = false;
+        let path_changed = if let Some(current_path) = &self.state.path {
+            path != current_path
+        } else {
+            true
+        };
+        if path_changed {
+            self.state.path = Some(PathBuf::from(path));
+        }
+        Ok(())
+    }
+
+    fn encode(&self, encoding: Encoding) -> Result<Vec<u8>, String> {
+        match encoding {
+            Encoding::Json => serde_json::to_vec(self).map_err(|error| error.to_string()),
+            Encoding::Compact => {
+                let mut bytes = COMPACT_MAGIC.to_vec();
+                // Named fields keep the defaults and the skipped fields working just like in JSON
+                rmp_serde::encode::write_named(&mut bytes, self).map_err(|error| error.to_string())?;
+                Ok(bytes)
+            }
+        }
+    }
+
+    /// Read a project in either encoding, telling them apart by the magic bytes rather than the extension.
+    fn decode(bytes: &[u8]) -> Result<Project, String> {
+        if bytes.starts_with(COMPACT_MAGIC) {
+            rmp_serde::from_slice(&bytes[COMPACT_MAGIC.len()..]).map_err(|error| error.to_string())
+        } else {
+            serde_json::from_slice(bytes).map_err(|error| error.to_string())
+        }
+    }
+
+    /// Write the pixels of every embedded image into the directory next to the project file at `path`.
+    ///
+    /// The files of embedded images that are no longer in the project are deleted.
+    fn save_embedded(&self, path: &Path) -> Result<(), String> {
+        let embedded_ids = self.embedded_ids();
+        let dir = embedded_dir(path);
+        if !embedded_ids.is_empty() {
+            fs::create_dir_all(&dir).map_err(|error| error.to_string())?;
+        }
+        for &embedded_id in &embedded_ids {
+            if let Some(pixels) = self.state.embedded.get(&embedded_id) {
+                formats::save_frame(&embedded_path(&dir, embedded_id), pixels)?;
+            }
+        }
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => return Ok(()),
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            // Only our own files, anything else in there was put there by someone else
+            let embedded_id = match path.extension() {
+                Some(extension) if extension == "png" => path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .and_then(|stem| stem.parse::<u64>().ok()),
+                _ => None,
             };
-            if path_changed {
-                self.state.path = Some(PathBuf::from(path));
+            if let Some(embedded_id) = embedded_id {
+                if !embedded_ids.contains(&embedded_id) {
+                    if let Err(error) = fs::remove_file(&path) {
+                        warn!("Failed to delete {}: {}", path.display(), error);
+                    }
+                }
             }
         }
+        if embedded_ids.is_empty() {
+            // Only succeeds when nothing else is left in there
+            let _ = fs::remove_dir(&dir);
+        }
+        Ok(())
+    }
+
+    /// The ids of the pixels that the embedded images refer to, including the ones in the trash.
+    fn embedded_ids(&self) -> Vec<u64> {
+        self.images
+            .iter()
+            .chain(self.trash.iter().map(|trashed| &trashed.image))
+            .filter_map(|image| match image.source {
+                Source::Embedded(embedded_id) => Some(embedded_id),
+                Source::File(_) => None,
+            })
+            .collect()
+    }
+
+    /// The embedded id after all the ones in use, including those whose pixels couldn't be read.
+    fn next_embedded_id(&self) -> u64 {
+        self.embedded_ids()
+            .into_iter()
+            .chain(self.state.embedded.keys().copied())
+            .max()
+            .map_or(0, |embedded_id| embedded_id + 1)
+    }
+
+    /// The pixels of an embedded image, if they could be read.
+    pub fn embedded(&self, embedded_id: u64) -> Option<Arc<ImgVec<RGBA8>>> {
+        self.state.embedded.get(&embedded_id).cloned()
+    }
+
+    /// Turn the image paths into absolute ones, so that the project file can be saved anywhere.
+    pub fn make_paths_absolute(&mut self) {
+        let full_paths: Vec<Option<PathBuf>> = self
+            .images
+            .iter()
+            .chain(self.trash.iter().map(|trashed| &trashed.image))
+            .map(|image| self.full_path(image))
+            .collect();
+        let images = self
+            .images
+            .iter_mut()
+            .chain(self.trash.iter_mut().map(|trashed| &mut trashed.image));
+        for (image, full_path) in images.zip(full_paths) {
+            if let Some(full_path) = full_path {
+                image.source = Source::File(full_path.canonicalize().unwrap_or(full_path));
+            }
+        }
+    }
+
+    pub fn duplicate_policy(&self) -> DuplicatePolicy {
+        self.duplicate_policy
+    }
+
+    pub fn set_duplicate_policy(&mut self, duplicate_policy: DuplicatePolicy) {
+        if self.duplicate_policy != duplicate_policy {
+            self.duplicate_policy = duplicate_policy;
+            self.state.dirty = true;
+        }
+    }
+
+    /// Returns the path of `image` resolved relative to the project file location, unless it's embedded.
+    pub fn full_path(&self, image: &Image) -> Option<PathBuf> {
+        image.path().map(|path| self.resolve(path))
+    }
+
+    /// Returns `path` resolved relative to the project file location, the way image paths are stored.
+    fn resolve(&self, path: &Path) -> PathBuf {
+        match self.path().and_then(|path| path.parent()) {
+            Some(dir) => dir.join(path),
+            None => path.to_path_buf(),
+        }
+    }
+
+    /// Returns the id of the image which refers to the same file as `path`.
+    ///
+    /// A relative `path` is taken relative to the project file location, just like `add` would store it.
+    pub fn find(&self, path: &Path) -> Option<usize> {
+        let path = self.resolve(path).canonicalize().ok()?;
+        self.images
+            .iter()
+            .find(|image| {
+                self.full_path(image)
+                    .and_then(|full_path| full_path.canonicalize().ok())
+                    .as_ref()
+                    == Some(&path)
+            })
+            .map(|image| image.id)
     }
 
-    pub fn add(&mut self, path: PathBuf) {
+    /// Add a new image, unless the duplicate policy says to reuse an existing one.
+    ///
+    /// New images start out with the image defaults of the project.
+    pub fn add(&mut self, path: PathBuf) -> Addition {
+        let mut origin = Point::ZERO;
+        let mut duplicate = false;
+        if let Some(existing_id) = self.find(&path) {
+            match self.duplicate_policy {
+                DuplicatePolicy::FocusExisting => return Addition::Existing(existing_id),
+                DuplicatePolicy::AddWithOffset => {
+                    origin = self.images[existing_id].origin + Vec2::new(24.0, 24.0);
+                    duplicate = true;
+                }
+            }
+        }
+
+        let next_id = self.push(Source::File(path), origin, Zoom::default());
+        self.apply_image_defaults(next_id);
+        if duplicate {
+            Addition::Duplicate(next_id)
+        } else {
+            Addition::New(next_id)
+        }
+    }
+
+    /// Add a new image at `origin` with `zoom`, on top of all the layers, and return its id.
+    ///
+    /// Unlike `add` this ignores the duplicate policy, so the same file can be added any number of times.
+    pub fn add_with_placement(&mut self, path: PathBuf, origin: Point, zoom: Zoom) -> usize {
+        self.push(Source::File(path), origin, zoom)
+    }
+
+    /// Add pixels that don't come from a file at `origin` with `zoom`, on top of all the layers, and return its id.
+    ///
+    /// The pixels are saved along with the project.
+    pub fn add_embedded(&mut self, pixels: ImgVec<RGBA8>, origin: Point, zoom: Zoom) -> usize {
+        let embedded_id = self.next_embedded_id();
+        self.state.embedded.insert(embedded_id, Arc::new(pixels));
+        self.push(Source::Embedded(embedded_id), origin, zoom)
+    }
+
+    fn push(&mut self, source: Source, origin: Point, zoom: Zoom) -> usize {
         let next_id = self.images.len();
         self.images.push(Image {
             id: next_id,
-            path: path,
-            origin: Point::ZERO,
-            zoom: Zoom::default(),
+            source: source,
+            name: None,
+            origin: origin,
+            zoom: zoom,
+            visible: true,
+            locked: false,
+            pinned: false,
+            paused: false,
+            resume_frame: 0,
+            offset: Point::ZERO,
+            time_remap: TimeRemap::Linear,
+            color_key: None,
+            rotation: 0.0,
+            sync_group: None,
+            proxy: 1.0,
+            smooth: None,
+            checkerboard: false,
         });
-        self.layers.push(next_id);
+        self.layers.push(Layer::Image(next_id));
+        self.state.dirty = true;
+        next_id
+    }
+
+    /// Append all the images of `other` on top of the current layer stack.
+    ///
+    /// The image paths of `other` are resolved relative to its own location,
+    /// the images get fresh ids and their origins are moved by `offset`.
+    /// Embedded images bring their pixels along.
+    pub fn merge(&mut self, other: Project, offset: Vec2) {
+        let base_dir = other.path().and_then(|path| path.parent()).map(PathBuf::from);
+        let first_id = self.images.len();
+        let first_embedded_id = self.next_embedded_id();
+        let first_sync_group = self.next_sync_group();
+        let Project {
+            images,
+            mut layers,
+            mut state,
+            ..
+        } = other;
+        for mut image in images {
+            match &mut image.source {
+                Source::File(path) => {
+                    if let Some(base_dir) = &base_dir {
+                        *path = base_dir.join(&*path);
+                    }
+                }
+                Source::Embedded(embedded_id) => {
+                    if let Some(pixels) = state.embedded.remove(embedded_id) {
+                        self.state.embedded.insert(*embedded_id + first_embedded_id, pixels);
+                    }
+                    *embedded_id += first_embedded_id;
+                }
+            }
+            image.id += first_id;
+            image.origin += offset;
+            image.sync_group = image.sync_group.map(|group| group + first_sync_group);
+            self.images.push(image);
+        }
+        map_ids(&mut layers, &|id| id + first_id);
+        self.layers.extend(layers);
         self.state.dirty = true;
     }
 
     pub fn remove(&mut self, image_id: usize) {
-        if self.images.is_empty() || self.images.len() <= image_id {
+        if let Some(image) = self.detach(image_id) {
+            if let Source::Embedded(embedded_id) = image.source {
+                self.state.embedded.remove(&embedded_id);
+            }
+        }
+    }
+
+    /// Remove the image, keeping it in the trash so that it can be restored to where it was.
+    pub fn trash(&mut self, image_id: usize) {
+        let layer = locate(&self.layers, image_id).unwrap_or_default();
+        if let Some(image) = self.detach(image_id) {
+            self.trash.push(Trashed { image, layer });
+            self.state.dirty = true;
+        }
+    }
+
+    /// Put the most recently trashed image back to where it was in the layers, returning its new id.
+    pub fn restore(&mut self) -> Option<usize> {
+        let Trashed { mut image, layer } = self.trash.pop()?;
+        let image_id = self.images.len();
+        image.id = image_id;
+        insert_leaf(&mut self.layers, &layer, image_id);
+        self.images.push(image);
+        self.state.dirty = true;
+        Some(image_id)
+    }
+
+    /// Whether there is anything to restore.
+    pub fn has_trash(&self) -> bool {
+        !self.trash.is_empty()
+    }
+
+    /// Forget the trashed images for good, along with any pixels they had embedded.
+    pub fn empty_trash(&mut self) {
+        if self.trash.is_empty() {
             return;
-        } else if self.images.len() == 1 {
-            self.images.clear();
-            self.layers.clear();
-        } else {
-            let last_id = self.images.len() - 1;
-            self.images[last_id].id = image_id;
-            self.images.swap(image_id, last_id);
-            self.images.pop();
-            self.layers
-                .remove(self.layers.iter().position(|id| *id == image_id).unwrap());
-            if let Some(pos) = self.layers.iter().position(|id| *id == last_id) {
-                self.layers[pos] = image_id;
+        }
+        for trashed in self.trash.drain(..) {
+            if let Source::Embedded(embedded_id) = trashed.image.source {
+                self.state.embedded.remove(&embedded_id);
             }
         }
+        self.state.dirty = true;
+    }
+
+    /// Take the image out of the project, moving the last image into its id.
+    fn detach(&mut self, image_id: usize) -> Option<Image> {
+        if self.images.is_empty() || self.images.len() <= image_id {
+            return None;
+        }
+        let last_id = self.images.len() - 1;
+        self.images[last_id].id = image_id;
+        let mut image = self.images.swap_remove(image_id);
+        image.id = image_id;
+        remove_leaf(&mut self.layers, image_id);
+        map_ids(&mut self.layers, &|id| if id == last_id { image_id } else { id });
+        Some(image)
     }
 
     pub fn set_origin(&mut self, image_id: usize, origin: Point) {
@@ -137,6 +496,100 @@ impl Project {
         }
     }
 
+    pub fn set_offset(&mut self, image_id: usize, offset: Point) {
+        if let Some(image) = self.images.iter_mut().find(|image| image.id == image_id) {
+            if image.offset != offset {
+                image.offset = offset;
+                self.state.dirty = true;
+            }
+        }
+    }
+
+    pub fn set_color_key(&mut self, image_id: usize, color_key: Option<ColorKey>) {
+        if let Some(image) = self.images.iter_mut().find(|image| image.id == image_id) {
+            if image.color_key != color_key {
+                image.color_key = color_key;
+                self.state.dirty = true;
+            }
+        }
+    }
+
+    /// Turn the image clockwise by `degrees`, kept within a single turn.
+    pub fn set_rotation(&mut self, image_id: usize, degrees: f64) {
+        let degrees = degrees.rem_euclid(360.0);
+        if let Some(image) = self.images.iter_mut().find(|image| image.id == image_id) {
+            if image.rotation != degrees {
+                image.rotation = degrees;
+                self.state.dirty = true;
+            }
+        }
+    }
+
+    pub fn set_proxy(&mut self, image_id: usize, proxy: f64) {
+        if let Some(image) = self.images.iter_mut().find(|image| image.id == image_id) {
+            if image.proxy != proxy {
+                image.proxy = proxy;
+                self.state.dirty = true;
+            }
+        }
+    }
+
+    /// A sync group id that no image is in yet.
+    fn next_sync_group(&self) -> u32 {
+        self.images
+            .iter()
+            .filter_map(|image| image.sync_group)
+            .max()
+            .map_or(0, |group| group + 1)
+    }
+
+    /// The images that are in `group`, in id order.
+    pub fn sync_group_members(&self, group: u32) -> Vec<usize> {
+        self.images
+            .iter()
+            .filter(|image| image.sync_group == Some(group))
+            .map(|image| image.id)
+            .collect()
+    }
+
+    /// Link two images so that their animations play in step, and return the id of their sync group.
+    ///
+    /// If either is already in a group, the other one joins it. Two groups are merged into one.
+    pub fn link(&mut self, image_id: usize, other_id: usize) -> u32 {
+        let groups = (self.images[image_id].sync_group, self.images[other_id].sync_group);
+        let group = groups.0.or(groups.1).unwrap_or_else(|| self.next_sync_group());
+        for image in self.images.iter_mut() {
+            let joins = image.id == image_id || image.id == other_id;
+            if joins || (image.sync_group.is_some() && image.sync_group == groups.1) {
+                image.sync_group = Some(group);
+            }
+        }
+        self.state.dirty = true;
+        group
+    }
+
+    /// Break up the sync group of the image, returning the images that were in it.
+    pub fn unlink(&mut self, image_id: usize) -> Vec<usize> {
+        let members = match self.images[image_id].sync_group {
+            Some(group) => self.sync_group_members(group),
+            None => return Vec::new(),
+        };
+        for &id in members.iter() {
+            self.images[id].sync_group = None;
+        }
+        self.state.dirty = true;
+        members
+    }
+
+    pub fn set_time_remap(&mut self, image_id: usize, time_remap: TimeRemap) {
+        if let Some(image) = self.images.iter_mut().find(|image| image.id == image_id) {
+            if image.time_remap != time_remap {
+                image.time_remap = time_remap;
+                self.state.dirty = true;
+            }
+        }
+    }
+
     pub fn set_zoom(&mut self, image_id: usize, zoom: Zoom) {
         if let Some(image) = self.images.iter_mut().find(|image| image.id == image_id) {
             if image.zoom != zoom {
@@ -146,35 +599,503 @@ impl Project {
         }
     }
 
+    /// Pick bilinear or nearest neighbor scaling for the image, or `None` to follow the `smooth_scaling` setting.
+    pub fn set_smooth(&mut self, image_id: usize, smooth: Option<bool>) {
+        if let Some(image) = self.images.iter_mut().find(|image| image.id == image_id) {
+            if image.smooth != smooth {
+                image.smooth = smooth;
+                self.state.dirty = true;
+            }
+        }
+    }
+
+    pub fn set_checkerboard(&mut self, image_id: usize, checkerboard: bool) {
+        if let Some(image) = self.images.iter_mut().find(|image| image.id == image_id) {
+            if image.checkerboard != checkerboard {
+                image.checkerboard = checkerboard;
+                self.state.dirty = true;
+            }
+        }
+    }
+
+    pub fn set_path(&mut self, image_id: usize, path: PathBuf) {
+        if let Some(image) = self.images.iter_mut().find(|image| image.id == image_id) {
+            if image.path() != Some(path.as_path()) {
+                image.source = Source::File(path);
+                self.state.dirty = true;
+            }
+        }
+    }
+
+    pub fn set_name(&mut self, image_id: usize, name: Option<String>) {
+        if let Some(image) = self.images.iter_mut().find(|image| image.id == image_id) {
+            if image.name != name {
+                image.name = name;
+                self.state.dirty = true;
+            }
+        }
+    }
+
+    pub fn set_visible(&mut self, image_id: usize, visible: bool) {
+        if let Some(image) = self.images.iter_mut().find(|image| image.id == image_id) {
+            if image.visible != visible {
+                image.visible = visible;
+                self.state.dirty = true;
+            }
+        }
+    }
+
+    pub fn set_locked(&mut self, image_id: usize, locked: bool) {
+        if let Some(image) = self.images.iter_mut().find(|image| image.id == image_id) {
+            if image.locked != locked {
+                image.locked = locked;
+                self.state.dirty = true;
+            }
+        }
+    }
+
+    pub fn set_pinned(&mut self, image_id: usize, pinned: bool) {
+        if let Some(image) = self.images.iter_mut().find(|image| image.id == image_id) {
+            if image.pinned != pinned {
+                image.pinned = pinned;
+                self.state.dirty = true;
+            }
+        }
+    }
+
+    /// Record where the animation of the image is, to resume from there when the project is reopened.
+    ///
+    /// Playback moves all the time, so this doesn't count as an unsaved change.
+    pub fn set_playback(&mut self, image_id: usize, paused: bool, resume_frame: usize) {
+        if let Some(image) = self.images.iter_mut().find(|image| image.id == image_id) {
+            image.paused = paused;
+            image.resume_frame = resume_frame;
+        }
+    }
+
+    pub fn set_canvas(&mut self, canvas: Option<Canvas>) {
+        if self.canvas != canvas {
+            self.canvas = canvas;
+            self.state.dirty = true;
+        }
+    }
+
+    /// The spacing used when arranging the images into tiles.
+    pub fn tile_spacing(&self) -> TileSpacing {
+        self.tile_spacing
+    }
+
+    pub fn set_tile_spacing(&mut self, tile_spacing: TileSpacing) {
+        if self.tile_spacing != tile_spacing {
+            self.tile_spacing = tile_spacing;
+            self.state.dirty = true;
+        }
+    }
+
+    /// Whether the images are scaled by their pixel density, so that they match in physical size.
+    pub fn physical_scale(&self) -> bool {
+        self.physical_scale
+    }
+
+    pub fn set_physical_scale(&mut self, physical_scale: bool) {
+        if self.physical_scale != physical_scale {
+            self.physical_scale = physical_scale;
+            self.state.dirty = true;
+        }
+    }
+
+    /// How new images start out in this project.
+    pub fn image_defaults(&self) -> ImageDefaults {
+        self.image_defaults
+    }
+
+    /// Change how new images start out. The images that are already in the project stay as they are.
+    pub fn set_image_defaults(&mut self, image_defaults: ImageDefaults) {
+        if self.image_defaults != image_defaults {
+            self.image_defaults = image_defaults;
+            self.state.dirty = true;
+        }
+    }
+
+    /// Make the image look like it was just added with the current image defaults, see `set_image_defaults`.
+    pub fn apply_image_defaults(&mut self, image_id: usize) {
+        let defaults = self.image_defaults;
+        self.set_smooth(image_id, defaults.smooth);
+        self.set_zoom(image_id, defaults.zoom);
+        self.set_checkerboard(image_id, defaults.checkerboard);
+        if let Some(image) = self.images.iter_mut().find(|image| image.id == image_id) {
+            if image.paused != defaults.paused {
+                image.paused = defaults.paused;
+                self.state.dirty = true;
+            }
+        }
+    }
+
+    /// Replace the layer tree with a flat paint order, bottom first, dissolving all groups.
+    ///
+    /// The order must contain every image id exactly once.
+    pub fn set_layer_order(&mut self, order: &[usize]) -> Result<(), String> {
+        let mut seen = vec![false; self.images.len()];
+        for &id in order {
+            match seen.get_mut(id) {
+                Some(seen) if !*seen => *seen = true,
+                Some(_) => return Err(format!("Image {} is in the layer order more than once", id)),
+                None => return Err(format!("There is no image with id {}", id)),
+            }
+        }
+        if let Some(id) = seen.iter().position(|seen| !seen) {
+            return Err(format!("Image {} is missing from the layer order", id));
+        }
+        let layers: Vec<Layer> = order.iter().copied().map(Layer::Image).collect();
+        if self.layers != layers {
+            self.layers = layers;
+            self.state.dirty = true;
+        }
+        Ok(())
+    }
+
+    /// Move the image by `delta` positions within its own group.
     pub fn shift_layer(&mut self, image_id: usize, delta: isize) {
-        if let Some(current_layer) = self.layers.iter().position(|&id| id == image_id) {
+        if let Some(path) = locate(&self.layers, image_id) {
+            let (&current_layer, group_path) = path.split_last().unwrap();
+            let siblings = container_mut(&mut self.layers, group_path);
             let new_layer = {
                 let new_layer = current_layer as isize + delta;
                 if new_layer < 0 {
                     0
-                } else if new_layer as usize >= self.layers.len() {
-                    self.layers.len() - 1
+                } else if new_layer as usize >= siblings.len() {
+                    siblings.len() - 1
                 } else {
                     new_layer as usize
                 }
             };
             if new_layer != current_layer {
-                self.layers[current_layer] = self.layers[new_layer];
-                self.layers[new_layer] = image_id;
+                siblings.swap(current_layer, new_layer);
+                self.state.dirty = true;
+            }
+        }
+    }
+
+    /// Move the image to `index` within its own group, where 0 is the bottom.
+    ///
+    /// An index past the end of the group moves the image to the top of it.
+    pub fn set_layer_position(&mut self, image_id: usize, index: usize) {
+        if let Some(path) = locate(&self.layers, image_id) {
+            let (&current_layer, group_path) = path.split_last().unwrap();
+            let siblings = container_mut(&mut self.layers, group_path);
+            let new_layer = index.min(siblings.len() - 1);
+            if new_layer != current_layer {
+                let layer = siblings.remove(current_layer);
+                siblings.insert(new_layer, layer);
                 self.state.dirty = true;
             }
         }
     }
+
+    /// Move the given images into a new group.
+    ///
+    /// The group takes the place of the first image in `image_ids`,
+    /// and the images keep their relative paint order inside the group.
+    pub fn create_group(&mut self, name: String, image_ids: &[usize]) {
+        self.group(name, image_ids, false);
+    }
+
+    /// Move the stills of a filmstrip into a new group, like `create_group`, tagged so that it can be collapsed.
+    pub fn create_filmstrip(&mut self, name: String, image_ids: &[usize]) {
+        self.group(name, image_ids, true);
+    }
+
+    /// The ids of all the images in the filmstrip that contains the image, if it's part of one.
+    pub fn filmstrip_of(&self, image_id: usize) -> Option<Vec<usize>> {
+        let path = locate(&self.layers, image_id)?;
+        if path.len() < 2 {
+            return None; // Not inside a group
+        }
+        let group_path = &path[..path.len() - 1];
+        let (&group_index, parent_path) = group_path.split_last().unwrap();
+        match &container(&self.layers, parent_path)[group_index] {
+            Layer::Group(group) if group.filmstrip => {
+                let mut ids = Vec::new();
+                flatten(&group.layers, &mut ids);
+                Some(ids)
+            }
+            _ => None,
+        }
+    }
+
+    fn group(&mut self, name: String, image_ids: &[usize], filmstrip: bool) {
+        let first_id = match image_ids.first() {
+            Some(&id) if locate(&self.layers, id).is_some() => id,
+            _ => return,
+        };
+        let members: Vec<Layer> = self
+            .layers()
+            .into_iter()
+            .filter(|id| image_ids.contains(id))
+            .map(Layer::Image)
+            .collect();
+        for &id in image_ids.iter().filter(|&&id| id != first_id) {
+            remove_leaf(&mut self.layers, id);
+        }
+        // The group takes the place of the first image
+        let path = locate(&self.layers, first_id).unwrap();
+        let (&index, group_path) = path.split_last().unwrap();
+        container_mut(&mut self.layers, group_path)[index] = Layer::Group(Group {
+            name: name,
+            layers: members,
+            filmstrip: filmstrip,
+        });
+        self.state.dirty = true;
+    }
+
+    /// Dissolve the group that directly contains the image, moving its contents to the parent.
+    pub fn dissolve_group(&mut self, image_id: usize) {
+        if let Some(path) = locate(&self.layers, image_id) {
+            if path.len() < 2 {
+                return; // Not inside a group
+            }
+            let (&group_index, parent_path) = path[..path.len() - 1].split_last().unwrap();
+            let parent = container_mut(&mut self.layers, parent_path);
+            if let Layer::Group(group) = parent.remove(group_index) {
+                for (i, layer) in group.layers.into_iter().enumerate() {
+                    parent.insert(group_index + i, layer);
+                }
+            }
+            self.state.dirty = true;
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize)]
+/// Compares the contents, but not where the projects were saved or whether they have unsaved changes.
+impl PartialEq for Project {
+    fn eq(&self, other: &Self) -> bool {
+        self.images == other.images
+            && self.layers == other.layers
+            && self.canvas == other.canvas
+            && self.duplicate_policy == other.duplicate_policy
+            && self.tile_spacing == other.tile_spacing
+            && self.physical_scale == other.physical_scale
+            && self.image_defaults == other.image_defaults
+    }
+}
+
+/// A node in the layer tree. Plain image ids keep the format of flat projects unchanged.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Layer {
+    Image(usize),
+    Group(Group),
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct Group {
+    name: String,
+    layers: Vec<Layer>,
+    #[serde(default, skip_serializing_if = "is_plain_group")]
+    filmstrip: bool, // The stills of an animation, which are removed together when collapsing it
+}
+
+impl Group {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+}
+
+fn flatten(layers: &[Layer], order: &mut Vec<usize>) {
+    for layer in layers {
+        match layer {
+            Layer::Image(id) => order.push(*id),
+            Layer::Group(group) => flatten(&group.layers, order),
+        }
+    }
+}
+
+/// Returns the index path through the tree to the image.
+fn locate(layers: &[Layer], image_id: usize) -> Option<Vec<usize>> {
+    for (index, layer) in layers.iter().enumerate() {
+        match layer {
+            Layer::Image(id) if *id == image_id => return Some(vec![index]),
+            Layer::Image(_) => (),
+            Layer::Group(group) => {
+                if let Some(mut path) = locate(&group.layers, image_id) {
+                    path.insert(0, index);
+                    return Some(path);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Returns the layer list of the group at `group_path`, which must only contain group indices.
+fn container<'a>(mut layers: &'a [Layer], group_path: &[usize]) -> &'a [Layer] {
+    for &index in group_path {
+        layers = match &layers[index] {
+            Layer::Group(group) => &group.layers,
+            Layer::Image(_) => panic!("Layer path goes through an image"),
+        };
+    }
+    layers
+}
+
+/// Returns the layer list of the group at `group_path`, which must only contain group indices.
+fn container_mut<'a>(mut layers: &'a mut Vec<Layer>, group_path: &[usize]) -> &'a mut Vec<Layer> {
+    for &index in group_path {
+        layers = match &mut layers[index] {
+            Layer::Group(group) => &mut group.layers,
+            Layer::Image(_) => panic!("Layer path goes through an image"),
+        };
+    }
+    layers
+}
+
+fn remove_leaf(layers: &mut Vec<Layer>, image_id: usize) {
+    layers.retain(|layer| !matches!(layer, Layer::Image(id) if *id == image_id));
+    for layer in layers.iter_mut() {
+        if let Layer::Group(group) = layer {
+            remove_leaf(&mut group.layers, image_id);
+        }
+    }
+    // A group that lost its last image has nothing left to group
+    layers.retain(|layer| !matches!(layer, Layer::Group(group) if group.layers.is_empty()));
+}
+
+/// Insert the image at `path` as found by `locate`, or as close to it as the layers still allow.
+fn insert_leaf(layers: &mut Vec<Layer>, path: &[usize], image_id: usize) {
+    match path {
+        [] => layers.push(Layer::Image(image_id)),
+        [index] => layers.insert((*index).min(layers.len()), Layer::Image(image_id)),
+        [index, rest @ ..] => match layers.get_mut(*index) {
+            Some(Layer::Group(group)) => insert_leaf(&mut group.layers, rest, image_id),
+            _ => layers.insert((*index).min(layers.len()), Layer::Image(image_id)),
+        },
+    }
+}
+
+fn map_ids(layers: &mut [Layer], map: &impl Fn(usize) -> usize) {
+    for layer in layers.iter_mut() {
+        match layer {
+            Layer::Image(id) => *id = map(*id),
+            Layer::Group(group) => map_ids(&mut group.layers, map),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq)]
 pub struct Image {
     id: usize,
-    path: PathBuf,
+    #[serde(flatten)]
+    source: Source,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
     #[serde(with = "PointDef")]
     origin: Point,
     #[serde(default)]
     zoom: Zoom,
+    #[serde(default = "default_visible", skip_serializing_if = "is_visible")]
+    visible: bool,
+    #[serde(default, skip_serializing_if = "is_unlocked")]
+    locked: bool,
+    #[serde(default, skip_serializing_if = "is_unpinned")]
+    pinned: bool,
+    #[serde(default, skip_serializing_if = "is_running")]
+    paused: bool,
+    #[serde(default, skip_serializing_if = "is_first_frame")]
+    resume_frame: usize,
+    #[serde(default = "default_offset", with = "PointDef", skip_serializing_if = "is_unpanned")]
+    offset: Point,
+    #[serde(default, skip_serializing_if = "is_linear")]
+    time_remap: TimeRemap,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    color_key: Option<ColorKey>,
+    #[serde(default, skip_serializing_if = "is_upright")]
+    rotation: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sync_group: Option<u32>,
+    #[serde(default = "default_proxy", skip_serializing_if = "is_full_size")]
+    proxy: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    smooth: Option<bool>,
+    #[serde(default, skip_serializing_if = "is_plain_background")]
+    checkerboard: bool,
+}
+
+fn default_visible() -> bool {
+    true
+}
+
+fn is_visible(visible: &bool) -> bool {
+    *visible
+}
+
+fn is_unlocked(locked: &bool) -> bool {
+    !*locked
+}
+
+fn is_unpinned(pinned: &bool) -> bool {
+    !*pinned
+}
+
+fn is_running(paused: &bool) -> bool {
+    !*paused
+}
+
+fn is_pixel_scale(physical_scale: &bool) -> bool {
+    !*physical_scale
+}
+
+fn is_plain_background(checkerboard: &bool) -> bool {
+    !*checkerboard
+}
+
+fn is_first_frame(frame: &usize) -> bool {
+    *frame == 0
+}
+
+fn default_offset() -> Point {
+    Point::ZERO
+}
+
+fn is_unpanned(offset: &Point) -> bool {
+    *offset == Point::ZERO
+}
+
+fn is_plain_group(filmstrip: &bool) -> bool {
+    !*filmstrip
+}
+
+fn is_linear(time_remap: &TimeRemap) -> bool {
+    *time_remap == TimeRemap::Linear
+}
+
+fn is_upright(rotation: &f64) -> bool {
+    *rotation == 0.0
+}
+
+fn default_proxy() -> f64 {
+    1.0
+}
+
+fn is_full_size(proxy: &f64) -> bool {
+    *proxy == 1.0
+}
+
+/// Where the pixels of an image come from.
+///
+/// Files keep the `path` key that projects have always had, so that projects without
+/// embedded images are saved exactly as before.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum Source {
+    /// A file, relative to the project file unless absolute.
+    #[serde(rename = "path")]
+    File(PathBuf),
+    /// Pixels kept in the project, saved as `<id>.png` in the directory next to the project file.
+    #[serde(rename = "embedded")]
+    Embedded(u64),
 }
 
 impl Image {
@@ -182,8 +1103,32 @@ impl Image {
         self.id
     }
 
-    pub fn path(&self) -> &Path {
-        &self.path
+    pub fn source(&self) -> &Source {
+        &self.source
+    }
+
+    /// The path as stored in the project, unless the image is embedded.
+    pub fn path(&self) -> Option<&Path> {
+        match &self.source {
+            Source::File(path) => Some(path),
+            Source::Embedded(_) => None,
+        }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// The custom name if there is one, otherwise the file stem.
+    pub fn display_name(&self) -> String {
+        match (&self.name, &self.source) {
+            (Some(name), _) => name.clone(),
+            (None, Source::File(path)) => match path.file_stem() {
+                Some(stem) => stem.to_string_lossy().into_owned(),
+                None => path.to_string_lossy().into_owned(),
+            },
+            (None, Source::Embedded(embedded_id)) => tr_args("embedded-image", &[("id", (*embedded_id).into())]),
+        }
     }
 
     pub fn origin(&self) -> &Point {
@@ -193,6 +1138,184 @@ impl Image {
     pub fn zoom(&self) -> &Zoom {
         &self.zoom
     }
+
+    pub fn visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Locked images can still be selected, but not moved.
+    pub fn locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Pinned images keep their place when the images are arranged automatically.
+    pub fn pinned(&self) -> bool {
+        self.pinned
+    }
+
+    /// Whether the animation was paused when the project was saved.
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    /// The frame that was being shown when the project was saved.
+    pub fn resume_frame(&self) -> usize {
+        self.resume_frame
+    }
+
+    /// The image pixel at the top left corner of the view, when the view is too small for the whole image.
+    pub fn offset(&self) -> &Point {
+        &self.offset
+    }
+
+    /// How fast the animation plays over the course of each loop.
+    pub fn time_remap(&self) -> TimeRemap {
+        self.time_remap
+    }
+
+    /// The color shown as transparent, if any.
+    pub fn color_key(&self) -> Option<ColorKey> {
+        self.color_key
+    }
+
+    /// How many degrees the image is turned clockwise about its center.
+    pub fn rotation(&self) -> f64 {
+        self.rotation
+    }
+
+    /// The group of images whose animations play in step with this one, if any.
+    pub fn sync_group(&self) -> Option<u32> {
+        self.sync_group
+    }
+
+    /// The fraction of the native size that the image is loaded at, below 1.0 for proxies of very large images.
+    pub fn proxy(&self) -> f64 {
+        self.proxy
+    }
+
+    /// Bilinear or nearest neighbor scaling, or `None` when the `smooth_scaling` setting decides.
+    pub fn smooth(&self) -> Option<bool> {
+        self.smooth
+    }
+
+    /// Whether a checkerboard shows through the transparent pixels.
+    pub fn checkerboard(&self) -> bool {
+        self.checkerboard
+    }
+}
+
+/// A removed image, along with where it was in the layers.
+#[derive(Serialize, Deserialize, PartialEq)]
+struct Trashed {
+    image: Image,
+    layer: Vec<usize>, // The index path through the tree that led to the image, see `locate`
+}
+
+/// What to do when a file that is already in the project gets added again.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum DuplicatePolicy {
+    /// Don't add anything, focus the existing image instead.
+    FocusExisting,
+    /// Add the duplicate slightly offset from the existing image.
+    AddWithOffset,
+}
+
+impl Default for DuplicatePolicy {
+    fn default() -> DuplicatePolicy {
+        DuplicatePolicy::FocusExisting
+    }
+}
+
+/// The outcome of `Project::add`.
+pub enum Addition {
+    /// A new image was added with this id.
+    New(usize),
+    /// The file was already present as this id and nothing was added.
+    Existing(usize),
+    /// The file was already present, but a duplicate was added with this id.
+    Duplicate(usize),
+}
+
+/// A fixed output region of the surface, starting at the surface origin.
+///
+/// Views are still free to be placed outside of it, but composite exports are framed by it.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct Canvas {
+    #[serde(with = "SizeDef")]
+    size: Size,
+    background: [u8; 4], // RGBA
+}
+
+impl Canvas {
+    pub fn new(size: Size, background: [u8; 4]) -> Canvas {
+        Canvas { size, background }
+    }
+
+    pub fn size(&self) -> Size {
+        self.size
+    }
+
+    pub fn rect(&self) -> Rect {
+        Rect::from_origin_size(Point::ZERO, self.size)
+    }
+
+    pub fn background(&self) -> [u8; 4] {
+        self.background
+    }
+
+    pub fn background_color(&self) -> Color {
+        let [r, g, b, a] = self.background;
+        Color::rgba8(r, g, b, a)
+    }
+}
+
+impl Default for Canvas {
+    fn default() -> Canvas {
+        Canvas {
+            size: Size::new(1920.0, 1080.0),
+            background: [255, 255, 255, 255],
+        }
+    }
+}
+
+/// How the images added to a project start out, so that they don't all have to be set up by hand.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(default)]
+pub struct ImageDefaults {
+    /// Bilinear or nearest neighbor scaling, or `None` when the `smooth_scaling` setting decides.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smooth: Option<bool>,
+    pub zoom: Zoom,
+    pub checkerboard: bool,
+    pub paused: bool,
+}
+
+impl ImageDefaults {
+    fn is_default(&self) -> bool {
+        *self == ImageDefaults::default()
+    }
+}
+
+/// The space left between tiles, and between the tiles and the surface edges.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct TileSpacing {
+    pub gap: f64,
+    pub margin: f64,
+}
+
+impl TileSpacing {
+    fn is_default(&self) -> bool {
+        *self == TileSpacing::default()
+    }
+}
+
+impl Default for TileSpacing {
+    fn default() -> TileSpacing {
+        TileSpacing {
+            gap: Tileize::DEFAULT_GAP,
+            margin: Tileize::DEFAULT_MARGIN,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -202,8 +1325,528 @@ struct PointDef {
     pub y: f64,
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Size")]
+struct SizeDef {
+    pub width: f64,
+    pub height: f64,
+}
+
 #[derive(Default)]
 struct State {
     path: Option<PathBuf>,
     dirty: bool,
+    embedded: HashMap<u64, Arc<ImgVec<RGBA8>>>, // The pixels of the embedded images, by embedded id
+}
+
+/// The order to remove the images with `image_ids` in one by one, so that each id still refers to the same image when its turn comes.
+///
+/// Removing moves the last image into the freed id, so going from the highest id down leaves the lower ones alone.
+pub fn removal_order(image_ids: &[usize]) -> Vec<usize> {
+    let mut order = image_ids.to_vec();
+    order.sort_unstable_by(|a, b| b.cmp(a));
+    order.dedup();
+    order
+}
+
+/// Whether `path` looks like a project file in either encoding.
+pub fn is_project_path(path: &Path) -> bool {
+    path.extension().map_or(false, |ext| {
+        ext.eq_ignore_ascii_case("ark") || ext.eq_ignore_ascii_case("arkb")
+    })
+}
+
+/// How a project file is laid out.
+///
+/// JSON is readable and diffable. The compact encoding is MessagePack behind [`COMPACT_MAGIC`],
+/// which is much quicker to write for projects with hundreds of images, so autosaves use it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Encoding {
+    Json,
+    Compact,
+}
+
+impl Encoding {
+    /// The encoding called for by the extension of `path`, `.arkb` being compact.
+    fn from_path(path: &Path) -> Encoding {
+        match path.extension() {
+            Some(ext) if ext.eq_ignore_ascii_case("arkb") => Encoding::Compact,
+            _ => Encoding::Json,
+        }
+    }
+}
+
+/// The start of every compact project file, which JSON can never start with.
+const COMPACT_MAGIC: &[u8] = b"SLARKB\x00\x01";
+
+/// The directory where the embedded images of the project file at `path` are kept.
+fn embedded_dir(path: &Path) -> PathBuf {
+    path.with_extension("embedded")
+}
+
+fn embedded_path(dir: &Path, embedded_id: u64) -> PathBuf {
+    dir.join(format!("{}.png", embedded_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A project with images `0..count` painted in id order.
+    fn project_of(count: usize) -> Project {
+        let mut project = Project::new();
+        for index in 0..count {
+            project.add(format!("nested-{}.png", index).into());
+        }
+        project
+    }
+
+    /// A small xorshift generator, so that the randomized tests are reproducible.
+    /// Returns a number below the given bound on every call.
+    fn xorshift(seed: u64) -> impl FnMut(usize) -> usize {
+        let mut state = seed;
+        move |bound| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % bound as u64) as usize
+        }
+    }
+
+    #[test]
+    fn shifting_stays_within_nested_groups() {
+        let mut project = project_of(6);
+        project.create_group(String::from("outer"), &[1, 2, 3, 4]);
+        project.create_group(String::from("inner"), &[2, 3]);
+        assert_eq!(locate(project.layer_tree(), 3), Some(vec![1, 1, 1]));
+
+        // Past the ends of the inner group it stops at its edges
+        project.shift_layer(3, -5);
+        assert_eq!(locate(project.layer_tree(), 3), Some(vec![1, 1, 0]));
+        project.shift_layer(2, 5);
+        assert_eq!(locate(project.layer_tree(), 2), Some(vec![1, 1, 1]));
+        assert_eq!(project.layers(), vec![0, 1, 3, 2, 4, 5]);
+
+        // Shifting past the inner group moves it as a whole, but never out of the outer group
+        project.shift_layer(4, -1);
+        assert_eq!(project.layers(), vec![0, 1, 4, 3, 2, 5]);
+        project.shift_layer(4, 9);
+        assert_eq!(locate(project.layer_tree(), 4), Some(vec![1, 2]));
+        assert_eq!(project.layers(), vec![0, 1, 3, 2, 4, 5]);
+    }
+
+    #[test]
+    fn dissolving_a_nested_group_keeps_its_place_in_the_parent() {
+        let mut project = project_of(5);
+        project.create_group(String::from("outer"), &[1, 2, 3]);
+        project.create_group(String::from("inner"), &[2, 3]);
+        project.dissolve_group(3);
+        assert_eq!(locate(project.layer_tree(), 2), Some(vec![1, 1]));
+        assert_eq!(locate(project.layer_tree(), 3), Some(vec![1, 2]));
+        assert_eq!(project.layers(), vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn removing_the_last_images_of_groups_removes_the_groups() {
+        let mut project = project_of(4);
+        project.create_group(String::from("outer"), &[1, 2]);
+        project.create_group(String::from("inner"), &[2]);
+        project.remove(2);
+        assert_eq!(locate(project.layer_tree(), 1), Some(vec![1, 0]));
+        project.remove(1);
+        assert!(project
+            .layer_tree()
+            .iter()
+            .all(|layer| matches!(layer, Layer::Image(_))));
+        assert_eq!(project.layers().len(), 2);
+    }
+
+    #[test]
+    fn round_trip() {
+        let dir = std::env::temp_dir().join(format!("slark-project-round-trip-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("scene.ark");
+
+        let mut project = Project::new();
+        let bottom = project.add_with_placement("bottom.png".into(), Point::ZERO, Zoom::default());
+        let top = project.add_with_placement("top.gif".into(), Point::new(64.0, 0.0), Zoom::exact(0.5));
+        project.set_layer_order(&[top, bottom]).unwrap();
+        project.set_playback(top, true, 3);
+        project.set_time_remap(top, TimeRemap::EaseIn);
+        project.set_rotation(bottom, -3.5);
+        project.save(&path).unwrap();
+
+        let reopened = Project::open(path.clone()).unwrap();
+        assert!(reopened == project);
+        assert_eq!(reopened.layers(), vec![top, bottom]);
+        assert_eq!(reopened.image(top).unwrap().origin(), &Point::new(64.0, 0.0));
+        assert!(reopened.image(top).unwrap().paused());
+        assert_eq!(reopened.image(top).unwrap().resume_frame(), 3);
+        assert_eq!(reopened.image(top).unwrap().time_remap(), TimeRemap::EaseIn);
+        assert!(!reopened.image(bottom).unwrap().paused());
+        assert_eq!(reopened.image(bottom).unwrap().rotation(), 356.5);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn relative_and_absolute_spellings_are_the_same_file() {
+        let dir = std::env::temp_dir().join(format!("slark-project-spellings-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.png"), b"").unwrap();
+        let mut project = Project::new();
+        project.save(&dir.join("scene.ark")).unwrap();
+
+        // Relative to the project file, just like the paths that projects store
+        let original = match project.add("a.png".into()) {
+            Addition::New(image_id) => image_id,
+            _ => panic!("The first spelling wasn't added"),
+        };
+        let absolute = dir.join("a.png");
+        assert!(matches!(project.add(absolute.clone()), Addition::Existing(id) if id == original));
+        assert!(matches!(project.add("./a.png".into()), Addition::Existing(id) if id == original));
+        assert!(matches!(project.add("a.png".into()), Addition::Existing(id) if id == original));
+
+        project.set_duplicate_policy(DuplicatePolicy::AddWithOffset);
+        match project.add(absolute) {
+            Addition::Duplicate(duplicate) => {
+                let offset = *project.image(original).unwrap().origin() + Vec2::new(24.0, 24.0);
+                assert_eq!(project.image(duplicate).unwrap().origin(), &offset);
+            }
+            _ => panic!("The duplicate wasn't added"),
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn spellings_relative_to_the_working_directory_are_the_same_file() {
+        let dir = std::env::temp_dir().join(format!("slark-project-cwd-spellings-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let absolute = dir.canonicalize().unwrap().join("a.png");
+        std::fs::write(&absolute, b"").unwrap();
+        // Climb from the working directory up to the root and back down to the file
+        let cwd = std::env::current_dir().unwrap().canonicalize().unwrap();
+        let mut relative = PathBuf::new();
+        let mut climbed = cwd.as_path();
+        while let Some(parent) = climbed.parent() {
+            relative.push("..");
+            climbed = parent;
+        }
+        let relative = match absolute.strip_prefix(climbed) {
+            Ok(rest) => relative.join(rest),
+            // On another drive there's no relative spelling
+            Err(_) => return,
+        };
+
+        // Without a project file the paths are as good as the working directory makes them
+        let mut project = Project::new();
+        let original = match project.add(absolute) {
+            Addition::New(image_id) => image_id,
+            _ => panic!("The first spelling wasn't added"),
+        };
+        assert!(matches!(project.add(relative), Addition::Existing(id) if id == original));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn image_defaults_only_apply_to_new_images() {
+        let dir = std::env::temp_dir().join(format!("slark-project-image-defaults-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("pixels.ark");
+
+        let mut project = Project::new();
+        let existing = project.add_with_placement("old.png".into(), Point::ZERO, Zoom::default());
+        let defaults = ImageDefaults {
+            smooth: Some(false),
+            zoom: Zoom::exact(4.0),
+            checkerboard: true,
+            paused: true,
+        };
+        project.set_image_defaults(defaults);
+        let added = match project.add("new.png".into()) {
+            Addition::New(image_id) => image_id,
+            _ => panic!("The new image wasn't added"),
+        };
+        project.save(&path).unwrap();
+
+        let reopened = Project::open(path.clone()).unwrap();
+        assert!(reopened == project);
+        assert!(reopened.image_defaults() == defaults);
+        let old = reopened.image(existing).unwrap();
+        assert_eq!((old.smooth(), old.checkerboard(), old.paused()), (None, false, false));
+        assert_eq!(old.zoom().scale_factor(), 1.0);
+        let new = reopened.image(added).unwrap();
+        assert_eq!(
+            (new.smooth(), new.checkerboard(), new.paused()),
+            (Some(false), true, true)
+        );
+        assert_eq!(new.zoom().scale_factor(), 4.0);
+
+        // Applying the defaults to an existing image is up to the user
+        project.apply_image_defaults(existing);
+        assert!(project.image(existing).unwrap().checkerboard());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn compact_and_json_encodings_agree() {
+        let mut project = Project::new();
+        let ids: Vec<usize> = (0..4)
+            .map(|index| project.add_with_placement(format!("{}.gif", index).into(), Point::ZERO, Zoom::default()))
+            .collect();
+        let pixels = ImgVec::new(vec![RGBA8::new(255, 0, 0, 255)], 1, 1);
+        project.add_embedded(pixels, Point::new(8.0, 0.0), Zoom::exact(2.0));
+        project.create_filmstrip(String::from("strip"), &[ids[1], ids[2]]);
+        project.link(ids[0], ids[3]);
+        project.set_time_remap(ids[3], TimeRemap::EaseIn);
+        project.set_rotation(ids[0], 90.0);
+        project.set_name(ids[2], Some(String::from("middle")));
+        project.set_physical_scale(true);
+
+        let compact = project.encode(Encoding::Compact).unwrap();
+        let json = project.encode(Encoding::Json).unwrap();
+        assert!(compact.starts_with(COMPACT_MAGIC));
+        let from_compact = Project::decode(&compact).unwrap();
+        let from_json = Project::decode(&json).unwrap();
+        assert!(from_compact == project);
+        assert!(from_compact == from_json);
+        // Converting between the encodings loses nothing either
+        assert!(Project::decode(&from_compact.encode(Encoding::Json).unwrap()).unwrap() == project);
+        assert!(compact.len() < json.len());
+    }
+
+    #[test]
+    fn compact_projects_are_detected_by_content() {
+        let dir = std::env::temp_dir().join(format!("slark-project-compact-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut project = Project::new();
+        project.add_with_placement("a.png".into(), Point::ZERO, Zoom::default());
+        project.save(&dir.join("scene.arkb")).unwrap();
+        assert!(std::fs::read(dir.join("scene.arkb"))
+            .unwrap()
+            .starts_with(COMPACT_MAGIC));
+        // A renamed file still opens
+        std::fs::rename(dir.join("scene.arkb"), dir.join("renamed.ark")).unwrap();
+        assert!(Project::open(dir.join("renamed.ark")).unwrap() == project);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn file_images_keep_their_format() {
+        let mut project = Project::new();
+        let id = project.add_with_placement("a.png".into(), Point::ZERO, Zoom::default());
+        let json = serde_json::to_string(project.image(id).unwrap()).unwrap();
+        assert!(json.starts_with(r#"{"id":0,"path":"a.png","origin":"#));
+        assert!(!json.contains("embedded"));
+    }
+
+    #[test]
+    fn embedded_images_round_trip() {
+        let dir = std::env::temp_dir().join(format!("slark-project-embedded-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("scene.ark");
+
+        let mut project = Project::new();
+        let file = project.add_with_placement("a.png".into(), Point::ZERO, Zoom::default());
+        let pixels = ImgVec::new(vec![RGBA8::new(255, 0, 0, 255), RGBA8::new(0, 0, 255, 128)], 2, 1);
+        let embedded = project.add_embedded(pixels.clone(), Point::new(8.0, 0.0), Zoom::default());
+        project.save(&path).unwrap();
+
+        let reopened = Project::open(path.clone()).unwrap();
+        assert!(reopened == project);
+        assert_eq!(reopened.image(file).unwrap().path(), Some(Path::new("a.png")));
+        let embedded_id = match reopened.image(embedded).unwrap().source() {
+            Source::Embedded(embedded_id) => *embedded_id,
+            Source::File(_) => panic!("The embedded image became a file"),
+        };
+        assert!(*reopened.embedded(embedded_id).unwrap() == pixels);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn unreadable_embedded_images_keep_their_files() {
+        let dir = std::env::temp_dir().join(format!("slark-project-unreadable-embedded-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("scene.ark");
+        let red = ImgVec::new(vec![RGBA8::new(255, 0, 0, 255)], 1, 1);
+        let blue = ImgVec::new(vec![RGBA8::new(0, 0, 255, 255)], 1, 1);
+
+        let mut project = Project::new();
+        let first = project.add_embedded(red.clone(), Point::ZERO, Zoom::default());
+        let second = project.add_embedded(red, Point::ZERO, Zoom::default());
+        project.save(&path).unwrap();
+        let embedded_id = |project: &Project, id: usize| match project.image(id).unwrap().source() {
+            Source::Embedded(embedded_id) => *embedded_id,
+            Source::File(_) => panic!("The embedded image became a file"),
+        };
+        let last_id = embedded_id(&project, second);
+        let last_file = embedded_path(&embedded_dir(&path), last_id);
+        std::fs::write(&last_file, b"not a png").unwrap();
+
+        // The unreadable one still holds on to its id
+        let mut reopened = Project::open(path.clone()).unwrap();
+        assert!(reopened.embedded(last_id).is_none());
+        let third = reopened.add_embedded(blue, Point::ZERO, Zoom::default());
+        assert!(embedded_id(&reopened, third) > last_id);
+        reopened.save(&path).unwrap();
+        assert_eq!(std::fs::read(&last_file).unwrap(), b"not a png");
+
+        // Removed images take their files along on the next save
+        let first_file = embedded_path(&embedded_dir(&path), embedded_id(&reopened, first));
+        reopened.remove(first);
+        reopened.save(&path).unwrap();
+        assert!(!first_file.exists());
+        assert!(last_file.exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn trashed_images_are_restored_where_they_were() {
+        let mut project = Project::new();
+        let ids: Vec<usize> = (0..4)
+            .map(|_| project.add_with_placement("a.png".into(), Point::ZERO, Zoom::default()))
+            .collect();
+        project.create_group(String::from("group"), &[ids[1], ids[2]]);
+        project.trash(ids[1]);
+        project.trash(ids[0]);
+        // The last images took over the ids of the removed ones
+        assert_eq!(project.layers(), vec![0, 1]);
+        assert_eq!(project.restore(), Some(2));
+        assert_eq!(project.restore(), Some(3));
+        assert_eq!(project.restore(), None);
+        // The first image is back at the bottom and the second one back at the start of the group
+        assert_eq!(project.layers(), vec![2, 3, 0, 1]);
+        assert_eq!(project.layer_tree().len(), 3);
+    }
+
+    #[test]
+    fn linking_merges_sync_groups() {
+        let mut project = Project::new();
+        let ids: Vec<usize> = (0..5)
+            .map(|_| project.add_with_placement("a.gif".into(), Point::ZERO, Zoom::default()))
+            .collect();
+        let first = project.link(ids[0], ids[1]);
+        let second = project.link(ids[3], ids[2]);
+        assert_ne!(first, second);
+        assert_eq!(project.link(ids[1], ids[2]), first);
+        assert_eq!(project.sync_group_members(first), ids[..4].to_vec());
+        assert!(project.sync_group_members(second).is_empty());
+        assert_eq!(project.unlink(ids[2]), ids[..4].to_vec());
+        assert!(project.images().iter().all(|image| image.sync_group().is_none()));
+    }
+
+    #[test]
+    fn layer_order_must_cover_every_image() {
+        let mut project = Project::new();
+        let first = project.add_with_placement("a.png".into(), Point::ZERO, Zoom::default());
+        let second = project.add_with_placement("a.png".into(), Point::ZERO, Zoom::default());
+        assert!(project.set_layer_order(&[first]).is_err());
+        assert!(project.set_layer_order(&[first, first]).is_err());
+        assert!(project.set_layer_order(&[first, second, 2]).is_err());
+        assert_eq!(project.layers(), vec![first, second]);
+        project.set_layer_order(&[second, first]).unwrap();
+        assert_eq!(project.layers(), vec![second, first]);
+    }
+
+    #[test]
+    fn layer_position_is_clamped() {
+        let mut project = Project::new();
+        let ids: Vec<usize> = (0..4)
+            .map(|_| project.add_with_placement("a.png".into(), Point::ZERO, Zoom::default()))
+            .collect();
+        project.state.dirty = false;
+        project.set_layer_position(ids[0], 2);
+        assert_eq!(project.layers(), vec![ids[1], ids[2], ids[0], ids[3]]);
+        assert!(project.state.dirty);
+        project.set_layer_position(ids[1], usize::MAX);
+        assert_eq!(project.layers(), vec![ids[2], ids[0], ids[3], ids[1]]);
+        project.state.dirty = false;
+        project.set_layer_position(ids[1], 3);
+        project.set_layer_position(ids.len(), 0);
+        assert!(!project.state.dirty);
+    }
+
+    #[test]
+    fn layer_position_stays_within_the_group() {
+        let mut project = Project::new();
+        let ids: Vec<usize> = (0..4)
+            .map(|_| project.add_with_placement("a.png".into(), Point::ZERO, Zoom::default()))
+            .collect();
+        project.create_group(String::from("group"), &[ids[1], ids[2]]);
+        project.set_layer_position(ids[2], 0);
+        assert_eq!(project.layers(), vec![ids[0], ids[2], ids[1], ids[3]]);
+        project.set_layer_position(ids[2], usize::MAX);
+        assert_eq!(project.layers(), vec![ids[0], ids[1], ids[2], ids[3]]);
+    }
+
+    #[test]
+    fn filmstrips_are_found_from_any_still() {
+        let mut project = Project::new();
+        let ids: Vec<usize> = (0..4)
+            .map(|_| project.add_with_placement("a.png".into(), Point::ZERO, Zoom::default()))
+            .collect();
+        project.create_filmstrip(String::from("strip"), &[ids[1], ids[2], ids[3]]);
+        assert_eq!(project.filmstrip_of(ids[2]), Some(vec![ids[1], ids[2], ids[3]]));
+        assert_eq!(project.filmstrip_of(ids[0]), None);
+        for &id in ids[1..].iter().rev() {
+            project.remove(id);
+        }
+        // The emptied group goes away with its last still
+        assert_eq!(project.layer_tree().len(), 1);
+    }
+
+    #[test]
+    fn removing_subsets_removes_exactly_them() {
+        let mut next = xorshift(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..100 {
+            let mut project = Project::new();
+            for index in 0..20 {
+                let id = project.add_with_placement("a.png".into(), Point::ZERO, Zoom::default());
+                project.set_name(id, Some(index.to_string()));
+            }
+            project.create_group(String::from("group"), &[3, 4, 5, 6]);
+            while !project.images().is_empty() {
+                let count = project.images().len();
+                let subset: Vec<usize> = (0..next(4) + 1).map(|_| next(count)).collect();
+                let names = |project: &Project| -> Vec<String> {
+                    let mut names: Vec<String> = project.images().iter().map(|image| image.display_name()).collect();
+                    names.sort();
+                    names
+                };
+                let mut expected = names(&project);
+                for &id in &subset {
+                    let name = project.image(id).unwrap().display_name();
+                    expected.retain(|kept| *kept != name);
+                }
+                for id in removal_order(&subset) {
+                    project.remove(id);
+                }
+                assert_eq!(names(&project), expected);
+                let mut layers = project.layers();
+                layers.sort_unstable();
+                assert_eq!(layers, (0..project.images().len()).collect::<Vec<_>>());
+            }
+        }
+    }
+
+    #[test]
+    fn layers_stay_a_permutation_of_the_images() {
+        let mut next = xorshift(0x2545_f491_4f6c_dd1d);
+        for _ in 0..100 {
+            let mut project = Project::new();
+            for _ in 0..200 {
+                let count = project.images().len();
+                match next(5) {
+                    0 | 1 => {
+                        project.add_with_placement("a.png".into(), Point::ZERO, Zoom::default());
+                    }
+                    2 => project.remove(next(count + 1)),
+                    3 => project.shift_layer(next(count + 1), next(9) as isize - 4),
+                    _ => project.set_layer_position(next(count + 1), next(count + 2)),
+                }
+                let mut layers = project.layers();
+                layers.sort_unstable();
+                assert_eq!(layers, (0..project.images().len()).collect::<Vec<_>>());
+            }
+        }
+    }
 }
@@ -0,0 +1,222 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Exporting the frames of an image as a sequence of files, named by a template like `{name}_{index:04}.png`.
+//!
+//! `{name}` is the name of the image, `{index}` the number of the frame counting from 1,
+//! and `{time}` when the frame is shown, in milliseconds from the start of the animation.
+//! The numbers can be zero-padded to a width, e.g. `{time:06}`. `{{` and `}}` stand for the braces themselves.
+
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use imgref::ImgVec;
+use rgb::RGBA8;
+
+use crate::formats::{self, Format};
+use crate::i18n::{tr, tr_args};
+use crate::ui::{post, ToastMessage};
+
+/// The template used until another one is picked.
+pub const DEFAULT_TEMPLATE: &str = "{name}_{index:04}.png";
+
+/// Characters that aren't allowed in file names on at least one of the supported systems.
+const ILLEGAL_CHARS: &[char] = &['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// The widest that a number can be padded to.
+const MAX_WIDTH: usize = 16;
+
+#[derive(Debug, PartialEq)]
+enum Part {
+    Text(String),
+    Name,
+    Index(usize), // Zero-padded to the width
+    Time(usize),  // Zero-padded to the width
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    /// Parse and check `text`, returning a message for the user if it can't be used.
+    pub fn parse(text: &str) -> Result<Template, String> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut token = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => token.push(c),
+                            None => return Err(tr("template-unclosed")),
+                        }
+                    }
+                    if !literal.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut literal)));
+                    }
+                    parts.push(parse_token(&token)?);
+                }
+                '}' => return Err(tr("template-stray-brace")),
+                c if is_illegal(c) => {
+                    return Err(tr_args(
+                        "template-illegal-char",
+                        &[("char", c.escape_default().to_string().into())],
+                    ))
+                }
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Text(literal));
+        }
+        // Without a number every frame would overwrite the previous one
+        if !parts.iter().any(|part| matches!(part, Part::Index(_) | Part::Time(_))) {
+            return Err(tr("template-no-number"));
+        }
+        let template = Template { parts: parts };
+        // The frames are encoded in the format that the extension calls for
+        match Format::from_path(Path::new(&template.expand("name", 0, 0))) {
+            Some(Format::Png) | Some(Format::Jpeg) => Ok(template),
+            _ => Err(tr("template-bad-extension")),
+        }
+    }
+
+    /// The file name of the frame at `index`, counting from 0, which is shown `time` milliseconds into the animation.
+    ///
+    /// Characters of the name that can't be in a file name are replaced with underscores.
+    pub fn expand(&self, name: &str, index: usize, time: u64) -> String {
+        let mut file_name = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => file_name.push_str(text),
+                Part::Name => file_name.extend(name.chars().map(|c| if is_illegal(c) { '_' } else { c })),
+                Part::Index(width) => file_name.push_str(&format!("{:0width$}", index + 1, width = width)),
+                Part::Time(width) => file_name.push_str(&format!("{:0width$}", time, width = width)),
+            }
+        }
+        file_name
+    }
+}
+
+/// Write the `frames`, with their delays in nanoseconds, into `dir` on a thread of its own.
+///
+/// The outcome is reported in a toast.
+pub fn export(dir: PathBuf, name: String, template: Template, frames: Vec<(ImgVec<RGBA8>, i64)>) {
+    thread::spawn(move || {
+        let mut time = 0;
+        for (index, (pixels, delay)) in frames.iter().enumerate() {
+            let path = dir.join(template.expand(&name, index, time / 1_000_000));
+            if let Err(error) = formats::save_frame(&path, pixels) {
+                post(ToastMessage::error(tr_args(
+                    "toast-save-failed",
+                    &[("path", path.display().to_string().into()), ("error", error.into())],
+                )));
+                return;
+            }
+            time += (*delay).max(0) as u64;
+        }
+        post(ToastMessage::info(tr_args(
+            "toast-frames-exported",
+            &[
+                ("count", frames.len().into()),
+                ("path", dir.display().to_string().into()),
+            ],
+        )));
+    });
+}
+
+fn is_illegal(c: char) -> bool {
+    ILLEGAL_CHARS.contains(&c) || c.is_control()
+}
+
+/// Parse what is between the braces, e.g. `index:04`.
+fn parse_token(token: &str) -> Result<Part, String> {
+    let unknown = || tr_args("template-unknown-token", &[("token", format!("{{{}}}", token).into())]);
+    let (name, width) = match token.find(':') {
+        Some(colon) => {
+            let spec = &token[colon + 1..];
+            let width = match spec.parse::<usize>() {
+                Ok(width) if spec.chars().all(|c| c.is_ascii_digit()) && width <= MAX_WIDTH => width,
+                _ => return Err(unknown()),
+            };
+            (&token[..colon], Some(width))
+        }
+        None => (token, None),
+    };
+    match (name, width) {
+        ("name", None) => Ok(Part::Name),
+        ("index", width) => Ok(Part::Index(width.unwrap_or(0))),
+        ("time", width) => Ok(Part::Time(width.unwrap_or(0))),
+        _ => Err(unknown()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_are_expanded() {
+        let template = Template::parse(DEFAULT_TEMPLATE).unwrap();
+        assert_eq!(template.expand("fire", 0, 0), "fire_0001.png");
+        assert_eq!(template.expand("fire", 41, 0), "fire_0042.png");
+        assert_eq!(template.expand("fire", 12344, 0), "fire_12345.png");
+        let template = Template::parse("{{{index}}} at {time:06} ms of {name}.jpg").unwrap();
+        assert_eq!(template.expand("fire", 2, 1250), "{3} at 001250 ms of fire.jpg");
+    }
+
+    #[test]
+    fn names_are_made_safe() {
+        let template = Template::parse("{name}-{index}.png").unwrap();
+        assert_eq!(template.expand("a/b: c?", 0, 0), "a_b_ c_-1.png");
+    }
+
+    #[test]
+    fn invalid_templates_are_rejected() {
+        for text in &[
+            "",
+            "{name}.png",
+            "{index}",
+            "{index}.gif",
+            "{frame}.png",
+            "{name:04}_{index}.png",
+            "{index:4a}.png",
+            "{index:99}.png",
+            "{index.png",
+            "index}.png",
+            "frames/{index}.png",
+            "{index}?.png",
+        ] {
+            assert!(Template::parse(text).is_err(), "{} was accepted", text);
+        }
+    }
+}
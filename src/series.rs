@@ -0,0 +1,115 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Saving the composited surface as a numbered series of PNG files, e.g. while reproducing an issue for a bug report.
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Sender};
+use std::thread;
+
+use druid::kurbo::{Affine, Rect};
+use imgref::ImgVec;
+use rgb::RGBA8;
+
+use crate::composite::{self, Placed};
+use crate::formats;
+use crate::i18n::tr_args;
+use crate::ui::{post, ToastMessage};
+
+/// Copies of what the surface showed at one moment, to be composited away from the UI thread.
+pub struct Snapshot {
+    pub backdrop: RGBA8,
+    pub width: usize,
+    pub height: usize,
+    pub layers: Vec<(ImgVec<RGBA8>, Rect, Affine)>, // The pixels of every visible view and where they are, bottom first
+}
+
+/// A series that is being recorded into a directory.
+pub struct Series {
+    dir: PathBuf,
+    count: usize, // How many snapshots have been sent to the worker
+    sender: Sender<(usize, Snapshot)>,
+}
+
+impl Series {
+    /// Start a worker that writes every snapshot it gets into `dir`. The worker stops when the series is dropped.
+    pub fn start(dir: PathBuf) -> Series {
+        let (sender, receiver) = channel::<(usize, Snapshot)>();
+        let worker_dir = dir.clone();
+        thread::spawn(move || {
+            for (number, snapshot) in receiver {
+                let layers: Vec<Placed> = snapshot
+                    .layers
+                    .iter()
+                    .map(|(image, rect, transform)| Placed {
+                        image,
+                        rect: *rect,
+                        transform: *transform,
+                    })
+                    .collect();
+                let pixels = composite::composite(snapshot.backdrop, &layers, snapshot.width, snapshot.height);
+                let path = worker_dir.join(file_name(number));
+                if let Err(error) = formats::save_frame(&path, &pixels) {
+                    post(ToastMessage::error(tr_args(
+                        "toast-series-failed",
+                        &[("path", path.display().to_string().into()), ("error", error.into())],
+                    )));
+                }
+            }
+        });
+        Series {
+            dir: dir,
+            count: 0,
+            sender: sender,
+        }
+    }
+
+    /// Queue the snapshot to be written as the next file of the series.
+    pub fn capture(&mut self, snapshot: Snapshot) {
+        self.count += 1;
+        // The worker only goes away with the series, so there's always someone to receive
+        let _ = self.sender.send((self.count, snapshot));
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The number of files in the series so far.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+/// The name of the file with the given number in the series, counting from 1.
+fn file_name(number: usize) -> String {
+    format!("capture_{:04}.png", number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_names_sort_by_number() {
+        assert_eq!(file_name(1), "capture_0001.png");
+        assert_eq!(file_name(12), "capture_0012.png");
+        assert_eq!(file_name(12345), "capture_12345.png");
+    }
+}
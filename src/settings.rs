@@ -0,0 +1,347 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! User settings that apply to the whole application, stored as TOML in the user's config directory.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::sequence;
+
+static CURRENT: Mutex<Option<Settings>> = Mutex::new(None);
+
+/// The scratch projects that the windows of this instance use, which no other window may autosave over.
+static CLAIMED_SCRATCH: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    /// The language to use for the UI, e.g. `en-US`. The system language is used when not set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Whether to use the dark palette instead of the light one.
+    pub dark_theme: bool,
+    /// Whether zoomed images are smoothed, or scaled with nearest neighbor to keep the pixels sharp.
+    pub smooth_scaling: bool,
+    /// Whether to show the stats bar at the top of the window.
+    pub show_stats: bool,
+    /// Whether animations keep running while the window is in the background.
+    pub animate_in_background: bool,
+    /// The project that was open when Slark was last closed, which may be the scratch project.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_project: Option<PathBuf>,
+    /// Whether launching without arguments reopens the last project instead of offering it on the welcome screen.
+    pub reopen_last_session: bool,
+    /// How many seconds a decoder may go without producing a frame before it's considered stalled.
+    pub decode_timeout: u64,
+    /// Whether every image starts out paused, even when added later, for those who find autoplay distracting.
+    pub start_paused: bool,
+    /// The smallest scale factor that images can be zoomed out to, at most 1.0.
+    pub min_zoom: f64,
+    /// The largest scale factor that images can be zoomed in to, at least 1.0.
+    pub max_zoom: f64,
+    /// How many times a second the animations may advance and repaint, with 0 meaning as often as the display refreshes.
+    pub animation_fps: u32,
+    /// How many seconds pass between the files of a screenshot series.
+    pub capture_interval: u64,
+    /// How the files are named when exporting the frames of an image, see `sequence::Template`.
+    pub frame_template: String,
+    /// Whether removed images are saved along with the project, so that they can still be restored after reopening it.
+    pub keep_trash: bool,
+    /// Images with more megapixels than this ask before being loaded, offering a smaller proxy instead. 0 never asks.
+    pub large_image_megapixels: u64,
+    /// Where the most recently closed window was, for opening windows that have no geometry of their own.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window: Option<WindowGeometry>,
+    /// Where the windows of saved projects were when they were last closed, by project path.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub project_windows: BTreeMap<PathBuf, WindowGeometry>,
+}
+
+/// The position and size of a window in display points, as it was when not maximized.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct WindowGeometry {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub maximized: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            language: None,
+            dark_theme: true,
+            smooth_scaling: true,
+            show_stats: true,
+            animate_in_background: false,
+            last_project: None,
+            reopen_last_session: false,
+            decode_timeout: 30,
+            start_paused: false,
+            min_zoom: 0.1,
+            max_zoom: 32.0,
+            animation_fps: 60,
+            capture_interval: 2,
+            frame_template: String::from(sequence::DEFAULT_TEMPLATE),
+            keep_trash: false,
+            large_image_megapixels: 100,
+            window: None,
+            project_windows: BTreeMap::new(),
+        }
+    }
+}
+
+impl Settings {
+    /// Load the settings, falling back to the defaults when there is no usable settings file.
+    pub fn load() -> Settings {
+        let path = match Settings::path() {
+            Some(path) => path,
+            None => {
+                warn!("No config directory, using the default settings");
+                return Settings::default();
+            }
+        };
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(error) => {
+                warn!(
+                    "Failed to read {}, using the default settings: {}",
+                    path.display(),
+                    error
+                );
+                return Settings::default();
+            }
+        };
+        match Settings::parse(&text) {
+            Ok(settings) => settings,
+            Err(error) => {
+                warn!(
+                    "Failed to parse {}, using the default settings: {}",
+                    path.display(),
+                    error
+                );
+                Settings::default()
+            }
+        }
+    }
+
+    fn parse(text: &str) -> Result<Settings, String> {
+        toml::from_str(text).map_err(|error| error.to_string())
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = Settings::path().ok_or_else(|| String::from("No config directory"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|error| format!("Failed to create {}: {}", dir.display(), error))?;
+        }
+        let text = toml::to_string_pretty(self).map_err(|error| error.to_string())?;
+        fs::write(&path, text).map_err(|error| format!("Failed to write {}: {}", path.display(), error))
+    }
+
+    /// The geometry to open the window of the project at `project` with, falling back to the last closed window.
+    pub fn window_geometry(&self, project: Option<&Path>) -> Option<WindowGeometry> {
+        project
+            .and_then(|path| self.project_windows.get(path))
+            .or_else(|| self.window.as_ref())
+            .copied()
+    }
+
+    /// Remember the geometry of a window that is being closed, also for its project if it has been saved.
+    pub fn remember_window(&mut self, geometry: WindowGeometry, project: Option<&Path>) {
+        self.window = Some(geometry);
+        if let Some(path) = project {
+            self.project_windows.insert(path.to_path_buf(), geometry);
+        }
+        // Forget the projects that are gone, so that the list doesn't keep growing
+        self.project_windows.retain(|path, _| path.is_file());
+    }
+
+    /// The last project, if it still exists.
+    pub fn last_session(&self) -> Option<&Path> {
+        self.last_project.as_deref().filter(|path| path.is_file())
+    }
+
+    /// The location of the settings file.
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("slark").join("settings.toml"))
+    }
+}
+
+/// Where a project that was never saved is kept between launches.
+///
+/// Every window gets a scratch project of its own, the first one is `scratch.arkb`
+/// and the rest are numbered from `scratch-2.arkb` on.
+fn scratch_path(index: usize) -> Option<PathBuf> {
+    let name = match index {
+        0 => String::from("scratch.arkb"),
+        _ => format!("scratch-{}.arkb", index + 1),
+    };
+    dirs::config_dir().map(|dir| dir.join("slark").join(name))
+}
+
+/// Whether `path` is one of the scratch projects.
+pub fn is_scratch(path: &Path) -> bool {
+    let name = match path.file_name().and_then(|name| name.to_str()) {
+        Some(name) => name,
+        None => return false,
+    };
+    let index = if name == "scratch.arkb" {
+        Some(0)
+    } else {
+        name.strip_prefix("scratch-")
+            .and_then(|name| name.strip_suffix(".arkb"))
+            .and_then(|number| number.parse::<usize>().ok())
+            .and_then(|number| number.checked_sub(1))
+    };
+    // Comparing the whole path also rules out other spellings of the number, like `scratch-02.arkb`
+    index.and_then(scratch_path).as_deref() == Some(path)
+}
+
+/// Keep the scratch project at `path` for the window that opened it.
+pub fn claim_scratch(path: &Path) {
+    let mut claimed = CLAIMED_SCRATCH.lock().unwrap();
+    if !claimed.iter().any(|claimed| claimed == path) {
+        claimed.push(path.to_path_buf());
+    }
+}
+
+/// Find a scratch project that no other window of this instance uses, and keep it for the calling window.
+pub fn claim_free_scratch() -> Option<PathBuf> {
+    let mut claimed = CLAIMED_SCRATCH.lock().unwrap();
+    let path = (0..)
+        .map(scratch_path)
+        .find(|path| !matches!(path, Some(path) if claimed.contains(path)))??;
+    claimed.push(path.clone());
+    Some(path)
+}
+
+/// Load the settings file. Call this once at startup, before anything reads the settings.
+pub fn init() -> Settings {
+    let settings = Settings::load();
+    *CURRENT.lock().unwrap() = Some(settings.clone());
+    settings
+}
+
+/// Returns the settings currently in use.
+pub fn current() -> Settings {
+    CURRENT.lock().unwrap().clone().unwrap_or_default()
+}
+
+/// Change the settings and write them to the settings file.
+pub fn update(change: impl FnOnce(&mut Settings)) {
+    let mut current = CURRENT.lock().unwrap();
+    let settings = current.get_or_insert_with(Settings::default);
+    change(settings);
+    if let Err(error) = settings.save() {
+        warn!("Failed to save the settings: {}", error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let settings = Settings {
+            language: Some(String::from("et-EE")),
+            dark_theme: false,
+            smooth_scaling: false,
+            show_stats: false,
+            animate_in_background: true,
+            last_project: Some(PathBuf::from("/home/slark/project.ark")),
+            reopen_last_session: true,
+            decode_timeout: 5,
+            start_paused: true,
+            min_zoom: 0.25,
+            max_zoom: 8.0,
+            animation_fps: 30,
+            capture_interval: 10,
+            frame_template: String::from("{index}.jpg"),
+            keep_trash: true,
+            large_image_megapixels: 0,
+            window: Some(WindowGeometry {
+                x: -1280.0,
+                y: 40.0,
+                width: 1024.0,
+                height: 768.0,
+                maximized: true,
+            }),
+            project_windows: vec![(
+                PathBuf::from("/home/slark/project.ark"),
+                WindowGeometry {
+                    x: 100.0,
+                    y: 100.0,
+                    width: 640.0,
+                    height: 480.0,
+                    maximized: false,
+                },
+            )]
+            .into_iter()
+            .collect(),
+        };
+        let text = toml::to_string_pretty(&settings).unwrap();
+        assert_eq!(Settings::parse(&text).unwrap(), settings);
+    }
+
+    #[test]
+    fn missing_fields_are_defaults() {
+        assert_eq!(Settings::parse("").unwrap(), Settings::default());
+        let settings = Settings::parse("dark_theme = false").unwrap();
+        assert!(!settings.dark_theme);
+        assert!(settings.smooth_scaling);
+    }
+
+    #[test]
+    fn every_window_gets_its_own_scratch_project() {
+        let (first, second) = match (claim_free_scratch(), claim_free_scratch()) {
+            (Some(first), Some(second)) => (first, second),
+            // No config directory to put them in
+            _ => return,
+        };
+        assert_ne!(first, second);
+        assert!(is_scratch(&first) && is_scratch(&second));
+        // Claiming a taken one again changes nothing
+        claim_scratch(&first);
+        let claimed = CLAIMED_SCRATCH
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|path| **path == first)
+            .count();
+        assert_eq!(claimed, 1);
+        assert!(!is_scratch(&first.with_file_name("scratch-1.arkb")));
+        assert!(!is_scratch(&first.with_file_name("scratch-02.arkb")));
+        assert!(!is_scratch(&first.with_file_name("scratch.ark")));
+        assert!(!is_scratch(Path::new("scratch.arkb")));
+    }
+
+    #[test]
+    fn corrupt_file() {
+        assert!(Settings::parse("dark_theme = ").is_err());
+        assert!(Settings::parse("dark_theme = 5").is_err());
+    }
+}
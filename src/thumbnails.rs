@@ -0,0 +1,254 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Small previews of project images, cached in memory and on disk.
+//!
+//! The thumbnails are keyed by the canonical path and the modification time of the source file,
+//! so an edited image gets a fresh thumbnail while an unchanged one is ready right away.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use imgref::ImgVec;
+use log::warn;
+use rgb::RGBA8;
+
+use crate::formats::{self, png};
+use crate::image::downscale;
+
+/// The maximum width and height of a thumbnail.
+pub const SIZE: usize = 128;
+
+/// How many bytes of thumbnails the disk cache keeps, the least recently written ones go first.
+const DISK_CACHE_BYTES: u64 = 64 * 1024 * 1024;
+
+static MEMORY: Mutex<Option<HashMap<Key, Arc<ImgVec<RGBA8>>>>> = Mutex::new(None);
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct Key {
+    path: PathBuf,                // Canonical, so that different routes to the same file share a thumbnail
+    modified: Option<SystemTime>, // None if the file system doesn't know
+}
+
+impl Key {
+    fn new(path: &Path) -> Key {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let modified = fs::metadata(&path).and_then(|metadata| metadata.modified()).ok();
+        Key { path, modified }
+    }
+
+    /// The name of the file in the disk cache.
+    ///
+    /// The hash has to stay the same across Rust releases, so it's FNV-1a rather than the standard library one.
+    fn file_name(&self) -> String {
+        let mut bytes = self.path.to_string_lossy().into_owned().into_bytes();
+        if let Some(since_epoch) = self
+            .modified
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        {
+            bytes.extend_from_slice(&since_epoch.as_secs().to_le_bytes());
+            bytes.extend_from_slice(&since_epoch.subsec_nanos().to_le_bytes());
+        }
+        format!("{:016x}.png", fnv1a(&bytes))
+    }
+}
+
+/// Returns a receiver for the thumbnail of the image at `path`, at most `SIZE` pixels wide and high.
+///
+/// A thumbnail that is already in memory is sent right away, otherwise it's sent once it has been
+/// loaded from the disk cache or decoded from the first frame. Nothing is sent if decoding fails.
+pub fn request(path: &Path) -> Receiver<Arc<ImgVec<RGBA8>>> {
+    let (sender, receiver) = channel();
+    let key = Key::new(path);
+    if let Some(thumbnail) = cached(&key) {
+        let _ = sender.send(thumbnail);
+        return receiver;
+    }
+    thread::spawn(move || {
+        if let Some(thumbnail) = load(&key).or_else(|| generate(&key)) {
+            let thumbnail = Arc::new(thumbnail);
+            remember(key, thumbnail.clone());
+            // The receiver may well be gone by now, but the thumbnail is remembered either way
+            let _ = sender.send(thumbnail);
+        }
+    });
+    receiver
+}
+
+/// Downscale `image` to a thumbnail, for images that have no file to cache it by.
+pub fn downscaled(image: &ImgVec<RGBA8>) -> ImgVec<RGBA8> {
+    downscale(image, SIZE)
+}
+
+fn cached(key: &Key) -> Option<Arc<ImgVec<RGBA8>>> {
+    MEMORY.lock().unwrap().as_ref()?.get(key).cloned()
+}
+
+fn remember(key: Key, thumbnail: Arc<ImgVec<RGBA8>>) {
+    MEMORY
+        .lock()
+        .unwrap()
+        .get_or_insert_with(HashMap::new)
+        .insert(key, thumbnail);
+}
+
+/// The location of the disk cache.
+fn cache_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("slark").join("thumbnails"))
+}
+
+/// Load the thumbnail from the disk cache, if it's there.
+fn load(key: &Key) -> Option<ImgVec<RGBA8>> {
+    let path = cache_dir()?.join(key.file_name());
+    if !path.is_file() {
+        return None;
+    }
+    png::Frames::open(&path).ok()?.next()?.ok().map(|frame| frame.image)
+}
+
+/// Decode the first frame of the source image into a thumbnail and put it in the disk cache.
+fn generate(key: &Key) -> Option<ImgVec<RGBA8>> {
+    let thumbnail = match formats::read_frame(&key.path) {
+        Ok(image) => downscaled(&image),
+        Err(error) => {
+            warn!("No thumbnail for {}: {}", key.path.display(), error);
+            return None;
+        }
+    };
+    store(key, &thumbnail);
+    Some(thumbnail)
+}
+
+/// Write the first frame of `input` to `output`, downscaled to fit within `size`.
+///
+/// This is for generating previews from scripts, so it skips the caches.
+pub fn poster(input: &Path, output: &Path, size: usize) -> Result<(), String> {
+    let image = formats::read_frame(input)?;
+    formats::save_frame(output, &downscale(&image, size))
+}
+
+/// Put the thumbnail in the disk cache, which is only an optimization and so only warns on failure.
+fn store(key: &Key, thumbnail: &ImgVec<RGBA8>) {
+    let dir = match cache_dir() {
+        Some(dir) => dir,
+        None => return,
+    };
+    let result = fs::create_dir_all(&dir)
+        .map_err(|error| error.to_string())
+        .and_then(|_| png::save(&dir.join(key.file_name()), thumbnail));
+    if let Err(error) = result {
+        warn!("Failed to cache the thumbnail of {}: {}", key.path.display(), error);
+    }
+    trim(&dir, DISK_CACHE_BYTES);
+}
+
+/// Delete the least recently written thumbnails in `dir` until they take up at most `max_bytes`.
+fn trim(dir: &Path, max_bytes: u64) {
+    let mut files: Vec<(SystemTime, u64, PathBuf)> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .flatten()
+            .filter_map(|entry| {
+                let metadata = entry.metadata().ok().filter(|metadata| metadata.is_file())?;
+                Some((metadata.modified().unwrap_or(UNIX_EPOCH), metadata.len(), entry.path()))
+            })
+            .collect(),
+        Err(_) => return,
+    };
+    let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+    files.sort();
+    for (_, len, path) in files {
+        if total <= max_bytes {
+            break;
+        }
+        match fs::remove_file(&path) {
+            Ok(()) => total -= len,
+            Err(error) => warn!("Failed to delete the cached thumbnail {}: {}", path.display(), error),
+        }
+    }
+}
+
+/// The 64-bit FNV-1a hash of `bytes`.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_follow_the_file() {
+        let dir = std::env::temp_dir().join(format!("slark-thumbnail-keys-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let first = dir.join("first.png");
+        let second = dir.join("second.png");
+        fs::write(&first, b"first").unwrap();
+        fs::write(&second, b"second").unwrap();
+
+        let roundabout = dir.join(".").join("first.png");
+        assert!(Key::new(&first) == Key::new(&roundabout));
+        assert_eq!(Key::new(&first).file_name(), Key::new(&roundabout).file_name());
+        assert_ne!(Key::new(&first).file_name(), Key::new(&second).file_name());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn file_names_are_stable() {
+        // Known FNV-1a values, so that the disk cache survives toolchain updates
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+        let key = Key {
+            path: PathBuf::from("/images/a.png"),
+            modified: None,
+        };
+        assert_eq!(key.file_name(), format!("{:016x}.png", fnv1a(b"/images/a.png")));
+    }
+
+    #[test]
+    fn the_disk_cache_is_trimmed_oldest_first() {
+        let dir = std::env::temp_dir().join(format!("slark-thumbnail-trim-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for name in &["old.png", "middle.png", "new.png"] {
+            fs::write(dir.join(name), [0u8; 100]).unwrap();
+            // Far enough apart for any file system to tell them apart
+            thread::sleep(std::time::Duration::from_millis(1100));
+        }
+        trim(&dir, 250);
+        assert!(!dir.join("old.png").exists());
+        assert!(dir.join("middle.png").exists() && dir.join("new.png").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn thumbnails_fit_the_size() {
+        let image = ImgVec::new(vec![RGBA8::new(1, 2, 3, 4); 300 * 150], 300, 150);
+        let thumbnail = downscale(&image, SIZE);
+        assert_eq!((thumbnail.width(), thumbnail.height()), (SIZE, SIZE / 2));
+        assert_eq!(thumbnail[(0, 0)], RGBA8::new(1, 2, 3, 4));
+    }
+}
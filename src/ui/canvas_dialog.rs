@@ -0,0 +1,115 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use druid::kurbo::Size;
+use druid::widget::{Checkbox, CrossAxisAlignment, Flex, Label, TextBox};
+use druid::{Data, Env, EventCtx, Lens, Widget, WidgetExt, WidgetId};
+
+use crate::i18n::tr;
+use crate::project::Canvas;
+use crate::ui::{dialog, theme, COMMAND_SET_CANVAS};
+
+#[derive(Clone, Data, Lens)]
+struct CanvasDialogData {
+    enabled: bool,
+    width: String,
+    height: String,
+    background: String,
+    error: String,
+}
+
+/// Open the canvas settings dialog. The result is sent to `surface` as `COMMAND_SET_CANVAS`.
+pub fn open_canvas_dialog(ctx: &mut EventCtx, env: &Env, canvas: Option<&Canvas>, surface: WidgetId) {
+    let data = CanvasDialogData {
+        enabled: canvas.is_some(),
+        width: format!("{}", canvas.copied().unwrap_or_default().size().width),
+        height: format!("{}", canvas.copied().unwrap_or_default().size().height),
+        background: format_hex_color(canvas.copied().unwrap_or_default().background()),
+        error: String::new(),
+    };
+    dialog::open(ctx, env, Size::new(320.0, 220.0), build(surface), data);
+}
+
+fn build(surface: WidgetId) -> impl Widget<CanvasDialogData> {
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Checkbox::new(tr("canvas-fixed")).lens(CanvasDialogData::enabled))
+        .with_spacer(8.0)
+        .with_child(row(tr("canvas-width"), CanvasDialogData::width))
+        .with_spacer(4.0)
+        .with_child(row(tr("canvas-height"), CanvasDialogData::height))
+        .with_spacer(4.0)
+        .with_child(row(tr("canvas-background"), CanvasDialogData::background))
+        .with_spacer(4.0)
+        .with_child(Label::dynamic(|data: &CanvasDialogData, _| data.error.clone()).with_text_color(theme::ERROR_TEXT))
+        .with_flex_spacer(1.0)
+        .with_child(dialog::buttons(move |ctx, data: &mut CanvasDialogData| {
+            if !data.enabled {
+                ctx.submit_command(COMMAND_SET_CANVAS.with(None).to(surface));
+                return true;
+            }
+            let width = data.width.trim().parse::<f64>().ok().filter(|w| *w >= 1.0);
+            let height = data.height.trim().parse::<f64>().ok().filter(|h| *h >= 1.0);
+            let background = parse_hex_color(&data.background);
+            match (width, height, background) {
+                (Some(width), Some(height), Some(background)) => {
+                    let canvas = Canvas::new(Size::new(width, height), background);
+                    ctx.submit_command(COMMAND_SET_CANVAS.with(Some(canvas)).to(surface));
+                    true
+                }
+                (None, _, _) | (_, None, _) => {
+                    data.error = tr("canvas-invalid-size");
+                    false
+                }
+                (_, _, None) => {
+                    data.error = tr("canvas-invalid-background");
+                    false
+                }
+            }
+        }))
+}
+
+fn row(label: String, lens: impl Lens<CanvasDialogData, String> + 'static) -> impl Widget<CanvasDialogData> {
+    Flex::row()
+        .with_child(Label::new(label).fix_width(90.0))
+        .with_flex_child(TextBox::new().lens(lens).expand_width(), 1.0)
+}
+
+/// Parses `#RRGGBB` or `#RRGGBBAA` into RGBA bytes.
+pub fn parse_hex_color(text: &str) -> Option<[u8; 4]> {
+    let hex = text.trim().trim_start_matches('#');
+    if !(hex.len() == 6 || hex.len() == 8) || !hex.is_ascii() {
+        return None;
+    }
+    let mut rgba = [255; 4];
+    for (i, byte) in rgba.iter_mut().enumerate().take(hex.len() / 2) {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(rgba)
+}
+
+/// Formats RGBA bytes as `#RRGGBB`, or `#RRGGBBAA` when not fully opaque.
+pub fn format_hex_color(rgba: [u8; 4]) -> String {
+    let [r, g, b, a] = rgba;
+    if a == 255 {
+        format!("#{:02X}{:02X}{:02X}", r, g, b)
+    } else {
+        format!("#{:02X}{:02X}{:02X}{:02X}", r, g, b, a)
+    }
+}
@@ -0,0 +1,190 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! A full screen window over a screenshot, for dragging out the region to capture.
+
+use std::sync::Arc;
+
+use druid::kurbo::{Point, Rect};
+use druid::piet::{ImageFormat, InterpolationMode, PietImage, Text, TextLayout, TextLayoutBuilder};
+use druid::widget::prelude::*;
+use druid::{commands, Cursor, FontFamily, KbKey, Scale, Selector, WindowDesc};
+use imgref::ImgVec;
+use rgb::{ComponentBytes, RGBA8};
+
+use crate::capture::{self, Screenshot};
+use crate::i18n::tr;
+use crate::ui::theme::{self, ThemeScope};
+
+/// Sent to the surface with the captured pixels.
+pub const COMMAND_REGION_CAPTURED: Selector<Arc<ImgVec<RGBA8>>> = Selector::new("slark.region_captured");
+/// Sent to the surface when the capture was cancelled.
+pub const COMMAND_CAPTURE_CANCELLED: Selector = Selector::new("slark.capture_cancelled");
+
+/// Cover all the monitors with `screenshot` and let the user drag out a region of it.
+///
+/// The outcome is sent to `surface` as either `COMMAND_REGION_CAPTURED` or `COMMAND_CAPTURE_CANCELLED`.
+pub fn open_capture_overlay(ctx: &mut EventCtx, screenshot: Screenshot, surface: WidgetId) {
+    // Window positions and sizes are in display points, the screenshot is in pixels
+    let scale = ctx.window().get_scale().unwrap_or(Scale::new(1.0, 1.0));
+    let Screenshot { image, origin } = screenshot;
+    let position = Point::new(origin.x / scale.x(), origin.y / scale.y());
+    let size = Size::new(image.width() as f64 / scale.x(), image.height() as f64 / scale.y());
+    let window = WindowDesc::new(ThemeScope::new(CaptureOverlay::new(image, surface)))
+        .title(tr("app-title"))
+        .show_titlebar(false)
+        .resizable(false)
+        .set_position(position)
+        .window_size(size)
+        .set_always_on_top(true);
+    ctx.new_window(window);
+}
+
+struct CaptureOverlay {
+    screenshot: ImgVec<RGBA8>,
+    image: Option<PietImage>, // The screenshot uploaded for painting
+    surface: WidgetId,
+    drag: Option<(Point, Point)>, // Where the drag started and where it is now
+}
+
+impl CaptureOverlay {
+    fn new(screenshot: ImgVec<RGBA8>, surface: WidgetId) -> CaptureOverlay {
+        CaptureOverlay {
+            screenshot,
+            image: None,
+            surface,
+            drag: None,
+        }
+    }
+
+    fn region(&self) -> Option<Rect> {
+        self.drag.map(|(start, end)| Rect::from_points(start, end))
+    }
+
+    fn finish(&mut self, ctx: &mut EventCtx) {
+        let region = match self.region() {
+            Some(region) => region,
+            None => return,
+        };
+        self.drag = None;
+        // Back from display points to screenshot pixels
+        let scale = self.screenshot.width() as f64 / ctx.size().width;
+        let pixels = Rect::new(
+            region.x0 * scale,
+            region.y0 * scale,
+            region.x1 * scale,
+            region.y1 * scale,
+        );
+        match capture::crop(&self.screenshot, pixels) {
+            Some(image) => {
+                ctx.submit_command(COMMAND_REGION_CAPTURED.with(Arc::new(image)).to(self.surface));
+                ctx.submit_command(commands::CLOSE_WINDOW);
+            }
+            // A click without dragging, keep waiting for a proper region
+            None => ctx.request_paint(),
+        }
+    }
+
+    fn paint_hint(&self, ctx: &mut PaintCtx, env: &Env) {
+        let layout = ctx
+            .text()
+            .new_text_layout(tr("capture-hint"))
+            .font(FontFamily::SYSTEM_UI, 14.0)
+            .text_color(env.get(theme::CAPTURE_OUTLINE))
+            .build()
+            .unwrap();
+        let size = layout.size();
+        let origin = Point::new((ctx.size().width - size.width) / 2.0, 24.0);
+        let backing = Rect::from_origin_size(origin, size).inset(6.0).to_rounded_rect(4.0);
+        ctx.fill(backing, &env.get(theme::CAPTURE_DIM));
+        ctx.draw_text(&layout, origin);
+    }
+}
+
+impl Widget<u64> for CaptureOverlay {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, _data: &mut u64, _env: &Env) {
+        match event {
+            Event::WindowConnected => {
+                ctx.request_focus();
+                ctx.set_cursor(&Cursor::Crosshair);
+            }
+            Event::MouseDown(mouse_event) if mouse_event.button.is_left() => {
+                self.drag = Some((mouse_event.pos, mouse_event.pos));
+                ctx.set_active(true);
+                ctx.request_paint();
+            }
+            Event::MouseMove(mouse_event) => {
+                if let Some((_, end)) = &mut self.drag {
+                    *end = mouse_event.pos;
+                    ctx.request_paint();
+                }
+            }
+            Event::MouseUp(mouse_event) if mouse_event.button.is_left() && ctx.is_active() => {
+                ctx.set_active(false);
+                self.finish(ctx);
+            }
+            Event::KeyDown(key_event) if key_event.key == KbKey::Escape => {
+                ctx.submit_command(COMMAND_CAPTURE_CANCELLED.to(self.surface));
+                ctx.submit_command(commands::CLOSE_WINDOW);
+            }
+            _ => (),
+        }
+    }
+
+    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, _data: &u64, _env: &Env) {
+        if let LifeCycle::WidgetAdded = event {
+            ctx.register_for_focus();
+        }
+    }
+
+    fn update(&mut self, _ctx: &mut UpdateCtx, _old_data: &u64, _data: &u64, _env: &Env) {}
+
+    fn layout(&mut self, _ctx: &mut LayoutCtx, bc: &BoxConstraints, _data: &u64, _env: &Env) -> Size {
+        bc.max()
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, _data: &u64, env: &Env) {
+        if self.image.is_none() {
+            let (width, height) = (self.screenshot.width(), self.screenshot.height());
+            let (buf, _, _) = self.screenshot.as_ref().to_contiguous_buf();
+            self.image = ctx
+                .make_image(width, height, buf.as_bytes(), ImageFormat::RgbaSeparate)
+                .ok();
+        }
+        let bounds = ctx.size().to_rect();
+        if let Some(image) = &self.image {
+            ctx.draw_image(image, bounds, InterpolationMode::NearestNeighbor);
+        }
+        let dim = env.get(theme::CAPTURE_DIM);
+        match self.region() {
+            Some(region) => {
+                // Darken everything around the region
+                ctx.fill(Rect::new(bounds.x0, bounds.y0, bounds.x1, region.y0), &dim);
+                ctx.fill(Rect::new(bounds.x0, region.y1, bounds.x1, bounds.y1), &dim);
+                ctx.fill(Rect::new(bounds.x0, region.y0, region.x0, region.y1), &dim);
+                ctx.fill(Rect::new(region.x1, region.y0, bounds.x1, region.y1), &dim);
+                ctx.stroke(region, &env.get(theme::CAPTURE_OUTLINE), 1.0);
+            }
+            None => {
+                ctx.fill(bounds, &dim);
+                self.paint_hint(ctx, env);
+            }
+        }
+    }
+}
@@ -0,0 +1,93 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use druid::Data;
+use imgref::ImgVec;
+use rgb::{RGB8, RGBA8};
+use serde::{Deserialize, Serialize};
+
+/// A color that is shown as transparent, e.g. the baked-in background of an old GIF.
+#[derive(Data, Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ColorKey {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    /// How far each channel may be from the key color for a pixel to still count as it.
+    pub tolerance: u8,
+}
+
+impl ColorKey {
+    /// The tolerance that picking a new key color starts out with.
+    pub const DEFAULT_TOLERANCE: u8 = 16;
+    /// The tolerances offered in the context menu.
+    pub const TOLERANCES: [u8; 4] = [0, 16, 32, 64];
+
+    pub fn new(color: RGB8, tolerance: u8) -> ColorKey {
+        ColorKey {
+            red: color.r,
+            green: color.g,
+            blue: color.b,
+            tolerance: tolerance,
+        }
+    }
+
+    /// Whether the pixel is close enough to the key color to be made transparent.
+    pub fn matches(&self, pixel: RGBA8) -> bool {
+        let near = |a: u8, b: u8| (a as i16 - b as i16).abs() <= self.tolerance as i16;
+        near(pixel.r, self.red) && near(pixel.g, self.green) && near(pixel.b, self.blue)
+    }
+
+    /// A copy of `pixels` with every pixel that matches the key made fully transparent.
+    pub fn key_out(&self, pixels: &ImgVec<RGBA8>) -> ImgVec<RGBA8> {
+        let buf = pixels
+            .pixels()
+            .map(|pixel| {
+                if self.matches(pixel) {
+                    RGBA8 { a: 0, ..pixel }
+                } else {
+                    pixel
+                }
+            })
+            .collect();
+        ImgVec::new(buf, pixels.width(), pixels.height())
+    }
+
+    /// The key color in the `#RRGGBB` form.
+    pub fn hex(&self) -> String {
+        format!("#{:02X}{:02X}{:02X}", self.red, self.green, self.blue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_close_colors_are_keyed_out() {
+        let white = RGBA8::new(255, 255, 255, 255);
+        let near_white = RGBA8::new(240, 250, 255, 255);
+        let gray = RGBA8::new(200, 200, 200, 255);
+        let clear = |pixel: RGBA8| RGBA8 { a: 0, ..pixel };
+        let pixels = ImgVec::new(vec![white, near_white, gray], 3, 1);
+        let keyed = ColorKey::new(RGB8::new(255, 255, 255), 16).key_out(&pixels);
+        assert_eq!(keyed.buf(), &vec![clear(white), clear(near_white), gray]);
+        let exact = ColorKey::new(RGB8::new(255, 255, 255), 0).key_out(&pixels);
+        assert_eq!(exact.buf(), &vec![clear(white), near_white, gray]);
+    }
+}
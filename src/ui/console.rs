@@ -0,0 +1,125 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! The debug console shows the most recent log lines at the bottom of the window.
+
+use druid::widget::prelude::*;
+use druid::widget::Label;
+use druid::{FontDescriptor, FontFamily, Selector};
+
+use crate::logger;
+use crate::ui::theme;
+
+/// Show or hide the debug console.
+pub const COMMAND_TOGGLE_CONSOLE: Selector = Selector::new("slark.toggle_console");
+
+pub struct Console {
+    visible: bool,
+    line_count: u64, // logger::line_count() when the text was last refreshed
+    scroll: usize,   // how many lines we are scrolled up from the most recent one
+    label: Label<()>,
+}
+
+impl Console {
+    const HEIGHT: f64 = 240.0;
+    const VISIBLE_LINES: usize = 15;
+
+    pub fn new() -> Console {
+        Console {
+            visible: false,
+            line_count: 0,
+            scroll: 0,
+            label: Label::new("")
+                .with_font(FontDescriptor::new(FontFamily::MONOSPACE).with_size(12.0))
+                .with_text_color(theme::PANEL_TEXT),
+        }
+    }
+
+    fn refresh(&mut self, ctx: &mut EventCtx) {
+        self.line_count = logger::line_count();
+        let lines = logger::lines(Console::VISIBLE_LINES, self.scroll);
+        self.label.set_text(lines.join("\n"));
+        ctx.request_update();
+        ctx.request_layout();
+    }
+}
+
+impl Widget<u64> for Console {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, _data: &mut u64, _env: &Env) {
+        match event {
+            Event::Command(cmd) if cmd.is(COMMAND_TOGGLE_CONSOLE) => {
+                self.visible = !self.visible;
+                self.scroll = 0;
+                if self.visible {
+                    self.refresh(ctx);
+                    ctx.request_anim_frame();
+                } else {
+                    ctx.request_layout();
+                }
+                ctx.set_handled();
+            }
+            Event::AnimFrame(_) if self.visible => {
+                // Stay at the most recent lines, unless scrolled up
+                if self.scroll == 0 && self.line_count != logger::line_count() {
+                    self.refresh(ctx);
+                }
+                ctx.request_anim_frame();
+            }
+            Event::Wheel(mouse_event) if self.visible => {
+                let max_scroll = logger::kept_line_count().saturating_sub(Console::VISIBLE_LINES);
+                if mouse_event.wheel_delta.y < 0.0 {
+                    self.scroll = (self.scroll + 3).min(max_scroll);
+                } else if mouse_event.wheel_delta.y > 0.0 {
+                    self.scroll = self.scroll.saturating_sub(3);
+                }
+                self.refresh(ctx);
+                ctx.set_handled();
+            }
+            _ => (),
+        }
+    }
+
+    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, _data: &u64, env: &Env) {
+        self.label.lifecycle(ctx, event, &(), env);
+    }
+
+    fn update(&mut self, ctx: &mut UpdateCtx, _old_data: &u64, _data: &u64, env: &Env) {
+        self.label.update(ctx, &(), &(), env); // We don't care about the data update
+    }
+
+    fn layout(&mut self, ctx: &mut LayoutCtx, bc: &BoxConstraints, _data: &u64, env: &Env) -> Size {
+        bc.debug_check("Console");
+        if !self.visible {
+            return bc.min();
+        }
+        let label_bc = BoxConstraints::new(Size::ZERO, Size::new(bc.max().width, Console::HEIGHT));
+        self.label.layout(ctx, &label_bc, &(), env);
+        bc.constrain((bc.max().width, Console::HEIGHT))
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, _data: &u64, env: &Env) {
+        if !self.visible {
+            return;
+        }
+        let rect = ctx.size().to_rect();
+        ctx.fill(rect, &env.get(theme::PANEL_BACKGROUND).with_alpha(0.9));
+        ctx.clip(rect);
+        self.label.paint(ctx, &(), env);
+    }
+}
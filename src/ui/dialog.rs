@@ -0,0 +1,73 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Small modal windows which report their results back via commands.
+
+use druid::kurbo::Size;
+use druid::widget::{Button, CrossAxisAlignment, Flex, Label, LineBreaking};
+use druid::{commands, Command, Data, Env, EventCtx, Widget, WidgetExt, WindowConfig, WindowLevel};
+
+use crate::i18n::tr;
+use crate::ui::theme::ThemeScope;
+
+/// Open `widget` as a modal window on top of the window that `ctx` belongs to.
+pub fn open<W: Widget<T> + 'static, T: Data>(ctx: &mut EventCtx, env: &Env, size: Size, widget: W, data: T) {
+    let config = WindowConfig::default()
+        .set_level(WindowLevel::Modal(ctx.window().clone()))
+        .window_size(size)
+        .resizable(false);
+    ctx.new_sub_window(config, ThemeScope::new(widget.padding(12.0)), data, env.clone());
+}
+
+/// Close the dialog window that `ctx` belongs to.
+pub fn close(ctx: &mut EventCtx) {
+    ctx.submit_command(commands::CLOSE_WINDOW);
+}
+
+/// The standard OK / Cancel row. The dialog is closed when `on_ok` returns `true`.
+pub fn buttons<T: Data>(on_ok: impl Fn(&mut EventCtx, &mut T) -> bool + 'static) -> impl Widget<T> {
+    Flex::row()
+        .with_flex_spacer(1.0)
+        .with_child(Button::new(tr("dialog-cancel")).on_click(|ctx, _data, _env| close(ctx)))
+        .with_spacer(8.0)
+        .with_child(Button::new(tr("dialog-ok")).on_click(move |ctx, data, _env| {
+            if on_ok(ctx, data) {
+                close(ctx);
+            }
+        }))
+}
+
+/// Ask a question with a button per choice. Clicking a button submits its command and closes the dialog.
+pub fn confirm(ctx: &mut EventCtx, env: &Env, message: String, choices: Vec<(String, Option<Command>)>) {
+    let mut row = Flex::row().with_flex_spacer(1.0);
+    for (label, command) in choices {
+        row.add_spacer(8.0);
+        row.add_child(Button::new(label).on_click(move |ctx, _data, _env| {
+            if let Some(command) = &command {
+                ctx.submit_command(command.clone());
+            }
+            close(ctx);
+        }));
+    }
+    let widget = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_flex_child(Label::new(message).with_line_break_mode(LineBreaking::WordWrap), 1.0)
+        .with_child(row);
+    open(ctx, env, Size::new(400.0, 140.0), widget, ());
+}
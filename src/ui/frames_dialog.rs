@@ -0,0 +1,83 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use druid::kurbo::Size;
+use druid::widget::{CrossAxisAlignment, Flex, Label, LineBreaking, TextBox};
+use druid::{Data, Env, EventCtx, Lens, Widget, WidgetExt, WidgetId};
+
+use crate::i18n::{tr, tr_args};
+use crate::sequence::Template;
+use crate::ui::{dialog, theme, COMMAND_PICK_FRAMES_DIRECTORY};
+
+#[derive(Clone, Data, Lens)]
+struct FramesDialogData {
+    template: String,
+    name: String, // Of the image, for the preview
+}
+
+/// Open the dialog for naming the exported frames of `image_id`.
+/// The template is sent to `surface` as `COMMAND_PICK_FRAMES_DIRECTORY`.
+pub fn open_frames_dialog(
+    ctx: &mut EventCtx,
+    env: &Env,
+    image_id: usize,
+    name: String,
+    template: String,
+    surface: WidgetId,
+) {
+    let data = FramesDialogData {
+        template: template,
+        name: name,
+    };
+    dialog::open(ctx, env, Size::new(420.0, 200.0), build(image_id, surface), data);
+}
+
+fn build(image_id: usize, surface: WidgetId) -> impl Widget<FramesDialogData> {
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Label::new(tr("frames-template")))
+        .with_spacer(4.0)
+        .with_child(TextBox::new().lens(FramesDialogData::template).expand_width())
+        .with_spacer(4.0)
+        .with_child(Label::new(tr("frames-template-hint")).with_line_break_mode(LineBreaking::WordWrap))
+        .with_spacer(4.0)
+        // Checked on every keystroke, so that the problem or the result is seen right away
+        .with_child(Label::dynamic(|data: &FramesDialogData, _| {
+            match Template::parse(&data.template) {
+                Ok(template) => tr_args("frames-preview", &[("file", template.expand(&data.name, 0, 0).into())]),
+                Err(_) => String::new(),
+            }
+        }))
+        .with_child(
+            Label::dynamic(|data: &FramesDialogData, _| Template::parse(&data.template).err().unwrap_or_default())
+                .with_text_color(theme::ERROR_TEXT),
+        )
+        .with_flex_spacer(1.0)
+        .with_child(dialog::buttons(move |ctx, data: &mut FramesDialogData| {
+            if Template::parse(&data.template).is_err() {
+                return false;
+            }
+            ctx.submit_command(
+                COMMAND_PICK_FRAMES_DIRECTORY
+                    .with((image_id, data.template.clone()))
+                    .to(surface),
+            );
+            true
+        }))
+}
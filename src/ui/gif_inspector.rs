@@ -0,0 +1,131 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use std::sync::Arc;
+
+use druid::kurbo::{Rect, Size};
+use druid::piet::{Color, RenderContext};
+use druid::widget::{Button, CrossAxisAlignment, Flex, Label, Painter, Scroll};
+use druid::{Env, EventCtx, Widget, WidgetExt, WidgetId};
+use rgb::RGB8;
+
+use crate::i18n::{tr, tr_args};
+use crate::image::{Disposal, FrameStructure};
+use crate::ui::{dialog, COMMAND_SHOW_FRAME};
+
+/// The size of a palette swatch, including the gap after it.
+const SWATCH_SIZE: f64 = 14.0;
+const SWATCHES_PER_ROW: usize = 16;
+
+/// Open the GIF inspector for the view with `view_id`.
+///
+/// `frames` holds the structure and the delay of every frame decoded so far.
+/// Clicking a frame sends `COMMAND_SHOW_FRAME` to `surface`.
+pub fn open_gif_inspector(
+    ctx: &mut EventCtx,
+    env: &Env,
+    name: String,
+    frames: Vec<(FrameStructure, i64)>,
+    view_id: usize,
+    surface: WidgetId,
+) {
+    dialog::open(
+        ctx,
+        env,
+        Size::new(520.0, 480.0),
+        build(name, frames, view_id, surface),
+        (),
+    );
+}
+
+fn build(name: String, frames: Vec<(FrameStructure, i64)>, view_id: usize, surface: WidgetId) -> impl Widget<()> {
+    let mut col = Flex::column().cross_axis_alignment(CrossAxisAlignment::Start);
+    col.add_child(Label::new(name));
+    col.add_spacer(8.0);
+
+    let global_palette = frames
+        .first()
+        .and_then(|(structure, _)| structure.global_palette.clone());
+    match global_palette {
+        Some(palette) => {
+            col.add_child(Label::new(tr_args(
+                "inspector-global-palette",
+                &[("count", palette.len().into())],
+            )));
+            col.add_child(swatches(palette));
+        }
+        None => col.add_child(Label::new(tr("inspector-no-global-palette"))),
+    }
+    col.add_spacer(8.0);
+    col.add_child(Label::new(tr("inspector-hint")));
+
+    let mut list = Flex::column().cross_axis_alignment(CrossAxisAlignment::Start);
+    for (index, (structure, delay)) in frames.into_iter().enumerate() {
+        let palette = match structure.local_palette {
+            Some(count) => tr_args("inspector-local-palette", &[("count", count.into())]),
+            None => tr("inspector-shared-palette"),
+        };
+        let disposal = match structure.disposal {
+            Disposal::Unspecified => tr("disposal-unspecified"),
+            Disposal::Keep => tr("disposal-keep"),
+            Disposal::Background => tr("disposal-background"),
+            Disposal::Previous => tr("disposal-previous"),
+        };
+        let text = tr_args(
+            "inspector-frame",
+            &[
+                ("index", (index + 1).into()),
+                ("width", structure.width.into()),
+                ("height", structure.height.into()),
+                ("left", structure.left.into()),
+                ("top", structure.top.into()),
+                ("palette", palette.into()),
+                ("disposal", disposal.into()),
+                ("delay", (delay / 1_000_000).into()),
+            ],
+        );
+        list.add_child(Label::new(text).on_click(move |ctx, _data, _env| {
+            ctx.submit_command(COMMAND_SHOW_FRAME.with((view_id, index)).to(surface));
+        }));
+    }
+    col.add_flex_child(Scroll::new(list).vertical(), 1.0);
+
+    col.add_spacer(8.0);
+    col.add_child(
+        Flex::row()
+            .with_flex_spacer(1.0)
+            .with_child(Button::new(tr("dialog-close")).on_click(|ctx, _data, _env| dialog::close(ctx))),
+    );
+    col
+}
+
+/// The palette colors as a grid of small squares.
+fn swatches(palette: Arc<Vec<RGB8>>) -> impl Widget<()> {
+    let rows = (palette.len() + SWATCHES_PER_ROW - 1) / SWATCHES_PER_ROW;
+    let size = Size::new(SWATCHES_PER_ROW as f64 * SWATCH_SIZE, rows as f64 * SWATCH_SIZE);
+    Painter::new(move |ctx, _data: &(), _env| {
+        for (i, color) in palette.iter().enumerate() {
+            let x = (i % SWATCHES_PER_ROW) as f64 * SWATCH_SIZE;
+            let y = (i / SWATCHES_PER_ROW) as f64 * SWATCH_SIZE;
+            let swatch = Rect::new(x, y, x + SWATCH_SIZE - 2.0, y + SWATCH_SIZE - 2.0);
+            ctx.fill(swatch, &Color::rgb8(color.r, color.g, color.b));
+        }
+    })
+    .fix_size(size.width, size.height)
+}
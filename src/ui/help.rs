@@ -0,0 +1,158 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! The help overlay shows a cheat sheet of the keyboard shortcuts on top of its child.
+
+use druid::kurbo::{Point, Rect};
+use druid::piet::{PietTextLayout, Text, TextLayout, TextLayoutBuilder};
+use druid::widget::prelude::*;
+use druid::{FontFamily, FontWeight, Selector, WidgetPod};
+
+use crate::ui::keymap::{Category, BINDINGS};
+use crate::ui::theme;
+
+/// Show or hide the help overlay.
+pub const COMMAND_TOGGLE_HELP: Selector = Selector::new("slark.toggle_help");
+
+pub struct Help<T> {
+    child: WidgetPod<T, Box<dyn Widget<T>>>,
+    visible: bool,
+}
+
+impl<T: Data> Help<T> {
+    const KEY_COLUMN_WIDTH: f64 = 140.0;
+    const ROW_SPACING: f64 = 4.0;
+    const CATEGORY_SPACING: f64 = 16.0;
+
+    pub fn new(child: impl Widget<T> + 'static) -> Help<T> {
+        Help {
+            child: WidgetPod::new(Box::new(child)),
+            visible: false,
+        }
+    }
+
+    fn text(ctx: &mut PaintCtx, env: &Env, text: String, bold: bool) -> PietTextLayout {
+        let weight = if bold { FontWeight::BOLD } else { FontWeight::REGULAR };
+        ctx.text()
+            .new_text_layout(text)
+            .font(FontFamily::SYSTEM_UI, 14.0)
+            .default_attribute(weight)
+            .text_color(env.get(theme::PANEL_TEXT))
+            .build()
+            .unwrap()
+    }
+}
+
+impl<T: Data> Widget<T> for Help<T> {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut T, env: &Env) {
+        match event {
+            Event::Command(cmd) if cmd.is(COMMAND_TOGGLE_HELP) => {
+                self.visible = !self.visible;
+                ctx.request_paint();
+                ctx.set_handled();
+                return;
+            }
+            // Any key dismisses the overlay
+            Event::KeyDown(_) if self.visible => {
+                ctx.set_handled();
+                return;
+            }
+            Event::KeyUp(_) if self.visible => {
+                self.visible = false;
+                ctx.request_paint();
+                ctx.set_handled();
+                return;
+            }
+            _ => (),
+        }
+        self.child.event(ctx, event, data, env);
+    }
+
+    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, data: &T, env: &Env) {
+        self.child.lifecycle(ctx, event, data, env);
+    }
+
+    fn update(&mut self, ctx: &mut UpdateCtx, _old_data: &T, data: &T, env: &Env) {
+        self.child.update(ctx, data, env);
+    }
+
+    fn layout(&mut self, ctx: &mut LayoutCtx, bc: &BoxConstraints, data: &T, env: &Env) -> Size {
+        let size = self.child.layout(ctx, bc, data, env);
+        self.child.set_origin(ctx, Point::ORIGIN);
+        size
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &T, env: &Env) {
+        self.child.paint(ctx, data, env);
+        if !self.visible {
+            return;
+        }
+
+        // Lay out every category first to know how big the sheet is
+        let mut sections = Vec::new();
+        for category in Category::ALL {
+            let rows: Vec<_> = BINDINGS
+                .iter()
+                .filter(|binding| binding.category == category)
+                .map(|binding| {
+                    (
+                        Help::<T>::text(ctx, env, binding.label(), true),
+                        Help::<T>::text(ctx, env, binding.action.description(), false),
+                    )
+                })
+                .collect();
+            if !rows.is_empty() {
+                sections.push((Help::<T>::text(ctx, env, category.name(), true), rows));
+            }
+        }
+        let mut width: f64 = 0.0;
+        let mut height = 0.0;
+        for (heading, rows) in &sections {
+            width = width.max(heading.size().width);
+            height += heading.size().height + Help::<T>::ROW_SPACING;
+            for (key, description) in rows {
+                width = width.max(Help::<T>::KEY_COLUMN_WIDTH + description.size().width);
+                height += key.size().height.max(description.size().height) + Help::<T>::ROW_SPACING;
+            }
+            height += Help::<T>::CATEGORY_SPACING;
+        }
+        height -= Help::<T>::CATEGORY_SPACING;
+
+        let size = ctx.size();
+        let rect = size.to_rect();
+        ctx.fill(rect, &env.get(theme::CANVAS_DIM));
+        let sheet = Rect::from_center_size(rect.center(), (width, height));
+        ctx.fill(
+            sheet.inflate(24.0, 24.0).to_rounded_rect(8.0),
+            &env.get(theme::PANEL_BACKGROUND),
+        );
+
+        let mut y = sheet.y0;
+        for (heading, rows) in &sections {
+            ctx.draw_text(heading, (sheet.x0, y));
+            y += heading.size().height + Help::<T>::ROW_SPACING;
+            for (key, description) in rows {
+                ctx.draw_text(key, (sheet.x0, y));
+                ctx.draw_text(description, (sheet.x0 + Help::<T>::KEY_COLUMN_WIDTH, y));
+                y += key.size().height.max(description.size().height) + Help::<T>::ROW_SPACING;
+            }
+            y += Help::<T>::CATEGORY_SPACING;
+        }
+    }
+}
@@ -0,0 +1,121 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use druid::kurbo::Size;
+use druid::widget::{Button, Checkbox, CrossAxisAlignment, Flex, Label, RadioGroup, TextBox};
+use druid::{Data, Env, EventCtx, Lens, Widget, WidgetExt, WidgetId};
+
+use crate::i18n::tr;
+use crate::project::ImageDefaults;
+use crate::ui::{dialog, theme, Zoom, COMMAND_SET_IMAGE_DEFAULTS};
+
+#[derive(Clone, Data, Lens)]
+struct ImageDefaultsDialogData {
+    smooth: Option<bool>,
+    zoom: String, // percent
+    checkerboard: bool,
+    paused: bool,
+    error: String,
+}
+
+/// Open the dialog for how new images start out in the project.
+/// The result is sent to `surface` as `COMMAND_SET_IMAGE_DEFAULTS`.
+///
+/// With `has_selection` set there's also a button for applying the defaults to the selected image.
+pub fn open_image_defaults_dialog(
+    ctx: &mut EventCtx,
+    env: &Env,
+    defaults: ImageDefaults,
+    has_selection: bool,
+    surface: WidgetId,
+) {
+    let data = ImageDefaultsDialogData {
+        smooth: defaults.smooth,
+        zoom: format!("{}", (defaults.zoom.scale_factor() * 100.0).round()),
+        checkerboard: defaults.checkerboard,
+        paused: defaults.paused,
+        error: String::new(),
+    };
+    dialog::open(ctx, env, Size::new(340.0, 300.0), build(has_selection, surface), data);
+}
+
+fn build(has_selection: bool, surface: WidgetId) -> impl Widget<ImageDefaultsDialogData> {
+    let mut column = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Label::new(tr("image-defaults-scaling")))
+        .with_spacer(4.0)
+        .with_child(
+            RadioGroup::column(vec![
+                (tr("image-defaults-scaling-setting"), None),
+                (tr("image-defaults-smooth"), Some(true)),
+                (tr("image-defaults-nearest"), Some(false)),
+            ])
+            .lens(ImageDefaultsDialogData::smooth),
+        )
+        .with_spacer(8.0)
+        .with_child(
+            Flex::row()
+                .with_child(Label::new(tr("image-defaults-zoom")).fix_width(90.0))
+                .with_flex_child(TextBox::new().lens(ImageDefaultsDialogData::zoom).expand_width(), 1.0),
+        )
+        .with_spacer(8.0)
+        .with_child(Checkbox::new(tr("image-defaults-checkerboard")).lens(ImageDefaultsDialogData::checkerboard))
+        .with_spacer(4.0)
+        .with_child(Checkbox::new(tr("image-defaults-paused")).lens(ImageDefaultsDialogData::paused))
+        .with_spacer(4.0)
+        .with_child(
+            Label::dynamic(|data: &ImageDefaultsDialogData, _| data.error.clone()).with_text_color(theme::ERROR_TEXT),
+        )
+        .with_flex_spacer(1.0);
+    if has_selection {
+        // Existing images are never changed along with the defaults, unless asked for explicitly
+        column.add_child(Button::new(tr("image-defaults-apply")).on_click(
+            move |ctx, data: &mut ImageDefaultsDialogData, _env| {
+                if submit(ctx, data, true, surface) {
+                    dialog::close(ctx);
+                }
+            },
+        ));
+        column.add_spacer(8.0);
+    }
+    column.with_child(dialog::buttons(move |ctx, data: &mut ImageDefaultsDialogData| {
+        submit(ctx, data, false, surface)
+    }))
+}
+
+/// Send the defaults to `surface`, unless the zoom doesn't make sense. Returns whether they were sent.
+fn submit(ctx: &mut EventCtx, data: &mut ImageDefaultsDialogData, apply: bool, surface: WidgetId) -> bool {
+    let percent = data.zoom.trim().trim_end_matches('%').trim_end().parse::<f64>();
+    match percent.ok().filter(|percent| *percent > 0.0 && percent.is_finite()) {
+        Some(percent) => {
+            let defaults = ImageDefaults {
+                smooth: data.smooth,
+                zoom: Zoom::exact(percent / 100.0),
+                checkerboard: data.checkerboard,
+                paused: data.paused,
+            };
+            ctx.submit_command(COMMAND_SET_IMAGE_DEFAULTS.with((defaults, apply)).to(surface));
+            true
+        }
+        None => {
+            data.error = tr("image-defaults-invalid-zoom");
+            false
+        }
+    }
+}
@@ -0,0 +1,299 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! The keyboard bindings of the surface. Everything that shows shortcuts is generated from here.
+
+use druid::{KbKey, KeyEvent};
+
+use crate::i18n::tr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    RemoveImage,
+    RestoreRemoved,
+    RenameImage,
+    ReloadImage,
+    ReplaceSource,
+    ExportFrame,
+    RaiseLayer,
+    LowerLayer,
+    RaiseToTop,
+    LowerToBottom,
+    TogglePin,
+    TogglePause,
+    PreviousFrame,
+    NextFrame,
+    ToggleDiff,
+    LinkViews,
+    UnlinkViews,
+    ArrangeTiles,
+    ArrangeGrid,
+    FitWidth,
+    FitHeight,
+    FrameSelected,
+    UndoArrangement,
+    NewProject,
+    OpenProject,
+    SaveProject,
+    ImportProject,
+    AddImages,
+    QuickOpen,
+    ShowSummary,
+    CanvasSettings,
+    ImageDefaults,
+    ExportLayout,
+    ApplyLayout,
+    ToggleHelp,
+    ToggleConsole,
+    ToggleTiming,
+    ToggleSeries,
+    ToggleLabels,
+    ToggleMeasure,
+    Eyedropper,
+    PickColorKey,
+    ToggleCrosshair,
+    ToggleCompare,
+    CaptureRegion,
+    InspectGif,
+    ShowMetadata,
+    TogglePhysical,
+    ToggleSolo,
+}
+
+impl Action {
+    /// A short description of what the action does.
+    pub fn description(self) -> String {
+        let key = match self {
+            Action::RemoveImage => "action-remove-image",
+            Action::RestoreRemoved => "action-restore-removed",
+            Action::RenameImage => "action-rename-image",
+            Action::ReloadImage => "action-reload-image",
+            Action::ReplaceSource => "action-replace-source",
+            Action::ExportFrame => "action-export-frame",
+            Action::RaiseLayer => "action-raise-layer",
+            Action::LowerLayer => "action-lower-layer",
+            Action::RaiseToTop => "action-raise-to-top",
+            Action::LowerToBottom => "action-lower-to-bottom",
+            Action::TogglePin => "action-toggle-pin",
+            Action::TogglePause => "action-toggle-pause",
+            Action::PreviousFrame => "action-previous-frame",
+            Action::NextFrame => "action-next-frame",
+            Action::ToggleDiff => "action-toggle-diff",
+            Action::LinkViews => "action-link-views",
+            Action::UnlinkViews => "action-unlink-views",
+            Action::ArrangeTiles => "action-arrange-tiles",
+            Action::ArrangeGrid => "action-arrange-grid",
+            Action::FitWidth => "action-fit-width",
+            Action::FitHeight => "action-fit-height",
+            Action::FrameSelected => "action-frame-selected",
+            Action::UndoArrangement => "action-undo-arrangement",
+            Action::NewProject => "action-new-project",
+            Action::OpenProject => "action-open-project",
+            Action::SaveProject => "action-save-project",
+            Action::ImportProject => "action-import-project",
+            Action::AddImages => "action-add-images",
+            Action::QuickOpen => "action-quick-open",
+            Action::ShowSummary => "action-show-summary",
+            Action::CanvasSettings => "action-canvas-settings",
+            Action::ImageDefaults => "action-image-defaults",
+            Action::ExportLayout => "action-export-layout",
+            Action::ApplyLayout => "action-apply-layout",
+            Action::ToggleHelp => "action-toggle-help",
+            Action::ToggleConsole => "action-toggle-console",
+            Action::ToggleTiming => "action-toggle-timing",
+            Action::ToggleSeries => "action-toggle-series",
+            Action::ToggleLabels => "action-toggle-labels",
+            Action::ToggleMeasure => "action-toggle-measure",
+            Action::Eyedropper => "action-eyedropper",
+            Action::PickColorKey => "action-pick-color-key",
+            Action::ToggleCrosshair => "action-toggle-crosshair",
+            Action::CaptureRegion => "action-capture-region",
+            Action::InspectGif => "action-inspect-gif",
+            Action::ShowMetadata => "action-show-metadata",
+            Action::TogglePhysical => "action-toggle-physical",
+            Action::ToggleSolo => "action-toggle-solo",
+            Action::ToggleCompare => "action-toggle-compare",
+        };
+        tr(key)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Category {
+    Images,
+    Project,
+    Layout,
+    Window,
+}
+
+impl Category {
+    pub const ALL: [Category; 4] = [Category::Images, Category::Project, Category::Layout, Category::Window];
+
+    pub fn name(self) -> String {
+        match self {
+            Category::Images => tr("category-images"),
+            Category::Project => tr("category-project"),
+            Category::Layout => tr("category-layout"),
+            Category::Window => tr("category-window"),
+        }
+    }
+}
+
+pub enum Key {
+    Named(KbKey),
+    Character(&'static str), // lowercase for letters
+}
+
+pub struct Binding {
+    pub key: Key,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub action: Action,
+    pub category: Category,
+}
+
+impl Binding {
+    const fn new(key: Key, ctrl: bool, shift: bool, action: Action, category: Category) -> Binding {
+        Binding {
+            key,
+            ctrl,
+            shift,
+            action,
+            category,
+        }
+    }
+
+    /// Whether `event` triggers this binding.
+    ///
+    /// Shift is ignored for characters other than letters, as it's needed to type some of them.
+    pub fn matches(&self, event: &KeyEvent) -> bool {
+        if event.mods.ctrl() != self.ctrl {
+            return false;
+        }
+        match (&self.key, &event.key) {
+            (Key::Named(key), event_key) => key == event_key && event.mods.shift() == self.shift,
+            (Key::Character(ch), KbKey::Character(event_ch)) => {
+                if ch.chars().all(char::is_alphabetic) {
+                    event_ch.to_lowercase() == *ch && event.mods.shift() == self.shift
+                } else {
+                    event_ch.as_str() == *ch
+                }
+            }
+            _ => false,
+        }
+    }
+
+    /// The key combination as shown to the user, e.g. `Ctrl+Shift+L`.
+    pub fn label(&self) -> String {
+        let mut label = String::new();
+        if self.ctrl {
+            label.push_str("Ctrl+");
+        }
+        if self.shift {
+            label.push_str("Shift+");
+        }
+        match &self.key {
+            Key::Named(KbKey::PageUp) => label.push_str("PgUp"),
+            Key::Named(KbKey::PageDown) => label.push_str("PgDn"),
+            Key::Named(KbKey::Delete) => label.push_str("Del"),
+            Key::Named(key) => label.push_str(&key.to_string()),
+            Key::Character(" ") => label.push_str("Space"),
+            Key::Character(ch) => label.push_str(&ch.to_uppercase()),
+        }
+        label
+    }
+}
+
+#[rustfmt::skip]
+pub static BINDINGS: &[Binding] = &[
+    Binding::new(Key::Named(KbKey::Delete),   false, false, Action::RemoveImage,     Category::Images),
+    Binding::new(Key::Character("z"),         true,  true,  Action::RestoreRemoved,  Category::Images),
+    Binding::new(Key::Named(KbKey::F2),       false, false, Action::RenameImage,     Category::Images),
+    Binding::new(Key::Named(KbKey::F5),       false, false, Action::ReloadImage,     Category::Images),
+    Binding::new(Key::Named(KbKey::F5),       false, true,  Action::ReplaceSource,   Category::Images),
+    Binding::new(Key::Character("e"),         true,  true,  Action::ExportFrame,     Category::Images),
+    Binding::new(Key::Named(KbKey::PageUp),   false, false, Action::RaiseLayer,      Category::Images),
+    Binding::new(Key::Named(KbKey::PageDown), false, false, Action::LowerLayer,      Category::Images),
+    Binding::new(Key::Named(KbKey::PageUp),   false, true,  Action::RaiseToTop,      Category::Images),
+    Binding::new(Key::Named(KbKey::PageDown), false, true,  Action::LowerToBottom,   Category::Images),
+    Binding::new(Key::Character("p"),         false, false, Action::TogglePin,       Category::Images),
+    Binding::new(Key::Character(" "),         false, false, Action::TogglePause,     Category::Images),
+    Binding::new(Key::Character(","),         false, false, Action::PreviousFrame,   Category::Images),
+    Binding::new(Key::Character("."),         false, false, Action::NextFrame,       Category::Images),
+    Binding::new(Key::Character("d"),         false, false, Action::ToggleDiff,      Category::Images),
+    Binding::new(Key::Character("y"),         false, false, Action::LinkViews,       Category::Images),
+    Binding::new(Key::Character("y"),         false, true,  Action::UnlinkViews,     Category::Images),
+    Binding::new(Key::Character("s"),         false, false, Action::ToggleSolo,      Category::Images),
+    Binding::new(Key::Character("g"),         true,  false, Action::InspectGif,      Category::Images),
+    Binding::new(Key::Character("m"),         true,  true,  Action::ShowMetadata,    Category::Images),
+    Binding::new(Key::Character("i"),         true,  false, Action::AddImages,       Category::Images),
+    Binding::new(Key::Character("p"),         true,  false, Action::QuickOpen,       Category::Images),
+    Binding::new(Key::Character("r"),         true,  true,  Action::CaptureRegion,   Category::Images),
+    Binding::new(Key::Character("n"),         true,  false, Action::NewProject,      Category::Project),
+    Binding::new(Key::Character("o"),         true,  false, Action::OpenProject,     Category::Project),
+    Binding::new(Key::Character("s"),         true,  false, Action::SaveProject,     Category::Project),
+    Binding::new(Key::Character("o"),         true,  true,  Action::ImportProject,   Category::Project),
+    Binding::new(Key::Character("i"),         true,  true,  Action::ShowSummary,     Category::Project),
+    Binding::new(Key::Character("p"),         true,  true,  Action::CanvasSettings,  Category::Project),
+    Binding::new(Key::Character("b"),         true,  true,  Action::ImageDefaults,   Category::Project),
+    Binding::new(Key::Character("t"),         true,  false, Action::ArrangeTiles,    Category::Layout),
+    Binding::new(Key::Named(KbKey::Home),     false, false, Action::ArrangeTiles,    Category::Layout),
+    Binding::new(Key::Character("t"),         true,  true,  Action::ArrangeGrid,     Category::Layout),
+    Binding::new(Key::Character("w"),         false, true,  Action::FitWidth,        Category::Layout),
+    Binding::new(Key::Character("h"),         false, true,  Action::FitHeight,       Category::Layout),
+    Binding::new(Key::Character("f"),         false, false, Action::FrameSelected,   Category::Layout),
+    Binding::new(Key::Character("u"),         true,  true,  Action::TogglePhysical,  Category::Layout),
+    Binding::new(Key::Character("z"),         true,  false, Action::UndoArrangement, Category::Layout),
+    Binding::new(Key::Character("l"),         true,  true,  Action::ExportLayout,    Category::Layout),
+    Binding::new(Key::Character("l"),         true,  false, Action::ApplyLayout,     Category::Layout),
+    Binding::new(Key::Character("m"),         false, true,  Action::ToggleMeasure,   Category::Layout),
+    Binding::new(Key::Character("x"),         false, true,  Action::ToggleCrosshair, Category::Layout),
+    Binding::new(Key::Character("e"),         false, false, Action::Eyedropper,      Category::Images),
+    Binding::new(Key::Character("e"),         false, true,  Action::PickColorKey,    Category::Images),
+    Binding::new(Key::Character("/"),         true,  false, Action::ToggleCompare,   Category::Images),
+    Binding::new(Key::Character("?"),         false, false, Action::ToggleHelp,      Category::Window),
+    Binding::new(Key::Character(";"),         true,  false, Action::ToggleLabels,    Category::Window),
+    Binding::new(Key::Named(KbKey::F12),      false, false, Action::ToggleConsole,   Category::Window),
+    Binding::new(Key::Character("d"),         true,  true,  Action::ToggleTiming,    Category::Window),
+    Binding::new(Key::Named(KbKey::F9),       true,  false, Action::ToggleSeries,    Category::Window),
+];
+
+/// Returns the action bound to the key combination of `event`, if any.
+pub fn action(event: &KeyEvent) -> Option<Action> {
+    BINDINGS
+        .iter()
+        .find(|binding| binding.matches(event))
+        .map(|binding| binding.action)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_chord_has_one_action() {
+        let mut labels: Vec<String> = BINDINGS.iter().map(Binding::label).collect();
+        // Taken by File → New Window, which isn't in the keymap
+        labels.push(String::from("Ctrl+Shift+N"));
+        let count = labels.len();
+        labels.sort();
+        labels.dedup();
+        assert_eq!(labels.len(), count);
+    }
+}
@@ -0,0 +1,154 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! The layers panel lists the project images from the topmost layer down.
+//!
+//! The surface owns the project, so it publishes the list with `COMMAND_LAYERS_CHANGED`
+//! and the panel sends any changes back as commands.
+
+use std::sync::Arc;
+
+use druid::widget::prelude::*;
+use druid::widget::{Button, CrossAxisAlignment, Flex, Label, List, Painter, Scroll};
+use druid::{Data, Point, Selector, Target, WidgetExt, WidgetPod};
+
+use crate::ui::theme;
+
+/// Sent by the surface whenever the layers or their state change.
+pub const COMMAND_LAYERS_CHANGED: Selector<Arc<Vec<LayerEntry>>> = Selector::new("slark.layers_changed");
+pub const COMMAND_SELECT_IMAGE: Selector<usize> = Selector::new("slark.select_image");
+pub const COMMAND_TOGGLE_VISIBILITY: Selector<usize> = Selector::new("slark.toggle_visibility");
+pub const COMMAND_TOGGLE_LOCK: Selector<usize> = Selector::new("slark.toggle_lock");
+/// Move the image by the given number of positions within its group, positive is up.
+pub const COMMAND_SHIFT_LAYER: Selector<(usize, isize)> = Selector::new("slark.shift_layer");
+
+#[derive(Data, Clone, PartialEq)]
+pub struct LayerEntry {
+    pub id: usize,
+    pub name: String,
+    pub depth: usize, // How many groups deep the image is
+    pub visible: bool,
+    pub locked: bool,
+    pub selected: bool,
+}
+
+pub struct LayersPanel {
+    entries: Arc<Vec<LayerEntry>>,
+    list: WidgetPod<Arc<Vec<LayerEntry>>, Scroll<Arc<Vec<LayerEntry>>, List<LayerEntry>>>,
+}
+
+impl LayersPanel {
+    pub const WIDTH: f64 = 240.0;
+
+    pub fn new() -> LayersPanel {
+        LayersPanel {
+            entries: Arc::new(Vec::new()),
+            list: WidgetPod::new(Scroll::new(List::new(row)).vertical()),
+        }
+    }
+}
+
+fn row() -> impl Widget<LayerEntry> {
+    let visibility =
+        Label::new(|entry: &LayerEntry, _env: &Env| String::from(if entry.visible { "👁" } else { "–" }))
+            .fix_width(20.0)
+            .on_click(|ctx, entry: &mut LayerEntry, _env| {
+                ctx.submit_command(
+                    COMMAND_TOGGLE_VISIBILITY
+                        .with(entry.id)
+                        .to(Target::Window(ctx.window_id())),
+                );
+            });
+    let lock = Label::new(|entry: &LayerEntry, _env: &Env| String::from(if entry.locked { "🔒" } else { "🔓" }))
+        .fix_width(20.0)
+        .on_click(|ctx, entry: &mut LayerEntry, _env| {
+            ctx.submit_command(COMMAND_TOGGLE_LOCK.with(entry.id).to(Target::Window(ctx.window_id())));
+        });
+    let name = Label::new(|entry: &LayerEntry, _env: &Env| format!("{}{}", "  ".repeat(entry.depth), entry.name))
+        .expand_width()
+        .on_click(|ctx, entry: &mut LayerEntry, _env| {
+            ctx.submit_command(COMMAND_SELECT_IMAGE.with(entry.id).to(Target::Window(ctx.window_id())));
+        });
+    let up = Button::new("▲").on_click(|ctx, entry: &mut LayerEntry, _env| {
+        ctx.submit_command(
+            COMMAND_SHIFT_LAYER
+                .with((entry.id, 1))
+                .to(Target::Window(ctx.window_id())),
+        );
+    });
+    let down = Button::new("▼").on_click(|ctx, entry: &mut LayerEntry, _env| {
+        ctx.submit_command(
+            COMMAND_SHIFT_LAYER
+                .with((entry.id, -1))
+                .to(Target::Window(ctx.window_id())),
+        );
+    });
+
+    Flex::row()
+        .cross_axis_alignment(CrossAxisAlignment::Center)
+        .with_child(visibility)
+        .with_child(lock)
+        .with_flex_child(name, 1.0)
+        .with_child(up)
+        .with_child(down)
+        .padding((4.0, 2.0))
+        .background(Painter::new(|ctx, entry: &LayerEntry, env| {
+            let rect = ctx.size().to_rect();
+            if entry.selected {
+                ctx.fill(rect, &env.get(theme::SELECTION).with_alpha(0.4));
+            } else if ctx.is_hot() {
+                ctx.fill(rect, &env.get(theme::HOVER));
+            }
+        }))
+}
+
+impl Widget<u64> for LayersPanel {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, _data: &mut u64, env: &Env) {
+        if let Event::Command(cmd) = event {
+            if let Some(entries) = cmd.get(COMMAND_LAYERS_CHANGED) {
+                self.entries = entries.clone();
+                ctx.request_update();
+                return;
+            }
+        }
+        self.list.event(ctx, event, &mut self.entries, env);
+    }
+
+    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, _data: &u64, env: &Env) {
+        self.list.lifecycle(ctx, event, &self.entries, env);
+    }
+
+    fn update(&mut self, ctx: &mut UpdateCtx, _old_data: &u64, _data: &u64, env: &Env) {
+        self.list.update(ctx, &self.entries, env); // We don't care about the data update
+    }
+
+    fn layout(&mut self, ctx: &mut LayoutCtx, bc: &BoxConstraints, _data: &u64, env: &Env) -> Size {
+        bc.debug_check("LayersPanel");
+        let size = bc.constrain((LayersPanel::WIDTH, bc.max().height));
+        self.list.layout(ctx, &BoxConstraints::tight(size), &self.entries, env);
+        self.list.set_origin(ctx, Point::ORIGIN);
+        size
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, _data: &u64, env: &Env) {
+        let rect = ctx.size().to_rect();
+        ctx.fill(rect, &env.get(theme::PANEL_BACKGROUND));
+        self.list.paint(ctx, &self.entries, env);
+    }
+}
@@ -0,0 +1,69 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use druid::kurbo::Size;
+use druid::widget::{Button, CrossAxisAlignment, Flex, Label, LineBreaking, Scroll};
+use druid::{Application, Env, EventCtx, Widget, WidgetExt};
+
+use crate::i18n::tr;
+use crate::ui::dialog;
+
+/// The width of the name column.
+const NAME_WIDTH: f64 = 180.0;
+
+/// Open a dialog listing the metadata of an image, with a button for copying each value.
+pub fn open_metadata_dialog(ctx: &mut EventCtx, env: &Env, name: String, entries: Vec<(String, String)>) {
+    dialog::open(ctx, env, Size::new(560.0, 400.0), build(name, entries), ());
+}
+
+fn build(name: String, entries: Vec<(String, String)>) -> impl Widget<()> {
+    let mut col = Flex::column().cross_axis_alignment(CrossAxisAlignment::Start);
+    col.add_child(Label::new(name));
+    col.add_spacer(8.0);
+
+    if entries.is_empty() {
+        col.add_child(Label::new(tr("metadata-empty")));
+        col.add_flex_spacer(1.0);
+    } else {
+        let mut list = Flex::column().cross_axis_alignment(CrossAxisAlignment::Start);
+        for (key, value) in entries {
+            let copied = value.clone();
+            list.add_child(
+                Flex::row()
+                    .cross_axis_alignment(CrossAxisAlignment::Start)
+                    .with_child(Label::new(key).fix_width(NAME_WIDTH))
+                    .with_flex_child(Label::new(value).with_line_break_mode(LineBreaking::WordWrap), 1.0)
+                    .with_spacer(8.0)
+                    .with_child(Button::new(tr("metadata-copy")).on_click(move |_ctx, _data, _env| {
+                        Application::global().clipboard().put_string(&copied);
+                    })),
+            );
+            list.add_spacer(4.0);
+        }
+        col.add_flex_child(Scroll::new(list).vertical(), 1.0);
+    }
+
+    col.add_spacer(8.0);
+    col.add_child(
+        Flex::row()
+            .with_flex_spacer(1.0)
+            .with_child(Button::new(tr("dialog-close")).on_click(|ctx, _data, _env| dialog::close(ctx))),
+    );
+    col
+}
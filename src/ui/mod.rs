@@ -17,20 +17,80 @@
     along with this program.  If not, see <http://www.gnu.org/licenses/>.
 */
 
+mod canvas_dialog;
+pub use canvas_dialog::*;
+
+mod capture_overlay;
+pub use capture_overlay::*;
+
+mod color_key;
+pub use color_key::*;
+
+mod console;
+pub use console::*;
+
+mod dialog;
+
+mod frames_dialog;
+pub use frames_dialog::*;
+
+mod gif_inspector;
+pub use gif_inspector::*;
+
+mod help;
+pub use help::*;
+
+mod image_defaults_dialog;
+pub use image_defaults_dialog::*;
+
+mod keymap;
+
+mod layers_panel;
+pub use layers_panel::*;
+
+mod metadata_dialog;
+pub use metadata_dialog::*;
+
+mod name_dialog;
+pub use name_dialog::*;
+
+mod quick_open;
+pub use quick_open::*;
+
 mod root;
-pub use root::ui_root;
+pub use root::main_window;
 
 mod stats;
 pub use stats::*;
 
+mod summary_dialog;
+pub use summary_dialog::*;
+
 mod surface;
 pub use surface::*;
 
+pub mod theme;
+
+mod ticker;
+use ticker::Ticker;
+
+mod thumbnail_strip;
+pub use thumbnail_strip::*;
+
 mod tileize;
 pub use tileize::*;
 
+mod time_remap;
+pub use time_remap::*;
+
+mod toast;
+pub use toast::*;
+
 mod view;
 pub use view::*;
 
+mod welcome;
+use welcome::welcome;
+
 mod zoom;
 pub use zoom::*;
@@ -0,0 +1,50 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use druid::kurbo::Size;
+use druid::widget::{CrossAxisAlignment, Flex, Label, TextBox};
+use druid::{Env, EventCtx, Widget, WidgetExt, WidgetId};
+
+use crate::i18n::tr;
+use crate::ui::{dialog, COMMAND_SET_NAME};
+
+/// Open the rename dialog for `image_id`. The result is sent to `surface` as `COMMAND_SET_NAME`.
+///
+/// An empty name clears the custom name, which makes the file name visible again.
+pub fn open_name_dialog(ctx: &mut EventCtx, env: &Env, image_id: usize, name: Option<&str>, surface: WidgetId) {
+    let data = String::from(name.unwrap_or_default());
+    dialog::open(ctx, env, Size::new(320.0, 120.0), build(image_id, surface), data);
+}
+
+fn build(image_id: usize, surface: WidgetId) -> impl Widget<String> {
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Label::new(tr("name-hint")))
+        .with_spacer(4.0)
+        .with_child(TextBox::new().expand_width())
+        .with_flex_spacer(1.0)
+        .with_child(dialog::buttons(move |ctx, name: &mut String| {
+            let name = match name.trim() {
+                "" => None,
+                name => Some(String::from(name)),
+            };
+            ctx.submit_command(COMMAND_SET_NAME.with((image_id, name)).to(surface));
+            true
+        }))
+}
@@ -0,0 +1,293 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! The quick-open overlay lists the images under the project directory, for adding one by typing part of its path.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use druid::kurbo::{Line, Point, Rect};
+use druid::piet::{PietTextLayout, Text, TextLayout, TextLayoutBuilder};
+use druid::widget::prelude::*;
+use druid::{FontFamily, KbKey, Selector, Target, WidgetPod};
+
+use crate::formats;
+use crate::fuzzy;
+use crate::i18n::{tr, tr_args};
+use crate::ui::{theme, COMMAND_ADD_IMAGE_AT};
+
+/// Show the quick-open overlay for the images under the directory, adding the picked one centered on the point if given.
+pub const COMMAND_SHOW_QUICK_OPEN: Selector<(PathBuf, Option<Point>)> = Selector::new("slark.show_quick_open");
+/// The images that were found under the directory, sent from the thread that searched it.
+const COMMAND_FILES_FOUND: Selector<(PathBuf, Vec<PathBuf>)> = Selector::new("slark.quick_open_files_found");
+
+pub struct QuickOpen<T> {
+    child: WidgetPod<T, Box<dyn Widget<T>>>,
+    search: Option<Search>,
+    found: HashMap<PathBuf, Vec<PathBuf>>, // The images under each directory as of the most recent search
+}
+
+/// The state of the overlay while it's open.
+struct Search {
+    dir: PathBuf,
+    at: Option<Point>,
+    query: String,
+    names: Vec<String>, // The paths of the images relative to `dir`, which is what the query matches against
+    matches: Vec<usize>, // Indices into `names`, best first
+    selected: usize,    // Index into `matches`
+    searching: bool,    // The list may still change, as the directory is being searched again
+}
+
+impl Search {
+    /// Match the names against the query again, starting over from the best match.
+    fn refilter(&mut self) {
+        self.matches = fuzzy::rank(&self.query, &self.names);
+        self.selected = 0;
+    }
+}
+
+/// The paths of the `files` relative to `dir`, as shown and matched.
+fn relative_names(dir: &Path, files: &[PathBuf]) -> Vec<String> {
+    files
+        .iter()
+        .map(|file| file.strip_prefix(dir).unwrap_or(file).display().to_string())
+        .collect()
+}
+
+impl<T: Data> QuickOpen<T> {
+    const WIDTH: f64 = 560.0;
+    const VISIBLE_ROWS: usize = 12;
+    const ROW_SPACING: f64 = 4.0;
+
+    pub fn new(child: impl Widget<T> + 'static) -> QuickOpen<T> {
+        QuickOpen {
+            child: WidgetPod::new(Box::new(child)),
+            search: None,
+            found: HashMap::new(),
+        }
+    }
+
+    /// Open the overlay with the images found the last time, and search the directory again in the background.
+    fn open(&mut self, ctx: &mut EventCtx, dir: &Path, at: Option<Point>) {
+        let names = self
+            .found
+            .get(dir)
+            .map_or_else(Vec::new, |files| relative_names(dir, files));
+        let mut search = Search {
+            dir: dir.to_path_buf(),
+            at: at,
+            query: String::new(),
+            names: names,
+            matches: Vec::new(),
+            selected: 0,
+            searching: true,
+        };
+        search.refilter();
+        self.search = Some(search);
+
+        let event_sink = ctx.get_external_handle();
+        let target = Target::Widget(ctx.widget_id());
+        let dir = dir.to_path_buf();
+        thread::spawn(move || {
+            let files = formats::images_in(&dir, true);
+            // The window may have been closed in the meantime, which is fine
+            let _ = event_sink.submit_command(COMMAND_FILES_FOUND, (dir, files), target);
+        });
+    }
+
+    /// Handle a key while the overlay is open, closing it once an image is picked or the search is given up on.
+    fn key_down(&mut self, ctx: &mut EventCtx, key: &KbKey, ctrl: bool) {
+        let search = match &mut self.search {
+            Some(search) => search,
+            None => return,
+        };
+        match key {
+            KbKey::Escape => self.search = None,
+            KbKey::Enter => {
+                if let Some(&index) = search.matches.get(search.selected) {
+                    let path = search.dir.join(&search.names[index]);
+                    // The same path as dropping the file on the window, so that it's handled exactly the same
+                    ctx.submit_command(
+                        COMMAND_ADD_IMAGE_AT
+                            .with((vec![path], search.at))
+                            .to(Target::Window(ctx.window_id())),
+                    );
+                    self.search = None;
+                }
+            }
+            KbKey::ArrowUp => search.selected = search.selected.saturating_sub(1),
+            KbKey::ArrowDown => {
+                search.selected = (search.selected + 1).min(search.matches.len().saturating_sub(1));
+            }
+            KbKey::Backspace => {
+                search.query.pop();
+                search.refilter();
+            }
+            KbKey::Character(text) if !ctrl => {
+                search.query.push_str(text);
+                search.refilter();
+            }
+            _ => (),
+        }
+        ctx.request_paint();
+    }
+
+    fn text(ctx: &mut PaintCtx, env: &Env, text: String) -> PietTextLayout {
+        ctx.text()
+            .new_text_layout(text)
+            .font(FontFamily::SYSTEM_UI, 14.0)
+            .text_color(env.get(theme::PANEL_TEXT))
+            .max_width(QuickOpen::<T>::WIDTH)
+            .build()
+            .unwrap()
+    }
+}
+
+impl<T: Data> Widget<T> for QuickOpen<T> {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut T, env: &Env) {
+        match event {
+            Event::Command(cmd) if cmd.is(COMMAND_SHOW_QUICK_OPEN) => {
+                let (dir, at) = cmd.get_unchecked(COMMAND_SHOW_QUICK_OPEN);
+                self.open(ctx, dir, *at);
+                ctx.request_paint();
+                ctx.set_handled();
+                return;
+            }
+            Event::Command(cmd) if cmd.is(COMMAND_FILES_FOUND) => {
+                let (dir, files) = cmd.get_unchecked(COMMAND_FILES_FOUND);
+                if let Some(search) = self.search.as_mut().filter(|search| search.dir == *dir) {
+                    // Keep the picked image picked, as long as it's still there
+                    let picked = search
+                        .matches
+                        .get(search.selected)
+                        .map(|&index| search.names[index].clone());
+                    search.names = relative_names(dir, files);
+                    search.searching = false;
+                    search.refilter();
+                    if let Some(position) =
+                        picked.and_then(|picked| search.matches.iter().position(|&index| search.names[index] == picked))
+                    {
+                        search.selected = position;
+                    }
+                    ctx.request_paint();
+                }
+                self.found.insert(dir.clone(), files.clone());
+                ctx.set_handled();
+                return;
+            }
+            // The overlay takes all the keys while it's open
+            Event::KeyDown(key_event) if self.search.is_some() => {
+                self.key_down(ctx, &key_event.key, key_event.mods.ctrl());
+                ctx.set_handled();
+                return;
+            }
+            Event::KeyUp(_) if self.search.is_some() => {
+                ctx.set_handled();
+                return;
+            }
+            Event::MouseDown(_) if self.search.is_some() => {
+                // Clicking anywhere gives up on the search
+                self.search = None;
+                ctx.request_paint();
+                ctx.set_handled();
+                return;
+            }
+            _ => (),
+        }
+        self.child.event(ctx, event, data, env);
+    }
+
+    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, data: &T, env: &Env) {
+        self.child.lifecycle(ctx, event, data, env);
+    }
+
+    fn update(&mut self, ctx: &mut UpdateCtx, _old_data: &T, data: &T, env: &Env) {
+        self.child.update(ctx, data, env);
+    }
+
+    fn layout(&mut self, ctx: &mut LayoutCtx, bc: &BoxConstraints, data: &T, env: &Env) -> Size {
+        let size = self.child.layout(ctx, bc, data, env);
+        self.child.set_origin(ctx, Point::ORIGIN);
+        size
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &T, env: &Env) {
+        self.child.paint(ctx, data, env);
+        let search = match &self.search {
+            Some(search) => search,
+            None => return,
+        };
+
+        let query = if search.query.is_empty() {
+            tr_args(
+                "quick-open-prompt",
+                &[("path", search.dir.display().to_string().into())],
+            )
+        } else {
+            search.query.clone()
+        };
+        let query = QuickOpen::<T>::text(ctx, env, query);
+        // Scroll along with the selection, keeping it on the last visible row when past the first page
+        let first = (search.selected + 1).saturating_sub(QuickOpen::<T>::VISIBLE_ROWS);
+        let mut rows: Vec<PietTextLayout> = search
+            .matches
+            .iter()
+            .skip(first)
+            .take(QuickOpen::<T>::VISIBLE_ROWS)
+            .map(|&index| QuickOpen::<T>::text(ctx, env, search.names[index].clone()))
+            .collect();
+        if rows.is_empty() {
+            let status = if search.searching {
+                tr("quick-open-searching")
+            } else {
+                tr("quick-open-no-matches")
+            };
+            rows.push(QuickOpen::<T>::text(ctx, env, status));
+        }
+        let row_height = |layout: &PietTextLayout| layout.size().height + QuickOpen::<T>::ROW_SPACING;
+        let height = row_height(&query) + QuickOpen::<T>::ROW_SPACING + rows.iter().map(row_height).sum::<f64>();
+
+        let rect = ctx.size().to_rect();
+        ctx.fill(rect, &env.get(theme::CANVAS_DIM));
+        let x0 = rect.center().x - QuickOpen::<T>::WIDTH / 2.0;
+        let sheet = Rect::new(x0, 48.0, x0 + QuickOpen::<T>::WIDTH, 48.0 + height);
+        ctx.fill(
+            sheet.inflate(12.0, 12.0).to_rounded_rect(8.0),
+            &env.get(theme::PANEL_BACKGROUND),
+        );
+
+        let mut y = sheet.y0;
+        ctx.draw_text(&query, (sheet.x0, y));
+        y += row_height(&query) + QuickOpen::<T>::ROW_SPACING;
+        ctx.stroke(
+            Line::new((sheet.x0, y - 3.0), (sheet.x1, y - 3.0)),
+            &env.get(theme::GUIDES),
+            1.0,
+        );
+        for (i, row) in rows.iter().enumerate() {
+            if !search.matches.is_empty() && first + i == search.selected {
+                let highlight = Rect::new(sheet.x0 - 4.0, y - 1.0, sheet.x1 + 4.0, y + row.size().height + 1.0);
+                ctx.fill(highlight.to_rounded_rect(3.0), &env.get(theme::PANEL_CELL));
+            }
+            ctx.draw_text(row, (sheet.x0, y));
+            y += row_height(row);
+        }
+    }
+}
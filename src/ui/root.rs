@@ -17,37 +17,40 @@
     along with this program.  If not, see <http://www.gnu.org/licenses/>.
 */
 
-use std::fs::read_dir;
-
-use druid::piet::Color;
+use druid::kurbo::{Rect, Size};
 use druid::widget::Flex;
 use druid::widget::{CrossAxisAlignment, MainAxisAlignment};
 use druid::widget::{Padding, SizedBox};
-use druid::Widget;
 use druid::WidgetExt;
+use druid::{commands, Env, Menu, MenuItem, Screen, SysMods, Target, WindowId};
+use druid::{Widget, WindowDesc, WindowState};
 
-use super::{Stats, Surface};
+use super::theme::{self, ThemeScope};
+use super::{Console, Help, LayersPanel, QuickOpen, Stats, Surface, ThumbnailStrip, Toasts};
+use crate::args::Args;
+use crate::i18n::tr;
 use crate::project::Project;
+use crate::settings::{self, WindowGeometry};
 
 pub fn ui_rootx() -> impl Widget<u64> {
     let mut col = Flex::column().cross_axis_alignment(CrossAxisAlignment::End);
 
-    let bc0 = Color::rgb8(255, 255, 255);
-    let bc1 = Color::rgb8(255, 0, 0);
-    let bc2 = Color::rgb8(0, 255, 0);
-    let bc3 = Color::rgb8(0, 0, 255);
-
-    col.add_child(Stats::new());
+    if settings::current().show_stats {
+        col.add_child(Stats::new());
+    }
 
-    col.add_child(SizedBox::empty().width(98.0).height(28.0).border(bc2.clone(), 1.0));
+    col.add_child(SizedBox::empty().width(98.0).height(28.0).border(theme::GUIDES, 1.0));
 
     let mut row = Flex::row()
         .must_fill_main_axis(true)
         .main_axis_alignment(MainAxisAlignment::SpaceBetween);
 
-    row.add_flex_child(SizedBox::empty().width(980.).height(28.0).border(bc1.clone(), 1.0), 1.0);
-    //row.add_flex_child(SizedBox::empty().width(980.).height(28.0).border(bc2.clone(), 1.0), 1.0);
-    //row.add_flex_child(SizedBox::empty().width(980.).height(28.0).border(bc3.clone(), 1.0), 1.0);
+    row.add_flex_child(
+        SizedBox::empty().width(980.).height(28.0).border(theme::GUIDES, 1.0),
+        1.0,
+    );
+    //row.add_flex_child(SizedBox::empty().width(980.).height(28.0).border(theme::GUIDES, 1.0), 1.0);
+    //row.add_flex_child(SizedBox::empty().width(980.).height(28.0).border(theme::GUIDES, 1.0), 1.0);
 
     col.add_flex_child(row, 1.0);
 
@@ -61,25 +64,100 @@ pub fn ui_rootx() -> impl Widget<u64> {
         SizedBox::new(col_container)
             .width(100.0)
             .height(150.0)
-            .border(bc0.clone(), 1.0),
+            .border(theme::GUIDES, 1.0),
     ));
 
     root_flex
 }
 
-pub fn ui_root(filenames: Vec<String>) -> impl Widget<u64> {
+/// Describe a main window, which has its own independent project.
+///
+/// The window opens where the window of the same project was last closed, or else the most recently closed window.
+pub fn main_window(args: &Args, project: Project) -> WindowDesc<u64> {
+    let geometry = settings::current().window_geometry(project.path());
+    let window = WindowDesc::<u64>::new(ThemeScope::new(ui_root(args, project)))
+        .title(tr("app-title"))
+        .menu(menu)
+        //.window_size((400.0, 300.0))
+        //.with_min_size((300.0, 200.0));
+        .window_size((1024.0, 768.0))
+        .with_min_size((320.0, 240.0));
+    match geometry {
+        Some(geometry) => restore(window, geometry),
+        None => window,
+    }
+}
+
+fn restore(window: WindowDesc<u64>, geometry: WindowGeometry) -> WindowDesc<u64> {
+    let monitors = Screen::get_monitors();
+    let primary = match monitors
+        .iter()
+        .find(|monitor| monitor.is_primary())
+        .or_else(|| monitors.first())
+    {
+        Some(monitor) => monitor.virtual_work_rect(),
+        None => return window,
+    };
+    let work_areas: Vec<Rect> = monitors.iter().map(|monitor| monitor.virtual_work_rect()).collect();
+    let saved = Rect::new(
+        geometry.x,
+        geometry.y,
+        geometry.x + geometry.width,
+        geometry.y + geometry.height,
+    );
+    let rect = fit_to_monitors(saved, &work_areas, primary);
+    let window = window.window_size(rect.size()).set_position(rect.origin());
+    if geometry.maximized {
+        window.set_window_state(WindowState::Maximized)
+    } else {
+        window
+    }
+}
+
+/// Move and shrink `rect` to fit the work area it overlaps the most.
+///
+/// A window that overlaps none of them, e.g. because its monitor has been disconnected, goes to `primary` instead.
+fn fit_to_monitors(rect: Rect, work_areas: &[Rect], primary: Rect) -> Rect {
+    let overlap = |area: &Rect| rect.intersect(*area).area();
+    let area = work_areas
+        .iter()
+        .filter(|area| overlap(area) > 0.0)
+        .max_by(|a, b| overlap(a).partial_cmp(&overlap(b)).unwrap())
+        .copied()
+        .unwrap_or(primary);
+    let size = Size::new(rect.width().min(area.width()), rect.height().min(area.height()));
+    let x = rect.x0.min(area.x1 - size.width).max(area.x0);
+    let y = rect.y0.min(area.y1 - size.height).max(area.y0);
+    Rect::from_origin_size((x, y), size)
+}
+
+fn menu(_window: Option<WindowId>, _data: &u64, _env: &Env) -> Menu<u64> {
+    let file = Menu::new(tr("menu-file"))
+        .entry(
+            MenuItem::new(tr("menu-new-window"))
+                .on_activate(|ctx, _data, _env| ctx.new_window(main_window(&Args::default(), Project::new())))
+                .hotkey(SysMods::CmdShift, "N"),
+        )
+        .entry(MenuItem::new(tr("menu-close-window")).command(commands::CLOSE_WINDOW))
+        .separator()
+        .entry(MenuItem::new(tr("menu-exit")).command(commands::QUIT_APP));
+    let view = Menu::new(tr("menu-view")).entry(
+        MenuItem::new(tr("menu-dark-theme"))
+            .selected_if(|_data, _env| theme::is_dark())
+            .on_activate(|ctx, _data, _env| {
+                theme::toggle();
+                settings::update(|settings| settings.dark_theme = theme::is_dark());
+                ctx.submit_command(theme::COMMAND_THEME_CHANGED.to(Target::Global));
+            }),
+    );
+    Menu::empty().entry(file).entry(view)
+}
+
+pub fn ui_root(args: &Args, project: Project) -> impl Widget<u64> {
     let mut col = Flex::column().cross_axis_alignment(CrossAxisAlignment::Start);
 
     col.add_child(Stats::new());
 
-    let mut project;
-    if filenames.len() > 0 && filenames[0].ends_with(".ark") {
-        project = Project::open((&filenames[0]).into());
-    } else {
-        project = Project::new();
-        filenames.iter().for_each(|filename| project.add(filename.into()));
-    };
-
     //project.add("images/fw.gif".into());
     //project.add("images/fw-alpha.gif".into());
     //project.add("images/large.gif".into());
@@ -96,24 +174,52 @@ pub fn ui_root(filenames: Vec<String>) -> impl Widget<u64> {
     //project.add("images/fire.png".into());
     //project.add("images/explosion.png".into());
 
-    //load_x(&mut project);
-
-    let surface = Surface::new(project);
-    col.add_flex_child(surface, 1.0);
+    let mut surface = Surface::new(project);
+    // Only ever pause here, the images that the project saved as paused stay paused either way
+    if args.paused || settings::current().start_paused {
+        surface.set_paused(true);
+    }
+    if args.fit {
+        surface.fit_on_start();
+    }
+    let row = Flex::row()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_flex_child(Toasts::new(Help::new(QuickOpen::new(surface))), 1.0)
+        .with_child(LayersPanel::new());
+    col.add_flex_child(row, 1.0);
+    col.add_child(ThumbnailStrip::new());
+    col.add_child(Console::new());
     col
 }
 
-fn load_x(project: &mut Project) {
-    let dir = read_dir("images/y").expect("Failed read_dir");
-    for entry in dir {
-        let entry = entry.expect("Entry failed");
-        let path = entry.path();
-        if !path.is_dir() {
-            if let Some(ext) = path.extension() {
-                if ext == "gif" {
-                    project.add(path);
-                }
-            }
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEFT: Rect = Rect::new(-1920.0, 0.0, 0.0, 1040.0);
+    const PRIMARY: Rect = Rect::new(0.0, 0.0, 2560.0, 1400.0);
+
+    #[test]
+    fn windows_on_screen_stay_put() {
+        let rect = Rect::new(-1800.0, 100.0, -800.0, 900.0);
+        assert_eq!(fit_to_monitors(rect, &[LEFT, PRIMARY], PRIMARY), rect);
+    }
+
+    #[test]
+    fn windows_on_a_missing_monitor_move_to_the_primary() {
+        let rect = Rect::new(-1800.0, 100.0, -800.0, 900.0);
+        assert_eq!(
+            fit_to_monitors(rect, &[PRIMARY], PRIMARY),
+            Rect::new(0.0, 100.0, 1000.0, 900.0)
+        );
+    }
+
+    #[test]
+    fn windows_are_pulled_into_the_monitor_they_overlap_the_most() {
+        let rect = Rect::new(-200.0, -50.0, 3000.0, 1000.0);
+        assert_eq!(
+            fit_to_monitors(rect, &[LEFT, PRIMARY], PRIMARY),
+            Rect::new(0.0, 0.0, 2560.0, 1050.0)
+        );
     }
 }
@@ -18,29 +18,98 @@
 */
 
 //! Stats will show the fps based on AnimFrame, which won't be accurate unless some widget is actually doing painting.
+//! It also shows the memory used by the decoded frames of all views, and the average time spent on painting
+//! and uploading frames along with the number of late frames and the rate of animation ticks, all refreshed once a second.
+//! Any decoding errors are counted for as long as the app runs, with the latest one spelled out.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
 
 use druid::widget::prelude::*;
 use druid::widget::Label;
 use druid::Application;
 use druid::Data;
 
+use crate::formats::DecodeError;
+
+use super::{cached_frame_bytes, format_bytes, COMMAND_SUSPEND_ANIMATIONS};
+
+static PAINT_NANOS: AtomicU64 = AtomicU64::new(0);
+static UPLOAD_NANOS: AtomicU64 = AtomicU64::new(0);
+static LATE_FRAMES: AtomicU64 = AtomicU64::new(0);
+static ANIMATION_TICKS: AtomicU64 = AtomicU64::new(0);
+static DECODE_ERRORS: AtomicU64 = AtomicU64::new(0);
+static LAST_DECODE_ERROR: Mutex<Option<String>> = Mutex::new(None);
+
+/// Record the time spent in painting the surface, which includes uploading.
+pub fn record_paint(duration: Duration) {
+    PAINT_NANOS.fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+}
+
+/// Record the time spent in uploading a decoded frame to the renderer.
+pub fn record_upload(duration: Duration) {
+    UPLOAD_NANOS.fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+}
+
+/// Record that an animation had to skip ahead more than one frame.
+pub fn record_late_frame() {
+    LATE_FRAMES.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Record that the views advanced their animations, which happens at most at the capped animation rate.
+pub fn record_animation_tick() {
+    ANIMATION_TICKS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Record that decoding an image stopped early because of `error`.
+pub fn record_decode_error(error: &DecodeError) {
+    DECODE_ERRORS.fetch_add(1, Ordering::Relaxed);
+    if let Ok(mut last) = LAST_DECODE_ERROR.lock() {
+        *last = Some(error.to_string());
+    }
+}
+
 pub struct Stats {
     frame_times: [u64; Stats::FRAME_TIME_COUNT],
     frame_time_index: usize,
     fps: u64,
+    memory: u64,
+    paint_time: f64,  // average milliseconds per frame
+    upload_time: f64, // average milliseconds per frame
+    late_frames: u64,
+    animation_fps: u64,  // animation ticks per second
+    refresh_age: u64,    // nanoseconds since the once-a-second stats were last refreshed
+    refresh_frames: u64, // frames since the once-a-second stats were last refreshed
     initializing: bool,
+    suspended: bool,     // no anim frames while the window is in the background
+    skip_interval: bool, // true when the next anim frame interval covers a suspension
+    decode_errors: u64,
+    last_decode_error: Option<String>,
     label_fps: Label<u64>,
 }
 
 impl Stats {
     const FRAME_TIME_COUNT: usize = 360;
+    const REFRESH_INTERVAL: u64 = 1_000_000_000;
 
     pub fn new() -> Stats {
         Stats {
             frame_times: [0; Stats::FRAME_TIME_COUNT],
             frame_time_index: 0,
             fps: 0,
+            memory: 0,
+            paint_time: 0.0,
+            upload_time: 0.0,
+            late_frames: 0,
+            animation_fps: 0,
+            refresh_age: 0,
+            refresh_frames: 0,
             initializing: true,
+            suspended: false,
+            skip_interval: false,
+            decode_errors: 0,
+            last_decode_error: None,
             label_fps: Label::new("FPS: 0"),
         }
     }
@@ -54,6 +123,37 @@ impl Stats {
         }
     }
 
+    fn text(&self) -> String {
+        let mut text = format!(
+            "FPS: {}  Animation: {}  Frames: {}  paint {:.1}ms, upload {:.1}ms, late {}",
+            self.fps,
+            self.animation_fps,
+            format_bytes(self.memory),
+            self.paint_time,
+            self.upload_time,
+            self.late_frames
+        );
+        if let Some(error) = &self.last_decode_error {
+            text += &format!("  Decode errors: {} ({})", self.decode_errors, error);
+        }
+        text
+    }
+
+    /// Refresh the once-a-second stats.
+    fn refresh(&mut self) {
+        let frames = self.refresh_frames.max(1) as f64;
+        self.memory = cached_frame_bytes();
+        self.paint_time = PAINT_NANOS.swap(0, Ordering::Relaxed) as f64 / frames / 1_000_000.0;
+        self.upload_time = UPLOAD_NANOS.swap(0, Ordering::Relaxed) as f64 / frames / 1_000_000.0;
+        self.late_frames = LATE_FRAMES.swap(0, Ordering::Relaxed);
+        let seconds = self.refresh_age as f64 / 1_000_000_000.0;
+        self.animation_fps = (ANIMATION_TICKS.swap(0, Ordering::Relaxed) as f64 / seconds).round() as u64;
+        self.decode_errors = DECODE_ERRORS.load(Ordering::Relaxed);
+        self.last_decode_error = LAST_DECODE_ERROR.lock().ok().and_then(|last| last.clone());
+        self.refresh_age = 0;
+        self.refresh_frames = 0;
+    }
+
     fn average_fps(&self) -> u64 {
         let timed_frame_count = if self.initializing {
             self.frame_time_index
@@ -84,13 +184,33 @@ impl<T: Data> Widget<T> for Stats {
             Event::MouseDown(_) => {
                 Application::global().quit();
             }
+            Event::Command(cmd) if cmd.is(COMMAND_SUSPEND_ANIMATIONS) => {
+                let suspended = *cmd.get_unchecked(COMMAND_SUSPEND_ANIMATIONS);
+                if self.suspended && !suspended {
+                    self.skip_interval = true;
+                    ctx.request_anim_frame();
+                }
+                self.suspended = suspended;
+            }
+            Event::AnimFrame(_) if self.suspended => (),
+            Event::AnimFrame(_) if self.skip_interval => {
+                self.skip_interval = false;
+                ctx.request_anim_frame();
+            }
             Event::AnimFrame(interval) => {
                 //println!("Interval: {}", *interval as f64 / 1_000_000.);
                 self.add_frame_time(*interval);
                 let fps = self.average_fps();
-                if self.fps != fps {
-                    self.fps = fps;
-                    self.label_fps.set_text(format!("FPS: {}", self.fps));
+                let mut changed = self.fps != fps;
+                self.fps = fps;
+                self.refresh_age += *interval;
+                self.refresh_frames += 1;
+                if self.refresh_age >= Stats::REFRESH_INTERVAL {
+                    self.refresh();
+                    changed = true;
+                }
+                if changed {
+                    self.label_fps.set_text(self.text());
                     ctx.request_update();
                     ctx.request_layout();
                 }
@@ -118,7 +238,7 @@ impl<T: Data> Widget<T> for Stats {
         bc.debug_check("Stats");
         let label_bc = bc.loosen();
         let label_size = self.label_fps.layout(ctx, &label_bc, &self.fps, env);
-        bc.constrain((70.0, 20.0))
+        bc.constrain((520.0, 20.0))
     }
 
     fn paint(&mut self, ctx: &mut PaintCtx, _data: &T, env: &Env) {
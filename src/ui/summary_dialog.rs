@@ -0,0 +1,174 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use std::path::PathBuf;
+
+use druid::kurbo::Size;
+use druid::widget::{Button, CrossAxisAlignment, Flex, Label, Scroll};
+use druid::{Env, EventCtx, Widget, WidgetExt, WidgetId};
+
+use crate::i18n::{tr, tr_args};
+use crate::image::AnimationInfo;
+use crate::ui::{dialog, theme, COMMAND_START_RELINK};
+
+/// A snapshot of what is known about a single project image.
+pub struct ImageSummary {
+    pub id: usize,
+    pub name: String,
+    pub path: Option<PathBuf>, // None for images embedded in the project
+    pub size: Option<Size>,
+    pub frame_count: usize,
+    pub animation: Option<AnimationInfo>, // Only known up front for some formats
+    pub memory_usage: u64,
+    pub file_size: Option<u64>, // None when the file is missing
+    pub stale: bool,            // the file has changed since it was loaded
+}
+
+/// Open the project summary dialog. Relink requests are sent to `surface` as `COMMAND_START_RELINK`.
+pub fn open_summary_dialog(ctx: &mut EventCtx, env: &Env, images: Vec<ImageSummary>, surface: WidgetId) {
+    dialog::open(ctx, env, Size::new(560.0, 400.0), build(images, surface), ());
+}
+
+fn build(images: Vec<ImageSummary>, surface: WidgetId) -> impl Widget<()> {
+    let total_memory: u64 = images.iter().map(|image| image.memory_usage).sum();
+    let total_file_size: u64 = images.iter().filter_map(|image| image.file_size).sum();
+    let missing_count = images
+        .iter()
+        .filter(|image| image.path.is_some() && image.file_size.is_none())
+        .count();
+
+    let mut col = Flex::column().cross_axis_alignment(CrossAxisAlignment::Start);
+    col.add_child(Label::new(tr_args("summary-images", &[("count", images.len().into())])));
+    col.add_child(Label::new(tr_args(
+        "summary-decoded-memory",
+        &[("size", format_bytes(total_memory).into())],
+    )));
+    col.add_child(Label::new(tr_args(
+        "summary-size-on-disk",
+        &[("size", format_bytes(total_file_size).into())],
+    )));
+    if missing_count > 0 {
+        col.add_child(
+            Label::new(tr_args("summary-missing-files", &[("count", missing_count.into())]))
+                .with_text_color(theme::ERROR_TEXT),
+        );
+    }
+    let stale_count = images.iter().filter(|image| image.stale).count();
+    if stale_count > 0 {
+        col.add_child(
+            Label::new(tr_args("summary-stale-files", &[("count", stale_count.into())]))
+                .with_text_color(theme::ERROR_TEXT),
+        );
+    }
+    col.add_spacer(8.0);
+
+    let mut list = Flex::column().cross_axis_alignment(CrossAxisAlignment::Start);
+    for image in images {
+        let resolution = match image.size {
+            Some(size) => format!("{} x {}", size.width, size.height),
+            None => tr("summary-unknown-resolution"),
+        };
+        match image.file_size {
+            Some(file_size) => {
+                list.add_child(Label::new(tr_args(
+                    "summary-image",
+                    &[
+                        ("name", image.name.into()),
+                        ("resolution", resolution.into()),
+                        ("frames", image.frame_count.into()),
+                        ("decoded", format_bytes(image.memory_usage).into()),
+                        ("disk", format_bytes(file_size).into()),
+                    ],
+                )));
+                if let (true, Some(path)) = (image.stale, &image.path) {
+                    list.add_child(
+                        Label::new(tr_args(
+                            "summary-stale-image",
+                            &[("path", path.display().to_string().into())],
+                        ))
+                        .with_text_color(theme::ERROR_TEXT),
+                    );
+                }
+                if let Some(animation) = image.animation {
+                    let seconds = animation.duration as f64 / 1_000_000_000.0;
+                    list.add_child(Label::new(tr_args(
+                        "summary-animation",
+                        &[
+                            ("decoded", image.frame_count.into()),
+                            ("total", animation.frame_count.into()),
+                            ("seconds", format!("{:.2}", seconds).into()),
+                        ],
+                    )));
+                }
+            }
+            None => {
+                let id = image.id;
+                list.add_child(
+                    Flex::row()
+                        .with_child(
+                            Label::new(tr_args(
+                                "summary-missing-image",
+                                &[
+                                    ("name", image.name.into()),
+                                    (
+                                        "path",
+                                        match &image.path {
+                                            Some(path) => path.display().to_string().into(),
+                                            None => tr("summary-embedded").into(),
+                                        },
+                                    ),
+                                ],
+                            ))
+                            .with_text_color(theme::ERROR_TEXT),
+                        )
+                        .with_spacer(8.0)
+                        .with_child(Button::new(tr("summary-relink")).on_click(move |ctx, _data, _env| {
+                            ctx.submit_command(COMMAND_START_RELINK.with(id).to(surface));
+                            dialog::close(ctx);
+                        })),
+                );
+            }
+        }
+    }
+    col.add_flex_child(Scroll::new(list).vertical(), 1.0);
+
+    col.add_spacer(8.0);
+    col.add_child(
+        Flex::row()
+            .with_flex_spacer(1.0)
+            .with_child(Button::new(tr("dialog-close")).on_click(|ctx, _data, _env| dialog::close(ctx))),
+    );
+    col
+}
+
+/// Formats a byte count in human readable units.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[unit])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
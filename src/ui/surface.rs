@@ -17,17 +17,105 @@
     along with this program.  If not, see <http://www.gnu.org/licenses/>.
 */
 
+use std::collections::{HashMap, HashSet};
+use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use druid::kurbo::{Point, Rect, Vec2};
+use druid::kurbo::{Affine, Circle, Line, Point, Rect, Vec2};
+use druid::piet::{Text, TextLayout, TextLayoutBuilder};
 use druid::widget::prelude::*;
-use druid::{commands, Command, KbKey, Selector, Target, WidgetPod};
+use druid::widget::Axis;
+use druid::{
+    commands, Application, ClipboardFormat, Command, Cursor, FileDialogOptions, FileInfo, FontFamily, KbKey, Menu,
+    MenuItem, Selector, SysMods, Target, TimerToken, WidgetPod, WindowState,
+};
+use imgref::ImgVec;
+use log::{debug, error, info, warn};
+use rgb::{RGB8, RGBA8};
 
-use crate::project::{Image as ProjectImage, Project};
-use crate::ui::view::{View, ViewData};
-use crate::ui::{Tile, Tileize};
+use crate::capture;
+use crate::composite::{self, Placed};
+use crate::formats::{self, Format};
+use crate::i18n::{tr, tr_args};
+use crate::image::Frame;
+use crate::layout;
+use crate::pool::COMMAND_REGISTER_WINDOW;
+use crate::project::{self, Addition, Canvas, Image as ProjectImage, ImageDefaults, Layer, Project, Source};
+use crate::sequence::{self, Template};
+use crate::series::{Series, Snapshot};
+use crate::settings::{self, WindowGeometry};
+use crate::thumbnails;
+use crate::ui::keymap::{self, Action};
+use crate::ui::view::{proxy_scale, rotated_bounds, View, ViewData};
+use crate::ui::{dialog, theme, welcome, Ticker};
+use crate::ui::{
+    free_spot, open_canvas_dialog, open_capture_overlay, open_frames_dialog, open_gif_inspector,
+    open_image_defaults_dialog, open_metadata_dialog, open_name_dialog, open_summary_dialog, post,
+    record_animation_tick, record_paint, ImageSummary, LayerEntry, SortBy, ThumbnailEntry, Tile, Tileize, ToastMessage,
+    COMMAND_CAPTURE_CANCELLED, COMMAND_FOCUS_IMAGE, COMMAND_LAYERS_CHANGED, COMMAND_REGION_CAPTURED,
+    COMMAND_SELECT_IMAGE, COMMAND_SHIFT_LAYER, COMMAND_SHOW_QUICK_OPEN, COMMAND_SHOW_TOAST, COMMAND_THUMBNAILS_CHANGED,
+    COMMAND_TOGGLE_CONSOLE, COMMAND_TOGGLE_HELP, COMMAND_TOGGLE_LOCK, COMMAND_TOGGLE_VISIBILITY,
+};
+use crate::ui::{zoom_limits, ColorKey, TimeRemap, Zoom};
+use crate::watcher::{self, canonical, FileWatcher, SourceStatus, COMMAND_FILE_CHANGED, COMMAND_SOURCES_CHECKED};
 
-pub const COMMAND_ADD_IMAGE: Selector<String> = Selector::new("slark.add_image");
+/// Add the images in the given order, so the last one ends up on top.
+pub const COMMAND_ADD_IMAGE: Selector<Vec<PathBuf>> = Selector::new("slark.add_image");
+/// Add the images like `COMMAND_ADD_IMAGE`, centered on the point in surface coordinates if given.
+pub const COMMAND_ADD_IMAGE_AT: Selector<(Vec<PathBuf>, Option<Point>)> = Selector::new("slark.add_image_at");
+pub const COMMAND_RAISE_WINDOW: Selector<()> = Selector::new("slark.raise_window");
+pub const COMMAND_OPEN_PROJECT: Selector<PathBuf> = Selector::new("slark.open_project");
+/// Replace the project with the one at the path, or with a new one. Save the current one first if the flag is set.
+pub const COMMAND_REPLACE_PROJECT: Selector<(Option<PathBuf>, bool)> = Selector::new("slark.replace_project");
+pub const COMMAND_NEW_PROJECT: Selector = Selector::new("slark.new_project");
+pub const COMMAND_SHOW_OPEN_PROJECT: Selector = Selector::new("slark.show_open_project");
+pub const COMMAND_SHOW_ADD_IMAGES: Selector = Selector::new("slark.show_add_images");
+pub const COMMAND_ADD_IMAGE_FILES: Selector<Vec<FileInfo>> = Selector::new("slark.add_image_files");
+pub const COMMAND_SET_CANVAS: Selector<Option<Canvas>> = Selector::new("slark.set_canvas");
+/// Change how new images start out, and also apply that to the selected image right away if the flag is set.
+pub const COMMAND_SET_IMAGE_DEFAULTS: Selector<(ImageDefaults, bool)> = Selector::new("slark.set_image_defaults");
+pub const COMMAND_IMPORT_PROJECT: Selector<FileInfo> = Selector::new("slark.import_project");
+pub const COMMAND_START_RELINK: Selector<usize> = Selector::new("slark.start_relink");
+pub const COMMAND_RELINK_IMAGE: Selector<FileInfo> = Selector::new("slark.relink_image");
+pub const COMMAND_EXPORT_LAYOUT: Selector<FileInfo> = Selector::new("slark.export_layout");
+pub const COMMAND_APPLY_LAYOUT: Selector<FileInfo> = Selector::new("slark.apply_layout");
+/// Arrange the views into non-overlapping tiles.
+pub const COMMAND_ARRANGE_TILES: Selector = Selector::new("slark.arrange_tiles");
+/// Arrange the views into a grid of equal cells.
+pub const COMMAND_ARRANGE_GRID: Selector = Selector::new("slark.arrange_grid");
+/// Put the most recently removed image back to where it was.
+pub const COMMAND_RESTORE_REMOVED: Selector = Selector::new("slark.restore_removed");
+pub const COMMAND_SET_NAME: Selector<(usize, Option<String>)> = Selector::new("slark.set_name");
+pub const COMMAND_EXPORT_FRAME: Selector<FileInfo> = Selector::new("slark.export_frame");
+/// Make a color transparent in the view with the given id, or show all the colors again with `None`.
+pub const COMMAND_SET_COLOR_KEY: Selector<(usize, Option<ColorKey>)> = Selector::new("slark.set_color_key");
+/// Change how fast the animation of the view with the given id plays over the course of each loop.
+pub const COMMAND_SET_TIME_REMAP: Selector<(usize, TimeRemap)> = Selector::new("slark.set_time_remap");
+/// Add stills of every few frames of the animation in the view with the given id, in a row below it.
+pub const COMMAND_EXPAND_FILMSTRIP: Selector<usize> = Selector::new("slark.expand_filmstrip");
+/// Remove the filmstrip that the view with the given id is a still of.
+pub const COMMAND_COLLAPSE_FILMSTRIP: Selector<usize> = Selector::new("slark.collapse_filmstrip");
+/// Start saving the surface into the directory as a numbered series of PNG files.
+pub const COMMAND_START_SERIES: Selector<FileInfo> = Selector::new("slark.start_series");
+/// Take the part of the current frame that the view with the given id shows, and ask what to do with it.
+pub const COMMAND_START_CROP_EXPORT: Selector<usize> = Selector::new("slark.start_crop_export");
+/// Ask how to name the files for exporting the decoded frames of the view with the given id.
+pub const COMMAND_START_FRAMES_EXPORT: Selector<usize> = Selector::new("slark.start_frames_export");
+/// Ask where to export the frames of the view with the given id, named by the template.
+pub const COMMAND_PICK_FRAMES_DIRECTORY: Selector<(usize, String)> = Selector::new("slark.pick_frames_directory");
+const COMMAND_EXPORT_FRAMES: Selector<FileInfo> = Selector::new("slark.export_frames");
+const COMMAND_SAVE_CROP: Selector = Selector::new("slark.save_crop");
+const COMMAND_COPY_CROP: Selector = Selector::new("slark.copy_crop");
+/// Add the images found in the directory, with the number of other files that were skipped.
+pub const COMMAND_ADD_DIRECTORY: Selector<(PathBuf, Vec<PathBuf>, usize)> = Selector::new("slark.add_directory");
+/// Add the very large image at the path, centered on the point if given, at the fraction of its size, or skip it with `None`.
+pub const COMMAND_LOAD_LARGE: Selector<(PathBuf, Option<Point>, Option<f64>)> = Selector::new("slark.load_large");
+/// Pause all the animations and show the frame with the given index in the view with the given id.
+pub const COMMAND_SHOW_FRAME: Selector<(usize, usize)> = Selector::new("slark.show_frame");
 
 pub struct Surface {
     project: Project,
@@ -35,50 +123,900 @@ pub struct Surface {
     view_trackers: Vec<ViewTracker>,
     active_view: Option<usize>,
     drag: Option<Drag>,
+    watcher: Option<FileWatcher>,
+    relink_view: Option<usize>,                  // The view waiting for a new source file
+    layer_entries: Arc<Vec<LayerEntry>>,         // What was last sent to the layers panel
+    thumbnail_entries: Arc<Vec<ThumbnailEntry>>, // What was last sent to the thumbnail strip
+    thumbnails: HashMap<ThumbnailSource, StripThumbnail>,
+    pan: Vec2, // How far the whole surface content is shifted from the project coordinates
+    welcome: WidgetPod<u64, Box<dyn Widget<u64>>>, // Only shown while there are no images
+    placement_history: Vec<Placement>, // What to restore when undoing an arrangement
+    arranged: Option<(Arrangement, Placement)>, // The result of the most recent arrangement
+    sort_by: SortBy, // The order used for the most recent arrangement
+    grid_columns: Option<usize>, // The columns used for the most recent grid, None for auto
+    view_defaults: ViewData, // What new views start out with, e.g. paused when all the animations are paused
+    fit_pending: bool, // Arrange the views once the size of the surface is known
+    background_timer: TimerToken, // For checking whether the window went to the background
+    source_timer: TimerToken, // For checking whether the source files are missing or have changed
+    checking_sources: bool, // Whether a source check is still out, e.g. waiting for a slow network drive
+    show_labels: bool, // Whether the image names are painted below the views
+    measure: Option<Vec<Measurement>>, // The measurements so far, None when not in measure mode
+    measuring: bool, // Whether the last measurement is still being dragged out
+    eyedropper: bool, // Whether clicking picks the color under the cursor
+    keying: bool, // Whether clicking a view makes the color under the cursor transparent in it
+    crosshair: bool, // Whether guide lines are painted through the mouse position
+    mouse_pos: Option<Point>, // Where the mouse is over the surface, None when it is elsewhere
+    compare: Option<Compare>, // The two views being compared side by side, if any
+    frame_export: Option<ImgVec<RGBA8>>, // The frame or crop waiting for the export dialog
+    frames_export: Option<(usize, Template)>, // The view whose frames wait for the directory dialog, and their names
+    capture_timer: TimerToken, // For waiting until the window is out of the way of a screen capture
+    capture_at: Option<Point>, // Where the cursor was when the screen capture started
+    solo: Option<usize>, // The only view shown while soloing it, which is never saved
+    ticker: Ticker, // Coalesces the animation frames for the views into ticks at the capped rate
+    framing: Option<Framing>, // The animated pan and zoom towards the selected view, if it's still going
+    series: Option<Series>, // The series of snapshots being recorded, if any
+    series_timer: TimerToken, // For taking the next snapshot of the series
+    window_geometry: Option<WindowGeometry>, // Where the window is, remembered for the next time the project is opened
+    large_images: Vec<(PathBuf, Option<Point>, Size)>, // Very large images waiting to be asked about, and where to add them
+    startup: Option<Instant>, // When the surface was created, until it has been painted for the first time
+}
+
+/// The size assumed for placing a new view when the decoder doesn't know it yet.
+const PLACEMENT_SIZE: Size = Size::new(256.0, 256.0);
+
+/// The most stills that expanding an animation into a filmstrip makes.
+const FILMSTRIP_STILLS: usize = 8;
+
+/// The space between the stills of a filmstrip, and between them and the animation.
+const FILMSTRIP_GAP: f64 = 8.0;
+
+/// How far above the top edge of the selected view its rotation handle is.
+const ROTATION_HANDLE_DISTANCE: f64 = 16.0;
+
+/// The radius of the rotation handle, which can be grabbed from a little further out.
+const ROTATION_HANDLE_RADIUS: f64 = 4.0;
+
+/// The step in degrees that rotating snaps to while Shift is held.
+const ROTATION_SNAP: f64 = 15.0;
+
+/// How far a new view is placed from the previous one when there's no free spot.
+const CASCADE_OFFSET: Vec2 = Vec2::new(24.0, 24.0);
+
+/// The space between a name label and its backing.
+const LABEL_PADDING: f64 = 2.0;
+
+/// How much of the window a framed view fills.
+const FRAMING_FILL: f64 = 0.8;
+
+/// How many nanoseconds the framing animation takes.
+const FRAMING_DURATION: u64 = 200_000_000;
+
+/// How many views of an opened project start decoding per animation frame, so that the window stays responsive.
+const STARTUP_BATCH: usize = 4;
+
+/// Adding more images than this from a single directory needs a confirmation.
+const DIRECTORY_CONFIRM_COUNT: usize = 50;
+
+/// How long it takes for the window to get out of the way before the screen is captured.
+const CAPTURE_DELAY: Duration = Duration::from_millis(300);
+
+/// How often to check whether the window is in the background.
+const BACKGROUND_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How many of the reasons for skipping layout entries are spelled out in the toast.
+const LAYOUT_PROBLEMS_SHOWN: usize = 3;
+
+/// How often to check the source files for changes that the watcher may have missed.
+const SOURCE_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Sent to the window when its animations are suspended because it went to the background, or resumed.
+pub const COMMAND_SUSPEND_ANIMATIONS: Selector<bool> = Selector::new("slark.suspend_animations");
+
+/// The origin and zoom of a set of views, by id.
+type Placement = Vec<(usize, Point, Zoom)>;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Arrangement {
+    Rows,
+    Grid,
+}
+
+/// The grid column counts to cycle through, with None picking the count automatically.
+const GRID_COLUMNS: [Option<usize>; 4] = [Some(2), Some(3), Some(4), None];
+
+fn next_grid_columns(columns: Option<usize>) -> Option<usize> {
+    let i = GRID_COLUMNS.iter().position(|&c| c == columns).unwrap_or(0);
+    GRID_COLUMNS[(i + 1) % GRID_COLUMNS.len()]
+}
+
+/// The rotation in degrees clockwise from straight up that points from `center` towards `pos`.
+fn rotation_towards(center: Point, pos: Point) -> f64 {
+    let direction = pos - center;
+    direction.x.atan2(-direction.y).to_degrees().rem_euclid(360.0)
 }
 
 impl Surface {
     pub fn new(project: Project) -> Surface {
+        if let Some(path) = project.path().filter(|path| settings::is_scratch(path)) {
+            settings::claim_scratch(path);
+        }
+        let view_defaults = ViewData {
+            selected: false,
+            pinned: false,
+            paused: false,
+            suspended: false,
+            smooth: settings::current().smooth_scaling,
+            checkerboard: false,
+            diff: false,
+            timing: false,
+            physical: project.physical_scale(),
+            zoom: Zoom::default(),
+            offset: Point::ZERO,
+            time_remap: TimeRemap::Linear,
+            color_key: None,
+            rotation: 0.0,
+            sync_group: None,
+            source: SourceStatus::Fresh,
+        };
         let mut view_trackers = Vec::new();
         for project_image in project.images() {
-            view_trackers.push(ViewTracker::new(project.path(), project_image));
+            view_trackers.push(ViewTracker::dormant(&project, project_image, &view_defaults));
         }
-        Surface {
+        let window_geometry = settings::current().window_geometry(project.path());
+        let mut surface = Surface {
             project: project,
             size: Size::ZERO,
             view_trackers: view_trackers,
             active_view: None,
             drag: None,
+            watcher: None,
+            relink_view: None,
+            layer_entries: Arc::new(Vec::new()),
+            thumbnail_entries: Arc::new(Vec::new()),
+            thumbnails: HashMap::new(),
+            pan: Vec2::ZERO,
+            welcome: WidgetPod::new(Box::new(welcome())),
+            placement_history: Vec::new(),
+            arranged: None,
+            sort_by: SortBy::AddOrder,
+            grid_columns: GRID_COLUMNS[0],
+            view_defaults: view_defaults,
+            fit_pending: false,
+            background_timer: TimerToken::INVALID,
+            source_timer: TimerToken::INVALID,
+            checking_sources: false,
+            show_labels: false,
+            measure: None,
+            measuring: false,
+            eyedropper: false,
+            keying: false,
+            crosshair: false,
+            mouse_pos: None,
+            compare: None,
+            frame_export: None,
+            frames_export: None,
+            capture_timer: TimerToken::INVALID,
+            capture_at: None,
+            solo: None,
+            ticker: Ticker::new(settings::current().animation_fps),
+            framing: None,
+            series: None,
+            series_timer: TimerToken::INVALID,
+            window_geometry: window_geometry,
+            large_images: Vec::new(),
+            startup: Some(Instant::now()),
+        };
+        surface.select_topmost();
+        surface
+    }
+
+    /// Pause or resume all the animations.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.view_defaults.paused = paused;
+        for view_tracker in &mut self.view_trackers {
+            view_tracker.data.paused = paused;
+        }
+    }
+
+    /// Pause all the animations and move the selected view `delta` frames forward or backward.
+    ///
+    /// The views linked with it move along.
+    fn step_frame(&mut self, ctx: &mut EventCtx, delta: isize) {
+        if let Some(view_id) = self.active_view {
+            // Stepping only makes sense while nothing moves on its own
+            self.set_paused(true);
+            for id in self.in_step_with(view_id) {
+                self.view_trackers[id].widget_pod.widget_mut().step(delta);
+            }
+            ctx.request_update();
+            ctx.request_paint();
+        }
+    }
+
+    /// The view and all the views linked with it.
+    fn in_step_with(&self, view_id: usize) -> Vec<usize> {
+        match self.view_trackers[view_id].data.sync_group {
+            Some(group) => self.project.sync_group_members(group),
+            None => vec![view_id],
+        }
+    }
+
+    /// Link the selected animation with the next visible animation below it, so that they play in step.
+    fn link_below(&mut self, ctx: &mut EventCtx) {
+        let layers = self.project.layers();
+        let animated = |id: usize| self.view_trackers[id].widget_pod.widget().frame_count() > 1;
+        let pair = self.active_view.filter(|&upper| animated(upper)).and_then(|upper| {
+            let position = layers.iter().position(|&id| id == upper)?;
+            layers[..position]
+                .iter()
+                .rev()
+                .copied()
+                .find(|&id| self.project.images()[id].visible() && animated(id))
+                .map(|lower| (lower, upper))
+        });
+        let message = match pair {
+            Some((lower, upper)) => {
+                let group = self.project.link(upper, lower);
+                let members = self.project.sync_group_members(group);
+                for &id in members.iter() {
+                    self.view_trackers[id].data.sync_group = Some(group);
+                }
+                // The selected view sets the pace for the ones that just joined
+                let clock = self.view_trackers[upper].widget_pod.widget().clock();
+                for &id in members.iter() {
+                    self.view_trackers[id].widget_pod.widget_mut().follow(clock);
+                }
+                ctx.request_update();
+                ToastMessage::info(tr_args("toast-linked", &[("count", members.len().into())]))
+            }
+            None => ToastMessage::warning(tr("toast-link-needs-two")),
+        };
+        ctx.submit_command(COMMAND_SHOW_TOAST.with(message).to(Target::Window(ctx.window_id())));
+    }
+
+    /// Let the views linked with the selected one play on their own again.
+    fn unlink(&mut self, ctx: &mut EventCtx) {
+        let members = match self.active_view {
+            Some(view_id) => self.project.unlink(view_id),
+            None => return,
+        };
+        for &id in members.iter() {
+            self.view_trackers[id].data.sync_group = None;
+        }
+        let message = if members.is_empty() {
+            ToastMessage::warning(tr("toast-not-linked"))
+        } else {
+            ToastMessage::info(tr("toast-unlinked"))
+        };
+        ctx.submit_command(COMMAND_SHOW_TOAST.with(message).to(Target::Window(ctx.window_id())));
+        ctx.request_update();
+    }
+
+    /// Keep the views of every sync group on the frame of the first shown view in the group.
+    ///
+    /// The views get the same intervals and so mostly stay in step on their own,
+    /// this catches up with frames that were decoded late or such.
+    fn sync_clocks(&mut self) {
+        let shown: Vec<usize> = (0..self.view_trackers.len()).filter(|&id| self.shown(id)).collect();
+        let mut clocks: HashMap<u32, (usize, i64)> = HashMap::new();
+        for id in shown {
+            let view_tracker = &mut self.view_trackers[id];
+            if let Some(group) = view_tracker.data.sync_group {
+                match clocks.get(&group) {
+                    Some(&clock) => view_tracker.widget_pod.widget_mut().follow(clock),
+                    None => {
+                        clocks.insert(group, view_tracker.widget_pod.widget().clock());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Keep track of where the window is, so that it can be remembered once the window closes.
+    ///
+    /// This is polled, because closing the window from the menu gives no chance to look at it beforehand.
+    fn track_geometry(&mut self, ctx: &EventCtx) {
+        let window = ctx.window();
+        let maximized = match window.get_window_state() {
+            // A minimized window has nothing worth remembering
+            WindowState::Minimized => return,
+            WindowState::Maximized => true,
+            WindowState::Restored => false,
+        };
+        match &mut self.window_geometry {
+            // Keep the size to restore to
+            Some(geometry) if maximized => geometry.maximized = true,
+            _ => {
+                let origin = window.get_position();
+                let size = window.get_size();
+                self.window_geometry = Some(WindowGeometry {
+                    x: origin.x,
+                    y: origin.y,
+                    width: size.width,
+                    height: size.height,
+                    maximized: maximized,
+                });
+            }
+        }
+    }
+
+    /// Stop the animation work while the window is in the background, unless the settings say otherwise.
+    fn check_background(&mut self, ctx: &mut EventCtx) {
+        let window = ctx.window();
+        let background = !window.is_foreground_window() || window.get_window_state() == WindowState::Minimized;
+        let suspended = background && !settings::current().animate_in_background;
+        if suspended == self.view_defaults.suspended {
+            return;
+        }
+        debug!("Animations {}", if suspended { "suspended" } else { "resumed" });
+        self.view_defaults.suspended = suspended;
+        self.update_suspended();
+        ctx.submit_command(
+            COMMAND_SUSPEND_ANIMATIONS
+                .with(suspended)
+                .to(Target::Window(ctx.window_id())),
+        );
+        ctx.request_update();
+    }
+
+    /// Look for missing and changed source files on a worker thread, unless the previous check is still out.
+    fn check_sources(&mut self, ctx: &mut EventCtx) {
+        if self.checking_sources {
+            return;
+        }
+        let sources: Vec<_> = self
+            .view_trackers
+            .iter()
+            .filter(|view_tracker| !view_tracker.is_dormant())
+            .filter_map(|view_tracker| {
+                let path = view_tracker.path.clone()?;
+                Some((path, view_tracker.widget_pod.widget().modified()))
+            })
+            .collect();
+        if sources.is_empty() {
+            return;
+        }
+        self.checking_sources = true;
+        watcher::check_sources(sources, ctx.get_external_handle(), Target::Widget(ctx.widget_id()));
+    }
+
+    /// Mark the views with what the source check found, returning `true` if any of them changed.
+    ///
+    /// The views may have been reloaded, removed or relinked while the check was out,
+    /// so a finding only counts if it's about the file as the view still knows it.
+    fn apply_source_findings(&mut self, findings: &[(PathBuf, Option<SystemTime>, SourceStatus)]) -> bool {
+        let mut changed = false;
+        for view_tracker in self.view_trackers.iter_mut() {
+            let path = match &view_tracker.path {
+                Some(path) => path,
+                None => continue,
+            };
+            let loaded = view_tracker.widget_pod.widget().modified();
+            let status = match findings.iter().find(|(found, _, _)| found == path) {
+                Some((_, checked, status)) if *checked == loaded => *status,
+                _ => continue,
+            };
+            if view_tracker.data.source != status {
+                view_tracker.data.source = status;
+                changed = true;
+            }
         }
+        changed
+    }
+
+    /// Arrange the views into tiles as soon as the surface has a size.
+    pub fn fit_on_start(&mut self) {
+        self.fit_pending = true;
     }
 
     pub fn set_project(&mut self, project: Project) {
         self.project = project;
+        self.view_defaults.physical = self.project.physical_scale();
+        match self.project.path() {
+            Some(path) if settings::is_scratch(path) => settings::claim_scratch(path),
+            Some(_) => self.remember_session(),
+            None => (),
+        }
         self.view_trackers = {
             let mut view_trackers = Vec::new();
             for project_image in self.project.images() {
-                view_trackers.push(ViewTracker::new(self.project.path(), project_image));
+                view_trackers.push(ViewTracker::dormant(&self.project, project_image, &self.view_defaults));
             }
             view_trackers
         };
         self.active_view = None;
         self.drag = None;
+        self.relink_view = None;
+        self.solo = None;
+        self.framing = None;
+        self.pan = Vec2::ZERO;
+        self.placement_history.clear();
+        self.arranged = None;
+        self.compare = None;
+        // The views are dormant, and get watched as they start
+        if let Some(watcher) = &mut self.watcher {
+            watcher.clear();
+        }
+        // Have something selected right away, so that the next keystroke does something
+        self.select_topmost();
+    }
+
+    /// Start decoding the next few dormant views, returning whether there are more left.
+    fn start_dormant_views(&mut self) -> bool {
+        let mut dormant = self
+            .view_trackers
+            .iter_mut()
+            .filter(|view_tracker| view_tracker.is_dormant());
+        for view_tracker in dormant.by_ref().take(STARTUP_BATCH) {
+            view_tracker.start();
+            if let (Some(watcher), Some(path)) = (&mut self.watcher, &view_tracker.path) {
+                watcher.watch(path);
+            }
+        }
+        dormant.next().is_some()
+    }
+
+    /// Add an image to the project, centered on `at` in surface coordinates if given.
+    ///
+    /// Images larger than the `large_image_megapixels` setting are only added once the user has been asked about them.
+    pub fn add(&mut self, ctx: &mut EventCtx, filename: PathBuf, at: Option<Point>) {
+        let megapixels = settings::current().large_image_megapixels;
+        if megapixels > 0 {
+            let size = Format::from_path(&filename).and_then(|format| format.read_size(&filename).ok());
+            if let Some(size) = size.filter(|size| size.area() > megapixels as f64 * 1e6) {
+                self.large_images.push((filename, at, size));
+                return;
+            }
+        }
+        self.add_at_proxy(ctx, filename, at, 1.0);
+    }
+
+    /// Add an image to the project like `add`, keeping only `proxy` of its native size.
+    fn add_at_proxy(&mut self, ctx: &mut EventCtx, filename: PathBuf, at: Option<Point>, proxy: f64) {
+        // Duplicates are already offset from the original by the project
+        let (image_id, placed) = match self.project.add(filename) {
+            Addition::New(image_id) => (image_id, true),
+            Addition::Duplicate(image_id) => {
+                let name = self.project.images()[image_id].display_name();
+                let message = ToastMessage::info(tr_args("toast-duplicate-added", &[("name", name.into())]));
+                ctx.submit_command(COMMAND_SHOW_TOAST.with(message).to(Target::Window(ctx.window_id())));
+                (image_id, false)
+            }
+            Addition::Existing(image_id) => {
+                // Focus the existing view instead
+                self.select(Some(image_id));
+                self.view_trackers[image_id].widget_pod.widget_mut().flash();
+                return;
+            }
+        };
+        self.project.set_proxy(image_id, proxy);
+        let project_image = &self.project.images()[image_id];
+        let mut view_tracker = ViewTracker::new(&self.project, project_image, &self.view_defaults);
+        if placed {
+            // The decoder already knows the size, even though no frames have been decoded yet
+            let size =
+                view_tracker.widget_pod.widget().image_size().unwrap_or(PLACEMENT_SIZE) * view_tracker.scale_factor();
+            view_tracker.origin = self.placement(size, at);
+            self.project.set_origin(image_id, view_tracker.origin);
+        }
+        if let (Some(watcher), Some(path)) = (&mut self.watcher, &view_tracker.path) {
+            watcher.watch(path);
+        }
+        self.view_trackers.push(view_tracker);
+        self.select(Some(image_id));
+    }
+
+    /// Ask whether to load each of the very large images in full, as a downscaled proxy, or not at all.
+    fn ask_about_large_images(&mut self, ctx: &mut EventCtx, env: &Env) {
+        let megapixels = settings::current().large_image_megapixels as f64 * 1e6;
+        for (path, at, size) in self.large_images.drain(..) {
+            let proxy = proxy_scale(size, megapixels);
+            let message = tr_args(
+                "large-image-confirm",
+                &[
+                    ("path", path.display().to_string().into()),
+                    ("width", (size.width as usize).into()),
+                    ("height", (size.height as usize).into()),
+                ],
+            );
+            let load = |proxy| COMMAND_LOAD_LARGE.with((path.clone(), at, proxy)).to(ctx.widget_id());
+            let choices = vec![
+                (tr("large-image-full"), Some(load(Some(1.0)))),
+                (
+                    tr_args(
+                        "large-image-proxy",
+                        &[("percent", ((proxy * 100.0).round() as usize).into())],
+                    ),
+                    Some(load(Some(proxy))),
+                ),
+                (tr("dialog-cancel"), None),
+            ];
+            dialog::confirm(ctx, env, message, choices);
+        }
+    }
+
+    /// Warn that exporting from the view only has the pixels of its proxy, when it is one.
+    fn warn_if_proxy(&self, ctx: &mut EventCtx, view_id: usize) {
+        if let Some(project_image) = self.project.image(view_id).filter(|image| image.proxy() < 1.0) {
+            let message = ToastMessage::warning(tr_args(
+                "toast-proxy-export",
+                &[
+                    ("name", project_image.display_name().into()),
+                    ("percent", ((project_image.proxy() * 100.0).round() as usize).into()),
+                ],
+            ));
+            ctx.submit_command(COMMAND_SHOW_TOAST.with(message).to(Target::Window(ctx.window_id())));
+        }
+    }
+
+    /// Add every few frames of the animation in the view as embedded stills in a filmstrip below it.
+    ///
+    /// Returns whether any views were added.
+    fn expand_filmstrip(&mut self, ctx: &mut EventCtx, view_id: usize) -> bool {
+        let view_tracker = &self.view_trackers[view_id];
+        let view = view_tracker.widget_pod.widget();
+        let frame_count = view.frame_count();
+        if frame_count < 2 {
+            let message = ToastMessage::info(tr("toast-filmstrip-needs-animation"));
+            ctx.submit_command(COMMAND_SHOW_TOAST.with(message).to(Target::Window(ctx.window_id())));
+            return false;
+        }
+        let step = (frame_count + FILMSTRIP_STILLS - 1) / FILMSTRIP_STILLS;
+        let stills: Vec<(usize, ImgVec<RGBA8>)> = (0..frame_count)
+            .step_by(step)
+            .map(|index| (index, view.frame_pixels(index).unwrap().clone()))
+            .collect();
+
+        // The stills keep the zoom of the animation, so that they line up with it
+        let zoom = view_tracker.data.zoom;
+        let scale = view_tracker.scale_factor();
+        let name = self.project.images()[view_id].display_name();
+        let mut origin =
+            view_tracker.origin + Vec2::new(0.0, view_tracker.widget_pod.layout_rect().height() + FILMSTRIP_GAP);
+        let mut ids = Vec::with_capacity(stills.len());
+        for (index, pixels) in stills {
+            let width = pixels.width() as f64;
+            let image_id = self.project.add_embedded(pixels, origin, zoom);
+            let label = tr_args("filmstrip-frame", &[("index", (index + 1).into())]);
+            self.project.set_name(image_id, Some(label));
+            let view_tracker = ViewTracker::new(&self.project, &self.project.images()[image_id], &self.view_defaults);
+            self.view_trackers.push(view_tracker);
+            ids.push(image_id);
+            origin.x += width * scale + FILMSTRIP_GAP;
+        }
+        self.project
+            .create_filmstrip(tr_args("filmstrip-group", &[("name", name.into())]), &ids);
+        // The frame numbers are in the names of the stills
+        self.show_labels = true;
+        true
+    }
+
+    /// Remove all the stills of the filmstrip that the view is part of.
+    fn collapse_filmstrip(&mut self, view_id: usize) {
+        let ids = match self.project.filmstrip_of(view_id) {
+            Some(ids) => ids,
+            None => return,
+        };
+        for id in project::removal_order(&ids) {
+            self.remove(id, false);
+        }
+    }
+
+    /// Add all the images in the directory at `path`, asking first if there are a lot of them.
+    fn add_directory(&mut self, ctx: &mut EventCtx, env: &Env, path: &Path) {
+        let (images, skipped) = formats::scan(path, false);
+        if images.is_empty() {
+            let message = ToastMessage::warning(tr_args(
+                "toast-no-images-in-directory",
+                &[("path", path.display().to_string().into())],
+            ));
+            ctx.submit_command(COMMAND_SHOW_TOAST.with(message).to(Target::Window(ctx.window_id())));
+        } else if images.len() > DIRECTORY_CONFIRM_COUNT {
+            let message = tr_args(
+                "directory-confirm",
+                &[
+                    ("path", path.display().to_string().into()),
+                    ("count", images.len().into()),
+                ],
+            );
+            let command = COMMAND_ADD_DIRECTORY.with((path.to_path_buf(), images, skipped));
+            let choices = vec![
+                (tr("directory-add-all"), Some(command.to(ctx.widget_id()))),
+                (tr("dialog-cancel"), None),
+            ];
+            dialog::confirm(ctx, env, message, choices);
+        } else {
+            self.add_images_from(ctx, path, images, skipped);
+        }
+    }
+
+    /// Add the images found in the directory at `path` and report how many there were.
+    fn add_images_from(&mut self, ctx: &mut EventCtx, path: &Path, images: Vec<PathBuf>, skipped: usize) {
+        let count = images.len();
+        for image in images {
+            self.add(ctx, image, None);
+        }
+        let message = ToastMessage::info(tr_args(
+            "toast-directory-added",
+            &[
+                ("path", path.display().to_string().into()),
+                ("count", count.into()),
+                ("skipped", skipped.into()),
+            ],
+        ));
+        ctx.submit_command(COMMAND_SHOW_TOAST.with(message).to(Target::Window(ctx.window_id())));
+    }
+
+    /// Where to put a new view of `size`, in project coordinates.
+    ///
+    /// The view is centered on `at` when given. Otherwise the first view is centered on the surface
+    /// and the rest go to the first free spot, or cascade down from the most recently added view.
+    fn placement(&self, size: Size, at: Option<Point>) -> Point {
+        let centered = |point: Point| point - size.to_vec2() / 2.0;
+        if let Some(at) = at {
+            return centered(at) - self.pan;
+        }
+        let taken: Vec<Rect> = self
+            .view_trackers
+            .iter()
+            .filter(|view_tracker| self.project.images()[view_tracker.id].visible())
+            .filter_map(|view_tracker| {
+                let image_size = view_tracker.widget_pod.widget().image_size()?;
+                let size = image_size * view_tracker.scale_factor();
+                Some(Rect::from_origin_size(view_tracker.origin + self.pan, size))
+            })
+            .collect();
+        let surface = self.size.to_rect();
+        if taken.is_empty() {
+            // Keep the top left corner in sight even if the view is larger than the surface
+            let origin = centered(surface.center());
+            return Point::new(origin.x.max(0.0), origin.y.max(0.0)) - self.pan;
+        }
+        let spacing = self.project.tile_spacing();
+        let area = surface.inset(-spacing.margin);
+        match free_spot(area, &taken, size, spacing.gap) {
+            Some(spot) => spot - self.pan,
+            None => match self.view_trackers.last() {
+                Some(view_tracker) => view_tracker.origin + CASCADE_OFFSET,
+                None => Point::ZERO - self.pan,
+            },
+        }
+    }
+
+    /// Whether the project has never been saved, or is the autosaved scratch project.
+    fn is_scratch(&self) -> bool {
+        match self.project.path() {
+            Some(path) => settings::is_scratch(path),
+            None => true,
+        }
+    }
+
+    /// Remember the project for the next launch, autosaving it first when it has never been saved.
+    fn remember_session(&mut self) {
+        let path = if self.is_scratch() {
+            // There's nothing worth keeping in an empty scratch project
+            if self.project.images().is_empty() {
+                return;
+            }
+            // Keep the scratch project of every window apart, so that closing one doesn't overwrite another
+            let scratch_path = match self.project.path() {
+                Some(path) if settings::is_scratch(path) => path.to_path_buf(),
+                _ => match settings::claim_free_scratch() {
+                    Some(path) => path,
+                    None => return,
+                },
+            };
+            if let Some(dir) = scratch_path.parent() {
+                if let Err(error) = fs::create_dir_all(dir) {
+                    warn!("Failed to create {}: {}", dir.display(), error);
+                    return;
+                }
+            }
+            self.settle_trash();
+            self.project.make_paths_absolute();
+            if let Err(error) = self.project.save(&scratch_path) {
+                warn!("Failed to save the scratch project: {}", error);
+                return;
+            }
+            scratch_path
+        } else {
+            match self.project.path() {
+                Some(path) => path.to_path_buf(),
+                None => return,
+            }
+        };
+        settings::update(|settings| settings.last_project = Some(path));
+    }
+
+    /// Open the project at `path`, or start a new one if there's no path, asking first if there are unsaved changes.
+    ///
+    /// Returns `true` if the project was replaced immediately.
+    fn open_project(&mut self, ctx: &mut EventCtx, env: &Env, path: Option<PathBuf>) -> bool {
+        if !self.project.dirty() {
+            return match self.read_project(ctx, path) {
+                Some(project) => {
+                    self.set_project(project);
+                    self.update_title(ctx);
+                    true
+                }
+                None => false,
+            };
+        }
+        let surface_id = ctx.widget_id();
+        let mut choices = Vec::new();
+        if self.project.path().is_some() {
+            choices.push((
+                tr("unsaved-save"),
+                Some(COMMAND_REPLACE_PROJECT.with((path.clone(), true)).to(surface_id)),
+            ));
+        }
+        choices.push((
+            tr("unsaved-discard"),
+            Some(COMMAND_REPLACE_PROJECT.with((path.clone(), false)).to(surface_id)),
+        ));
+        choices.push((tr("dialog-cancel"), None));
+        let message = match &path {
+            Some(path) => tr_args("unsaved-open", &[("path", path.display().to_string().into())]),
+            None => tr("unsaved-new"),
+        };
+        dialog::confirm(ctx, env, message, choices);
+        false
+    }
+
+    /// Read the project at `path`, or start a new one if there's no path, reporting failures with a toast.
+    fn read_project(&self, ctx: &mut EventCtx, path: Option<PathBuf>) -> Option<Project> {
+        let path = match path {
+            Some(path) => path,
+            None => return Some(Project::new()),
+        };
+        let path_arg = path.display().to_string();
+        match Project::open(path) {
+            Ok(project) => Some(project),
+            Err(error) => {
+                let message = ToastMessage::error(tr_args(
+                    "toast-open-failed",
+                    &[("path", path_arg.into()), ("error", error.into())],
+                ));
+                ctx.submit_command(COMMAND_SHOW_TOAST.with(message).to(Target::Window(ctx.window_id())));
+                None
+            }
+        }
+    }
+
+    /// Bring the window back after it got out of the way of a screen capture.
+    fn restore_window(&self, ctx: &mut EventCtx) {
+        ctx.window().set_window_state(WindowState::Restored);
+        ctx.window().bring_to_front_and_focus();
+    }
+
+    /// Save a screen capture as a PNG next to the project file, or in the temp directory
+    /// if the project hasn't been saved yet. Failures are reported with a toast.
+    fn save_capture(&self, ctx: &mut EventCtx, image: &ImgVec<RGBA8>) -> Option<PathBuf> {
+        let dir = match self.project.path().and_then(|path| path.parent()) {
+            Some(dir) => dir.to_path_buf(),
+            None => std::env::temp_dir().join("slark-captures"),
+        };
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        let mut path = dir.join(format!("capture-{}.png", stamp));
+        let mut copy = 1;
+        while path.exists() {
+            copy += 1;
+            path = dir.join(format!("capture-{}-{}.png", stamp, copy));
+        }
+        // The view needs the file right away, so there's no point in encoding it on another thread
+        let result = fs::create_dir_all(&dir)
+            .map_err(|error| error.to_string())
+            .and_then(|()| formats::save_frame(&path, image));
+        match result {
+            Ok(()) => Some(path),
+            Err(error) => {
+                let message = ToastMessage::error(tr_args(
+                    "toast-save-failed",
+                    &[("path", path.display().to_string().into()), ("error", error.into())],
+                ));
+                ctx.submit_command(COMMAND_SHOW_TOAST.with(message).to(Target::Window(ctx.window_id())));
+                None
+            }
+        }
+    }
+
+    /// Store where every animation is, so that reopening the project continues from there.
+    fn sync_playback(&mut self) {
+        for view_tracker in &self.view_trackers {
+            let frame = view_tracker.widget_pod.widget().frame_index();
+            self.project
+                .set_playback(view_tracker.id, view_tracker.data.paused, frame);
+        }
+    }
+
+    /// Forget the removed images before saving, unless the settings ask to keep them.
+    fn settle_trash(&mut self) {
+        if !settings::current().keep_trash {
+            self.project.empty_trash();
+        }
+    }
+
+    /// Save the project to `path`, reporting the outcome with a toast. Returns `true` on success.
+    fn save_project(&mut self, ctx: &mut EventCtx, path: &Path) -> bool {
+        let path_arg = path.display().to_string();
+        self.sync_playback();
+        self.settle_trash();
+        let result = self.project.save(path);
+        let message = match &result {
+            Ok(()) => ToastMessage::info(tr_args("toast-saved", &[("path", path_arg.into())])),
+            Err(error) => ToastMessage::error(tr_args(
+                "toast-save-failed",
+                &[("path", path_arg.into()), ("error", error.as_str().into())],
+            )),
+        };
+        ctx.submit_command(COMMAND_SHOW_TOAST.with(message).to(Target::Window(ctx.window_id())));
+        result.is_ok()
+    }
+
+    fn show_open_project(&self, ctx: &mut EventCtx) {
+        ctx.submit_command(Command::new(
+            commands::SHOW_OPEN_PANEL,
+            self.project.file_dialog_options(),
+            Target::Auto,
+        ));
     }
 
-    pub fn add(&mut self, filename: PathBuf) {
-        self.project.add(filename);
-        let project_image = self.project.images().last().unwrap();
-        self.view_trackers
-            .push(ViewTracker::new(self.project.path(), project_image));
+    fn show_add_images(&self, ctx: &mut EventCtx) {
+        ctx.submit_command(Command::new(
+            commands::SHOW_OPEN_PANEL,
+            self.project
+                .image_dialog_options()
+                .multi_selection()
+                .title(tr("file-add-images"))
+                .accept_multiple_command(COMMAND_ADD_IMAGE_FILES),
+            Target::Auto,
+        ));
+    }
+
+    /// Merge the images of another project on top of the current one.
+    pub fn import(&mut self, project: Project) {
+        // Offset the imported images so they don't land exactly on top of existing content
+        let offset = if self.project.images().is_empty() {
+            Vec2::ZERO
+        } else {
+            Vec2::new(24.0, 24.0)
+        };
+        let first_id = self.project.images().len();
+        self.project.merge(project, offset);
+        for project_image in &self.project.images()[first_id..] {
+            let view_tracker = ViewTracker::new(&self.project, project_image, &self.view_defaults);
+            if let (Some(watcher), Some(path)) = (&mut self.watcher, &view_tracker.path) {
+                watcher.watch(path);
+            }
+            self.view_trackers.push(view_tracker);
+        }
+        if self.project.images().len() > first_id {
+            self.select_topmost();
+        }
     }
 
     // Super fragile function, must be same as the project removal.
-    pub fn remove(&mut self, view_id: usize) {
+    /// Remove the view and its image, keeping the image in the trash of the project if `trash` is set.
+    pub fn remove(&mut self, view_id: usize, trash: bool) {
         if self.view_trackers.is_empty() || self.view_trackers.len() <= view_id {
             return;
-        } else if self.view_trackers.len() == 1 {
+        }
+        // Dormant views haven't been watched yet
+        let view_tracker = &self.view_trackers[view_id];
+        if let (Some(watcher), Some(path), false) = (&mut self.watcher, &view_tracker.path, view_tracker.is_dormant()) {
+            watcher.unwatch(path);
+        }
+        // Removing shifts the ids around, so the history no longer applies
+        self.placement_history.clear();
+        self.set_solo(None);
+        self.framing = None;
+        self.arranged = None;
+        self.compare = None;
+        if self.view_trackers.len() == 1 {
             self.view_trackers.clear();
-            self.project.remove(view_id);
+            self.remove_from_project(view_id, trash);
             self.drag = None;
             self.active_view = None;
         } else {
@@ -86,7 +1024,7 @@ impl Surface {
             self.view_trackers[last_id].id = view_id;
             self.view_trackers.swap(view_id, last_id);
             self.view_trackers.pop();
-            self.project.remove(view_id);
+            self.remove_from_project(view_id, trash);
 
             if let Some(drag) = &self.drag {
                 if drag.view_id == view_id {
@@ -95,6 +1033,8 @@ impl Surface {
                     self.drag = Some(Drag {
                         view_id: view_id,
                         start: drag.start,
+                        panning: drag.panning,
+                        rotating: drag.rotating,
                     });
                 }
             }
@@ -107,42 +1047,988 @@ impl Surface {
         }
     }
 
-    /// Automatically change the location and zoom factor of all the images in order to fit them all.
-    pub fn tileize(&mut self, ctx: &mut EventCtx) {
-        let mut tileize = Tileize::new(self.size);
+    fn remove_from_project(&mut self, image_id: usize, trash: bool) {
+        if trash {
+            self.project.trash(image_id);
+        } else {
+            self.project.remove(image_id);
+        }
+    }
+
+    /// Put the most recently removed image back to where it was, with a new view.
+    fn restore_removed(&mut self, ctx: &mut EventCtx) {
+        let view_id = match self.project.restore() {
+            Some(view_id) => view_id,
+            None => {
+                ctx.submit_command(
+                    COMMAND_SHOW_TOAST
+                        .with(ToastMessage::warning(tr("toast-trash-empty")))
+                        .to(Target::Window(ctx.window_id())),
+                );
+                return;
+            }
+        };
+        let project_image = &self.project.images()[view_id];
+        let name = project_image.display_name();
+        let view_tracker = ViewTracker::new(&self.project, project_image, &self.view_defaults);
+        if let (Some(watcher), Some(path)) = (&mut self.watcher, &view_tracker.path) {
+            watcher.watch(path);
+        }
+        self.view_trackers.push(view_tracker);
+        self.select(Some(view_id));
+        ctx.children_changed();
+        self.update_title(ctx);
+        ctx.submit_command(
+            COMMAND_SHOW_TOAST
+                .with(ToastMessage::info(tr_args("toast-restored", &[("name", name.into())])))
+                .to(Target::Window(ctx.window_id())),
+        );
+    }
+
+    /// Point the view at a different source file while keeping its placement.
+    pub fn relink(&mut self, view_id: usize, path: PathBuf) {
+        if view_id >= self.view_trackers.len() {
+            return;
+        }
+        self.project.set_path(view_id, path.clone());
+        let view_tracker = &mut self.view_trackers[view_id];
+        let dormant = view_tracker.is_dormant();
+        if let Some(watcher) = &mut self.watcher {
+            if let (Some(old_path), false) = (&view_tracker.path, dormant) {
+                watcher.unwatch(old_path);
+            }
+            watcher.watch(&path);
+        }
+        view_tracker.reload(&path);
+        view_tracker.path = Some(path);
+    }
+
+    /// Bring the view placement up to date after the project was changed directly.
+    fn sync_placement(&mut self) {
+        for project_image in self.project.images() {
+            let view_tracker = &mut self.view_trackers[project_image.id()];
+            view_tracker.origin = *project_image.origin();
+            view_tracker.data.zoom = *project_image.zoom();
+        }
+    }
+
+    /// Gather what is currently known about every image.
+    fn summary(&self) -> Vec<ImageSummary> {
+        self.project
+            .images()
+            .iter()
+            .map(|image| {
+                let view_tracker = &self.view_trackers[image.id()];
+                let view = view_tracker.widget_pod.widget();
+                ImageSummary {
+                    id: image.id(),
+                    name: image.display_name(),
+                    path: view_tracker.path.clone(),
+                    size: view.image_size(),
+                    frame_count: view.frame_count(),
+                    animation: view.animation_info(),
+                    memory_usage: view.memory_usage(),
+                    file_size: view_tracker
+                        .path
+                        .as_ref()
+                        .and_then(|path| std::fs::metadata(path).ok())
+                        .map(|metadata| metadata.len()),
+                    stale: view_tracker.data.source == SourceStatus::Stale,
+                }
+            })
+            .collect()
+    }
+
+    fn select(&mut self, view_id: Option<usize>) {
+        if let Some(view_id) = self.active_view {
+            self.view_trackers[view_id].data.selected = false;
+        }
+        self.active_view = view_id;
+        if let Some(view_id) = view_id {
+            self.view_trackers[view_id].data.selected = true;
+        }
+        if self.solo.is_some() && self.solo != view_id {
+            self.set_solo(None);
+        }
+    }
+
+    /// Show only the view with the id, or all of them again with `None`.
+    fn set_solo(&mut self, solo: Option<usize>) {
+        self.solo = solo;
+        self.update_suspended();
+    }
+
+    /// Suspend the animations of the views that can't be seen, either because of the window or soloing.
+    fn update_suspended(&mut self) {
+        let suspended = self.view_defaults.suspended;
+        let solo = self.solo;
+        for view_tracker in &mut self.view_trackers {
+            view_tracker.data.suspended = suspended || solo.map_or(false, |solo| solo != view_tracker.id);
+        }
+    }
+
+    /// Whether the view with the id is painted, as it's visible and not hidden by soloing another view.
+    fn shown(&self, id: usize) -> bool {
+        self.project.images()[id].visible() && self.solo.map_or(true, |solo| solo == id)
+    }
+
+    /// Enter solo mode with the selected view, or leave it.
+    fn toggle_solo(&mut self, ctx: &mut EventCtx) {
+        if self.solo.is_some() {
+            self.set_solo(None);
+        } else if let Some(view_id) = self.active_view {
+            self.set_solo(Some(view_id));
+        }
+        ctx.request_update();
+        ctx.request_paint();
+    }
+
+    /// Mark solo mode with a badge in the top left corner.
+    fn paint_solo_badge(&self, ctx: &mut PaintCtx, env: &Env) {
+        let surface = self.size.to_rect();
+        self.paint_tag(
+            ctx,
+            env,
+            tr("solo-badge"),
+            Point::new(surface.x0 + 8.0, surface.y0 + 8.0),
+            1.0,
+        );
+    }
+
+    fn paint_series_badge(&self, ctx: &mut PaintCtx, env: &Env, count: usize) {
+        let surface = self.size.to_rect();
+        // The tag is kept within the surface, so this puts it in the top right corner
+        self.paint_tag(
+            ctx,
+            env,
+            tr_args("series-badge", &[("count", count.into())]),
+            Point::new(surface.x1, surface.y0 + 8.0),
+            1.0,
+        );
+    }
+
+    /// Paint the name of every visible view just below it, kept within the surface.
+    fn paint_labels(&self, ctx: &mut PaintCtx, env: &Env) {
+        let surface = self.size.to_rect();
+        for &id in self.project.layers().iter() {
+            let image = &self.project.images()[id];
+            if !self.shown(id) {
+                continue;
+            }
+            let view_tracker = &self.view_trackers[id];
+            let view_rect = view_tracker.widget_pod.layout_rect();
+            if view_rect.intersect(surface).area() == 0.0 {
+                continue;
+            }
+            // Dim the labels of unselected views so the selected one stands out
+            let alpha = if view_tracker.data.selected { 1.0 } else { 0.6 };
+            self.paint_tag(
+                ctx,
+                env,
+                image.display_name(),
+                Point::new(view_rect.x0, view_rect.y1),
+                alpha,
+            );
+        }
+    }
+
+    /// Paint a small piece of text on a backing at `origin`, kept within the surface.
+    fn paint_tag(&self, ctx: &mut PaintCtx, env: &Env, text: String, origin: Point, alpha: f64) {
+        let surface = self.size.to_rect();
+        let layout = ctx
+            .text()
+            .new_text_layout(text)
+            .font(FontFamily::SYSTEM_UI, 11.0)
+            .text_color(env.get(theme::PANEL_TEXT).with_alpha(alpha))
+            .build()
+            .unwrap();
+        let size = layout.size() + Size::new(2.0 * LABEL_PADDING, 2.0 * LABEL_PADDING);
+        let origin = Point::new(
+            origin.x.min(surface.x1 - size.width).max(surface.x0),
+            origin.y.min(surface.y1 - size.height).max(surface.y0),
+        );
+        let backing = Rect::from_origin_size(origin, size).to_rounded_rect(3.0);
+        ctx.render_ctx
+            .fill(backing, &env.get(theme::PANEL_BACKGROUND).with_alpha(0.7 * alpha));
+        ctx.render_ctx
+            .draw_text(&layout, origin + Vec2::new(LABEL_PADDING, LABEL_PADDING));
+    }
+
+    /// Paint a measurement line with its length next to the middle of it.
+    fn paint_measurement(&self, ctx: &mut PaintCtx, env: &Env, measurement: &Measurement) {
+        let start = measurement.start + self.pan;
+        let end = measurement.end + self.pan;
+        let color = env.get(theme::SELECTION);
+        ctx.render_ctx.stroke(Line::new(start, end), &color, 1.5);
+        for &point in [start, end].iter() {
+            ctx.render_ctx.fill(Circle::new(point, 3.0), &color);
+        }
+
+        let length = (end - start).hypot();
+        let text = match (self.view_at(start), self.view_at(end)) {
+            (Some(a), Some(b)) if a == b => {
+                let native = length / self.view_trackers[a].scale_factor();
+                tr_args(
+                    "measure-length-native",
+                    &[
+                        ("length", format!("{:.1}", length).into()),
+                        ("native", format!("{:.1}", native).into()),
+                    ],
+                )
+            }
+            _ => tr_args("measure-length", &[("length", format!("{:.1}", length).into())]),
+        };
+        self.paint_tag(ctx, env, text, start.midpoint(end) + Vec2::new(6.0, 6.0), 1.0);
+    }
+
+    /// Enter or leave the eyedropper mode, where clicking copies the color under the cursor.
+    fn toggle_eyedropper(&mut self, ctx: &mut EventCtx) {
+        let message = if self.eyedropper {
+            self.eyedropper = false;
+            ctx.set_cursor(&Cursor::Arrow);
+            tr("toast-eyedropper-off")
+        } else {
+            if self.measure.is_some() {
+                self.toggle_measure(ctx);
+            }
+            if self.keying {
+                self.toggle_keying(ctx);
+            }
+            self.eyedropper = true;
+            ctx.set_cursor(&Cursor::Crosshair);
+            tr("toast-eyedropper-on")
+        };
+        ctx.submit_command(
+            COMMAND_SHOW_TOAST
+                .with(ToastMessage::info(message))
+                .to(Target::Window(ctx.window_id())),
+        );
+    }
+
+    /// Enter or leave the keying mode, where clicking a view makes the color under the cursor transparent in it.
+    fn toggle_keying(&mut self, ctx: &mut EventCtx) {
+        let message = if self.keying {
+            self.keying = false;
+            ctx.set_cursor(&Cursor::Arrow);
+            tr("toast-keying-off")
+        } else {
+            if self.measure.is_some() {
+                self.toggle_measure(ctx);
+            }
+            if self.eyedropper {
+                self.toggle_eyedropper(ctx);
+            }
+            self.keying = true;
+            ctx.set_cursor(&Cursor::Crosshair);
+            tr("toast-keying-on")
+        };
+        ctx.submit_command(
+            COMMAND_SHOW_TOAST
+                .with(ToastMessage::info(message))
+                .to(Target::Window(ctx.window_id())),
+        );
+    }
+
+    /// Key out the color of the image pixel at `pos` in surface coordinates, in the view that shows it.
+    fn pick_color_key(&mut self, ctx: &mut EventCtx, pos: Point) {
+        let view_id = match self.view_at(pos) {
+            Some(view_id) => view_id,
+            None => return,
+        };
+        let view_tracker = &mut self.view_trackers[view_id];
+        let pixels = match view_tracker.widget_pod.widget().current_pixels() {
+            Some(pixels) if pixels.width() > 0 && pixels.height() > 0 => pixels,
+            _ => return,
+        };
+        let rect = view_tracker.image_rect();
+        let pos = view_tracker.unrotate(pos);
+        let x = ((pos.x - rect.x0) / rect.width() * pixels.width() as f64).floor() as usize;
+        let y = ((pos.y - rect.y0) / rect.height() * pixels.height() as f64).floor() as usize;
+        let pixel = pixels[(x.min(pixels.width() - 1), y.min(pixels.height() - 1))];
+        // Picking again keeps the tolerance that was already chosen
+        let tolerance = view_tracker
+            .data
+            .color_key
+            .map_or(ColorKey::DEFAULT_TOLERANCE, |key| key.tolerance);
+        let key = ColorKey::new(RGB8::new(pixel.r, pixel.g, pixel.b), tolerance);
+        view_tracker.data.color_key = Some(key);
+        self.project.set_color_key(view_id, Some(key));
+        self.toggle_keying(ctx);
+        ctx.submit_command(
+            COMMAND_SHOW_TOAST
+                .with(ToastMessage::info(tr_args(
+                    "toast-color-keyed",
+                    &[("color", key.hex().into())],
+                )))
+                .to(Target::Window(ctx.window_id())),
+        );
+        ctx.request_update();
+    }
+
+    /// Ask for a directory to start saving a series of snapshots into, or stop the series that is being saved.
+    fn toggle_series(&mut self, ctx: &mut EventCtx) {
+        match self.series.take() {
+            Some(series) => {
+                self.series_timer = TimerToken::INVALID;
+                let message = tr_args(
+                    "toast-series-stopped",
+                    &[
+                        ("count", series.count().into()),
+                        ("path", series.dir().display().to_string().into()),
+                    ],
+                );
+                ctx.submit_command(
+                    COMMAND_SHOW_TOAST
+                        .with(ToastMessage::info(message))
+                        .to(Target::Window(ctx.window_id())),
+                );
+                ctx.request_paint();
+            }
+            None => ctx.submit_command(Command::new(
+                commands::SHOW_OPEN_PANEL,
+                FileDialogOptions::new()
+                    .select_directories()
+                    .title(tr("file-series-directory"))
+                    .accept_command(COMMAND_START_SERIES),
+                Target::Auto,
+            )),
+        }
+    }
+
+    /// Copy what the surface shows right now, for compositing it into the next file of the series.
+    fn snapshot(&self, env: &Env) -> Snapshot {
+        let (r, g, b, a) = env.get(theme::SURFACE_BACKGROUND).as_rgba8();
+        let mut layers = Vec::new();
+        if let Some(canvas) = self.project.canvas() {
+            let (r, g, b, a) = canvas.background_color().as_rgba8();
+            layers.push((
+                ImgVec::new(vec![RGBA8::new(r, g, b, a)], 1, 1),
+                canvas.rect() + self.pan,
+                Affine::IDENTITY,
+            ));
+        }
+        for id in self.project.layers() {
+            if !self.shown(id) {
+                continue;
+            }
+            let view_tracker = &self.view_trackers[id];
+            let rect = view_tracker.content_rect();
+            // Only the part of the image that fits the view is shown
+            if let Some(pixels) = view_tracker
+                .widget_pod
+                .widget()
+                .cropped_pixels(&view_tracker.data, rect.size())
+            {
+                layers.push((pixels, rect, view_tracker.turn()));
+            }
+        }
+        Snapshot {
+            backdrop: RGBA8::new(r, g, b, a),
+            width: self.size.width.ceil() as usize,
+            height: self.size.height.ceil() as usize,
+            layers: layers,
+        }
+    }
+
+    /// Copy the composited color at `pos` in surface coordinates to the clipboard.
+    fn pick_color(&self, ctx: &mut EventCtx, env: &Env, pos: Point) {
+        let backdrop = match self.project.canvas() {
+            Some(canvas) if (canvas.rect() + self.pan).contains(pos) => canvas.background_color(),
+            _ => env.get(theme::SURFACE_BACKGROUND),
+        };
+        let (r, g, b, a) = backdrop.as_rgba8();
+        let layers: Vec<Placed> = self
+            .project
+            .layers()
+            .into_iter()
+            .filter(|&id| self.shown(id))
+            // Views can be too small for their image, so only the part within them counts
+            .filter(|&id| self.view_trackers[id].contains(pos))
+            .filter_map(|id| {
+                let view_tracker = &self.view_trackers[id];
+                view_tracker.widget_pod.widget().current_pixels().map(|pixels| Placed {
+                    image: pixels,
+                    rect: view_tracker.image_rect(),
+                    transform: view_tracker.turn(),
+                })
+            })
+            .collect();
+        let color = composite::hex(composite::composite_at(RGBA8::new(r, g, b, a), &layers, pos));
+        Application::global().clipboard().put_string(&color);
+        ctx.submit_command(
+            COMMAND_SHOW_TOAST
+                .with(ToastMessage::info(tr_args(
+                    "toast-color-copied",
+                    &[("color", color.into())],
+                )))
+                .to(Target::Window(ctx.window_id())),
+        );
+    }
+
+    /// Paint hairlines across the whole surface through `pos`, with its project coordinates at the edges.
+    fn paint_crosshair(&self, ctx: &mut PaintCtx, env: &Env, pos: Point) {
+        let surface = self.size.to_rect();
+        // Half pixel offsets keep the hairlines sharp
+        let (x, y) = (pos.x.floor() + 0.5, pos.y.floor() + 0.5);
+        let color = env.get(theme::GUIDES);
+        ctx.render_ctx
+            .stroke(Line::new((x, surface.y0), (x, surface.y1)), &color, 1.0);
+        ctx.render_ctx
+            .stroke(Line::new((surface.x0, y), (surface.x1, y)), &color, 1.0);
+
+        let project_pos = pos - self.pan;
+        let x_label = format!("{:.0}", project_pos.x.floor());
+        let y_label = format!("{:.0}", project_pos.y.floor());
+        self.paint_tag(ctx, env, x_label, Point::new(x + 2.0, surface.y0), 1.0);
+        self.paint_tag(ctx, env, y_label, Point::new(surface.x0, y + 2.0), 1.0);
+    }
+
+    /// The selected view whose rotation handle is at `pos` in surface coordinates, unless it's locked in place.
+    fn rotation_handle_at(&self, pos: Point) -> Option<usize> {
+        self.active_view.filter(|&view_id| {
+            self.shown(view_id)
+                && !self.project.images()[view_id].locked()
+                && self.view_trackers[view_id].rotation_handle().distance(pos) <= 2.0 * ROTATION_HANDLE_RADIUS
+        })
+    }
+
+    /// Paint the handle above the selected view that Alt dragging rotates it with.
+    fn paint_rotation_handle(&self, ctx: &mut PaintCtx, env: &Env, view_id: usize) {
+        let view_tracker = &self.view_trackers[view_id];
+        let handle = view_tracker.rotation_handle();
+        // The stem starts from the middle of the top edge, turned along with the view
+        let top = view_tracker.turned(Point::new(view_tracker.content_rect().width() / 2.0, 0.0));
+        let color = env.get(theme::SELECTION);
+        ctx.render_ctx.stroke(Line::new(top, handle), &color, 1.0);
+        ctx.render_ctx.fill(Circle::new(handle, ROTATION_HANDLE_RADIUS), &color);
+    }
+
+    /// Returns the topmost visible view at `pos` in surface coordinates.
+    fn view_at(&self, pos: Point) -> Option<usize> {
+        self.project
+            .layers()
+            .iter()
+            .rev()
+            .copied()
+            .find(|&id| self.shown(id) && self.view_trackers[id].contains(pos))
+    }
+
+    /// Start comparing the selected view with the next visible view below it, or stop comparing.
+    ///
+    /// The selected view is moved on top of the other one, until comparing stops.
+    fn toggle_compare(&mut self, ctx: &mut EventCtx) {
+        if let Some(compare) = self.compare.take() {
+            if compare.dragging {
+                ctx.set_active(false);
+            }
+            self.apply_placement(compare.restore);
+            ctx.request_layout();
+            return;
+        }
+        let layers = self.project.layers();
+        let pair = self.active_view.and_then(|upper| {
+            let position = layers.iter().position(|&id| id == upper)?;
+            layers[..position]
+                .iter()
+                .rev()
+                .copied()
+                .find(|&id| self.project.images()[id].visible())
+                .map(|lower| (lower, upper))
+        });
+        let message = match pair {
+            Some((lower, upper)) => {
+                let origin = self.view_trackers[lower].origin;
+                let zoom = self.view_trackers[upper].data.zoom;
+                let restore = self.apply_placement(vec![(upper, origin, zoom)]);
+                self.compare = Some(Compare {
+                    lower: lower,
+                    upper: upper,
+                    restore: restore,
+                    split: 0.5,
+                    dragging: false,
+                });
+                ctx.request_layout();
+                ToastMessage::info(tr("toast-compare-on"))
+            }
+            None => ToastMessage::warning(tr("toast-compare-needs-two")),
+        };
+        ctx.submit_command(COMMAND_SHOW_TOAST.with(message).to(Target::Window(ctx.window_id())));
+    }
+
+    /// The area covered by the two compared views, in surface coordinates.
+    fn compare_region(&self, compare: &Compare) -> Rect {
+        let lower = self.view_trackers[compare.lower].widget_pod.layout_rect();
+        let upper = self.view_trackers[compare.upper].widget_pod.layout_rect();
+        lower.union(upper)
+    }
+
+    /// The horizontal position of the compare divider, in surface coordinates.
+    fn compare_divider(&self, compare: &Compare) -> f64 {
+        let region = self.compare_region(compare);
+        region.x0 + compare.split * region.width()
+    }
+
+    /// Move the compare divider to the horizontal position of `pos`.
+    fn compare_drag(&mut self, pos: Point) {
+        if let Some(compare) = &self.compare {
+            let region = self.compare_region(compare);
+            let split = if region.width() > 0.0 {
+                ((pos.x - region.x0) / region.width()).max(0.0).min(1.0)
+            } else {
+                0.5
+            };
+            let compare = self.compare.as_mut().unwrap();
+            compare.split = split;
+            compare.dragging = true;
+        }
+    }
+
+    /// Paint the divider between the compared views with a handle in the middle of it.
+    fn paint_compare_divider(&self, ctx: &mut PaintCtx, env: &Env, compare: &Compare) {
+        let region = self.compare_region(compare);
+        let x = self.compare_divider(compare);
+        let color = env.get(theme::SELECTION);
+        ctx.render_ctx
+            .stroke(Line::new((x, region.y0), (x, region.y1)), &color, 2.0);
+        ctx.render_ctx.fill(Circle::new((x, region.center().y), 6.0), &color);
+    }
+
+    /// Enter or leave the measure mode, where dragging measures distances instead of moving views.
+    fn toggle_measure(&mut self, ctx: &mut EventCtx) {
+        let message = if self.measure.take().is_some() {
+            self.measuring = false;
+            ctx.set_active(false);
+            ctx.set_cursor(&Cursor::Arrow);
+            tr("toast-measure-off")
+        } else {
+            if self.eyedropper {
+                self.toggle_eyedropper(ctx);
+            }
+            if self.keying {
+                self.toggle_keying(ctx);
+            }
+            self.measure = Some(Vec::new());
+            ctx.set_cursor(&Cursor::Crosshair);
+            tr("toast-measure-on")
+        };
+        ctx.submit_command(
+            COMMAND_SHOW_TOAST
+                .with(ToastMessage::info(message))
+                .to(Target::Window(ctx.window_id())),
+        );
+        ctx.request_paint();
+    }
+
+    /// Select the topmost visible view, if there is one.
+    fn select_topmost(&mut self) {
+        let topmost = self
+            .project
+            .layers()
+            .into_iter()
+            .rev()
+            .find(|&id| self.project.images()[id].visible());
+        self.select(topmost);
+    }
+
+    /// Describe the layers from the topmost one down.
+    fn layer_entries(&self) -> Vec<LayerEntry> {
+        fn collect(surface: &Surface, layers: &[Layer], depth: usize, entries: &mut Vec<LayerEntry>) {
+            for layer in layers {
+                match layer {
+                    Layer::Image(id) => {
+                        let image = &surface.project.images()[*id];
+                        entries.push(LayerEntry {
+                            id: *id,
+                            name: image.display_name(),
+                            depth: depth,
+                            visible: image.visible(),
+                            locked: image.locked(),
+                            selected: surface.active_view == Some(*id),
+                        });
+                    }
+                    Layer::Group(group) => collect(surface, group.layers(), depth + 1, entries),
+                }
+            }
+        }
+        let mut entries = Vec::new();
+        collect(self, self.project.layer_tree(), 0, &mut entries);
+        entries.reverse();
+        entries
+    }
+
+    /// Let the layers panel know if anything changed since the last time.
+    fn publish_layers(&mut self, window: Target, submit: impl FnOnce(Command)) {
+        let entries = self.layer_entries();
+        if *self.layer_entries != entries {
+            self.layer_entries = Arc::new(entries);
+            submit(COMMAND_LAYERS_CHANGED.with(self.layer_entries.clone()).to(window));
+        }
+    }
+
+    /// Let the thumbnail strip know if any thumbnail or the selection changed since the last time.
+    ///
+    /// This is checked on every event, as thumbnails arrive from the thumbnail cache in the background.
+    fn publish_thumbnails(&mut self, window: Target, submit: impl FnOnce(Command)) {
+        let sources: Vec<Option<ThumbnailSource>> = self
+            .project
+            .images()
+            .iter()
+            .map(|image| match image.source() {
+                Source::File(_) => self.project.full_path(image).map(|path| {
+                    // A reloaded file gets a fresh thumbnail
+                    let modified = self.view_trackers[image.id()].widget_pod.widget().modified();
+                    ThumbnailSource::File(path, modified)
+                }),
+                Source::Embedded(embedded_id) => Some(ThumbnailSource::Embedded(*embedded_id)),
+            })
+            .collect();
+        if self.thumbnails.len() > sources.len() {
+            // Forget the thumbnails of images that are gone or have changed
+            let current: HashSet<&ThumbnailSource> = sources.iter().flatten().collect();
+            self.thumbnails.retain(|source, _| current.contains(source));
+        }
+        let entries: Vec<ThumbnailEntry> = sources
+            .into_iter()
+            .enumerate()
+            .map(|(id, source)| ThumbnailEntry {
+                id: id,
+                thumbnail: source.and_then(|source| self.strip_thumbnail(source)),
+                selected: self.active_view == Some(id),
+            })
+            .collect();
+        if *self.thumbnail_entries != entries {
+            self.thumbnail_entries = Arc::new(entries);
+            submit(
+                COMMAND_THUMBNAILS_CHANGED
+                    .with(self.thumbnail_entries.clone())
+                    .to(window),
+            );
+        }
+    }
+
+    /// The thumbnail of `source`, requesting it from the thumbnail cache the first time.
+    fn strip_thumbnail(&mut self, source: ThumbnailSource) -> Option<Arc<ImgVec<RGBA8>>> {
+        let project = &self.project;
+        let thumbnail = self.thumbnails.entry(source).or_insert_with_key(|source| match source {
+            ThumbnailSource::File(path, _) => StripThumbnail::Pending(thumbnails::request(path)),
+            ThumbnailSource::Embedded(embedded_id) => match project.embedded(*embedded_id) {
+                Some(pixels) => StripThumbnail::Ready(Arc::new(thumbnails::downscaled(&pixels))),
+                None => StripThumbnail::Failed,
+            },
+        });
+        if let StripThumbnail::Pending(receiver) = thumbnail {
+            match receiver.try_recv() {
+                Ok(ready) => *thumbnail = StripThumbnail::Ready(ready),
+                Err(TryRecvError::Empty) => (),
+                // The cache gives up without sending anything when decoding fails
+                Err(TryRecvError::Disconnected) => *thumbnail = StripThumbnail::Failed,
+            }
+        }
+        match thumbnail {
+            StripThumbnail::Ready(ready) => Some(ready.clone()),
+            _ => None,
+        }
+    }
+
+    /// Select the view and pan the surface so that the view is in the middle.
+    fn focus(&mut self, view_id: usize) {
+        if view_id >= self.view_trackers.len() {
+            return;
+        }
+        self.select(Some(view_id));
+        let view_tracker = &self.view_trackers[view_id];
+        let view_size = view_tracker.widget_pod.layout_rect().size();
+        let view_center = view_tracker.origin + view_size.to_vec2() / 2.0;
+        self.pan = self.size.to_rect().center() - view_center;
+    }
+
+    /// Show the name of the active view in the window title.
+    fn update_title(&self, ctx: &mut EventCtx) {
+        let title = match self.active_view {
+            Some(view_id) => match self.project.images().iter().find(|image| image.id() == view_id) {
+                Some(image) => tr_args("window-title", &[("name", image.display_name().into())]),
+                None => tr("app-title"),
+            },
+            None => tr("app-title"),
+        };
+        ctx.window().set_title(&title);
+    }
+
+    /// Automatically change the location and zoom factor of all the visible images in order to fit them all.
+    /// This is a single undoable step.
+    ///
+    /// Rows keep the current zoom levels where possible, while the grid zooms every image to fill its cell.
+    /// Arranging the same way again without any changes in between switches to the next sort order
+    /// or grid column count.
+    pub fn tileize(&mut self, ctx: &mut EventCtx, arrangement: Arrangement) {
+        if let Some((arranged_as, arranged)) = &self.arranged {
+            let unchanged = arranged.iter().all(|&(id, origin, zoom)| {
+                let view_tracker = &self.view_trackers[id];
+                view_tracker.origin == origin && view_tracker.data.zoom == zoom
+            });
+            if unchanged && *arranged_as == arrangement {
+                match arrangement {
+                    Arrangement::Rows => self.sort_by = self.sort_by.next(),
+                    Arrangement::Grid => self.grid_columns = next_grid_columns(self.grid_columns),
+                }
+            }
+        }
+        let mut tileize = Tileize::new(self.size, self.sort_by);
+        let spacing = self.project.tile_spacing();
+        tileize.set_spacing(spacing.gap, spacing.margin);
 
         // Add the images
+        let layers = self.project.layers();
         for view_tracker in &self.view_trackers {
+            let project_image = &self.project.images()[view_tracker.id];
+            if !project_image.visible() {
+                continue;
+            }
             if let Some(image_size) = view_tracker.widget_pod.widget().image_size() {
+                if project_image.pinned() {
+                    // Pinned views stay where they are and the rest flow around them
+                    let size = image_size * view_tracker.scale_factor();
+                    tileize.add_obstacle(Rect::from_origin_size(view_tracker.origin, size));
+                    continue;
+                }
+                let name = match project_image.path().and_then(Path::file_name) {
+                    Some(name) => name.to_string_lossy().into_owned(),
+                    None => project_image.display_name(),
+                };
+                let layer = layers.iter().position(|&id| id == view_tracker.id).unwrap_or(0);
                 tileize.add(Tile::new(
                     view_tracker.id,
+                    name,
+                    layer,
                     view_tracker.origin,
-                    image_size,
+                    // Tiles are zoomed from the physical size, when the images are matched by it
+                    image_size * view_tracker.physical_scale(),
                     view_tracker.data.zoom,
                 ));
             } // .. and ignore the not-yet-loaded views
         }
 
         // Do the magic
-        tileize.fit();
+        let message = match arrangement {
+            Arrangement::Rows => {
+                tileize.fit();
+                tr_args("toast-arranged", &[("order", self.sort_by.name().into())])
+            }
+            Arrangement::Grid => {
+                let columns = tileize.fit_grid(self.grid_columns);
+                tr_args("toast-arranged-grid", &[("columns", columns.into())])
+            }
+        };
+
+        // Apply the changes, the tiles are relative to what is currently visible
+        self.pan = Vec2::ZERO;
+        let placement = tileize
+            .tiles()
+            .iter()
+            .map(|tile| (tile.id(), tile.origin(), tile.zoom()))
+            .collect();
+        let previous = self.apply_placement(placement.clone());
+        self.placement_history.push(previous);
+        self.arranged = Some((arrangement, placement));
+
+        ctx.submit_command(
+            COMMAND_SHOW_TOAST
+                .with(ToastMessage::info(message))
+                .to(Target::Window(ctx.window_id())),
+        );
+
+        ctx.request_update();
+        ctx.request_layout();
+    }
 
-        // Apply the changes
-        for tile in tileize.tiles() {
-            self.view_trackers[tile.id()].origin = tile.origin();
-            self.view_trackers[tile.id()].data.zoom = tile.zoom();
+    /// Revert the most recent arrangement.
+    fn undo_placement(&mut self, ctx: &mut EventCtx) {
+        if let Some(placement) = self.placement_history.pop() {
+            self.apply_placement(placement);
+            ctx.request_update();
+            ctx.request_layout();
+        }
+    }
+
+    /// Zoom the selected view to span the whole surface along `axis`, starting from its edge.
+    ///
+    /// This only needs the image size, so it works before the frames have been decoded.
+    fn fit_selected(&mut self, ctx: &mut EventCtx, axis: Axis) {
+        let view_id = match self.active_view {
+            Some(view_id) => view_id,
+            None => return,
+        };
+        let view_tracker = &self.view_trackers[view_id];
+        let image_size = match view_tracker.widget_pod.widget().image_size() {
+            Some(image_size) if axis.major(image_size) > 0.0 => image_size * view_tracker.physical_scale(),
+            _ => return,
+        };
+        let zoom = Zoom::exact(axis.major(self.size) / axis.major(image_size));
+        let mut origin = view_tracker.origin;
+        match axis {
+            Axis::Horizontal => origin.x = -self.pan.x,
+            Axis::Vertical => origin.y = -self.pan.y,
         }
+        let previous = self.apply_placement(vec![(view_id, origin, zoom)]);
+        self.placement_history.push(previous);
+        ctx.request_update();
+        ctx.request_layout();
+    }
+
+    /// Start animating the pan and the zoom so that the selected view fills most of the window.
+    fn frame_selected(&mut self, ctx: &mut EventCtx) {
+        let view_id = match self.active_view {
+            Some(view_id) => view_id,
+            None => return,
+        };
+        let view_tracker = &self.view_trackers[view_id];
+        let image_size = match view_tracker.widget_pod.widget().image_size() {
+            Some(image_size) if image_size.width > 0.0 && image_size.height > 0.0 => {
+                image_size * view_tracker.physical_scale()
+            }
+            _ => return,
+        };
+        let fill = FRAMING_FILL * (self.size.width / image_size.width).min(self.size.height / image_size.height);
+        // A tiny image would need more zoom than allowed, so it is framed at the largest zoom instead
+        let (min, max) = zoom_limits();
+        let to_scale = fill.max(min).min(max);
+        let to_center = view_tracker.origin + (image_size * to_scale).to_vec2() / 2.0;
+        self.placement_history
+            .push(vec![(view_id, view_tracker.origin, view_tracker.data.zoom)]);
+        self.framing = Some(Framing {
+            view_id: view_id,
+            from_pan: self.pan,
+            to_pan: self.size.to_rect().center() - to_center,
+            from_scale: view_tracker.data.zoom.scale_factor(),
+            to_scale: to_scale,
+            elapsed: 0,
+        });
+        ctx.request_anim_frame();
+    }
 
+    /// Advance the framing animation by `interval` nanoseconds.
+    fn advance_framing(&mut self, ctx: &mut EventCtx, interval: u64) {
+        let framing = match &mut self.framing {
+            Some(framing) => framing,
+            None => return,
+        };
+        framing.elapsed += interval;
+        let progress = (framing.elapsed as f64 / FRAMING_DURATION as f64).min(1.0);
+        // Ease out, so that it slows down when arriving
+        let eased = 1.0 - (1.0 - progress).powi(3);
+        // The zoom is interpolated geometrically, so that every step looks like the same amount of zooming
+        let scale = framing.from_scale * (framing.to_scale / framing.from_scale).powf(eased);
+        let zoom = Zoom::exact(scale);
+        let view_id = framing.view_id;
+        self.pan = framing.from_pan.lerp(framing.to_pan, eased);
+        if progress < 1.0 {
+            ctx.request_anim_frame();
+        } else {
+            self.framing = None;
+        }
+        self.view_trackers[view_id].data.zoom = zoom;
+        self.project.set_zoom(view_id, zoom);
+        ctx.request_update();
         ctx.request_layout();
     }
+
+    /// Set the view up as if it had just been added, with the image defaults of the project.
+    fn apply_image_defaults(&mut self, view_id: usize) {
+        self.project.apply_image_defaults(view_id);
+        let project_image = &self.project.images()[view_id];
+        let data = &mut self.view_trackers[view_id].data;
+        data.smooth = project_image.smooth().unwrap_or(self.view_defaults.smooth);
+        data.checkerboard = project_image.checkerboard();
+        data.zoom = *project_image.zoom();
+        // Everything paused together stays that way
+        data.paused = project_image.paused() || self.view_defaults.paused;
+    }
+
+    /// Move and zoom the views, returning the placement they had before.
+    fn apply_placement(&mut self, placement: Placement) -> Placement {
+        let mut previous = Vec::with_capacity(placement.len());
+        for (id, origin, zoom) in placement {
+            let view_tracker = &mut self.view_trackers[id];
+            previous.push((id, view_tracker.origin, view_tracker.data.zoom));
+            view_tracker.origin = origin;
+            view_tracker.data.zoom = zoom;
+            self.project.set_origin(id, origin);
+            self.project.set_zoom(id, zoom);
+        }
+        previous
+    }
 }
 
 impl Widget<u64> for Surface {
-    fn event(&mut self, ctx: &mut EventCtx, event: &Event, _data: &mut u64, env: &Env) {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut u64, env: &Env) {
         let mut hacky_children_added = false;
 
+        if let Event::AnimFrame(_) = event {
+            // A view whose first frame has just arrived is painted right away, without waiting for an animation tick
+            let mut primed = false;
+            for view_tracker in self.view_trackers.iter_mut() {
+                primed |= view_tracker.widget_pod.widget_mut().prime();
+            }
+            if primed {
+                ctx.request_paint();
+            }
+        }
+
+        // The views of an opened project start a few at a time, once the window has shown up
+        if let Event::AnimFrame(_) = event {
+            if self.startup.is_some() {
+                ctx.request_anim_frame();
+            } else if self.view_trackers.iter().any(ViewTracker::is_dormant) {
+                if self.start_dormant_views() {
+                    ctx.request_anim_frame();
+                }
+                // The sizes of the started images are known right away
+                ctx.request_layout();
+            }
+        }
+
         match event {
+            Event::AnimFrame(interval) if self.framing.is_some() => {
+                self.advance_framing(ctx, *interval);
+            }
             Event::MouseDown(mouse_event) => {
-                if mouse_event.button.is_left() {
+                // Taking over with the mouse stops the framing animation where it is
+                self.framing = None;
+                // Other instances should add their images to the most recently used window
+                ctx.submit_command(COMMAND_REGISTER_WINDOW.with(ctx.window_id()).to(Target::Global));
+                if mouse_event.button.is_left() && self.keying {
+                    self.pick_color_key(ctx, mouse_event.pos);
+                } else if mouse_event.button.is_left() && self.eyedropper {
+                    self.pick_color(ctx, env, mouse_event.pos);
+                } else if let (true, Some(compare)) = (mouse_event.button.is_left(), &self.compare) {
+                    if self.compare_region(compare).contains(mouse_event.pos) {
+                        self.compare_drag(mouse_event.pos);
+                        ctx.request_focus();
+                        ctx.set_active(true);
+                    }
+                } else if let (true, Some(measurements)) = (mouse_event.button.is_left(), &mut self.measure) {
+                    // Measurements are kept in project coordinates so that they follow panning
+                    let pos = mouse_event.pos - self.pan;
+                    measurements.push(Measurement { start: pos, end: pos });
+                    self.measuring = true;
+                    ctx.request_focus();
+                    ctx.set_active(true);
+                    ctx.request_paint();
+                } else if let (true, true, Some(view_id)) = (
+                    mouse_event.button.is_left(),
+                    mouse_event.mods.alt(),
+                    self.rotation_handle_at(mouse_event.pos),
+                ) {
+                    // Alt dragging the handle turns the selected view about its center
+                    ctx.request_focus();
+                    ctx.set_active(true);
+                    self.drag = Some(Drag {
+                        view_id: view_id,
+                        start: mouse_event.pos,
+                        panning: false,
+                        rotating: true,
+                    });
+                } else if mouse_event.button.is_left() {
                     // TODO: Move this focus request elsewhere?
                     ctx.request_focus();
                     ctx.set_active(true);
@@ -153,41 +2039,203 @@ impl Widget<u64> for Surface {
                     }
                     // Locate the topmost layer that gets hit
                     for &id in self.project.layers().iter().rev() {
+                        if !self.shown(id) {
+                            continue;
+                        }
+                        let project_image = &self.project.images()[id];
                         let view_tracker = &mut self.view_trackers[id];
-                        let rect = view_tracker.widget_pod.layout_rect();
-                        if rect.contains(mouse_event.pos) {
+                        if view_tracker.contains(mouse_event.pos) {
                             // Set active view
                             self.active_view = Some(view_tracker.id);
                             view_tracker.data.selected = true;
-                            // Start the drag event
-                            self.drag = Some(Drag {
-                                view_id: view_tracker.id,
-                                start: mouse_event.pos,
-                            });
+                            // Start the drag event, unless the image is locked in place.
+                            // With Alt held the image is panned within the view, which even locked images allow.
+                            let panning = mouse_event.mods.alt();
+                            if panning || !project_image.locked() {
+                                self.drag = Some(Drag {
+                                    view_id: view_tracker.id,
+                                    start: mouse_event.pos,
+                                    panning: panning,
+                                    rotating: false,
+                                });
+                            }
                             break;
                         }
                     }
+                    // Clicking next to the soloed view selects nothing, which ends soloing
+                    if self.solo.is_some() && self.active_view.is_none() {
+                        self.set_solo(None);
+                        ctx.request_update();
+                        ctx.request_paint();
+                    }
+                    self.update_title(ctx);
+                } else if mouse_event.button.is_right() {
+                    let mut menu = Menu::empty()
+                        .entry(
+                            MenuItem::new(tr("menu-arrange-tiles"))
+                                .command(COMMAND_ARRANGE_TILES.to(ctx.widget_id()))
+                                .hotkey(SysMods::Cmd, "t"),
+                        )
+                        .entry(
+                            MenuItem::new(tr("menu-arrange-grid"))
+                                .command(COMMAND_ARRANGE_GRID.to(ctx.widget_id()))
+                                .hotkey(SysMods::CmdShift, "T"),
+                        );
+                    if self.project.has_trash() {
+                        menu = menu.entry(
+                            MenuItem::new(tr("menu-restore-removed"))
+                                .command(COMMAND_RESTORE_REMOVED.to(ctx.widget_id()))
+                                .hotkey(SysMods::CmdShift, "Z"),
+                        );
+                    }
+                    if let Some(view_id) = self.view_at(mouse_event.pos) {
+                        menu = menu.separator().entry(
+                            MenuItem::new(tr("menu-replace-source"))
+                                .command(COMMAND_START_RELINK.with(view_id).to(ctx.widget_id()))
+                                .hotkey(SysMods::Shift, KbKey::F5),
+                        );
+                        let view_tracker = &self.view_trackers[view_id];
+                        let mut time_remap = Menu::new(tr("menu-time-remap"));
+                        for &preset in &TimeRemap::ALL {
+                            time_remap = time_remap.entry(
+                                MenuItem::new(tr(preset.label_key()))
+                                    .selected(view_tracker.data.time_remap == preset)
+                                    .command(COMMAND_SET_TIME_REMAP.with((view_id, preset)).to(ctx.widget_id())),
+                            );
+                        }
+                        menu = menu.entry(time_remap);
+                        if self.project.filmstrip_of(view_id).is_some() {
+                            menu = menu.entry(
+                                MenuItem::new(tr("menu-collapse-filmstrip"))
+                                    .command(COMMAND_COLLAPSE_FILMSTRIP.with(view_id).to(ctx.widget_id())),
+                            );
+                        } else if view_tracker.widget_pod.widget().frame_count() > 1 {
+                            menu = menu.entry(
+                                MenuItem::new(tr("menu-expand-filmstrip"))
+                                    .command(COMMAND_EXPAND_FILMSTRIP.with(view_id).to(ctx.widget_id())),
+                            );
+                        }
+                        if view_tracker.widget_pod.widget().frame_count() > 1 {
+                            menu = menu.entry(
+                                MenuItem::new(tr("menu-export-frames"))
+                                    .command(COMMAND_START_FRAMES_EXPORT.with(view_id).to(ctx.widget_id())),
+                            );
+                        }
+                        if let Some(key) = view_tracker.data.color_key {
+                            let mut color_key = Menu::new(tr_args("menu-color-key", &[("color", key.hex().into())]));
+                            for &tolerance in &ColorKey::TOLERANCES {
+                                let changed = ColorKey { tolerance, ..key };
+                                color_key = color_key.entry(
+                                    MenuItem::new(tr_args("menu-key-tolerance", &[("tolerance", tolerance.into())]))
+                                        .selected(key.tolerance == tolerance)
+                                        .command(
+                                            COMMAND_SET_COLOR_KEY.with((view_id, Some(changed))).to(ctx.widget_id()),
+                                        ),
+                                );
+                            }
+                            color_key = color_key.separator().entry(
+                                MenuItem::new(tr("menu-remove-color-key"))
+                                    .command(COMMAND_SET_COLOR_KEY.with((view_id, None)).to(ctx.widget_id())),
+                            );
+                            menu = menu.entry(color_key);
+                        }
+                        let view_size = view_tracker.widget_pod.widget().content_size();
+                        if view_tracker
+                            .widget_pod
+                            .widget()
+                            .is_cropped(&view_tracker.data, view_size)
+                        {
+                            menu = menu.entry(
+                                MenuItem::new(tr("menu-export-crop"))
+                                    .command(COMMAND_START_CROP_EXPORT.with(view_id).to(ctx.widget_id())),
+                            );
+                        }
+                    }
+                    ctx.show_context_menu::<u64>(menu, mouse_event.window_pos);
                 }
             }
             Event::MouseMove(mouse_event) => {
+                self.mouse_pos = Some(mouse_event.pos);
+                if self.crosshair {
+                    ctx.request_paint();
+                }
+                if self.eyedropper || self.keying {
+                    ctx.set_cursor(&Cursor::Crosshair);
+                }
+                if let (true, Some(compare)) = (ctx.is_active(), &self.compare) {
+                    if compare.dragging {
+                        self.compare_drag(mouse_event.pos);
+                        ctx.request_paint();
+                    }
+                }
+                if let Some(measurements) = &mut self.measure {
+                    ctx.set_cursor(&Cursor::Crosshair);
+                    if let (true, Some(measurement)) = (self.measuring, measurements.last_mut()) {
+                        measurement.end = mouse_event.pos - self.pan;
+                        ctx.request_paint();
+                    }
+                }
                 if let Some(drag) = &mut self.drag {
                     if let Some(view_tracker) = self.view_trackers.iter_mut().find(|vt| vt.id == drag.view_id) {
-                        self.project.set_origin(
-                            view_tracker.id,
-                            view_tracker.adjust_origin(&ctx.size(), mouse_event.pos - drag.start),
-                        );
+                        if drag.rotating {
+                            let center = view_tracker.widget_pod.layout_rect().center();
+                            let mut degrees = rotation_towards(center, mouse_event.pos);
+                            if mouse_event.mods.shift() {
+                                degrees = (degrees / ROTATION_SNAP).round() * ROTATION_SNAP;
+                            }
+                            self.project
+                                .set_origin(view_tracker.id, view_tracker.rotate_to(degrees));
+                            self.project.set_rotation(view_tracker.id, degrees);
+                            ctx.request_update();
+                            ctx.request_layout();
+                        } else if drag.panning {
+                            self.project
+                                .set_offset(view_tracker.id, view_tracker.pan_by(mouse_event.pos - drag.start));
+                            ctx.request_update();
+                        } else {
+                            self.project.set_origin(
+                                view_tracker.id,
+                                view_tracker.adjust_origin(&ctx.size(), self.pan, mouse_event.pos - drag.start),
+                            );
+                            ctx.request_layout();
+                        }
                         drag.start = mouse_event.pos;
-                        ctx.request_layout();
                     }
                 }
             }
             Event::MouseUp(mouse_event) => {
                 if mouse_event.button.is_left() {
+                    if let Some(compare) = &mut self.compare {
+                        if compare.dragging {
+                            compare.dragging = false;
+                            ctx.set_active(false);
+                        }
+                    }
+                    if let (true, Some(measurements)) = (self.measuring, &mut self.measure) {
+                        if let Some(measurement) = measurements.last_mut() {
+                            measurement.end = mouse_event.pos - self.pan;
+                        }
+                        self.measuring = false;
+                        ctx.set_active(false);
+                        ctx.request_paint();
+                    }
+                    if self.drag.as_ref().map_or(false, |drag| drag.rotating) {
+                        // The rotation already follows the mouse as it moves
+                        self.drag = None;
+                        ctx.request_paint();
+                    }
+                    if let Some(drag) = self.drag.as_ref().filter(|drag| drag.panning) {
+                        let view_tracker = &mut self.view_trackers[drag.view_id];
+                        self.project
+                            .set_offset(view_tracker.id, view_tracker.pan_by(mouse_event.pos - drag.start));
+                        self.drag = None;
+                        ctx.request_update();
+                    }
                     if let Some(drag) = &self.drag {
                         let view_tracker = &mut self.view_trackers[drag.view_id];
                         self.project.set_origin(
                             view_tracker.id,
-                            view_tracker.adjust_origin(&ctx.size(), mouse_event.pos - drag.start),
+                            view_tracker.adjust_origin(&ctx.size(), self.pan, mouse_event.pos - drag.start),
                         );
                         self.drag = None;
                         ctx.request_layout();
@@ -195,179 +2243,1224 @@ impl Widget<u64> for Surface {
                 }
             }
             Event::Wheel(mouse_event) => {
+                self.framing = None;
                 if let Some(view_id) = self.active_view {
-                    if mouse_event.wheel_delta.y < 0.0 {
-                        self.view_trackers[view_id].data.zoom.turn_the_knob(1);
+                    let delta = if mouse_event.wheel_delta.y < 0.0 {
+                        1
                     } else if mouse_event.wheel_delta.y > 0.0 {
-                        self.view_trackers[view_id].data.zoom.turn_the_knob(-1);
+                        -1
+                    } else {
+                        0
+                    };
+                    if delta != 0 && self.view_trackers[view_id].data.zoom.turn_the_knob(delta) {
+                        let (min, max) = zoom_limits();
+                        let (key, limit) = if delta > 0 {
+                            ("toast-zoom-max", max)
+                        } else {
+                            ("toast-zoom-min", min)
+                        };
+                        let message = tr_args(key, &[("percent", format!("{:.0}", limit * 100.0).into())]);
+                        ctx.submit_command(
+                            COMMAND_SHOW_TOAST
+                                .with(ToastMessage::info(message))
+                                .to(Target::Window(ctx.window_id())),
+                        );
                     }
                     self.project
                         .set_zoom(self.view_trackers[view_id].id, self.view_trackers[view_id].data.zoom);
                     ctx.request_update();
-                    println!(
-                        "Scale factor now: {}",
-                        self.view_trackers[view_id].data.zoom.scale_factor()
-                    );
+                    debug!("Scale factor now: {}", self.view_trackers[view_id].scale_factor());
                 }
             }
-            Event::KeyUp(key_event) => match &key_event.key {
-                KbKey::Delete => {
+            Event::KeyDown(_) => {
+                ctx.submit_command(COMMAND_REGISTER_WINDOW.with(ctx.window_id()).to(Target::Global));
+            }
+            Event::KeyUp(key_event) if key_event.key == KbKey::Escape && self.measure.is_some() => {
+                self.toggle_measure(ctx);
+            }
+            Event::KeyUp(key_event) if key_event.key == KbKey::Escape && self.keying => {
+                self.toggle_keying(ctx);
+            }
+            Event::KeyUp(key_event) if key_event.key == KbKey::Escape && self.eyedropper => {
+                self.toggle_eyedropper(ctx);
+            }
+            Event::KeyUp(key_event) if key_event.key == KbKey::Escape && self.compare.is_some() => {
+                self.toggle_compare(ctx);
+            }
+            Event::KeyUp(key_event) => match keymap::action(key_event) {
+                Some(Action::RemoveImage) => {
                     if let Some(view_id) = self.active_view {
-                        self.remove(view_id);
+                        self.remove(view_id, true);
                         ctx.children_changed();
+                        self.update_title(ctx);
+                    }
+                }
+                Some(Action::RestoreRemoved) => {
+                    self.restore_removed(ctx);
+                }
+                Some(Action::ReloadImage) => {
+                    if let Some(view_id) = self.active_view {
+                        let view_tracker = &mut self.view_trackers[view_id];
+                        // Embedded images have no file to reload from
+                        if let Some(path) = view_tracker.path.clone() {
+                            info!("Reloading {}", path.display());
+                            if view_tracker.reload(&path) {
+                                let path = path.display().to_string();
+                                ctx.submit_command(
+                                    COMMAND_SHOW_TOAST
+                                        .with(ToastMessage::info(tr_args("toast-reloaded", &[("path", path.into())])))
+                                        .to(Target::Window(ctx.window_id())),
+                                );
+                            }
+                            ctx.request_layout();
+                        }
+                    }
+                }
+                Some(Action::ReplaceSource) => {
+                    if let Some(view_id) = self.active_view {
+                        ctx.submit_command(COMMAND_START_RELINK.with(view_id).to(ctx.widget_id()));
                     }
                 }
-                KbKey::PageUp => {
+                Some(Action::ExportFrame) => {
+                    // Take the frame right away, so that the animation can't move on while the dialog is open
+                    let pixels = self.active_view.and_then(|view_id| {
+                        self.view_trackers[view_id]
+                            .widget_pod
+                            .widget()
+                            .current_pixels()
+                            .cloned()
+                    });
+                    if pixels.is_some() {
+                        self.frame_export = pixels;
+                        self.warn_if_proxy(ctx, self.active_view.unwrap());
+                        ctx.submit_command(Command::new(
+                            commands::SHOW_SAVE_PANEL,
+                            formats::frame_dialog_options()
+                                .title(tr("file-export-frame"))
+                                .accept_command(COMMAND_EXPORT_FRAME),
+                            Target::Auto,
+                        ));
+                    }
+                }
+                Some(Action::RenameImage) => {
+                    if let Some(view_id) = self.active_view {
+                        let name = self.project.images()[view_id].name();
+                        let surface_id = ctx.widget_id();
+                        open_name_dialog(ctx, env, view_id, name, surface_id);
+                    }
+                }
+                Some(Action::RaiseLayer) => {
                     if let Some(view_id) = self.active_view {
                         self.project.shift_layer(view_id, 1);
+                        ctx.request_paint();
                     }
                 }
-                KbKey::PageDown => {
+                Some(Action::LowerLayer) => {
                     if let Some(view_id) = self.active_view {
                         self.project.shift_layer(view_id, -1);
+                        ctx.request_paint();
+                    }
+                }
+                Some(Action::RaiseToTop) => {
+                    if let Some(view_id) = self.active_view {
+                        self.project.set_layer_position(view_id, usize::MAX);
+                        ctx.request_paint();
                     }
                 }
-                KbKey::Home => {
-                    self.tileize(ctx);
+                Some(Action::LowerToBottom) => {
+                    if let Some(view_id) = self.active_view {
+                        self.project.set_layer_position(view_id, 0);
+                        ctx.request_paint();
+                    }
                 }
-                KbKey::Character(ch) => {
-                    if key_event.mods.ctrl() {
-                        match ch.as_str() {
-                            "s" => {
-                                ctx.submit_command(Command::new(
-                                    commands::SHOW_SAVE_PANEL,
-                                    self.project.file_dialog_options(),
-                                    Target::Auto,
-                                ));
-                            }
-                            "o" => {
-                                ctx.submit_command(Command::new(
-                                    commands::SHOW_OPEN_PANEL,
-                                    self.project.file_dialog_options(),
-                                    Target::Auto,
-                                ));
-                            }
-                            _ => (),
+                Some(Action::FitWidth) => {
+                    self.fit_selected(ctx, Axis::Horizontal);
+                }
+                Some(Action::FitHeight) => {
+                    self.fit_selected(ctx, Axis::Vertical);
+                }
+                Some(Action::FrameSelected) => {
+                    self.frame_selected(ctx);
+                }
+                Some(Action::TogglePin) => {
+                    if let Some(view_id) = self.active_view {
+                        let pinned = !self.project.images()[view_id].pinned();
+                        self.project.set_pinned(view_id, pinned);
+                        self.view_trackers[view_id].data.pinned = pinned;
+                        ctx.request_update();
+                    }
+                }
+                Some(Action::LinkViews) => {
+                    self.link_below(ctx);
+                }
+                Some(Action::UnlinkViews) => {
+                    self.unlink(ctx);
+                }
+                Some(Action::PreviousFrame) => {
+                    self.step_frame(ctx, -1);
+                }
+                Some(Action::NextFrame) => {
+                    self.step_frame(ctx, 1);
+                }
+                Some(Action::ToggleDiff) => {
+                    if let Some(view_id) = self.active_view {
+                        let data = &mut self.view_trackers[view_id].data;
+                        data.diff = !data.diff;
+                        if data.diff && !data.paused {
+                            ctx.submit_command(
+                                COMMAND_SHOW_TOAST
+                                    .with(ToastMessage::warning(tr("toast-diff-needs-pause")))
+                                    .to(Target::Window(ctx.window_id())),
+                            );
+                        }
+                        ctx.request_update();
+                    }
+                }
+                Some(Action::TogglePause) => {
+                    // Views that started out paused on their own are resumed along with the rest
+                    let any_paused = self.view_trackers.iter().any(|view_tracker| view_tracker.data.paused);
+                    self.set_paused(!self.view_defaults.paused && !any_paused);
+                    let message = if self.view_defaults.paused {
+                        tr("toast-paused")
+                    } else {
+                        tr("toast-resumed")
+                    };
+                    ctx.submit_command(
+                        COMMAND_SHOW_TOAST
+                            .with(ToastMessage::info(message))
+                            .to(Target::Window(ctx.window_id())),
+                    );
+                    ctx.request_update();
+                }
+                Some(Action::ArrangeTiles) => {
+                    self.tileize(ctx, Arrangement::Rows);
+                }
+                Some(Action::ArrangeGrid) => {
+                    self.tileize(ctx, Arrangement::Grid);
+                }
+                Some(Action::UndoArrangement) => {
+                    self.undo_placement(ctx);
+                }
+                Some(Action::NewProject) => {
+                    ctx.submit_command(COMMAND_NEW_PROJECT.to(ctx.widget_id()));
+                }
+                Some(Action::OpenProject) => {
+                    self.show_open_project(ctx);
+                }
+                Some(Action::SaveProject) => {
+                    ctx.submit_command(Command::new(
+                        commands::SHOW_SAVE_PANEL,
+                        self.project.file_dialog_options(),
+                        Target::Auto,
+                    ));
+                }
+                Some(Action::ImportProject) => {
+                    ctx.submit_command(Command::new(
+                        commands::SHOW_OPEN_PANEL,
+                        self.project
+                            .file_dialog_options()
+                            .title(tr("file-import-project"))
+                            .accept_command(COMMAND_IMPORT_PROJECT),
+                        Target::Auto,
+                    ));
+                }
+                Some(Action::AddImages) => {
+                    self.show_add_images(ctx);
+                }
+                Some(Action::QuickOpen) => match self.project.path().and_then(|path| path.parent()) {
+                    Some(dir) => {
+                        let command = COMMAND_SHOW_QUICK_OPEN.with((dir.to_path_buf(), self.mouse_pos));
+                        ctx.submit_command(command.to(Target::Window(ctx.window_id())));
+                    }
+                    None => {
+                        let message = ToastMessage::info(tr("toast-quick-open-needs-project"));
+                        ctx.submit_command(COMMAND_SHOW_TOAST.with(message).to(Target::Window(ctx.window_id())));
+                    }
+                },
+                Some(Action::CaptureRegion) => {
+                    self.capture_at = self.mouse_pos;
+                    ctx.window().set_window_state(WindowState::Minimized);
+                    self.capture_timer = ctx.request_timer(CAPTURE_DELAY);
+                }
+                Some(Action::ShowMetadata) => {
+                    if let Some(view_id) = self.active_view {
+                        let entries = self.view_trackers[view_id].widget_pod.widget().metadata().to_vec();
+                        let name = self.project.images()[view_id].display_name();
+                        open_metadata_dialog(ctx, env, name, entries);
+                    }
+                }
+                Some(Action::InspectGif) => {
+                    if let Some(view_id) = self.active_view {
+                        let frames = self.view_trackers[view_id].widget_pod.widget().frame_structures();
+                        if frames.is_empty() {
+                            let message = ToastMessage::info(tr("toast-inspector-needs-gif"));
+                            ctx.submit_command(COMMAND_SHOW_TOAST.with(message).to(Target::Window(ctx.window_id())));
+                        } else {
+                            let name = self.project.images()[view_id].display_name();
+                            let surface_id = ctx.widget_id();
+                            open_gif_inspector(ctx, env, name, frames, view_id, surface_id);
                         }
                     }
                 }
-                _ => (),
+                Some(Action::ShowSummary) => {
+                    let surface_id = ctx.widget_id();
+                    open_summary_dialog(ctx, env, self.summary(), surface_id);
+                }
+                Some(Action::CanvasSettings) => {
+                    let surface_id = ctx.widget_id();
+                    open_canvas_dialog(ctx, env, self.project.canvas(), surface_id);
+                }
+                Some(Action::ImageDefaults) => {
+                    let surface_id = ctx.widget_id();
+                    let has_selection = self.active_view.is_some();
+                    open_image_defaults_dialog(ctx, env, self.project.image_defaults(), has_selection, surface_id);
+                }
+                Some(Action::ExportLayout) => {
+                    ctx.submit_command(Command::new(
+                        commands::SHOW_SAVE_PANEL,
+                        layout::file_dialog_options()
+                            .title(tr("file-export-layout"))
+                            .accept_command(COMMAND_EXPORT_LAYOUT),
+                        Target::Auto,
+                    ));
+                }
+                Some(Action::ApplyLayout) => {
+                    ctx.submit_command(Command::new(
+                        commands::SHOW_OPEN_PANEL,
+                        layout::file_dialog_options()
+                            .title(tr("file-apply-layout"))
+                            .accept_command(COMMAND_APPLY_LAYOUT),
+                        Target::Auto,
+                    ));
+                }
+                Some(Action::ToggleHelp) => {
+                    ctx.submit_command(COMMAND_TOGGLE_HELP.to(Target::Window(ctx.window_id())));
+                }
+                Some(Action::ToggleConsole) => {
+                    ctx.submit_command(COMMAND_TOGGLE_CONSOLE.to(Target::Window(ctx.window_id())));
+                }
+                Some(Action::ToggleMeasure) => {
+                    self.toggle_measure(ctx);
+                }
+                Some(Action::Eyedropper) => {
+                    self.toggle_eyedropper(ctx);
+                }
+                Some(Action::PickColorKey) => {
+                    self.toggle_keying(ctx);
+                }
+                Some(Action::ToggleSeries) => {
+                    self.toggle_series(ctx);
+                }
+                Some(Action::ToggleCompare) => {
+                    self.toggle_compare(ctx);
+                }
+                Some(Action::ToggleCrosshair) => {
+                    self.crosshair = !self.crosshair;
+                    ctx.request_paint();
+                }
+                Some(Action::ToggleSolo) => {
+                    self.toggle_solo(ctx);
+                }
+                Some(Action::TogglePhysical) => {
+                    let physical = !self.project.physical_scale();
+                    self.project.set_physical_scale(physical);
+                    self.view_defaults.physical = physical;
+                    for view_tracker in &mut self.view_trackers {
+                        view_tracker.data.physical = physical;
+                    }
+                    let message = if physical {
+                        tr("toast-physical-on")
+                    } else {
+                        tr("toast-physical-off")
+                    };
+                    ctx.submit_command(
+                        COMMAND_SHOW_TOAST
+                            .with(ToastMessage::info(message))
+                            .to(Target::Window(ctx.window_id())),
+                    );
+                    ctx.request_update();
+                }
+                Some(Action::ToggleTiming) => {
+                    let timing = !self.view_defaults.timing;
+                    self.view_defaults.timing = timing;
+                    for view_tracker in &mut self.view_trackers {
+                        view_tracker.data.timing = timing;
+                    }
+                    let message = if timing {
+                        tr("toast-timing-on")
+                    } else {
+                        tr("toast-timing-off")
+                    };
+                    ctx.submit_command(
+                        COMMAND_SHOW_TOAST
+                            .with(ToastMessage::info(message))
+                            .to(Target::Window(ctx.window_id())),
+                    );
+                    ctx.request_update();
+                }
+                Some(Action::ToggleLabels) => {
+                    self.show_labels = !self.show_labels;
+                    ctx.request_paint();
+                }
+                None => (),
             },
+            Event::WindowConnected => {
+                self.background_timer = ctx.request_timer(BACKGROUND_CHECK_INTERVAL);
+                self.source_timer = ctx.request_timer(SOURCE_CHECK_INTERVAL);
+            }
+            Event::Timer(token) if *token == self.capture_timer => {
+                self.capture_timer = TimerToken::INVALID;
+                match capture::screen() {
+                    Ok(screenshot) => {
+                        let surface_id = ctx.widget_id();
+                        open_capture_overlay(ctx, screenshot, surface_id);
+                    }
+                    Err(error) => {
+                        self.restore_window(ctx);
+                        let message = ToastMessage::error(tr_args("toast-capture-failed", &[("error", error.into())]));
+                        ctx.submit_command(COMMAND_SHOW_TOAST.with(message).to(Target::Window(ctx.window_id())));
+                    }
+                }
+            }
+            Event::Timer(token) if *token == self.series_timer => {
+                let snapshot = self.snapshot(env);
+                if let Some(series) = &mut self.series {
+                    series.capture(snapshot);
+                    self.series_timer =
+                        ctx.request_timer(Duration::from_secs(settings::current().capture_interval.max(1)));
+                    ctx.request_paint();
+                }
+            }
+            Event::Timer(token) if *token == self.background_timer => {
+                self.track_geometry(ctx);
+                self.check_background(ctx);
+                self.background_timer = ctx.request_timer(BACKGROUND_CHECK_INTERVAL);
+            }
+            Event::Timer(token) if *token == self.source_timer => {
+                self.check_sources(ctx);
+                self.source_timer = ctx.request_timer(SOURCE_CHECK_INTERVAL);
+            }
+            Event::WindowDisconnected => {
+                self.sync_playback();
+                if let Some(geometry) = self.window_geometry {
+                    let project = if self.is_scratch() { None } else { self.project.path() };
+                    settings::update(|settings| settings.remember_window(geometry, project));
+                }
+                self.remember_session();
+            }
             Event::Command(command) => {
                 if command.is(commands::SAVE_FILE_AS) {
                     let info = command.get_unchecked(commands::SAVE_FILE_AS);
-                    self.project.save(info.path());
+                    self.save_project(ctx, info.path());
                 } else if command.is(commands::OPEN_FILE) {
                     let info = command.get_unchecked(commands::OPEN_FILE);
-                    self.set_project(Project::open(PathBuf::from(info.path())));
+                    if self.open_project(ctx, env, Some(PathBuf::from(info.path()))) {
+                        // Need to inform of children changes
+                        ctx.children_changed();
+                        hacky_children_added = true;
+                    }
+                } else if command.is(COMMAND_RAISE_WINDOW) {
+                    ctx.window().bring_to_front_and_focus();
+                } else if command.is(COMMAND_OPEN_PROJECT) {
+                    let path = command.get_unchecked(COMMAND_OPEN_PROJECT);
+                    if self.open_project(ctx, env, Some(path.clone())) {
+                        // Need to inform of children changes
+                        ctx.children_changed();
+                        hacky_children_added = true;
+                    }
+                } else if command.is(COMMAND_REPLACE_PROJECT) {
+                    let (path, save_first) = command.get_unchecked(COMMAND_REPLACE_PROJECT);
+                    let saved = match self.project.path().map(PathBuf::from) {
+                        Some(current_path) if *save_first => self.save_project(ctx, &current_path),
+                        _ => true,
+                    };
+                    // Keep the unsaved changes around rather than throwing them away if saving failed
+                    let project = if saved {
+                        self.read_project(ctx, path.clone())
+                    } else {
+                        None
+                    };
+                    if let Some(project) = project {
+                        self.set_project(project);
+                        self.update_title(ctx);
+                        // Need to inform of children changes
+                        ctx.children_changed();
+                        hacky_children_added = true;
+                    }
+                } else if command.is(COMMAND_ARRANGE_TILES) {
+                    self.tileize(ctx, Arrangement::Rows);
+                } else if command.is(COMMAND_ARRANGE_GRID) {
+                    self.tileize(ctx, Arrangement::Grid);
+                } else if command.is(COMMAND_RESTORE_REMOVED) {
+                    self.restore_removed(ctx);
+                } else if command.is(COMMAND_NEW_PROJECT) {
+                    if self.open_project(ctx, env, None) {
+                        ctx.children_changed();
+                        hacky_children_added = true;
+                    }
+                } else if command.is(COMMAND_SHOW_OPEN_PROJECT) {
+                    self.show_open_project(ctx);
+                } else if command.is(COMMAND_SHOW_ADD_IMAGES) {
+                    self.show_add_images(ctx);
+                } else if command.is(COMMAND_ADD_IMAGE_FILES) {
+                    let files = command.get_unchecked(COMMAND_ADD_IMAGE_FILES);
+                    for info in files {
+                        self.add(ctx, PathBuf::from(info.path()), None);
+                    }
+                    self.update_title(ctx);
+                    ctx.request_update();
                     // Need to inform of children changes
                     ctx.children_changed();
                     hacky_children_added = true;
-                } else if command.is(COMMAND_ADD_IMAGE) {
-                    let filename = command.get_unchecked(COMMAND_ADD_IMAGE);
-                    self.add(filename.into());
+                } else if command.is(COMMAND_IMPORT_PROJECT) {
+                    let info = command.get_unchecked(COMMAND_IMPORT_PROJECT);
+                    if let Some(project) = self.read_project(ctx, Some(PathBuf::from(info.path()))) {
+                        self.import(project);
+                    }
+                    self.update_title(ctx);
+                    ctx.request_update();
                     // Need to inform of children changes
                     ctx.children_changed();
                     hacky_children_added = true;
+                } else if command.is(COMMAND_ADD_IMAGE) || command.is(COMMAND_ADD_IMAGE_AT) {
+                    let (paths, at) = match command.get(COMMAND_ADD_IMAGE) {
+                        Some(paths) => (paths, None),
+                        None => {
+                            let (paths, at) = command.get_unchecked(COMMAND_ADD_IMAGE_AT);
+                            (paths, *at)
+                        }
+                    };
+                    for path in paths {
+                        if path.is_dir() {
+                            self.add_directory(ctx, env, path);
+                        } else if project::is_project_path(path) {
+                            // Projects can arrive here from older instances or other producers
+                            self.open_project(ctx, env, Some(path.clone()));
+                        } else {
+                            self.add(ctx, path.clone(), at);
+                        }
+                    }
+                    self.update_title(ctx);
+                    ctx.request_update();
+                    // Need to inform of children changes, once for the whole batch
+                    ctx.children_changed();
+                    hacky_children_added = true;
+                } else if command.is(COMMAND_ADD_DIRECTORY) {
+                    let (path, images, skipped) = command.get_unchecked(COMMAND_ADD_DIRECTORY);
+                    self.add_images_from(ctx, path, images.clone(), *skipped);
+                    self.update_title(ctx);
+                    ctx.request_update();
+                    ctx.children_changed();
+                    hacky_children_added = true;
+                } else if command.is(COMMAND_LOAD_LARGE) {
+                    let (path, at, proxy) = command.get_unchecked(COMMAND_LOAD_LARGE);
+                    if let Some(proxy) = *proxy {
+                        self.add_at_proxy(ctx, path.clone(), *at, proxy);
+                        self.update_title(ctx);
+                        ctx.request_update();
+                        ctx.children_changed();
+                        hacky_children_added = true;
+                    }
+                } else if command.is(COMMAND_FILE_CHANGED) {
+                    let path = command.get_unchecked(COMMAND_FILE_CHANGED);
+                    for view_tracker in self.view_trackers.iter_mut() {
+                        let changed = view_tracker
+                            .path
+                            .clone()
+                            .filter(|view_path| canonical(view_path) == *path);
+                        if let Some(view_path) = changed {
+                            info!("Reloading {}", view_path.display());
+                            view_tracker.reload(&view_path);
+                            ctx.request_layout();
+                        }
+                    }
+                } else if command.is(COMMAND_SOURCES_CHECKED) {
+                    self.checking_sources = false;
+                    let findings = command.get_unchecked(COMMAND_SOURCES_CHECKED);
+                    if self.apply_source_findings(findings) {
+                        ctx.request_paint();
+                    }
+                } else if command.is(COMMAND_SHOW_FRAME) {
+                    let (view_id, index) = *command.get_unchecked(COMMAND_SHOW_FRAME);
+                    if view_id < self.view_trackers.len() {
+                        for id in self.in_step_with(view_id) {
+                            self.view_trackers[id].widget_pod.widget_mut().go_to(index);
+                        }
+                        self.set_paused(true);
+                        ctx.request_update();
+                        ctx.request_paint();
+                    }
+                } else if command.is(COMMAND_START_RELINK) {
+                    self.relink_view = Some(*command.get_unchecked(COMMAND_START_RELINK));
+                    ctx.submit_command(Command::new(
+                        commands::SHOW_OPEN_PANEL,
+                        self.project
+                            .image_dialog_options()
+                            .title(tr("file-relink-image"))
+                            .accept_command(COMMAND_RELINK_IMAGE),
+                        Target::Auto,
+                    ));
+                } else if command.is(COMMAND_RELINK_IMAGE) {
+                    let info = command.get_unchecked(COMMAND_RELINK_IMAGE);
+                    if let Some(view_id) = self.relink_view.take() {
+                        // The origin stays put even if the new image has a different size
+                        self.relink(view_id, PathBuf::from(info.path()));
+                        ctx.request_layout();
+                        self.update_title(ctx);
+                    }
+                } else if command.is(COMMAND_REGION_CAPTURED) {
+                    let image = command.get_unchecked(COMMAND_REGION_CAPTURED);
+                    self.restore_window(ctx);
+                    let at = self.capture_at.take();
+                    if let Some(path) = self.save_capture(ctx, image) {
+                        self.add(ctx, path, at);
+                        self.update_title(ctx);
+                        ctx.request_update();
+                        // Need to inform of children changes
+                        ctx.children_changed();
+                        hacky_children_added = true;
+                    }
+                } else if command.is(COMMAND_CAPTURE_CANCELLED) {
+                    self.capture_at = None;
+                    self.restore_window(ctx);
+                } else if command.is(COMMAND_EXPORT_FRAME) {
+                    let path = command.get_unchecked(COMMAND_EXPORT_FRAME).path().to_path_buf();
+                    if let Some(pixels) = self.frame_export.take() {
+                        // Encoding a large frame takes a while, so keep it away from the UI thread
+                        thread::spawn(move || {
+                            let path_arg = path.display().to_string();
+                            let message = match formats::save_frame(&path, &pixels) {
+                                Ok(()) => {
+                                    ToastMessage::info(tr_args("toast-frame-exported", &[("path", path_arg.into())]))
+                                }
+                                Err(error) => ToastMessage::error(tr_args(
+                                    "toast-frame-export-failed",
+                                    &[("path", path_arg.into()), ("error", error.into())],
+                                )),
+                            };
+                            post(message);
+                        });
+                    }
+                } else if command.is(COMMAND_EXPAND_FILMSTRIP) {
+                    let view_id = *command.get_unchecked(COMMAND_EXPAND_FILMSTRIP);
+                    if view_id < self.view_trackers.len() && self.expand_filmstrip(ctx, view_id) {
+                        ctx.request_update();
+                        // Need to inform of children changes
+                        ctx.children_changed();
+                        hacky_children_added = true;
+                    }
+                } else if command.is(COMMAND_COLLAPSE_FILMSTRIP) {
+                    let view_id = *command.get_unchecked(COMMAND_COLLAPSE_FILMSTRIP);
+                    self.collapse_filmstrip(view_id);
+                    ctx.children_changed();
+                    self.update_title(ctx);
+                } else if command.is(COMMAND_START_SERIES) {
+                    let dir = command.get_unchecked(COMMAND_START_SERIES).path().to_path_buf();
+                    let interval = settings::current().capture_interval.max(1);
+                    let message = tr_args(
+                        "toast-series-started",
+                        &[("seconds", interval.into()), ("path", dir.display().to_string().into())],
+                    );
+                    let mut series = Series::start(dir);
+                    series.capture(self.snapshot(env));
+                    self.series = Some(series);
+                    self.series_timer = ctx.request_timer(Duration::from_secs(interval));
+                    ctx.submit_command(
+                        COMMAND_SHOW_TOAST
+                            .with(ToastMessage::info(message))
+                            .to(Target::Window(ctx.window_id())),
+                    );
+                    ctx.request_paint();
+                } else if command.is(COMMAND_SET_COLOR_KEY) {
+                    let (view_id, color_key) = *command.get_unchecked(COMMAND_SET_COLOR_KEY);
+                    if let Some(view_tracker) = self.view_trackers.get_mut(view_id) {
+                        view_tracker.data.color_key = color_key;
+                        self.project.set_color_key(view_id, color_key);
+                        ctx.request_update();
+                    }
+                } else if command.is(COMMAND_SET_TIME_REMAP) {
+                    let (view_id, time_remap) = *command.get_unchecked(COMMAND_SET_TIME_REMAP);
+                    if let Some(view_tracker) = self.view_trackers.get_mut(view_id) {
+                        view_tracker.data.time_remap = time_remap;
+                        self.project.set_time_remap(view_id, time_remap);
+                        ctx.request_update();
+                    }
+                } else if command.is(COMMAND_START_CROP_EXPORT) {
+                    let view_id = *command.get_unchecked(COMMAND_START_CROP_EXPORT);
+                    // Take the crop right away, so that the animation can't move on while the dialog is open
+                    if let Some(view_tracker) = self.view_trackers.get(view_id) {
+                        let view_size = view_tracker.widget_pod.widget().content_size();
+                        self.frame_export = view_tracker
+                            .widget_pod
+                            .widget()
+                            .cropped_pixels(&view_tracker.data, view_size);
+                    }
+                    if self.frame_export.is_some() {
+                        self.warn_if_proxy(ctx, view_id);
+                    }
+                    if let Some(pixels) = &self.frame_export {
+                        let message = tr_args(
+                            "crop-export",
+                            &[("width", pixels.width().into()), ("height", pixels.height().into())],
+                        );
+                        let surface_id = ctx.widget_id();
+                        dialog::confirm(
+                            ctx,
+                            env,
+                            message,
+                            vec![
+                                (tr("crop-save"), Some(COMMAND_SAVE_CROP.to(surface_id))),
+                                (tr("crop-copy"), Some(COMMAND_COPY_CROP.to(surface_id))),
+                                (tr("dialog-cancel"), None),
+                            ],
+                        );
+                    }
+                } else if command.is(COMMAND_START_FRAMES_EXPORT) {
+                    let view_id = *command.get_unchecked(COMMAND_START_FRAMES_EXPORT);
+                    if let Some(project_image) = self.project.image(view_id) {
+                        let surface_id = ctx.widget_id();
+                        let name = project_image.display_name();
+                        open_frames_dialog(ctx, env, view_id, name, settings::current().frame_template, surface_id);
+                        self.warn_if_proxy(ctx, view_id);
+                    }
+                } else if command.is(COMMAND_PICK_FRAMES_DIRECTORY) {
+                    let (view_id, text) = command.get_unchecked(COMMAND_PICK_FRAMES_DIRECTORY);
+                    if let Ok(template) = Template::parse(text) {
+                        settings::update(|settings| settings.frame_template = text.clone());
+                        self.frames_export = Some((*view_id, template));
+                        ctx.submit_command(Command::new(
+                            commands::SHOW_OPEN_PANEL,
+                            FileDialogOptions::new()
+                                .select_directories()
+                                .title(tr("file-frames-directory"))
+                                .accept_command(COMMAND_EXPORT_FRAMES),
+                            Target::Auto,
+                        ));
+                    }
+                } else if command.is(COMMAND_EXPORT_FRAMES) {
+                    let dir = command.get_unchecked(COMMAND_EXPORT_FRAMES).path().to_path_buf();
+                    if let Some((view_id, template)) = self.frames_export.take() {
+                        // The dialogs are modal, so the view can't have been removed in the meantime
+                        if let Some(view_tracker) = self.view_trackers.get(view_id) {
+                            let frames = view_tracker.widget_pod.widget().shown_frames();
+                            let name = self.project.images()[view_id].display_name();
+                            sequence::export(dir, name, template, frames);
+                        }
+                    }
+                } else if command.is(COMMAND_SAVE_CROP) {
+                    ctx.submit_command(Command::new(
+                        commands::SHOW_SAVE_PANEL,
+                        formats::frame_dialog_options()
+                            .title(tr("file-export-crop"))
+                            .accept_command(COMMAND_EXPORT_FRAME),
+                        Target::Auto,
+                    ));
+                } else if command.is(COMMAND_COPY_CROP) {
+                    if let Some(pixels) = self.frame_export.take() {
+                        let message = match formats::png::encode(&pixels) {
+                            Ok(data) => {
+                                // Image editors and browsers on Windows take PNG data by this registered format name
+                                Application::global()
+                                    .clipboard()
+                                    .put_formats(&[ClipboardFormat::new("PNG", data)]);
+                                ToastMessage::info(tr("toast-crop-copied"))
+                            }
+                            Err(error) => {
+                                ToastMessage::error(tr_args("toast-crop-copy-failed", &[("error", error.into())]))
+                            }
+                        };
+                        ctx.submit_command(COMMAND_SHOW_TOAST.with(message).to(Target::Window(ctx.window_id())));
+                    }
+                } else if command.is(COMMAND_EXPORT_LAYOUT) {
+                    let info = command.get_unchecked(COMMAND_EXPORT_LAYOUT);
+                    if let Err(error) = layout::export(&self.project, info.path()) {
+                        error!("{}", error);
+                    }
+                } else if command.is(COMMAND_APPLY_LAYOUT) {
+                    let info = command.get_unchecked(COMMAND_APPLY_LAYOUT);
+                    let message = match layout::apply(&mut self.project, info.path()) {
+                        Ok(problems) if problems.is_empty() => None,
+                        Ok(problems) => {
+                            for problem in &problems {
+                                warn!("Layout entry skipped: {}", problem);
+                            }
+                            // The rest are in the log, a toast only has room for a few
+                            let mut reasons = problems[..problems.len().min(LAYOUT_PROBLEMS_SHOWN)].join("; ");
+                            if problems.len() > LAYOUT_PROBLEMS_SHOWN {
+                                reasons.push_str("; …");
+                            }
+                            Some(ToastMessage::warning(tr_args(
+                                "toast-layout-skipped",
+                                &[("count", problems.len().into()), ("reasons", reasons.into())],
+                            )))
+                        }
+                        Err(error) => {
+                            error!("{}", error);
+                            Some(ToastMessage::error(tr_args(
+                                "toast-layout-failed",
+                                &[("error", error.into())],
+                            )))
+                        }
+                    };
+                    if let Some(message) = message {
+                        ctx.submit_command(COMMAND_SHOW_TOAST.with(message).to(Target::Window(ctx.window_id())));
+                    }
+                    self.sync_placement();
+                    ctx.request_update();
+                    ctx.request_layout();
+                } else if command.is(COMMAND_SET_NAME) {
+                    let (image_id, name) = command.get_unchecked(COMMAND_SET_NAME);
+                    self.project.set_name(*image_id, name.clone());
+                    self.update_title(ctx);
+                } else if command.is(COMMAND_SELECT_IMAGE) {
+                    let image_id = *command.get_unchecked(COMMAND_SELECT_IMAGE);
+                    if image_id < self.view_trackers.len() {
+                        self.select(Some(image_id));
+                        self.update_title(ctx);
+                        ctx.request_update();
+                    }
+                } else if command.is(COMMAND_FOCUS_IMAGE) {
+                    let image_id = *command.get_unchecked(COMMAND_FOCUS_IMAGE);
+                    self.focus(image_id);
+                    self.update_title(ctx);
+                    ctx.request_update();
+                    ctx.request_layout();
+                } else if command.is(COMMAND_TOGGLE_VISIBILITY) {
+                    let image_id = *command.get_unchecked(COMMAND_TOGGLE_VISIBILITY);
+                    if let Some(image) = self.project.images().get(image_id) {
+                        let visible = !image.visible();
+                        self.project.set_visible(image_id, visible);
+                        ctx.request_paint();
+                    }
+                } else if command.is(COMMAND_TOGGLE_LOCK) {
+                    let image_id = *command.get_unchecked(COMMAND_TOGGLE_LOCK);
+                    if let Some(image) = self.project.images().get(image_id) {
+                        let locked = !image.locked();
+                        self.project.set_locked(image_id, locked);
+                    }
+                } else if command.is(COMMAND_SHIFT_LAYER) {
+                    let (image_id, delta) = *command.get_unchecked(COMMAND_SHIFT_LAYER);
+                    self.project.shift_layer(image_id, delta);
+                    ctx.request_paint();
+                } else if command.is(COMMAND_SET_CANVAS) {
+                    let canvas = *command.get_unchecked(COMMAND_SET_CANVAS);
+                    self.project.set_canvas(canvas);
+                    ctx.request_paint();
+                } else if command.is(COMMAND_SET_IMAGE_DEFAULTS) {
+                    let (image_defaults, apply) = *command.get_unchecked(COMMAND_SET_IMAGE_DEFAULTS);
+                    self.project.set_image_defaults(image_defaults);
+                    if let (true, Some(view_id)) = (apply, self.active_view) {
+                        self.apply_image_defaults(view_id);
+                        ctx.request_update();
+                        ctx.request_layout();
+                    }
                 }
             }
             _ => (),
         }
 
         if !hacky_children_added {
-            // Pass the event to all the views
-            for view_tracker in self.view_trackers.iter_mut() {
-                view_tracker.widget_pod.event(ctx, event, &mut view_tracker.data, env);
+            // The views only get the animation frames that are due a tick, carrying all the time since the last one
+            let tick;
+            let view_event = match event {
+                Event::AnimFrame(interval) => match self.ticker.advance(*interval) {
+                    Some(elapsed) => {
+                        record_animation_tick();
+                        tick = Event::AnimFrame(elapsed);
+                        Some(&tick)
+                    }
+                    None => None,
+                },
+                _ => Some(event),
+            };
+            match view_event {
+                // Pass the event to all the views
+                Some(view_event) => {
+                    for view_tracker in self.view_trackers.iter_mut() {
+                        view_tracker
+                            .widget_pod
+                            .event(ctx, view_event, &mut view_tracker.data, env);
+                    }
+                }
+                // Keep the animation loop going for the views that would've wanted this frame
+                None if self.view_trackers.iter().any(|vt| !vt.data.suspended) => ctx.request_anim_frame(),
+                None => (),
+            }
+            // The welcome panel only takes part while it is visible, so it can't get in the way otherwise
+            if self.project.images().is_empty() {
+                self.welcome.event(ctx, event, data, env);
             }
         }
+
+        if !self.large_images.is_empty() {
+            self.ask_about_large_images(ctx, env);
+        }
+
+        // Animation frames don't change the layers, so skip the check for those.
+        // Thumbnails arrive in the background, but the regular timers come by often enough to pick them up.
+        let window = Target::Window(ctx.window_id());
+        if !matches!(event, Event::AnimFrame(_)) {
+            self.publish_layers(window, |command| ctx.submit_command(command));
+            self.publish_thumbnails(window, |command| ctx.submit_command(command));
+        }
     }
 
-    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, _data: &u64, env: &Env) {
+    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, data: &u64, env: &Env) {
         // Pass the lifecycle to all the views
         for view_tracker in self.view_trackers.iter_mut() {
             view_tracker.widget_pod.lifecycle(ctx, event, &view_tracker.data, env);
         }
+        self.welcome.lifecycle(ctx, event, data, env);
         match event {
-            LifeCycle::HotChanged(hot) => {
-                //println!("Hot changed: {}", hot);
+            LifeCycle::WidgetAdded => {
+                // Receive the images sent by other instances
+                ctx.submit_command(COMMAND_REGISTER_WINDOW.with(ctx.window_id()).to(Target::Global));
+                // Start watching the source files for changes, leaving the dormant views for when they start
+                let mut watcher = FileWatcher::new(ctx.get_external_handle(), Target::Widget(ctx.widget_id()));
+                for path in self
+                    .view_trackers
+                    .iter()
+                    .filter(|view_tracker| !view_tracker.is_dormant())
+                    .filter_map(|view_tracker| view_tracker.path.as_ref())
+                {
+                    watcher.watch(path);
+                }
+                self.watcher = Some(watcher);
+                // Fill the layers panel and the thumbnail strip
+                let window = Target::Window(ctx.window_id());
+                self.publish_layers(window, |command| ctx.submit_command(command));
+                self.publish_thumbnails(window, |command| ctx.submit_command(command));
+            }
+            LifeCycle::HotChanged(false) => {
+                // The mouse left the surface
+                self.mouse_pos = None;
+                if self.crosshair {
+                    ctx.request_paint();
+                }
             }
             _ => (),
         }
     }
 
-    fn update(&mut self, ctx: &mut UpdateCtx, _old_data: &u64, _data: &u64, env: &Env) {
+    fn update(&mut self, ctx: &mut UpdateCtx, _old_data: &u64, data: &u64, env: &Env) {
         // Pass the update to all the views
         for view_tracker in self.view_trackers.iter_mut() {
             view_tracker.widget_pod.update(ctx, &view_tracker.data, env);
         }
+        self.welcome.update(ctx, data, env);
     }
 
-    fn layout(&mut self, ctx: &mut LayoutCtx, bc: &BoxConstraints, _data: &u64, env: &Env) -> Size {
+    fn layout(&mut self, ctx: &mut LayoutCtx, bc: &BoxConstraints, data: &u64, env: &Env) -> Size {
         bc.debug_check("Surface");
 
         // Determine the layout for all the views
+        let size = bc.max();
+        // Views are kept within the size of the surface, showing only part of larger images,
+        // and everything gets clipped at the surface level
+        let view_bc = BoxConstraints::new(Size::ZERO, size);
         for view_tracker in self.view_trackers.iter_mut() {
-            // We give unbounded constraints as we'll clip everything at the surface level
-            view_tracker
-                .widget_pod
-                .layout(ctx, &BoxConstraints::UNBOUNDED, &view_tracker.data, env);
-            view_tracker.widget_pod.set_origin(ctx, view_tracker.origin);
+            view_tracker.widget_pod.layout(ctx, &view_bc, &view_tracker.data, env);
+            view_tracker.widget_pod.set_origin(ctx, view_tracker.origin + self.pan);
         }
 
+        // Center the welcome panel
+        let welcome_size = self.welcome.layout(ctx, &bc.loosen(), data, env);
+        let welcome_origin = (size.to_rect().center() - welcome_size.to_vec2() / 2.0).round();
+        self.welcome.set_origin(ctx, welcome_origin);
+
         // The surface always uses the whole area provided to it
-        bc.max()
+        self.size = size;
+        // Arranging needs the image sizes, which are only known once the views have started
+        let starting = self.view_trackers.iter().any(ViewTracker::is_dormant);
+        if self.fit_pending && !size.is_empty() && !starting {
+            self.fit_pending = false;
+            ctx.submit_command(COMMAND_ARRANGE_TILES.to(ctx.widget_id()));
+        }
+        size
     }
 
-    fn paint(&mut self, ctx: &mut PaintCtx, _data: &u64, env: &Env) {
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &u64, env: &Env) {
+        let start = Instant::now();
+        if let Some(startup) = self.startup.take() {
+            let dormant = self.view_trackers.iter().filter(|vt| vt.is_dormant()).count();
+            info!(
+                "First paint {} ms after the surface was created, with {} of {} views still to start.",
+                startup.elapsed().as_millis(),
+                dormant,
+                self.view_trackers.len()
+            );
+        }
         // Store our size
         self.size = ctx.size();
         // Clip the overflow
         ctx.render_ctx.clip(Rect::from_origin_size(Point::ZERO, self.size));
 
-        // Paint all the views in the configured layer order
+        ctx.render_ctx
+            .fill(self.size.to_rect(), &env.get(theme::SURFACE_BACKGROUND));
+
+        // Paint the canvas background below all the views
+        if let Some(canvas) = self.project.canvas() {
+            ctx.render_ctx
+                .fill(canvas.rect() + self.pan, &canvas.background_color());
+        }
+
+        // When comparing, the lower view is only shown left of the divider and the upper one right of it
+        let compare_clips = self.compare.as_ref().map(|compare| {
+            let region = self.compare_region(compare);
+            let x = self.compare_divider(compare);
+            (
+                (compare.lower, Rect::new(region.x0, region.y0, x, region.y1)),
+                (compare.upper, Rect::new(x, region.y0, region.x1, region.y1)),
+            )
+        });
+
+        // Paint all the visible views in the configured layer order
         for &id in self.project.layers().iter() {
+            if !self.shown(id) {
+                continue;
+            }
             let view_tracker = &mut self.view_trackers[id];
-            view_tracker.widget_pod.paint(ctx, &view_tracker.data, env);
+            let clip = match compare_clips {
+                Some(((lower, clip), _)) if lower == id => Some(clip),
+                Some((_, (upper, clip))) if upper == id => Some(clip),
+                _ => None,
+            };
+            match clip {
+                Some(clip) => ctx.with_save(|ctx| {
+                    ctx.render_ctx.clip(clip);
+                    view_tracker.widget_pod.paint(ctx, &view_tracker.data, env);
+                }),
+                None => view_tracker.widget_pod.paint(ctx, &view_tracker.data, env),
+            }
+        }
+
+        self.sync_clocks();
+
+        if let Some(compare) = &self.compare {
+            self.paint_compare_divider(ctx, env, compare);
+        }
+
+        if let Some(view_id) = self.active_view {
+            if self.shown(view_id) && !self.project.images()[view_id].locked() {
+                self.paint_rotation_handle(ctx, env, view_id);
+            }
+        }
+
+        if self.solo.is_some() {
+            self.paint_solo_badge(ctx, env);
         }
+
+        if let Some(series) = &self.series {
+            self.paint_series_badge(ctx, env, series.count());
+        }
+
+        // Dim everything outside of the canvas and frame it
+        if let Some(canvas) = self.project.canvas() {
+            let surface = self.size.to_rect();
+            let frame = canvas.rect() + self.pan;
+            let dim = env.get(theme::CANVAS_DIM);
+            let around = [
+                Rect::new(surface.x0, surface.y0, surface.x1, frame.y0),
+                Rect::new(surface.x0, frame.y1, surface.x1, surface.y1),
+                Rect::new(surface.x0, frame.y0, frame.x0, frame.y1),
+                Rect::new(frame.x1, frame.y0, surface.x1, frame.y1),
+            ];
+            for rect in around.iter() {
+                let rect = rect.intersect(surface);
+                if rect.area() > 0.0 {
+                    ctx.render_ctx.fill(rect, &dim);
+                }
+            }
+            ctx.render_ctx.stroke(frame.inset(0.5), &env.get(theme::GUIDES), 1.0);
+        }
+
+        if self.show_labels {
+            self.paint_labels(ctx, env);
+        }
+
+        if let Some(measurements) = &self.measure {
+            for measurement in measurements.iter() {
+                self.paint_measurement(ctx, env, measurement);
+            }
+        }
+
+        if let (true, Some(pos)) = (self.crosshair, self.mouse_pos) {
+            self.paint_crosshair(ctx, env, pos);
+        }
+
+        if self.project.images().is_empty() {
+            self.welcome.paint(ctx, data, env);
+        }
+
+        // This only covers recording the draw calls, the renderer may do more work later
+        record_paint(start.elapsed());
     }
 }
 
 struct ViewTracker {
     id: usize,
+    path: Option<PathBuf>, // Resolved path of the source image, None when it's embedded in the project
     widget_pod: WidgetPod<ViewData, View>,
-    origin: Point, // View's origin in relation to Surface
+    origin: Point, // View's origin in relation to Surface, before panning
     data: ViewData,
 }
 
 impl ViewTracker {
-    fn new(project_path: Option<&Path>, project_image: &ProjectImage) -> ViewTracker {
-        let image_full_path = match project_path {
-            Some(path) => match path.parent() {
-                Some(path) => path.join(project_image.path()).canonicalize().unwrap(), // TODO: This is a common unwrap panic, if .ark contains path which doesn't exist
-                None => project_image.path().to_path_buf(),
-            },
-            None => project_image.path().to_path_buf(),
+    fn new(project: &Project, project_image: &ProjectImage, defaults: &ViewData) -> ViewTracker {
+        let mut view_tracker = ViewTracker::dormant(project, project_image, defaults);
+        view_tracker.start();
+        view_tracker
+    }
+
+    /// Like `new`, but leaves the source file alone until `start` is called.
+    fn dormant(project: &Project, project_image: &ProjectImage, defaults: &ViewData) -> ViewTracker {
+        let (mut view, path) = match project_image.source() {
+            Source::File(_) => {
+                let path = project.full_path(project_image).unwrap();
+                (View::dormant(&path), Some(path))
+            }
+            Source::Embedded(embedded_id) => {
+                let frames = match project.embedded(*embedded_id) {
+                    Some(pixels) => vec![Frame::new(pixels.as_ref().clone(), 0)],
+                    None => Vec::new(),
+                };
+                (View::from_frames(&project_image.display_name(), frames), None)
+            }
         };
+        view.set_proxy(project_image.proxy());
+        view.resume_at(project_image.resume_frame());
 
         ViewTracker {
             id: project_image.id(),
-            widget_pod: WidgetPod::new(View::new(&image_full_path)),
+            widget_pod: WidgetPod::new(view),
+            path: path,
             origin: *project_image.origin(),
             data: ViewData {
-                selected: false,
+                pinned: project_image.pinned(),
+                paused: defaults.paused || project_image.paused() || settings::current().start_paused,
+                smooth: project_image.smooth().unwrap_or(defaults.smooth),
+                checkerboard: project_image.checkerboard(),
                 zoom: *project_image.zoom(),
+                offset: *project_image.offset(),
+                time_remap: project_image.time_remap(),
+                color_key: project_image.color_key(),
+                rotation: project_image.rotation(),
+                sync_group: project_image.sync_group(),
+                source: SourceStatus::Fresh,
+                ..defaults.clone()
             },
         }
     }
 
-    fn adjust_origin(&mut self, surface_size: &Size, delta: Vec2) -> Point {
+    /// Resolve the path of the source file and start decoding it, unless that has already been done.
+    fn start(&mut self) {
+        let view = self.widget_pod.widget_mut();
+        if let (true, Some(path)) = (view.is_dormant(), &mut self.path) {
+            // The file may be missing, in which case the view will be empty until relinked
+            *path = path.canonicalize().unwrap_or_else(|_| path.clone());
+            view.start(path);
+        }
+    }
+
+    fn is_dormant(&self) -> bool {
+        self.widget_pod.widget().is_dormant()
+    }
+
+    /// Decode the image again from `path`, after which the view is up to date with the file.
+    fn reload(&mut self, path: &Path) -> bool {
+        let reloaded = self.widget_pod.widget_mut().reload(path);
+        if reloaded {
+            self.data.source = SourceStatus::Fresh;
+        }
+        reloaded
+    }
+
+    /// How much the image is scaled to match the physical size of the others, see `View::physical_scale`.
+    fn physical_scale(&self) -> f64 {
+        self.widget_pod.widget().physical_scale(&self.data)
+    }
+
+    /// The scale factor of the image on the surface, see `View::scale_factor`.
+    fn scale_factor(&self) -> f64 {
+        self.widget_pod.widget().scale_factor(&self.data)
+    }
+
+    /// Move the image within the view by `delta` in surface coordinates, as if dragging it, and return the new offset.
+    fn pan_by(&mut self, delta: Vec2) -> Point {
+        let view = self.widget_pod.widget();
+        let offset = self.data.offset - delta / view.scale_factor(&self.data);
+        self.data.offset = view.clamp_offset(&self.data, view.content_size(), offset);
+        self.data.offset
+    }
+
+    /// The area that the view would cover on the surface if it weren't rotated.
+    fn content_rect(&self) -> Rect {
+        Rect::from_center_size(
+            self.widget_pod.layout_rect().center(),
+            self.widget_pod.widget().content_size(),
+        )
+    }
+
+    /// The transform that moves the unrotated view to where it's shown turned on the surface.
+    fn turn(&self) -> Affine {
+        let origin = self.widget_pod.layout_rect().origin().to_vec2();
+        let transform = self.widget_pod.widget().rotation_transform(&self.data);
+        Affine::translate(origin) * transform * Affine::translate(-self.content_rect().origin().to_vec2())
+    }
+
+    /// Map `pos` in surface coordinates to where it would be if the view weren't rotated.
+    fn unrotate(&self, pos: Point) -> Point {
+        self.turn().inverse() * pos
+    }
+
+    /// Whether `pos` in surface coordinates is within the view, as rotated.
+    fn contains(&self, pos: Point) -> bool {
+        self.content_rect().contains(self.unrotate(pos))
+    }
+
+    /// Map `point` within the unrotated view to where it's shown on the surface.
+    fn turned(&self, point: Point) -> Point {
+        let origin = self.widget_pod.layout_rect().origin().to_vec2();
+        self.widget_pod.widget().rotation_transform(&self.data) * point + origin
+    }
+
+    /// Where the handle for rotating the view is, in surface coordinates.
+    fn rotation_handle(&self) -> Point {
+        let width = self.widget_pod.widget().content_size().width;
+        self.turned(Point::new(width / 2.0, -ROTATION_HANDLE_DISTANCE))
+    }
+
+    /// Turn the view to `degrees` clockwise about its center and return the origin that keeps the center in place.
+    fn rotate_to(&mut self, degrees: f64) -> Point {
+        let content_size = self.widget_pod.widget().content_size();
+        let before = rotated_bounds(content_size, self.data.rotation);
+        let after = rotated_bounds(content_size, degrees);
+        self.data.rotation = degrees.rem_euclid(360.0);
+        self.origin += (before.to_vec2() - after.to_vec2()) / 2.0;
+        self.origin
+    }
+
+    /// The area that the whole image would cover on the surface if it weren't rotated,
+    /// even where the view is too small for it.
+    fn image_rect(&self) -> Rect {
+        let view = self.widget_pod.widget();
+        let rect = self.content_rect();
+        let scale = view.scale_factor(&self.data);
+        let offset = view.clamp_offset(&self.data, rect.size(), self.data.offset);
+        match view.image_size() {
+            Some(image_size) => Rect::from_origin_size(rect.origin() - offset.to_vec2() * scale, image_size * scale),
+            None => rect,
+        }
+    }
+
+    fn adjust_origin(&mut self, surface_size: &Size, pan: Vec2, delta: Vec2) -> Point {
         // Make sure there remains at least 5dp visible on each axis
         let mut origin = self.origin + delta;
         let rect = self.widget_pod.layout_rect();
-        let min_x = -(rect.width()) + 5.0;
-        let min_y = -(rect.height()) + 5.0;
-        let max_x = surface_size.width - 5.0;
-        let max_y = surface_size.height - 5.0;
+        let min_x = -(rect.width()) + 5.0 - pan.x;
+        let min_y = -(rect.height()) + 5.0 - pan.y;
+        let max_x = surface_size.width - 5.0 - pan.x;
+        let max_y = surface_size.height - 5.0 - pan.y;
         origin.x = origin.x.max(min_x).min(max_x);
         origin.y = origin.y.max(min_y).min(max_y);
         self.origin = origin;
@@ -375,7 +3468,47 @@ impl ViewTracker {
     }
 }
 
+/// An animated change of the surface pan and the zoom of a view, for framing it.
+struct Framing {
+    view_id: usize,
+    from_pan: Vec2,
+    to_pan: Vec2,
+    from_scale: f64,
+    to_scale: f64,
+    elapsed: u64, // Nanoseconds since the animation started
+}
+
 struct Drag {
     view_id: usize,
     start: Point,
+    panning: bool,  // moves the image within the view, instead of moving the view
+    rotating: bool, // turns the view about its center, instead of moving it
+}
+
+/// What the thumbnail of an image in the thumbnail strip is made from.
+#[derive(PartialEq, Eq, Hash)]
+enum ThumbnailSource {
+    File(PathBuf, Option<SystemTime>), // The full path and when the view last opened the file
+    Embedded(u64),
+}
+
+enum StripThumbnail {
+    Pending(Receiver<Arc<ImgVec<RGBA8>>>),
+    Ready(Arc<ImgVec<RGBA8>>),
+    Failed,
+}
+
+/// Two overlapping views shown side by side, split by a divider.
+struct Compare {
+    lower: usize,
+    upper: usize,
+    restore: Placement, // Where the upper view was before it was moved on top of the lower one
+    split: f64,         // Where the divider is, as a fraction of the width of both views
+    dragging: bool,
+}
+
+/// A line measured on the surface, in project coordinates.
+struct Measurement {
+    start: Point,
+    end: Point,
 }
@@ -0,0 +1,241 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Colors used by Slark widgets, with a dark and a light palette.
+//!
+//! The palette is global and can be swapped at runtime. Each window is wrapped in a `ThemeScope`
+//! which applies the current palette to the environment of everything inside it.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use druid::piet::Color;
+use druid::widget::prelude::*;
+use druid::{Key, Point, Selector, WidgetPod};
+
+pub const SURFACE_BACKGROUND: Key<Color> = Key::new("slark.theme.surface_background");
+pub const SELECTION: Key<Color> = Key::new("slark.theme.selection");
+pub const HOVER: Key<Color> = Key::new("slark.theme.hover");
+pub const GUIDES: Key<Color> = Key::new("slark.theme.guides");
+pub const CANVAS_DIM: Key<Color> = Key::new("slark.theme.canvas_dim");
+pub const FLASH: Key<Color> = Key::new("slark.theme.flash");
+pub const CHECKER_LIGHT: Key<Color> = Key::new("slark.theme.checker_light");
+pub const CHECKER_DARK: Key<Color> = Key::new("slark.theme.checker_dark");
+pub const CAPTURE_DIM: Key<Color> = Key::new("slark.theme.capture_dim");
+pub const CAPTURE_OUTLINE: Key<Color> = Key::new("slark.theme.capture_outline");
+pub const PANEL_BACKGROUND: Key<Color> = Key::new("slark.theme.panel_background");
+pub const PANEL_CELL: Key<Color> = Key::new("slark.theme.panel_cell");
+pub const PANEL_TEXT: Key<Color> = Key::new("slark.theme.panel_text");
+pub const ERROR_TEXT: Key<Color> = Key::new("slark.theme.error_text");
+pub const TOAST_INFO: Key<Color> = Key::new("slark.theme.toast_info");
+pub const TOAST_WARNING: Key<Color> = Key::new("slark.theme.toast_warning");
+pub const TOAST_ERROR: Key<Color> = Key::new("slark.theme.toast_error");
+pub const TOAST_TEXT: Key<Color> = Key::new("slark.theme.toast_text");
+
+/// Sent to all windows after the palette was swapped.
+pub const COMMAND_THEME_CHANGED: Selector = Selector::new("slark.theme_changed");
+
+static DARK: AtomicBool = AtomicBool::new(true);
+
+pub struct Palette {
+    window_background: Color,
+    text: Color,
+    surface_background: Color,
+    selection: Color,
+    hover: Color,
+    guides: Color,
+    canvas_dim: Color,
+    flash: Color,
+    checker_light: Color,
+    checker_dark: Color,
+    capture_dim: Color,
+    capture_outline: Color,
+    panel_background: Color,
+    panel_cell: Color,
+    panel_text: Color,
+    error_text: Color,
+    toast_info: Color,
+    toast_warning: Color,
+    toast_error: Color,
+    toast_text: Color,
+}
+
+impl Palette {
+    pub const DARK: Palette = Palette {
+        window_background: Color::rgb8(0x29, 0x29, 0x29),
+        text: Color::rgb8(0xf0, 0xf0, 0xea),
+        surface_background: Color::rgb8(0x29, 0x29, 0x29),
+        selection: Color::rgb8(245, 132, 66),
+        hover: Color::rgba8(255, 255, 255, 24),
+        guides: Color::rgba8(255, 255, 255, 160),
+        canvas_dim: Color::rgba8(0, 0, 0, 128),
+        flash: Color::rgb8(255, 255, 255),
+        checker_light: Color::rgb8(102, 102, 102),
+        checker_dark: Color::rgb8(76, 76, 76),
+        capture_dim: Color::rgba8(0, 0, 0, 128),
+        capture_outline: Color::rgb8(255, 255, 255),
+        panel_background: Color::rgb8(40, 40, 40),
+        panel_cell: Color::rgb8(60, 60, 60),
+        panel_text: Color::rgb8(220, 220, 220),
+        error_text: Color::rgb8(220, 50, 50),
+        toast_info: Color::rgba8(60, 90, 130, 235),
+        toast_warning: Color::rgba8(150, 110, 20, 235),
+        toast_error: Color::rgba8(160, 40, 40, 235),
+        toast_text: Color::rgb8(245, 245, 245),
+    };
+
+    pub const LIGHT: Palette = Palette {
+        window_background: Color::rgb8(0xf2, 0xf2, 0xf2),
+        text: Color::rgb8(0x20, 0x20, 0x20),
+        surface_background: Color::rgb8(0xe6, 0xe6, 0xe6),
+        selection: Color::rgb8(0, 120, 215),
+        hover: Color::rgba8(0, 0, 0, 24),
+        guides: Color::rgba8(0, 0, 0, 160),
+        canvas_dim: Color::rgba8(255, 255, 255, 128),
+        flash: Color::rgb8(255, 255, 255),
+        checker_light: Color::rgb8(255, 255, 255),
+        checker_dark: Color::rgb8(204, 204, 204),
+        capture_dim: Color::rgba8(0, 0, 0, 128),
+        capture_outline: Color::rgb8(255, 255, 255),
+        panel_background: Color::rgb8(225, 225, 225),
+        panel_cell: Color::rgb8(200, 200, 200),
+        panel_text: Color::rgb8(30, 30, 30),
+        error_text: Color::rgb8(190, 20, 20),
+        toast_info: Color::rgba8(70, 110, 170, 235),
+        toast_warning: Color::rgba8(190, 130, 20, 235),
+        toast_error: Color::rgba8(190, 40, 40, 235),
+        toast_text: Color::rgb8(255, 255, 255),
+    };
+
+    /// Set all the palette colors in `env`, including the relevant druid defaults.
+    pub fn apply(&self, env: &mut Env) {
+        env.set(druid::theme::WINDOW_BACKGROUND_COLOR, self.window_background.clone());
+        env.set(druid::theme::TEXT_COLOR, self.text.clone());
+        env.set(SURFACE_BACKGROUND, self.surface_background.clone());
+        env.set(SELECTION, self.selection.clone());
+        env.set(HOVER, self.hover.clone());
+        env.set(GUIDES, self.guides.clone());
+        env.set(CANVAS_DIM, self.canvas_dim.clone());
+        env.set(FLASH, self.flash.clone());
+        env.set(CHECKER_LIGHT, self.checker_light.clone());
+        env.set(CHECKER_DARK, self.checker_dark.clone());
+        env.set(CAPTURE_DIM, self.capture_dim.clone());
+        env.set(CAPTURE_OUTLINE, self.capture_outline.clone());
+        env.set(PANEL_BACKGROUND, self.panel_background.clone());
+        env.set(PANEL_CELL, self.panel_cell.clone());
+        env.set(PANEL_TEXT, self.panel_text.clone());
+        env.set(ERROR_TEXT, self.error_text.clone());
+        env.set(TOAST_INFO, self.toast_info.clone());
+        env.set(TOAST_WARNING, self.toast_warning.clone());
+        env.set(TOAST_ERROR, self.toast_error.clone());
+        env.set(TOAST_TEXT, self.toast_text.clone());
+    }
+}
+
+/// Returns the palette currently in use.
+pub fn current() -> &'static Palette {
+    if is_dark() {
+        &Palette::DARK
+    } else {
+        &Palette::LIGHT
+    }
+}
+
+pub fn is_dark() -> bool {
+    DARK.load(Ordering::Relaxed)
+}
+
+pub fn set_dark(dark: bool) {
+    DARK.store(dark, Ordering::Relaxed);
+}
+
+/// Switch between the dark and light palettes.
+///
+/// The caller must submit `COMMAND_THEME_CHANGED` to all windows afterwards.
+pub fn toggle() {
+    DARK.fetch_xor(true, Ordering::Relaxed);
+}
+
+/// Applies the current palette to the environment of its child.
+pub struct ThemeScope<T> {
+    child: WidgetPod<T, Box<dyn Widget<T>>>,
+    env: Option<(bool, Env, Env)>, // The palette, the parent environment, and the resulting environment
+}
+
+impl<T: Data> ThemeScope<T> {
+    pub fn new(child: impl Widget<T> + 'static) -> ThemeScope<T> {
+        ThemeScope {
+            child: WidgetPod::new(Box::new(child)),
+            env: None,
+        }
+    }
+
+    fn env(&mut self, parent: &Env) -> Env {
+        let dark = is_dark();
+        match &self.env {
+            Some((cached_dark, cached_parent, env)) if *cached_dark == dark && cached_parent.same(parent) => {
+                env.clone()
+            }
+            _ => {
+                let mut env = parent.clone();
+                current().apply(&mut env);
+                self.env = Some((dark, parent.clone(), env.clone()));
+                env
+            }
+        }
+    }
+}
+
+impl<T: Data> Widget<T> for ThemeScope<T> {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut T, env: &Env) {
+        if let Event::Command(cmd) = event {
+            if cmd.is(COMMAND_THEME_CHANGED) {
+                // Let everything inside pick up the new colors
+                ctx.request_update();
+                ctx.request_layout();
+                ctx.request_paint();
+            }
+        }
+        let env = self.env(env);
+        self.child.event(ctx, event, data, &env);
+    }
+
+    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, data: &T, env: &Env) {
+        let env = self.env(env);
+        self.child.lifecycle(ctx, event, data, &env);
+    }
+
+    fn update(&mut self, ctx: &mut UpdateCtx, _old_data: &T, data: &T, env: &Env) {
+        let env = self.env(env);
+        self.child.update(ctx, data, &env);
+    }
+
+    fn layout(&mut self, ctx: &mut LayoutCtx, bc: &BoxConstraints, data: &T, env: &Env) -> Size {
+        let env = self.env(env);
+        let size = self.child.layout(ctx, bc, data, &env);
+        self.child.set_origin(ctx, Point::ORIGIN);
+        size
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &T, env: &Env) {
+        let env = self.env(env);
+        let rect = ctx.size().to_rect();
+        ctx.fill(rect, &env.get(druid::theme::WINDOW_BACKGROUND_COLOR));
+        self.child.paint(ctx, data, &env);
+    }
+}
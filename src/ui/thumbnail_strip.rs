@@ -0,0 +1,189 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! The thumbnail strip shows a small version of every project image along the bottom of the window.
+//!
+//! Like the layers panel, it gets its content from the surface via `COMMAND_THUMBNAILS_CHANGED`.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use druid::kurbo::{Point, Rect};
+use druid::piet::{ImageFormat, InterpolationMode, PietImage};
+use druid::widget::prelude::*;
+use druid::{Selector, Target};
+use imgref::ImgVec;
+use log::warn;
+use rgb::{ComponentBytes, RGBA8};
+
+use crate::ui::theme;
+
+/// Sent by the surface whenever the thumbnails or the selection change.
+pub const COMMAND_THUMBNAILS_CHANGED: Selector<Arc<Vec<ThumbnailEntry>>> = Selector::new("slark.thumbnails_changed");
+/// Select the image and bring it into view.
+pub const COMMAND_FOCUS_IMAGE: Selector<usize> = Selector::new("slark.focus_image");
+
+#[derive(Clone)]
+pub struct ThumbnailEntry {
+    pub id: usize,
+    pub thumbnail: Option<Arc<ImgVec<RGBA8>>>,
+    pub selected: bool,
+}
+
+impl PartialEq for ThumbnailEntry {
+    fn eq(&self, other: &ThumbnailEntry) -> bool {
+        let same_thumbnail = match (&self.thumbnail, &other.thumbnail) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        };
+        self.id == other.id && self.selected == other.selected && same_thumbnail
+    }
+}
+
+pub struct ThumbnailStrip {
+    entries: Arc<Vec<ThumbnailEntry>>,
+    images: HashMap<usize, (Arc<ImgVec<RGBA8>>, PietImage)>, // Uploaded thumbnails by image id
+    scroll: f64,
+}
+
+impl ThumbnailStrip {
+    const PADDING: f64 = 6.0;
+    const CELL: f64 = 64.0; // Thumbnails are scaled down to fit
+
+    pub fn new() -> ThumbnailStrip {
+        ThumbnailStrip {
+            entries: Arc::new(Vec::new()),
+            images: HashMap::new(),
+            scroll: 0.0,
+        }
+    }
+
+    fn cell_rect(&self, index: usize) -> Rect {
+        let x = ThumbnailStrip::PADDING + index as f64 * (ThumbnailStrip::CELL + ThumbnailStrip::PADDING) - self.scroll;
+        Rect::from_origin_size(
+            Point::new(x, ThumbnailStrip::PADDING),
+            (ThumbnailStrip::CELL, ThumbnailStrip::CELL),
+        )
+    }
+
+    fn content_width(&self) -> f64 {
+        ThumbnailStrip::PADDING + self.entries.len() as f64 * (ThumbnailStrip::CELL + ThumbnailStrip::PADDING)
+    }
+
+    fn clamp_scroll(&mut self, width: f64) {
+        let max_scroll = (self.content_width() - width).max(0.0);
+        self.scroll = self.scroll.max(0.0).min(max_scroll);
+    }
+}
+
+impl Widget<u64> for ThumbnailStrip {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, _data: &mut u64, _env: &Env) {
+        match event {
+            Event::Command(cmd) if cmd.is(COMMAND_THUMBNAILS_CHANGED) => {
+                let had_entries = !self.entries.is_empty();
+                self.entries = cmd.get_unchecked(COMMAND_THUMBNAILS_CHANGED).clone();
+                // Forget the thumbnails of images that are gone or have been reloaded
+                let entries = &self.entries;
+                self.images.retain(|id, (thumbnail, _)| {
+                    entries.iter().any(|entry| match &entry.thumbnail {
+                        Some(current) => entry.id == *id && Arc::ptr_eq(current, thumbnail),
+                        None => false,
+                    })
+                });
+                if had_entries != !self.entries.is_empty() {
+                    ctx.request_layout();
+                }
+                ctx.request_paint();
+            }
+            Event::MouseDown(mouse_event) if mouse_event.button.is_left() => {
+                let index = (0..self.entries.len()).find(|&index| self.cell_rect(index).contains(mouse_event.pos));
+                if let Some(index) = index {
+                    let id = self.entries[index].id;
+                    ctx.submit_command(COMMAND_FOCUS_IMAGE.with(id).to(Target::Window(ctx.window_id())));
+                }
+                ctx.set_handled();
+            }
+            Event::Wheel(mouse_event) => {
+                self.scroll += mouse_event.wheel_delta.y + mouse_event.wheel_delta.x;
+                self.clamp_scroll(ctx.size().width);
+                ctx.request_paint();
+                ctx.set_handled();
+            }
+            _ => (),
+        }
+    }
+
+    fn lifecycle(&mut self, _ctx: &mut LifeCycleCtx, _event: &LifeCycle, _data: &u64, _env: &Env) {}
+
+    fn update(&mut self, _ctx: &mut UpdateCtx, _old_data: &u64, _data: &u64, _env: &Env) {}
+
+    fn layout(&mut self, _ctx: &mut LayoutCtx, bc: &BoxConstraints, _data: &u64, _env: &Env) -> Size {
+        bc.debug_check("ThumbnailStrip");
+        // Stay out of the way until there's something to show
+        if self.entries.is_empty() {
+            return bc.min();
+        }
+        let size = bc.constrain((bc.max().width, ThumbnailStrip::CELL + 2.0 * ThumbnailStrip::PADDING));
+        self.clamp_scroll(size.width);
+        size
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, _data: &u64, env: &Env) {
+        let bounds = ctx.size().to_rect();
+        if bounds.is_empty() {
+            return;
+        }
+        ctx.fill(bounds, &env.get(theme::PANEL_BACKGROUND));
+        ctx.clip(bounds);
+
+        let entries = self.entries.clone();
+        for (index, entry) in entries.iter().enumerate() {
+            let cell = self.cell_rect(index);
+            if cell.x1 < bounds.x0 || cell.x0 > bounds.x1 {
+                continue;
+            }
+            ctx.fill(cell, &env.get(theme::PANEL_CELL));
+
+            if let Some(thumbnail) = &entry.thumbnail {
+                if !self.images.contains_key(&entry.id) {
+                    let (buf, width, height) = ImgVec::as_ref(thumbnail).to_contiguous_buf();
+                    match ctx.make_image(width, height, buf.as_bytes(), ImageFormat::RgbaSeparate) {
+                        Ok(image) => {
+                            self.images.insert(entry.id, (thumbnail.clone(), image));
+                        }
+                        Err(error) => warn!("Failed to create thumbnail image: {}", error),
+                    }
+                }
+                if let Some((_, image)) = self.images.get(&entry.id) {
+                    // Center the thumbnail in its cell
+                    let size = Size::new(thumbnail.width() as f64, thumbnail.height() as f64);
+                    let size = size * (ThumbnailStrip::CELL / size.width.max(size.height)).min(1.0);
+                    let origin = cell.center() - size.to_vec2() / 2.0;
+                    let dst_rect = Rect::from_origin_size(origin, size);
+                    ctx.draw_image(image, dst_rect, InterpolationMode::Bilinear);
+                }
+            }
+
+            if entry.selected {
+                ctx.stroke(cell.inset(1.0), &env.get(theme::SELECTION), 2.0);
+            }
+        }
+    }
+}
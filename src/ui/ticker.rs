@@ -0,0 +1,95 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+/// Coalesces the animation frames of the display into ticks at a capped rate.
+/// Each tick carries all the time since the previous one, so the frame delays keep following the wall clock.
+pub struct Ticker {
+    interval: u64, // nanoseconds between ticks, 0 for a tick on every animation frame
+    phase: u64,    // nanoseconds towards the next tick, which carries over to keep the average rate
+    elapsed: u64,  // nanoseconds since the previous tick
+}
+
+impl Ticker {
+    /// Create a ticker that ticks at most `fps` times a second, with 0 meaning no cap.
+    pub fn new(fps: u32) -> Ticker {
+        Ticker {
+            interval: if fps > 0 { 1_000_000_000 / fps as u64 } else { 0 },
+            phase: 0,
+            elapsed: 0,
+        }
+    }
+
+    /// Advance by an animation frame interval, returning the nanoseconds since the previous tick when it's time for one.
+    pub fn advance(&mut self, interval: u64) -> Option<u64> {
+        self.elapsed += interval;
+        self.phase += interval;
+        if self.phase < self.interval {
+            return None;
+        }
+        // Carry over at most one tick, so a long pause doesn't cause a burst of ticks afterwards
+        self.phase = (self.phase - self.interval).min(self.interval);
+        Some(std::mem::take(&mut self.elapsed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: u64 = 1_000_000;
+
+    fn ticks(ticker: &mut Ticker, interval: u64, count: usize) -> Vec<u64> {
+        (0..count).filter_map(|_| ticker.advance(interval)).collect()
+    }
+
+    #[test]
+    fn uncapped_ticks_every_frame() {
+        let mut ticker = Ticker::new(0);
+        assert_eq!(ticks(&mut ticker, 7 * MS, 3), vec![7 * MS; 3]);
+    }
+
+    #[test]
+    fn faster_display_is_coalesced() {
+        // A 240 Hz display with a 60 Hz cap ticks every fourth frame
+        let mut ticker = Ticker::new(60);
+        let interval = 1_000_000_000 / 240;
+        let ticks = ticks(&mut ticker, interval, 240);
+        assert!((59..=61).contains(&ticks.len()), "{} ticks", ticks.len());
+        assert_eq!(ticks.iter().sum::<u64>() % interval, 0);
+    }
+
+    #[test]
+    fn jitter_at_the_cap_keeps_the_rate() {
+        // Frames slightly shorter than the tick interval shouldn't halve the rate
+        let mut ticker = Ticker::new(60);
+        let ticks = ticks(&mut ticker, 16 * MS, 60);
+        assert!(ticks.len() >= 57, "{} ticks", ticks.len());
+    }
+
+    #[test]
+    fn elapsed_time_is_kept() {
+        let mut ticker = Ticker::new(30);
+        let mut total = 0;
+        for _ in 0..100 {
+            total += ticker.advance(5 * MS).unwrap_or(0);
+        }
+        total += ticker.advance(100 * MS).unwrap();
+        assert_eq!(total, 600 * MS);
+    }
+}
@@ -17,23 +17,87 @@
     along with this program.  If not, see <http://www.gnu.org/licenses/>.
 */
 
-use druid::kurbo::{Point, Size, Vec2};
+use std::cmp::Ordering;
 
+use druid::kurbo::{Point, Rect, Size};
+
+use crate::i18n::tr;
 use crate::ui::Zoom;
 
+/// The order in which the tiles are placed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortBy {
+    AddOrder,
+    Name,
+    Area,  // largest first
+    Layer, // topmost first
+}
+
+impl SortBy {
+    /// The next mode, wrapping around after the last one.
+    pub fn next(self) -> SortBy {
+        match self {
+            SortBy::AddOrder => SortBy::Name,
+            SortBy::Name => SortBy::Area,
+            SortBy::Area => SortBy::Layer,
+            SortBy::Layer => SortBy::AddOrder,
+        }
+    }
+
+    pub fn name(self) -> String {
+        match self {
+            SortBy::AddOrder => tr("sort-add-order"),
+            SortBy::Name => tr("sort-name"),
+            SortBy::Area => tr("sort-area"),
+            SortBy::Layer => tr("sort-layer"),
+        }
+    }
+
+    fn compare(self, a: &Tile, b: &Tile) -> Ordering {
+        let ordering = match self {
+            SortBy::AddOrder => Ordering::Equal,
+            SortBy::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            SortBy::Area => {
+                let a_area = a.effective_size().area();
+                let b_area = b.effective_size().area();
+                b_area.partial_cmp(&a_area).unwrap_or(Ordering::Equal)
+            }
+            SortBy::Layer => b.layer.cmp(&a.layer),
+        };
+        ordering.then_with(|| a.id.cmp(&b.id))
+    }
+}
+
 pub struct Tileize {
     surface: Size,
+    sort_by: SortBy,
+    gap: f64,    // between tiles
+    margin: f64, // between the tiles and the surface edges
     tiles: Vec<Tile>,
+    obstacles: Vec<Rect>, // space taken by pinned content
 }
 
 impl Tileize {
-    pub fn new(surface: Size) -> Tileize {
+    pub const DEFAULT_GAP: f64 = 8.0;
+    pub const DEFAULT_MARGIN: f64 = 16.0;
+
+    pub fn new(surface: Size, sort_by: SortBy) -> Tileize {
         Tileize {
             surface,
+            sort_by,
+            gap: Tileize::DEFAULT_GAP,
+            margin: Tileize::DEFAULT_MARGIN,
             tiles: Vec::new(),
+            obstacles: Vec::new(),
         }
     }
 
+    /// Set the space between tiles and around them. Zero for both packs the tiles flush.
+    pub fn set_spacing(&mut self, gap: f64, margin: f64) {
+        self.gap = gap.max(0.0);
+        self.margin = margin.max(0.0);
+    }
+
     pub fn tiles(&self) -> &[Tile] {
         &self.tiles
     }
@@ -42,34 +106,180 @@ impl Tileize {
         self.tiles.push(tile);
     }
 
+    /// Mark `rect` as occupied, so that no tile gets placed on top of it.
+    pub fn add_obstacle(&mut self, rect: Rect) {
+        self.obstacles.push(rect);
+    }
+
+    /// Place the tiles left to right in rows, starting a new row when the surface width runs out.
+    ///
+    /// If the rows don't fit the surface, all the tiles are zoomed out step by step until they do,
+    /// or until they can't be zoomed out any further.
     pub fn fit(&mut self) {
-        let surface_area = self.surface.area();
+        self.sort();
+        loop {
+            let size = self.wrap();
+            if size.width <= self.surface.width && size.height <= self.surface.height {
+                break;
+            }
+            let mut shrunk = false;
+            for tile in &mut self.tiles {
+                let scale = tile.zoom.scale_factor();
+                tile.zoom.turn_the_knob(-1);
+                shrunk |= tile.zoom.scale_factor() < scale;
+            }
+            if !shrunk {
+                break;
+            }
+        }
+    }
+
+    /// Place the tiles into a grid of equal cells, each tile zoomed to fit its cell and centered in it.
+    ///
+    /// Without a column count the grid is made as square as possible. Returns the column count used.
+    pub fn fit_grid(&mut self, columns: Option<usize>) -> usize {
+        self.sort();
+        let count = self.tiles.len();
+        if count == 0 {
+            return columns.unwrap_or(1);
+        }
+        let columns = columns.unwrap_or_else(|| (count as f64).sqrt().ceil() as usize).max(1);
+        let rows = (count + columns - 1) / columns;
+        let cell = Size::new(
+            (self.surface.width - 2.0 * self.margin - (columns - 1) as f64 * self.gap) / columns as f64,
+            (self.surface.height - 2.0 * self.margin - (rows - 1) as f64 * self.gap) / rows as f64,
+        );
+        let mut i = 0;
+        for tile in &mut self.tiles {
+            let scale = (cell.width / tile.size.width).min(cell.height / tile.size.height);
+            tile.zoom = Zoom::from_scale_factor(scale);
+            // The closest zoom might be slightly too big
+            while tile.zoom.scale_factor() > scale {
+                let previous = tile.zoom.scale_factor();
+                tile.zoom.turn_the_knob(-1);
+                if tile.zoom.scale_factor() == previous {
+                    break;
+                }
+            }
+            // Skip the cells where the tile would overlap an obstacle
+            loop {
+                let cell_origin = Point::new(
+                    self.margin + (i % columns) as f64 * (cell.width + self.gap),
+                    self.margin + (i / columns) as f64 * (cell.height + self.gap),
+                );
+                i += 1;
+                let free = cell - tile.effective_size();
+                tile.origin = cell_origin + (free.to_vec2() / 2.0);
+                let rect = Rect::from_origin_size(tile.origin, tile.effective_size());
+                if blocker(&self.obstacles, rect, self.gap).is_none() {
+                    break;
+                }
+            }
+        }
+        columns
+    }
+
+    fn sort(&mut self) {
+        let sort_by = self.sort_by;
+        self.tiles.sort_by(|a, b| sort_by.compare(a, b));
+    }
 
-        // TODO: Determine if the area is enough to fit all the images with current zoom levels,
-        //       reduce some zoom levels if not.
+    /// Place the tiles in rows at their current zoom levels, returning the size of the result.
+    ///
+    /// The size includes the margins on all sides.
+    fn wrap(&mut self) -> Size {
+        let mut size = Size::ZERO;
+        let mut x = self.margin;
+        let mut y = self.margin;
+        let mut row_height: f64 = 0.0;
+        for tile in &mut self.tiles {
+            let tile_size = tile.effective_size();
+            loop {
+                // Every row gets at least one tile, even if it's too wide
+                if x > self.margin && x + tile_size.width + self.margin > self.surface.width {
+                    x = self.margin;
+                    y = if row_height > 0.0 {
+                        y + row_height + self.gap
+                    } else {
+                        // Obstacles took up the whole row, so continue below them
+                        below(&self.obstacles, y, tile_size.height, self.gap)
+                    };
+                    row_height = 0.0;
+                }
+                let rect = Rect::from_origin_size((x, y), tile_size);
+                match blocker(&self.obstacles, rect, self.gap) {
+                    Some(obstacle) => x = obstacle.x1,
+                    None => break,
+                }
+            }
+            tile.origin = Point::new(x, y);
+            size.width = size.width.max(x + tile_size.width + self.margin);
+            x += tile_size.width + self.gap;
+            row_height = row_height.max(tile_size.height);
+        }
+        size.height = y + row_height + self.margin;
+        size
+    }
+}
 
-        let tiles_len = self.tiles.len();
-        for i in 0..tiles_len {
-            if i == 0 {
-                self.tiles[i].origin = Point::ZERO;
-                continue;
+/// The first position, scanning rows from the top left of `area`, where a rect of `size`
+/// stays at least `gap` away from all of the `taken` rects. Returns `None` if there's no room.
+pub fn free_spot(area: Rect, taken: &[Rect], size: Size, gap: f64) -> Option<Point> {
+    let mut y = area.y0;
+    while y + size.height <= area.y1 {
+        let mut x = area.x0;
+        while x + size.width <= area.x1 {
+            let rect = Rect::from_origin_size((x, y), size);
+            match blocker(taken, rect, gap) {
+                Some(blocker) => x = blocker.x1,
+                None => return Some(Point::new(x, y)),
             }
-            let effective_tile_size = self.tiles[i - 1].effective_size();
-            self.tiles[i].origin = self.tiles[i - 1].origin + Vec2::new(effective_tile_size.width, 0.0);
         }
+        y = below(taken, y, size.height, gap);
     }
+    None
+}
+
+/// The first obstacle, grown by `gap`, that overlaps `rect`.
+fn blocker(obstacles: &[Rect], rect: Rect, gap: f64) -> Option<Rect> {
+    obstacles
+        .iter()
+        .map(|obstacle| obstacle.inflate(gap, gap))
+        .find(|obstacle| obstacle.intersect(rect).area() > 0.0)
+}
+
+/// The closest height below `y` where an obstacle in the band of `height` ends.
+fn below(obstacles: &[Rect], y: f64, height: f64, gap: f64) -> f64 {
+    obstacles
+        .iter()
+        .map(|obstacle| obstacle.inflate(gap, gap))
+        .filter(|obstacle| obstacle.y0 < y + height && obstacle.y1 > y)
+        .map(|obstacle| obstacle.y1)
+        .fold(None, |lowest: Option<f64>, y1| {
+            Some(lowest.map_or(y1, |lowest| lowest.min(y1)))
+        })
+        .unwrap_or(y + height)
 }
 
 pub struct Tile {
     id: usize,
+    name: String, // usually the file name
+    layer: usize, // position in the layer order, higher is on top
     origin: Point,
     size: Size,
     zoom: Zoom,
 }
 
 impl Tile {
-    pub fn new(id: usize, origin: Point, size: Size, zoom: Zoom) -> Tile {
-        Tile { id, origin, size, zoom }
+    pub fn new(id: usize, name: String, layer: usize, origin: Point, size: Size, zoom: Zoom) -> Tile {
+        Tile {
+            id,
+            name,
+            layer,
+            origin,
+            size,
+            zoom,
+        }
     }
 
     pub fn id(&self) -> usize {
@@ -88,3 +298,211 @@ impl Tile {
         self.zoom
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fit(surface: Size, sizes: &[Size]) -> Tileize {
+        fit_spaced(surface, sizes, 0.0, 0.0)
+    }
+
+    fn fit_spaced(surface: Size, sizes: &[Size], gap: f64, margin: f64) -> Tileize {
+        let mut tileize = Tileize::new(surface, SortBy::AddOrder);
+        tileize.set_spacing(gap, margin);
+        for (id, &size) in sizes.iter().enumerate() {
+            tileize.add(Tile::new(
+                id,
+                format!("{}", id),
+                id,
+                Point::new(50.0, 50.0),
+                size,
+                Zoom::default(),
+            ));
+        }
+        tileize.fit();
+        tileize
+    }
+
+    fn order(sort_by: SortBy) -> Vec<usize> {
+        let mut tileize = Tileize::new(Size::new(1000.0, 1000.0), sort_by);
+        let tiles = [
+            ("b.gif", 2, 10.0),
+            ("A.png", 0, 30.0),
+            ("c.webp", 1, 30.0),
+            ("a.png", 3, 20.0),
+        ];
+        for (id, &(name, layer, side)) in tiles.iter().enumerate() {
+            let size = Size::new(side, side);
+            tileize.add(Tile::new(
+                id,
+                String::from(name),
+                layer,
+                Point::ZERO,
+                size,
+                Zoom::default(),
+            ));
+        }
+        tileize.fit();
+        tileize.tiles().iter().map(|tile| tile.id()).collect()
+    }
+
+    #[test]
+    fn sorting() {
+        assert_eq!(order(SortBy::AddOrder), vec![0, 1, 2, 3]);
+        // Ties are broken by id
+        assert_eq!(order(SortBy::Name), vec![1, 3, 0, 2]);
+        assert_eq!(order(SortBy::Area), vec![1, 2, 3, 0]);
+        assert_eq!(order(SortBy::Layer), vec![3, 0, 2, 1]);
+    }
+
+    fn assert_origin(tile: &Tile, x: f64, y: f64) {
+        let origin = tile.origin();
+        assert!(
+            (origin.x - x).abs() < 0.001 && (origin.y - y).abs() < 0.001,
+            "tile {} is at {} instead of ({}, {})",
+            tile.id(),
+            origin,
+            x,
+            y
+        );
+    }
+
+    #[test]
+    fn single_row() {
+        let tileize = fit(Size::new(300.0, 200.0), &[Size::new(100.0, 100.0); 3]);
+        assert_origin(&tileize.tiles()[0], 0.0, 0.0);
+        assert_origin(&tileize.tiles()[1], 100.0, 0.0);
+        assert_origin(&tileize.tiles()[2], 200.0, 0.0);
+        assert!(tileize.tiles().iter().all(|tile| tile.zoom().scale_factor() == 1.0));
+    }
+
+    #[test]
+    fn wraps_rows_by_tallest_tile() {
+        let sizes = [
+            Size::new(100.0, 50.0),
+            Size::new(150.0, 80.0),
+            Size::new(100.0, 40.0),
+            Size::new(60.0, 60.0),
+        ];
+        let tileize = fit(Size::new(300.0, 200.0), &sizes);
+        assert_origin(&tileize.tiles()[0], 0.0, 0.0);
+        assert_origin(&tileize.tiles()[1], 100.0, 0.0);
+        assert_origin(&tileize.tiles()[2], 0.0, 80.0);
+        assert_origin(&tileize.tiles()[3], 100.0, 80.0);
+        assert!(tileize.tiles().iter().all(|tile| tile.zoom().scale_factor() == 1.0));
+    }
+
+    #[test]
+    fn zooms_out_until_it_fits() {
+        let tileize = fit(Size::new(300.0, 200.0), &[Size::new(100.0, 100.0); 7]);
+        // Four steps out makes the tiles about 68.3 wide, which fits four per row in two rows
+        let scale = 1.1f64.powi(-4);
+        let side = 100.0 * scale;
+        assert!(tileize.tiles().iter().all(|tile| tile.zoom().scale_factor() == scale));
+        assert_origin(&tileize.tiles()[3], 3.0 * side, 0.0);
+        assert_origin(&tileize.tiles()[4], 0.0, side);
+        assert_origin(&tileize.tiles()[6], 2.0 * side, side);
+    }
+
+    #[test]
+    fn stops_at_minimum_zoom() {
+        let tileize = fit(Size::new(10.0, 10.0), &[Size::new(1000.0, 1000.0); 2]);
+        assert!(tileize.tiles().iter().all(|tile| tile.zoom().scale_factor() == 0.1));
+        assert_origin(&tileize.tiles()[1], 0.0, 100.0);
+    }
+
+    #[test]
+    fn spacing() {
+        let tileize = fit_spaced(Size::new(300.0, 300.0), &[Size::new(100.0, 100.0); 3], 10.0, 20.0);
+        assert_origin(&tileize.tiles()[0], 20.0, 20.0);
+        assert_origin(&tileize.tiles()[1], 130.0, 20.0);
+        // 240 + 100 + 20 doesn't fit into 300
+        assert_origin(&tileize.tiles()[2], 20.0, 130.0);
+        assert!(tileize.tiles().iter().all(|tile| tile.zoom().scale_factor() == 1.0));
+    }
+
+    #[test]
+    fn grid() {
+        let sizes = [Size::new(200.0, 100.0), Size::new(50.0, 100.0), Size::new(100.0, 100.0)];
+        let mut tileize = Tileize::new(Size::new(200.0, 200.0), SortBy::AddOrder);
+        tileize.set_spacing(0.0, 0.0);
+        for (id, &size) in sizes.iter().enumerate() {
+            tileize.add(Tile::new(id, format!("{}", id), id, Point::ZERO, size, Zoom::default()));
+        }
+        // Three tiles make for an automatic 2 x 2 grid of 100 x 100 cells
+        assert_eq!(tileize.fit_grid(None), 2);
+        let tiles = tileize.tiles();
+        for tile in tiles {
+            let size = tile.effective_size();
+            assert!(size.width <= 100.0 && size.height <= 100.0);
+        }
+        // Zooming happens in steps, so the wide tile ends up a bit smaller than half
+        let scale = tiles[0].zoom().scale_factor();
+        assert!(scale <= 0.5 && scale > 0.5 / 1.1);
+        assert_origin(&tiles[0], (100.0 - 200.0 * scale) / 2.0, (100.0 - 100.0 * scale) / 2.0);
+        assert!(tiles[1].zoom().scale_factor() == 1.0);
+        assert_origin(&tiles[1], 125.0, 0.0);
+        assert!(tiles[2].zoom().scale_factor() == 1.0);
+        assert_origin(&tiles[2], 0.0, 100.0);
+    }
+
+    #[test]
+    fn flows_around_obstacles() {
+        let mut tileize = Tileize::new(Size::new(300.0, 300.0), SortBy::AddOrder);
+        tileize.set_spacing(0.0, 0.0);
+        tileize.add_obstacle(Rect::new(100.0, 0.0, 200.0, 100.0));
+        for id in 0..3 {
+            let size = Size::new(100.0, 100.0);
+            tileize.add(Tile::new(id, format!("{}", id), id, Point::ZERO, size, Zoom::default()));
+        }
+        tileize.fit();
+        assert_origin(&tileize.tiles()[0], 0.0, 0.0);
+        assert_origin(&tileize.tiles()[1], 200.0, 0.0);
+        assert_origin(&tileize.tiles()[2], 0.0, 100.0);
+    }
+
+    #[test]
+    fn skips_rows_taken_by_obstacles() {
+        let mut tileize = Tileize::new(Size::new(300.0, 300.0), SortBy::AddOrder);
+        tileize.set_spacing(0.0, 0.0);
+        tileize.add_obstacle(Rect::new(0.0, 0.0, 300.0, 50.0));
+        tileize.add(Tile::new(
+            0,
+            String::from("0"),
+            0,
+            Point::ZERO,
+            Size::new(100.0, 100.0),
+            Zoom::default(),
+        ));
+        tileize.fit();
+        assert_origin(&tileize.tiles()[0], 0.0, 50.0);
+    }
+
+    #[test]
+    fn free_spot_in_empty_area() {
+        let area = Rect::new(10.0, 20.0, 300.0, 300.0);
+        let spot = free_spot(area, &[], Size::new(100.0, 100.0), 0.0);
+        assert_eq!(spot, Some(Point::new(10.0, 20.0)));
+    }
+
+    #[test]
+    fn free_spot_avoids_taken_rects() {
+        let area = Rect::new(0.0, 0.0, 300.0, 300.0);
+        let taken = [Rect::new(0.0, 0.0, 100.0, 100.0), Rect::new(150.0, 0.0, 300.0, 50.0)];
+        let spot = free_spot(area, &taken, Size::new(100.0, 100.0), 0.0);
+        assert_eq!(spot, Some(Point::new(100.0, 50.0)));
+        let spot = free_spot(area, &taken, Size::new(40.0, 40.0), 0.0);
+        assert_eq!(spot, Some(Point::new(100.0, 0.0)));
+        let spot = free_spot(area, &taken, Size::new(40.0, 40.0), 10.0);
+        assert_eq!(spot, Some(Point::new(110.0, 60.0)));
+    }
+
+    #[test]
+    fn no_free_spot_when_full() {
+        let area = Rect::new(0.0, 0.0, 200.0, 200.0);
+        let taken = [Rect::new(50.0, 50.0, 150.0, 150.0)];
+        assert_eq!(free_spot(area, &taken, Size::new(120.0, 120.0), 0.0), None);
+        assert_eq!(free_spot(area, &[], Size::new(300.0, 100.0), 0.0), None);
+    }
+}
@@ -0,0 +1,82 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use druid::Data;
+use serde::{Deserialize, Serialize};
+
+/// How slow the eased presets play at their slowest, as a fraction of the normal speed.
+const MIN_SPEED: f64 = 0.25;
+
+/// A curve for how fast an animation plays over the course of each loop.
+#[derive(Data, Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeRemap {
+    /// The normal speed throughout.
+    Linear,
+    /// Slow at first, ramping up to the normal speed by the end of the loop.
+    EaseIn,
+    /// The normal speed at first, slowing down towards the end of the loop.
+    EaseOut,
+}
+
+impl Default for TimeRemap {
+    fn default() -> TimeRemap {
+        TimeRemap::Linear
+    }
+}
+
+impl TimeRemap {
+    pub const ALL: [TimeRemap; 3] = [TimeRemap::Linear, TimeRemap::EaseIn, TimeRemap::EaseOut];
+
+    /// The speed multiplier at `progress` through the loop, which goes from 0.0 to 1.0.
+    pub fn speed(self, progress: f64) -> f64 {
+        let progress = progress.max(0.0).min(1.0);
+        match self {
+            TimeRemap::Linear => 1.0,
+            TimeRemap::EaseIn => MIN_SPEED + (1.0 - MIN_SPEED) * progress,
+            TimeRemap::EaseOut => 1.0 - (1.0 - MIN_SPEED) * progress,
+        }
+    }
+
+    /// The translation key of the name of the curve.
+    pub fn label_key(self) -> &'static str {
+        match self {
+            TimeRemap::Linear => "time-remap-linear",
+            TimeRemap::EaseIn => "time-remap-ease-in",
+            TimeRemap::EaseOut => "time-remap-ease-out",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eased_presets_ramp_between_the_ends() {
+        assert_eq!(TimeRemap::Linear.speed(0.3), 1.0);
+        assert_eq!(TimeRemap::EaseIn.speed(0.0), MIN_SPEED);
+        assert_eq!(TimeRemap::EaseIn.speed(1.0), 1.0);
+        assert_eq!(TimeRemap::EaseOut.speed(0.0), 1.0);
+        assert_eq!(TimeRemap::EaseOut.speed(1.0), MIN_SPEED);
+        assert!(TimeRemap::EaseIn.speed(0.2) < TimeRemap::EaseIn.speed(0.8));
+        assert_eq!(TimeRemap::EaseIn.speed(-1.0), MIN_SPEED);
+        assert_eq!(TimeRemap::EaseOut.speed(2.0), MIN_SPEED);
+    }
+}
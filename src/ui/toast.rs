@@ -0,0 +1,225 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Toasts are short notifications shown on top of the surface which disappear on their own.
+
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use druid::kurbo::{Point, Rect, Vec2};
+use druid::piet::{Text, TextLayout, TextLayoutBuilder};
+use druid::widget::prelude::*;
+use druid::{ExtEventSink, Selector, Target, WidgetPod};
+use log::{error, info, warn};
+
+use crate::ui::theme;
+
+/// Show a toast. Submitted to a window, or globally to reach the most recently used window.
+pub const COMMAND_SHOW_TOAST: Selector<ToastMessage> = Selector::new("slark.show_toast");
+
+static EVENT_SINK: OnceLock<ExtEventSink> = OnceLock::new();
+static PENDING: Mutex<Vec<ToastMessage>> = Mutex::new(Vec::new()); // posted before there was an event sink
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToastLevel {
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Clone, Debug)]
+pub struct ToastMessage {
+    pub level: ToastLevel,
+    pub text: String,
+}
+
+impl ToastMessage {
+    pub fn info(text: impl Into<String>) -> ToastMessage {
+        ToastMessage {
+            level: ToastLevel::Info,
+            text: text.into(),
+        }
+    }
+
+    pub fn warning(text: impl Into<String>) -> ToastMessage {
+        ToastMessage {
+            level: ToastLevel::Warning,
+            text: text.into(),
+        }
+    }
+
+    pub fn error(text: impl Into<String>) -> ToastMessage {
+        ToastMessage {
+            level: ToastLevel::Error,
+            text: text.into(),
+        }
+    }
+}
+
+/// Set the event sink used by `post`. Toasts posted before this are submitted now.
+pub fn set_toast_event_sink(event_sink: ExtEventSink) {
+    if EVENT_SINK.set(event_sink).is_err() {
+        warn!("Toast event sink was already set");
+    }
+    let mut pending = PENDING.lock().unwrap();
+    for message in pending.drain(..) {
+        submit(message);
+    }
+}
+
+/// Log the message and show it as a toast. Works from any thread, even without access to a context.
+pub fn post(message: ToastMessage) {
+    match message.level {
+        ToastLevel::Info => info!("{}", message.text),
+        ToastLevel::Warning => warn!("{}", message.text),
+        ToastLevel::Error => error!("{}", message.text),
+    }
+    // Hold the lock, so that the message can't slip past the flush in set_toast_event_sink
+    let mut pending = PENDING.lock().unwrap();
+    if EVENT_SINK.get().is_some() {
+        submit(message);
+    } else {
+        pending.push(message);
+    }
+}
+
+fn submit(message: ToastMessage) {
+    if let Some(event_sink) = EVENT_SINK.get() {
+        if let Err(error) = event_sink.submit_command(COMMAND_SHOW_TOAST, message, Target::Global) {
+            error!("Failed to submit toast: {}", error);
+        }
+    }
+}
+
+struct Toast {
+    message: ToastMessage,
+    shown: Instant,
+    rect: Rect, // where it was last painted, for dismissing with a click
+}
+
+/// Shows the toasts on top of its child, stacked in the bottom right corner.
+pub struct Toasts<T> {
+    child: WidgetPod<T, Box<dyn Widget<T>>>,
+    toasts: Vec<Toast>, // The newest toast is last
+}
+
+impl<T: Data> Toasts<T> {
+    const MAX_TOASTS: usize = 3;
+    const DURATION: Duration = Duration::from_secs(4);
+    const WIDTH: f64 = 320.0;
+    const PADDING: f64 = 8.0;
+    const MARGIN: f64 = 12.0;
+
+    pub fn new(child: impl Widget<T> + 'static) -> Toasts<T> {
+        Toasts {
+            child: WidgetPod::new(Box::new(child)),
+            toasts: Vec::new(),
+        }
+    }
+}
+
+impl<T: Data> Widget<T> for Toasts<T> {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut T, env: &Env) {
+        match event {
+            Event::Command(cmd) if cmd.is(COMMAND_SHOW_TOAST) => {
+                if self.toasts.len() == Toasts::<T>::MAX_TOASTS {
+                    self.toasts.remove(0);
+                }
+                self.toasts.push(Toast {
+                    message: cmd.get_unchecked(COMMAND_SHOW_TOAST).clone(),
+                    shown: Instant::now(),
+                    rect: Rect::ZERO,
+                });
+                ctx.request_anim_frame();
+                ctx.request_paint();
+                ctx.set_handled();
+                return;
+            }
+            Event::AnimFrame(_) if !self.toasts.is_empty() => {
+                let count = self.toasts.len();
+                self.toasts
+                    .retain(|toast| toast.shown.elapsed() < Toasts::<T>::DURATION);
+                if self.toasts.len() != count {
+                    ctx.request_paint();
+                }
+                if !self.toasts.is_empty() {
+                    ctx.request_anim_frame();
+                }
+            }
+            Event::MouseDown(mouse_event) => {
+                if let Some(i) = self
+                    .toasts
+                    .iter()
+                    .position(|toast| toast.rect.contains(mouse_event.pos))
+                {
+                    self.toasts.remove(i);
+                    ctx.request_paint();
+                    ctx.set_handled();
+                    return;
+                }
+            }
+            _ => (),
+        }
+        self.child.event(ctx, event, data, env);
+    }
+
+    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, data: &T, env: &Env) {
+        self.child.lifecycle(ctx, event, data, env);
+    }
+
+    fn update(&mut self, ctx: &mut UpdateCtx, _old_data: &T, data: &T, env: &Env) {
+        self.child.update(ctx, data, env);
+    }
+
+    fn layout(&mut self, ctx: &mut LayoutCtx, bc: &BoxConstraints, data: &T, env: &Env) -> Size {
+        let size = self.child.layout(ctx, bc, data, env);
+        self.child.set_origin(ctx, Point::ORIGIN);
+        size
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &T, env: &Env) {
+        self.child.paint(ctx, data, env);
+
+        let size = ctx.size();
+        let width = Toasts::<T>::WIDTH.min(size.width - 2.0 * Toasts::<T>::MARGIN);
+        let mut bottom = size.height - Toasts::<T>::MARGIN;
+        // Paint the newest toast at the bottom, with the older ones stacked above it
+        for toast in self.toasts.iter_mut().rev() {
+            let layout = ctx
+                .text()
+                .new_text_layout(toast.message.text.clone())
+                .text_color(env.get(theme::TOAST_TEXT))
+                .max_width(width - 2.0 * Toasts::<T>::PADDING)
+                .build()
+                .unwrap();
+            let height = layout.size().height + 2.0 * Toasts::<T>::PADDING;
+            let origin = Point::new(size.width - Toasts::<T>::MARGIN - width, bottom - height);
+            toast.rect = Rect::from_origin_size(origin, (width, height));
+            let background = match toast.message.level {
+                ToastLevel::Info => env.get(theme::TOAST_INFO),
+                ToastLevel::Warning => env.get(theme::TOAST_WARNING),
+                ToastLevel::Error => env.get(theme::TOAST_ERROR),
+            };
+            ctx.fill(toast.rect.to_rounded_rect(4.0), &background);
+            let padding = Vec2::new(Toasts::<T>::PADDING, Toasts::<T>::PADDING);
+            ctx.draw_text(&layout, origin + padding);
+            bottom -= height + Toasts::<T>::PADDING;
+        }
+    }
+}
@@ -17,135 +17,1088 @@
     along with this program.  If not, see <http://www.gnu.org/licenses/>.
 */
 
-use std::ffi::OsStr;
-use std::path::Path;
-use std::sync::mpsc::Receiver;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::time::{Duration, Instant, SystemTime};
 
-use druid::piet::{Color, ImageFormat, InterpolationMode, RenderContext};
+use druid::kurbo::{Affine, BezPath, Circle, Line, Point, Rect, Shape, Vec2};
+use druid::piet::{ImageFormat, InterpolationMode, RenderContext, Text, TextLayout, TextLayoutBuilder};
 use druid::widget::prelude::*;
-use druid::Data;
-use rgb::ComponentBytes;
+use druid::{Data, FontFamily};
+use imgref::ImgVec;
+use log::{info, warn};
+use rgb::{ComponentBytes, RGBA8};
 
-use crate::formats::{gif, jpeg, png, webp};
-use crate::image::Frame;
-use crate::ui::Zoom;
+use crate::formats::{Decoded, Format};
+use crate::i18n::{tr, tr_args};
+use crate::image::{difference, downscale, downscaled_size, AnimationInfo, Frame, FrameStructure};
+use crate::settings;
+use crate::ui::{
+    post, record_decode_error, record_late_frame, record_upload, theme, ColorKey, TimeRemap, ToastMessage, Zoom,
+};
+use crate::watcher::{self, SourceStatus};
+
+/// The pixel density assumed for images that don't tell theirs, when matching physical sizes.
+const DEFAULT_DPI: f64 = 96.0;
+
+/// The side of a checkerboard square in display points, which stays the same at every zoom.
+const CHECKER_SIZE: f64 = 8.0;
+
+/// The largest bitmap width or height that the GPU can be relied on to handle.
+const MAX_TEXTURE_SIZE: usize = 16384;
+
+/// How many pixels of the neighboring tiles each tile bitmap also has, so that bilinear sampling at a seam sees across it.
+const TILE_APRON: usize = 1;
+
+/// The largest part of a frame that a single tile is responsible for, leaving room for the apron on both sides.
+const MAX_TILE_SIZE: usize = MAX_TEXTURE_SIZE - 2 * TILE_APRON;
+
+/// Total number of bytes used by the decoded frames of all views.
+static CACHED_FRAME_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// Returns the number of bytes used by the decoded frames of all views.
+pub fn cached_frame_bytes() -> u64 {
+    CACHED_FRAME_BYTES.load(Ordering::Relaxed)
+}
 
 #[derive(Data, Clone)]
 pub struct ViewData {
     pub selected: bool,
-    pub zoom: Zoom, // Use the zoom method to change
+    pub pinned: bool,                // kept in place when arranging tiles
+    pub paused: bool,                // stays on the current frame
+    pub suspended: bool,             // no animation work at all, as the window is in the background
+    pub smooth: bool,                // bilinear instead of nearest neighbor scaling
+    pub checkerboard: bool,          // a checkerboard shows through the transparent pixels
+    pub diff: bool,                  // shows how the current frame differs from the previous one, while paused
+    pub timing: bool,                // logs how long the frames were actually shown compared to their delays
+    pub physical: bool,              // scaled by the pixel density of the file, so that images match in physical size
+    pub zoom: Zoom,                  // Use the zoom method to change
+    pub offset: Point, // the image pixel at the top left corner, when the view is too small for the whole image
+    pub time_remap: TimeRemap, // how fast the animation plays over the course of each loop
+    pub color_key: Option<ColorKey>, // the color shown as transparent, if any
+    pub rotation: f64, // degrees clockwise about the center of the view
+    pub sync_group: Option<u32>, // the views in the same group play in step
+    pub source: SourceStatus, // whether the file is missing or has changed since it was loaded
 }
 
 pub struct View {
-    pending_frames: Option<Receiver<Frame>>,
+    path: PathBuf, // only for telling the user which image something is about
+    pending_frames: Option<Receiver<Decoded>>,
+    last_progress: Instant,   // when the decoder last delivered a frame, or was started
+    decode_timeout: Duration, // how long the decoder may go without delivering a frame
+    failure: Option<String>,  // why there are no frames coming, shown in place of the image
     image_size: Option<Size>,
+    animation: Option<AnimationInfo>, // the length of the animation, if the format tells it up front
     frames: Vec<CachedFrame>,
+    frame_bytes: u64, // our share of CACHED_FRAME_BYTES
     current_frame: usize,
     current_delay: i64,
 
-    need_legit_layout: bool, // true when we've had to give a fake size in layout
+    need_legit_layout: bool,            // true when we've had to give a fake size in layout
+    skip_interval: bool,                // true when the next anim frame interval covers a suspension
+    flash_start: Option<Instant>,       // when the view was last flashed
+    diff_frame: Option<DiffFrame>,      // the difference heatmap of the most recently diffed frame
+    timing: Option<TimingLog>,          // collected while timing reports are enabled
+    resume_frame: Option<usize>,        // the frame to jump to once it has been decoded
+    resolution: f64,                    // what the frames are uploaded at, as a fraction of the native size
+    metadata: Vec<(String, String)>,    // the EXIF tags, text chunks and such of the file
+    dpi: Option<f64>,                   // the pixel density of the file, if it tells
+    color_key: Option<ColorKey>,        // what the frames are keyed with, taken from the data when painting
+    content_size: Size,                 // the size of the view before it's rotated, which is what shows the image
+    proxy: f64,                         // the fraction of the native size the frames are kept at
+    dormant: bool,                      // the file hasn't been opened yet, which waits for `start`
+    modified: Option<SystemTime>,       // when the file was last modified as of opening it
+    primed: Option<(Decoded, Instant)>, // the first frame, taken off the channel as soon as it arrived, and when
 }
 
-struct CachedFrame {
+/// Wall-clock display times of the frames over one loop of the animation.
+#[derive(Default)]
+struct TimingLog {
+    shown: Option<(usize, Instant)>, // the frame that is being shown and since when
+    samples: Vec<(i64, i64)>,        // the declared and the actual display time of each frame, in nanoseconds
+}
+
+impl TimingLog {
+    /// Start over, e.g. after a pause that would otherwise count as a very long frame.
+    fn restart(&mut self) {
+        self.shown = None;
+        self.samples.clear();
+    }
+
+    /// Log how the actual timing of the loop compared to the declared delays.
+    fn report(&self, path: &Path) {
+        let count = self.samples.len() as f64;
+        let declared: i64 = self.samples.iter().map(|&(declared, _)| declared).sum();
+        let actual: i64 = self.samples.iter().map(|&(_, actual)| actual).sum();
+        let worst = self
+            .samples
+            .iter()
+            .map(|&(declared, actual)| actual - declared)
+            .max_by_key(|drift| drift.abs())
+            .unwrap_or(0);
+        let ms = |ns: i64| ns as f64 / 1_000_000.0;
+        info!(
+            "Frame timing of {}: the loop of {} frames took {:.1} ms instead of {:.1} ms, with an average drift of {:.2} ms and the worst at {:.2} ms per frame",
+            path.display(),
+            self.samples.len(),
+            ms(actual),
+            ms(declared),
+            ms(actual - declared) / count,
+            ms(worst),
+        );
+    }
+}
+
+struct DiffFrame {
+    frame: usize, // the index of the frame that was compared to the one before it
+    image: Tiles,
+}
+
+/// A frame uploaded as a grid of bitmaps, as a single bitmap can only be so large.
+///
+/// Frames that fit into a single bitmap have only one tile.
+struct Tiles {
+    size: Size,       // the size of the whole uploaded frame
+    tiles: Vec<Tile>, // row by row
+}
+
+struct Tile {
+    rect: Rect,                      // the part of the frame that this tile draws
+    origin: Point,                   // where the bitmap starts in the frame, which is before `rect` by the apron
     image: druid::piet::d2d::Bitmap, // TODO: Get druid::piet::Image working for cross-platform support
+}
+
+impl Tiles {
+    /// Draw the part of the frame that `crop` covers into `dst_rect`, with `crop` in fractions of the size.
+    fn draw(&self, ctx: &mut PaintCtx, crop: Rect, dst_rect: Rect, interpolation: InterpolationMode) {
+        let rects: Vec<Rect> = self.tiles.iter().map(|tile| tile.rect).collect();
+        for (index, src, dst) in tile_draws(&rects, crop_of(self.size, crop), dst_rect) {
+            let tile = &self.tiles[index];
+            let src = src - tile.origin.to_vec2();
+            ctx.render_ctx.draw_image_area(&tile.image, src, dst, interpolation);
+        }
+    }
+}
+
+struct CachedFrame {
+    image: Tiles,
+    resolution: f64,              // The size of the bitmap as a fraction of the native size
+    image_bytes: u64,             // The size of the bitmap in memory
+    pixels: ImgVec<RGBA8>,        // The full resolution frame kept on the CPU side for sampling
+    key: Option<ColorKey>,        // The color key the bitmap was uploaded with
+    keyed: Option<ImgVec<RGBA8>>, // The pixels with the key color made transparent, while there is a key
     delay: i64,
+    structure: Option<FrameStructure>, // How the frame was put together, only known for GIFs
+}
+
+/// The part of an image of `image_size` that a view of `view_size` shows at `scale`, starting from `offset`.
+///
+/// The edges are rounded to whole pixels of the image, so that what is shown can be cut out of the frame exactly.
+/// The result is in fractions of the image size.
+fn shown_part(image_size: Size, view_size: Size, scale: f64, offset: Point) -> Rect {
+    let full = Rect::new(0.0, 0.0, 1.0, 1.0);
+    if image_size.is_empty() {
+        return full;
+    }
+    let shown = Rect::from_origin_size(offset, view_size / scale).round();
+    Rect::new(
+        shown.x0 / image_size.width,
+        shown.y0 / image_size.height,
+        shown.x1 / image_size.width,
+        shown.y1 / image_size.height,
+    )
+    .intersect(full)
+}
+
+/// Cut the part given by `crop` in fractions out of `pixels`, using the same rounding as painting does.
+fn crop_pixels(pixels: &ImgVec<RGBA8>, crop: Rect) -> ImgVec<RGBA8> {
+    let size = Size::new(pixels.width() as f64, pixels.height() as f64);
+    let area = crop_of(size, crop).round();
+    let (x, y) = (area.x0 as usize, area.y0 as usize);
+    let width = (area.width() as usize).min(pixels.width() - x);
+    let height = (area.height() as usize).min(pixels.height() - y);
+    let (buf, _, _) = pixels.sub_image(x, y, width, height).to_contiguous_buf();
+    ImgVec::new(buf.into_owned(), width, height)
+}
+
+/// The area of a bitmap of `size` that `crop` covers, with `crop` in fractions of the size.
+fn crop_of(size: Size, crop: Rect) -> Rect {
+    Rect::new(
+        crop.x0 * size.width,
+        crop.y0 * size.height,
+        crop.x1 * size.width,
+        crop.y1 * size.height,
+    )
+}
+
+/// The cosine and sine of turning by `degrees`, exact for quarter turns.
+fn turn(degrees: f64) -> (f64, f64) {
+    let degrees = degrees.rem_euclid(360.0);
+    if degrees == 0.0 {
+        (1.0, 0.0)
+    } else if degrees == 90.0 {
+        (0.0, 1.0)
+    } else if degrees == 180.0 {
+        (-1.0, 0.0)
+    } else if degrees == 270.0 {
+        (0.0, -1.0)
+    } else {
+        let radians = degrees.to_radians();
+        (radians.cos(), radians.sin())
+    }
+}
+
+/// Whether turning by `degrees` keeps the pixels on the pixel grid.
+fn is_quarter_turn(degrees: f64) -> bool {
+    degrees.rem_euclid(90.0) == 0.0
+}
+
+/// The size of the box that fits `size` turned by `degrees`.
+pub fn rotated_bounds(size: Size, degrees: f64) -> Size {
+    let (cos, sin) = turn(degrees);
+    Size::new(
+        size.width * cos.abs() + size.height * sin.abs(),
+        size.width * sin.abs() + size.height * cos.abs(),
+    )
+}
+
+/// The transform that turns `size` clockwise by `degrees` about its center, keeping it within its rotated bounds.
+///
+/// Quarter turns only swap and mirror the axes, so that pixels land exactly where they would without turning.
+fn rotation_transform(size: Size, degrees: f64) -> Affine {
+    let (cos, sin) = turn(degrees);
+    let bounds = rotated_bounds(size, degrees);
+    let center = Vec2::new(size.width, size.height) / 2.0;
+    Affine::new([
+        cos,
+        sin,
+        -sin,
+        cos,
+        bounds.width / 2.0 - (cos * center.x - sin * center.y),
+        bounds.height / 2.0 - (sin * center.x + cos * center.y),
+    ])
+}
+
+/// The fraction of the native size that a proxy of an image of `size` is kept at, so that it has at most `max_pixels`.
+pub fn proxy_scale(size: Size, max_pixels: f64) -> f64 {
+    (max_pixels / size.area()).sqrt().min(1.0)
+}
+
+/// The longest side of a proxy of an image of `size`, as given to `downscale`.
+fn proxy_max_size(size: Size, proxy: f64) -> usize {
+    ((size.width.max(size.height) * proxy).round() as usize).max(1)
+}
+
+/// The size of a proxy of an image of `size`.
+fn proxied(size: Size, proxy: f64) -> Size {
+    if proxy >= 1.0 || size.is_empty() {
+        return size;
+    }
+    let (width, height) = downscaled_size(size.width as usize, size.height as usize, proxy_max_size(size, proxy));
+    Size::new(width as f64, height as f64)
+}
+
+/// Split `length` pixels into as few spans of at most `max` pixels as possible, all about the same length.
+fn tile_spans(length: usize, max: usize) -> Vec<(usize, usize)> {
+    let count = ((length + max - 1) / max).max(1);
+    (0..count)
+        .map(|i| (i * length / count, (i + 1) * length / count))
+        .collect()
+}
+
+/// The parts of a frame of `width` by `height` pixels that each get a bitmap of their own, row by row.
+fn tile_grid(width: usize, height: usize, max: usize) -> Vec<Rect> {
+    let columns = tile_spans(width, max);
+    tile_spans(height, max)
+        .into_iter()
+        .flat_map(|(y0, y1)| {
+            columns
+                .iter()
+                .map(move |&(x0, x1)| Rect::new(x0 as f64, y0 as f64, x1 as f64, y1 as f64))
+        })
+        .collect()
+}
+
+/// Which of the `tiles` show when drawing the `src` part of a frame into `dst`,
+/// as the index of the tile, the part of the frame it draws and where it goes.
+///
+/// Both tiles at a seam map it through the same arithmetic, so they meet exactly at any scale,
+/// without a gap or a row that is drawn twice.
+fn tile_draws(tiles: &[Rect], src: Rect, dst: Rect) -> Vec<(usize, Rect, Rect)> {
+    if src.width() <= 0.0 || src.height() <= 0.0 {
+        return Vec::new();
+    }
+    let map = |value: f64, src0: f64, src1: f64, dst0: f64, dst1: f64| {
+        // The far edge is taken as is, so that rounding doesn't leave a sliver at the end
+        if value == src1 {
+            dst1
+        } else {
+            dst0 + (value - src0) * (dst1 - dst0) / (src1 - src0)
+        }
+    };
+    tiles
+        .iter()
+        .enumerate()
+        .filter_map(|(index, tile)| {
+            let part = tile.intersect(src);
+            if part.width() <= 0.0 || part.height() <= 0.0 {
+                return None;
+            }
+            let to = Rect::new(
+                map(part.x0, src.x0, src.x1, dst.x0, dst.x1),
+                map(part.y0, src.y0, src.y1, dst.y0, dst.y1),
+                map(part.x1, src.x0, src.x1, dst.x0, dst.x1),
+                map(part.y1, src.y0, src.y1, dst.y0, dst.y1),
+            );
+            Some((index, part, to))
+        })
+        .collect()
+}
+
+/// The smallest fraction of the native size that frames are uploaded at.
+const MIN_RESOLUTION: f64 = 1.0 / 64.0;
+
+/// The resolution, as a fraction of the native size, to upload the frames at when shown at `display_scale`.
+///
+/// Heavily zoomed out views get frames that are still at least twice the displayed size.
+/// Halving in steps keeps small zoom changes from causing another round of uploads.
+fn upload_resolution(display_scale: f64) -> f64 {
+    let mut resolution = 1.0;
+    while resolution / 2.0 >= 2.0 * display_scale && resolution > MIN_RESOLUTION {
+        resolution /= 2.0;
+    }
+    resolution
+}
+
+/// The number of bytes used by the keyed copy of a frame.
+fn keyed_bytes(keyed: &Option<ImgVec<RGBA8>>) -> u64 {
+    keyed.as_ref().map_or(0, |keyed| keyed.buf().as_bytes().len() as u64)
+}
+
+/// Upload the pixels as bitmaps at `resolution`, returning them with their size in memory.
+///
+/// Frames larger than a bitmap can be are split into tiles.
+fn upload(ctx: &mut PaintCtx, pixels: &ImgVec<RGBA8>, resolution: f64) -> Result<(Tiles, u64), String> {
+    let upload_start = Instant::now();
+    let reduced;
+    let pixels = if resolution < 1.0 {
+        let max_size = (pixels.width().max(pixels.height()) as f64 * resolution).round() as usize;
+        reduced = downscale(pixels, max_size);
+        &reduced
+    } else {
+        pixels
+    };
+    let (width, height) = (pixels.width(), pixels.height());
+    let mut tiles = Vec::new();
+    let mut bytes = 0;
+    for rect in tile_grid(width, height, MAX_TILE_SIZE) {
+        let x0 = (rect.x0 as usize).saturating_sub(TILE_APRON);
+        let y0 = (rect.y0 as usize).saturating_sub(TILE_APRON);
+        let x1 = (rect.x1 as usize + TILE_APRON).min(width);
+        let y1 = (rect.y1 as usize + TILE_APRON).min(height);
+        let (buf, tile_width, tile_height) = pixels.sub_image(x0, y0, x1 - x0, y1 - y0).to_contiguous_buf();
+        let image = ctx
+            .render_ctx
+            .make_image(tile_width, tile_height, buf.as_bytes(), ImageFormat::RgbaSeparate)
+            .map_err(|error| error.to_string())?;
+        bytes += buf.as_bytes().len() as u64;
+        tiles.push(Tile {
+            rect: rect,
+            origin: Point::new(x0 as f64, y0 as f64),
+            image: image,
+        });
+    }
+    record_upload(upload_start.elapsed());
+    let size = Size::new(width as f64, height as f64);
+    Ok((
+        Tiles {
+            size: size,
+            tiles: tiles,
+        },
+        bytes,
+    ))
+}
+
+/// Paint a checkerboard over `rect`, for the transparent pixels of the image on top to show.
+///
+/// All the dark squares go into a single path, so that even a large view takes just two fills.
+fn paint_checkerboard(ctx: &mut PaintCtx, rect: Rect, env: &Env) {
+    ctx.render_ctx.fill(rect, &env.get(theme::CHECKER_LIGHT));
+    let mut squares = BezPath::new();
+    let (columns, rows) = (
+        (rect.width() / CHECKER_SIZE).ceil() as usize,
+        (rect.height() / CHECKER_SIZE).ceil() as usize,
+    );
+    for row in 0..rows {
+        for column in (row % 2..columns).step_by(2) {
+            let origin = rect.origin() + Vec2::new(column as f64 * CHECKER_SIZE, row as f64 * CHECKER_SIZE);
+            let square = Rect::from_origin_size(origin, (CHECKER_SIZE, CHECKER_SIZE)).intersect(rect);
+            squares.extend(square.path_elements(0.0));
+        }
+    }
+    ctx.render_ctx.fill(squares, &env.get(theme::CHECKER_DARK));
+}
+
+/// What to show in place of the image when opening it didn't get a decoder going.
+fn open_failure(receiver: &Option<Receiver<Decoded>>) -> Option<String> {
+    if receiver.is_none() {
+        Some(tr("view-open-failed"))
+    } else {
+        None
+    }
 }
 
 impl View {
+    const FLASH_DURATION: Duration = Duration::from_millis(400);
+
     pub fn new(path: &Path) -> View {
-        let gif_ext = OsStr::new("gif");
-        let webp_ext = OsStr::new("webp");
-        let jpg_ext = OsStr::new("jpg");
-        let jpeg_ext = OsStr::new("jpeg");
-        let png_ext = OsStr::new("png");
-
-        let (receiver, image_size) = match path.extension() {
-            Some(ext) => {
-                if ext == gif_ext {
-                    let (receiver, image_size) = gif::open_async(path);
-                    (Some(receiver), Some(image_size))
-                } else if ext == webp_ext {
-                    let (receiver, image_size) = webp::open_async(path);
-                    (Some(receiver), Some(image_size))
-                } else if ext == jpg_ext || ext == jpeg_ext {
-                    let (receiver, image_size) = jpeg::open_async(path);
-                    (Some(receiver), Some(image_size))
-                } else if ext == png_ext {
-                    let (receiver, image_size) = png::open_async(path);
-                    (Some(receiver), Some(image_size))
-                } else {
-                    println!("WARNING: Unsupported file extension: {}", ext.to_str().unwrap());
-                    (None, None)
-                }
-            }
-            _ => {
-                println!(
-                    "WARNING: Slark needs a proper file extension for format detection. {}",
-                    path.to_str().unwrap()
-                );
-                (None, None)
+        let modified = watcher::modified(path);
+        let (receiver, image_size, animation) = View::open(path);
+        let failure = open_failure(&receiver);
+        let mut view = View::with_frames(path.to_path_buf(), receiver, image_size, animation);
+        view.failure = failure;
+        view.modified = modified;
+        view
+    }
+
+    /// A view of the file at `path` that doesn't touch the file until `start` is called.
+    ///
+    /// Opening a project creates its views this way, so that the window can show up before all the decoders have started.
+    pub fn dormant(path: &Path) -> View {
+        let mut view = View::with_frames(path.to_path_buf(), None, None, None);
+        view.dormant = true;
+        view
+    }
+
+    /// Open the file of a dormant view at its resolved `path` and start decoding it.
+    ///
+    /// Views that have already started are left as they are.
+    pub fn start(&mut self, path: &Path) {
+        if !self.dormant {
+            return;
+        }
+        self.dormant = false;
+        self.modified = watcher::modified(path);
+        let (receiver, image_size, animation) = View::open(path);
+        self.path = path.to_path_buf();
+        self.last_progress = Instant::now();
+        self.failure = open_failure(&receiver);
+        self.pending_frames = receiver;
+        self.image_size = image_size.map(|size| proxied(size, self.proxy));
+        self.animation = animation;
+    }
+
+    pub fn is_dormant(&self) -> bool {
+        self.dormant
+    }
+
+    /// Show frames that are already in memory, e.g. pixels that never came from a file.
+    ///
+    /// The `name` is only for telling the user which image something is about.
+    pub fn from_frames(name: &str, frames: Vec<Frame>) -> View {
+        let image_size = match frames.first() {
+            Some(frame) => Size::new(frame.image.width() as f64, frame.image.height() as f64),
+            None => {
+                post(ToastMessage::warning(tr_args(
+                    "toast-missing-embedded",
+                    &[("name", name.into())],
+                )));
+                let mut view = View::with_frames(PathBuf::from(name), None, None, None);
+                view.failure = open_failure(&None);
+                return view;
             }
         };
+        // The frames go through the same channel as decoded ones, which is closed once they're all taken
+        let (sender, receiver) = channel();
+        for frame in frames {
+            sender.send(Ok(frame)).unwrap();
+        }
+        View::with_frames(PathBuf::from(name), Some(receiver), Some(image_size), None)
+    }
 
+    fn with_frames(
+        path: PathBuf,
+        receiver: Option<Receiver<Decoded>>,
+        image_size: Option<Size>,
+        animation: Option<AnimationInfo>,
+    ) -> View {
         View {
+            path: path,
             pending_frames: receiver,
+            last_progress: Instant::now(),
+            decode_timeout: Duration::from_secs(settings::current().decode_timeout),
+            failure: None,
             image_size: image_size,
+            animation: animation,
             frames: Vec::new(),
+            frame_bytes: 0,
             current_frame: 0,
             current_delay: 0,
             need_legit_layout: false,
+            skip_interval: false,
+            flash_start: None,
+            diff_frame: None,
+            timing: None,
+            resume_frame: None,
+            resolution: 1.0,
+            metadata: Vec::new(),
+            dpi: None,
+            color_key: None,
+            content_size: Size::ZERO,
+            proxy: 1.0,
+            dormant: false,
+            modified: None,
+            primed: None,
+        }
+    }
+
+    fn open(path: &Path) -> (Option<Receiver<Decoded>>, Option<Size>, Option<AnimationInfo>) {
+        if !path.is_file() {
+            post(ToastMessage::warning(tr_args(
+                "toast-missing-file",
+                &[("path", path.display().to_string().into())],
+            )));
+            return (None, None, None);
+        }
+
+        let result = match path.extension() {
+            Some(ext) => match Format::from_extension(ext) {
+                Some(format) => format.open_async(path),
+                None => {
+                    post(ToastMessage::warning(tr_args(
+                        "toast-unsupported-extension",
+                        &[("extension", ext.to_string_lossy().into_owned().into())],
+                    )));
+                    return (None, None, None);
+                }
+            },
+            _ => {
+                post(ToastMessage::warning(tr_args(
+                    "toast-no-extension",
+                    &[("path", path.display().to_string().into())],
+                )));
+                return (None, None, None);
+            }
+        };
+        match result {
+            Ok((receiver, image_size, animation)) => (Some(receiver), Some(image_size), animation),
+            Err(error) => {
+                post(ToastMessage::error(tr_args(
+                    "toast-open-failed",
+                    &[("path", path.display().to_string().into()), ("error", error.into())],
+                )));
+                (None, None, None)
+            }
+        }
+    }
+
+    /// Throw away all the decoded frames and start decoding `path` from scratch.
+    ///
+    /// The caller is responsible for requesting a new layout, as the image size may have changed.
+    /// Returns `false` if the file couldn't be opened, which has already been reported to the user.
+    pub fn reload(&mut self, path: &Path) -> bool {
+        self.modified = watcher::modified(path);
+        let (receiver, image_size, animation) = View::open(path);
+        let opened = receiver.is_some();
+        self.dormant = false;
+        self.path = path.to_path_buf();
+        self.last_progress = Instant::now();
+        self.decode_timeout = Duration::from_secs(settings::current().decode_timeout);
+        self.failure = open_failure(&receiver);
+        self.pending_frames = receiver;
+        self.image_size = image_size.map(|size| proxied(size, self.proxy));
+        self.animation = animation;
+        self.clear_frames();
+        self.metadata.clear();
+        self.dpi = None;
+        self.current_frame = 0;
+        self.current_delay = 0;
+        self.resume_frame = None;
+        self.primed = None;
+        self.need_legit_layout = false;
+        if let Some(timing) = &mut self.timing {
+            timing.restart();
+        }
+        self.flash();
+        opened
+    }
+
+    /// Move `delta` frames forward or backward, wrapping around at either end.
+    pub fn step(&mut self, delta: isize) {
+        let count = self.frames.len() as isize;
+        if count > 0 {
+            self.current_frame = (self.current_frame as isize + delta).rem_euclid(count) as usize;
+        }
+        if let Some(timing) = &mut self.timing {
+            timing.restart();
+        }
+    }
+
+    /// The index of the frame that is shown, or that will be shown once it has been decoded.
+    pub fn frame_index(&self) -> usize {
+        self.resume_frame.unwrap_or(self.current_frame)
+    }
+
+    /// Jump to `frame` as soon as it has been decoded, or to the last frame if the animation is shorter.
+    pub fn resume_at(&mut self, frame: usize) {
+        self.resume_frame = if frame > 0 { Some(frame) } else { None };
+        self.resume();
+    }
+
+    fn resume(&mut self) {
+        if let Some(frame) = self.resume_frame {
+            let decoded = frame < self.frames.len();
+            if decoded || (self.pending_frames.is_none() && !self.frames.is_empty()) {
+                self.current_frame = frame.min(self.frames.len() - 1);
+                self.current_delay = self.frames[self.current_frame].delay;
+                self.resume_frame = None;
+            }
+        }
+    }
+
+    /// The frame that is shown and how many nanoseconds it still has left, for keeping other views in step.
+    pub fn clock(&self) -> (usize, i64) {
+        (self.current_frame, self.current_delay)
+    }
+
+    /// Take over the `clock` of another view, so that both advance in step from here on.
+    ///
+    /// Frames that haven't been decoded yet are caught up with once they have been.
+    pub fn follow(&mut self, clock: (usize, i64)) {
+        let (frame, delay) = clock;
+        if frame < self.frames.len() {
+            self.current_frame = frame;
+            self.current_delay = delay;
+        }
+    }
+
+    /// Show the frame at `index`, if it has been decoded.
+    pub fn go_to(&mut self, index: usize) {
+        if index < self.frames.len() {
+            self.current_frame = index;
+            if let Some(timing) = &mut self.timing {
+                timing.restart();
+            }
+        }
+    }
+
+    /// The metadata of the file as names and values, once the first frame has been decoded.
+    pub fn metadata(&self) -> &[(String, String)] {
+        &self.metadata
+    }
+
+    /// The GIF structure and the delay of every frame decoded so far. Empty for other formats.
+    pub fn frame_structures(&self) -> Vec<(FrameStructure, i64)> {
+        self.frames
+            .iter()
+            .filter_map(|frame| Some((frame.structure.clone()?, frame.delay)))
+            .collect()
+    }
+
+    /// How much the image is scaled to match the physical size of the others, 1.0 unless `data.physical`.
+    ///
+    /// Images that don't tell their pixel density are treated as having the default density.
+    pub fn physical_scale(&self, data: &ViewData) -> f64 {
+        if data.physical {
+            DEFAULT_DPI / self.dpi.unwrap_or(DEFAULT_DPI)
+        } else {
+            1.0
+        }
+    }
+
+    /// The scale factor of the image on the surface, both zoomed and matched to the physical size.
+    ///
+    /// Proxies are scaled up by as much as they were scaled down, so that they take up the space of the full image.
+    pub fn scale_factor(&self, data: &ViewData) -> f64 {
+        data.zoom.scale_factor() * self.physical_scale(data) / self.proxy
+    }
+
+    /// Keep only `proxy` of the native size of the frames, for images too large to show in full.
+    ///
+    /// This has to be set before the first frame has been decoded.
+    pub fn set_proxy(&mut self, proxy: f64) {
+        self.image_size = self.image_size.map(|size| proxied(size, proxy));
+        self.proxy = proxy;
+    }
+
+    /// Keep `offset` within the image, so that a view of `view_size` stays covered by it.
+    pub fn clamp_offset(&self, data: &ViewData, view_size: Size, offset: Point) -> Point {
+        let image_size = match self.image_size {
+            Some(image_size) => image_size,
+            None => return Point::ZERO,
+        };
+        let scale = self.scale_factor(data);
+        let max_x = (image_size.width - view_size.width / scale).max(0.0);
+        let max_y = (image_size.height - view_size.height / scale).max(0.0);
+        Point::new(offset.x.max(0.0).min(max_x), offset.y.max(0.0).min(max_y))
+    }
+
+    /// The part of the image shown in a view of `view_size`, as fractions of the image size.
+    fn crop(&self, data: &ViewData, view_size: Size) -> Rect {
+        match self.image_size {
+            Some(image_size) => shown_part(
+                image_size,
+                view_size,
+                self.scale_factor(data),
+                self.clamp_offset(data, view_size, data.offset),
+            ),
+            None => Rect::new(0.0, 0.0, 1.0, 1.0),
         }
     }
 
+    /// The size of the view before it's rotated, as of the latest layout.
+    pub fn content_size(&self) -> Size {
+        self.content_size
+    }
+
+    /// The transform from the unrotated view to where it's shown within the layout rect.
+    pub fn rotation_transform(&self, data: &ViewData) -> Affine {
+        rotation_transform(self.content_size, data.rotation)
+    }
+
+    /// Whether a view of `view_size` is too small to show all of the image.
+    pub fn is_cropped(&self, data: &ViewData, view_size: Size) -> bool {
+        self.crop(data, view_size) != Rect::new(0.0, 0.0, 1.0, 1.0)
+    }
+
+    /// The part of the current frame that a view of `view_size` shows, at the native resolution.
+    pub fn cropped_pixels(&self, data: &ViewData, view_size: Size) -> Option<ImgVec<RGBA8>> {
+        let crop = self.crop(data, view_size);
+        self.current_pixels().map(|pixels| crop_pixels(pixels, crop))
+    }
+
+    /// Briefly highlight the view to draw attention to it.
+    pub fn flash(&mut self) {
+        self.flash_start = Some(Instant::now());
+    }
+
+    /// When the file was last modified as of opening it, for telling whether it has changed since.
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+
     pub fn image_size(&self) -> Option<Size> {
         self.image_size
     }
 
+    /// The pixels of the frame that is currently shown with the color key applied, if any have been decoded.
+    pub fn current_pixels(&self) -> Option<&ImgVec<RGBA8>> {
+        self.frames
+            .get(self.current_frame)
+            .map(|frame| frame.keyed.as_ref().unwrap_or(&frame.pixels))
+    }
+
+    /// The number of frames and the total duration, if the format tells them before everything is decoded.
+    pub fn animation_info(&self) -> Option<AnimationInfo> {
+        self.animation
+    }
+
+    /// How far into the loop the current frame starts, from 0.0 to 1.0, going by the frames decoded so far.
+    fn loop_progress(&self) -> f64 {
+        let total: i64 = self.frames.iter().map(|frame| frame.delay).sum();
+        if total <= 0 {
+            return 0.0;
+        }
+        let elapsed: i64 = self
+            .frames
+            .iter()
+            .take(self.current_frame)
+            .map(|frame| frame.delay)
+            .sum();
+        elapsed as f64 / total as f64
+    }
+
+    /// The number of frames decoded so far.
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// The decoded pixels of the frame at `index`, as the file has them.
+    pub fn frame_pixels(&self, index: usize) -> Option<&ImgVec<RGBA8>> {
+        self.frames.get(index).map(|frame| &frame.pixels)
+    }
+
+    /// Copies of the decoded frames as they are shown, along with their delays.
+    pub fn shown_frames(&self) -> Vec<(ImgVec<RGBA8>, i64)> {
+        self.frames
+            .iter()
+            .map(|frame| (frame.keyed.as_ref().unwrap_or(&frame.pixels).clone(), frame.delay))
+            .collect()
+    }
+
+    /// Estimated number of bytes used by the decoded frames.
+    pub fn memory_usage(&self) -> u64 {
+        self.frame_bytes
+    }
+
+    fn clear_frames(&mut self) {
+        self.frames.clear();
+        self.diff_frame = None;
+        CACHED_FRAME_BYTES.fetch_sub(self.frame_bytes, Ordering::Relaxed);
+        self.frame_bytes = 0;
+    }
+
+    /// Take the first frame off the channel as soon as the decoder has delivered it, returning `true` if it just was.
+    ///
+    /// The frame is only uploaded when painting, so the caller should request a paint right away
+    /// rather than leave the frame waiting for the next animation tick.
+    pub fn prime(&mut self) -> bool {
+        if !self.frames.is_empty() || self.primed.is_some() {
+            return false;
+        }
+        let received = match &self.pending_frames {
+            Some(receiver) => receiver.try_recv(),
+            None => return false,
+        };
+        match received {
+            Ok(decoded) => {
+                self.primed = Some((decoded, Instant::now()));
+                true
+            }
+            // An empty or closed channel is left for `load_frame` to deal with
+            Err(_) => false,
+        }
+    }
+
     // Returns `true` if a new frame was loaded.
     fn load_frame(&mut self, ctx: &mut PaintCtx) -> bool {
         if self.pending_frames.is_some() {
             let receiver = self.pending_frames.as_ref().unwrap();
-            if let Ok(frame) = receiver.recv() {
-                let (buf, width, height) = frame.image.into_contiguous_buf();
-                let image = ctx
-                    .render_ctx
-                    .make_image(width, height, buf.as_bytes(), ImageFormat::RgbaSeparate)
-                    .expect("Failed to create image");
-                self.frames.push(CachedFrame {
-                    image: image,
-                    delay: frame.delay,
-                });
-                // Set the image's dimensions based on the first frame, unless we already have that info
-                if self.image_size.is_none() {
-                    self.image_size = Some(Size::new(width as f64, height as f64));
-                } else if self.image_size.unwrap() != Size::new(width as f64, height as f64) {
-                    println!("WARNING: Probably a broken image format import code path. View expects all frames to be with full dimensions. {} != {} ", self.image_size.unwrap(), Size::new(width as f64, height as f64));
+            // Never wait for the decoder, so that a stuck one can't hold up the whole window
+            let (received, primed_at) = match self.primed.take() {
+                Some((decoded, primed_at)) => (Ok(decoded), Some(primed_at)),
+                None => (receiver.try_recv(), None),
+            };
+            let frame = match received {
+                Ok(Ok(frame)) => frame,
+                Ok(Err(error)) => {
+                    warn!("Decoding stopped after {} frames: {}", self.frames.len(), error);
+                    record_decode_error(&error);
+                    post(ToastMessage::error(tr_args(
+                        "toast-decode-failed",
+                        &[
+                            ("path", error.path.display().to_string().into()),
+                            ("error", error.cause.clone().into()),
+                        ],
+                    )));
+                    self.fail(tr_args("view-decoding-failed", &[("error", error.cause.into())]));
+                    return false;
                 }
-                return true;
+                Err(TryRecvError::Empty) => {
+                    if self.last_progress.elapsed() > self.decode_timeout {
+                        self.stall();
+                    }
+                    return false;
+                }
+                Err(TryRecvError::Disconnected) => {
+                    self.pending_frames = None;
+                    self.resume();
+                    return false;
+                }
+            };
+            self.last_progress = Instant::now();
+            if let Some(metadata) = frame.metadata {
+                self.metadata = metadata;
+            }
+            if frame.dpi.is_some() && frame.dpi != self.dpi {
+                self.dpi = frame.dpi;
+                // The physical size has changed
+                self.need_legit_layout = true;
+            }
+            let (buf, width, height) = frame.image.into_contiguous_buf();
+            let pixels = ImgVec::new(buf, width, height);
+            // Proxies of very large images only keep the downscaled pixels around
+            let pixels = if self.proxy < 1.0 {
+                downscale(
+                    &pixels,
+                    proxy_max_size(Size::new(width as f64, height as f64), self.proxy),
+                )
             } else {
-                self.pending_frames = None;
+                pixels
+            };
+            let (width, height) = (pixels.width(), pixels.height());
+            let keyed = self.color_key.map(|key| key.key_out(&pixels));
+            let upload_start = Instant::now();
+            let (image, image_bytes) = match upload(ctx, keyed.as_ref().unwrap_or(&pixels), self.resolution) {
+                Ok(uploaded) => uploaded,
+                Err(error) => {
+                    post(ToastMessage::error(tr_args(
+                        "toast-upload-failed",
+                        &[
+                            ("path", self.path.display().to_string().into()),
+                            ("error", error.into()),
+                        ],
+                    )));
+                    self.fail(tr("view-upload-failed"));
+                    return false;
+                }
+            };
+            if let Some(primed_at) = primed_at {
+                info!(
+                    "First frame of {} uploaded in {:?}, {:?} after it arrived",
+                    self.path.display(),
+                    upload_start.elapsed(),
+                    primed_at.elapsed()
+                );
             }
+            let image_bytes = image_bytes + keyed_bytes(&keyed);
+            // Both the uploaded bitmap and the pixels kept around for sampling
+            let bytes = image_bytes + pixels.buf().as_bytes().len() as u64;
+            self.frames.push(CachedFrame {
+                image: image,
+                resolution: self.resolution,
+                image_bytes: image_bytes,
+                pixels: pixels,
+                key: self.color_key,
+                keyed: keyed,
+                delay: frame.delay,
+                structure: frame.structure,
+            });
+            self.frame_bytes += bytes;
+            CACHED_FRAME_BYTES.fetch_add(bytes, Ordering::Relaxed);
+            self.resume();
+            // Set the image's dimensions based on the first frame, unless we already have that info
+            if self.image_size.is_none() {
+                self.image_size = Some(Size::new(width as f64, height as f64));
+            } else if self.image_size.unwrap() != Size::new(width as f64, height as f64) {
+                warn!("Probably a broken image format import code path. View expects all frames to be with full dimensions. {} != {} ", self.image_size.unwrap(), Size::new(width as f64, height as f64));
+            }
+            return true;
         }
         false
     }
 
-    fn current_frame(&mut self, ctx: &mut PaintCtx) -> Option<&druid::piet::d2d::Bitmap> {
+    /// Stop waiting for more frames, showing `failure` in place of the image if there are none,
+    /// or along the bottom of the frames that did make it.
+    ///
+    /// Dropping the receiver makes the decoder stop as soon as it tries to deliver another frame.
+    fn fail(&mut self, failure: String) {
+        self.pending_frames = None;
+        self.failure = Some(failure);
+        self.resume();
+    }
+
+    /// Give up on a decoder that has stopped delivering frames, keeping whatever frames it did deliver.
+    fn stall(&mut self) {
+        self.fail(tr("view-decoding-stalled"));
+        post(ToastMessage::error(tr_args(
+            "toast-decode-stalled",
+            &[
+                ("path", self.path.display().to_string().into()),
+                ("seconds", self.decode_timeout.as_secs().into()),
+            ],
+        )));
+    }
+
+    /// The bitmap of the frame at `index`, uploaded again first if it was uploaded at another resolution or key.
+    fn frame_image(&mut self, ctx: &mut PaintCtx, index: usize) -> &Tiles {
+        let frame = &mut self.frames[index];
+        if frame.key != self.color_key {
+            // The original pixels are still around, so a changed key only needs another upload
+            frame.keyed = self.color_key.map(|key| key.key_out(&frame.pixels));
+            frame.key = self.color_key;
+            frame.resolution = 0.0;
+        }
+        if frame.resolution != self.resolution {
+            // Keep showing what was uploaded before rather than nothing at all
+            let (image, image_bytes) = match upload(ctx, frame.keyed.as_ref().unwrap_or(&frame.pixels), self.resolution)
+            {
+                Ok(uploaded) => uploaded,
+                Err(error) => {
+                    warn!("Failed to upload a frame of {}: {}", self.path.display(), error);
+                    return &frame.image;
+                }
+            };
+            let image_bytes = image_bytes + keyed_bytes(&frame.keyed);
+            self.frame_bytes = self.frame_bytes - frame.image_bytes + image_bytes;
+            CACHED_FRAME_BYTES.fetch_sub(frame.image_bytes, Ordering::Relaxed);
+            CACHED_FRAME_BYTES.fetch_add(image_bytes, Ordering::Relaxed);
+            frame.image = image;
+            frame.resolution = self.resolution;
+            frame.image_bytes = image_bytes;
+        }
+        &frame.image
+    }
+
+    fn current_frame(&mut self, ctx: &mut PaintCtx) -> Option<&Tiles> {
         self.load_frame(ctx);
 
         if self.frames.is_empty() {
             None
         } else {
-            Some(&self.frames[self.current_frame].image)
+            Some(self.frame_image(ctx, self.current_frame))
+        }
+    }
+
+    /// The difference heatmap between the current and the previous frame, computed on demand.
+    fn current_diff(&mut self, ctx: &mut PaintCtx) -> Option<&Tiles> {
+        self.load_frame(ctx);
+
+        if self.frames.len() < 2 {
+            return None;
+        }
+
+        let current_frame = self.current_frame;
+        if self
+            .diff_frame
+            .as_ref()
+            .map_or(true, |diff| diff.frame != current_frame)
+        {
+            let previous_frame = (current_frame + self.frames.len() - 1) % self.frames.len();
+            let diff = difference(&self.frames[previous_frame].pixels, &self.frames[current_frame].pixels);
+            let image = match upload(ctx, &diff, 1.0) {
+                Ok((image, _)) => image,
+                Err(error) => {
+                    warn!("Failed to upload the difference of {}: {}", self.path.display(), error);
+                    return None;
+                }
+            };
+            self.diff_frame = Some(DiffFrame {
+                frame: current_frame,
+                image: image,
+            });
+        }
+        self.diff_frame.as_ref().map(|diff| &diff.image)
+    }
+
+    /// Note down when the current frame started to be shown, reporting the timing when the loop starts over.
+    fn record_timing(&mut self) {
+        let timing = match &mut self.timing {
+            Some(timing) => timing,
+            None => return,
+        };
+        if timing.shown.map(|(frame, _)| frame) == Some(self.current_frame) {
+            // Still waiting for the decoder to deliver the next frame
+            return;
+        }
+        let now = Instant::now();
+        if let Some((frame, since)) = timing.shown {
+            let actual = now.duration_since(since).as_nanos() as i64;
+            timing.samples.push((self.frames[frame].delay, actual));
+        }
+        timing.shown = Some((self.current_frame, now));
+        if self.current_frame == 0 && !timing.samples.is_empty() {
+            // Only a loop over all the frames says something about the whole animation
+            if self.pending_frames.is_none() {
+                timing.report(&self.path);
+            }
+            timing.samples.clear();
         }
     }
 
-    fn next_frame(&mut self, ctx: &mut PaintCtx) -> Option<&druid::piet::d2d::Bitmap> {
+    fn next_frame(&mut self, ctx: &mut PaintCtx) -> Option<&Tiles> {
         self.load_frame(ctx);
 
         if self.frames.len() == 0 {
             return None;
         }
 
+        if self.current_frame + 1 >= self.frames.len() && self.pending_frames.is_some() {
+            // The next frame is still being decoded, so keep showing this one a little longer
+            return Some(self.frame_image(ctx, self.current_frame));
+        }
+
         // Progress to the next frame
         self.current_frame += 1;
         if self.current_frame >= self.frames.len() {
@@ -155,17 +1108,36 @@ impl View {
         // Add the post-frame delay to our counter
         self.current_delay += self.frames[self.current_frame].delay;
         // Return the frame
-        Some(&self.frames[self.current_frame].image)
+        Some(self.frame_image(ctx, self.current_frame))
+    }
+}
+
+impl Drop for View {
+    fn drop(&mut self) {
+        self.clear_frames();
     }
 }
 
 impl Widget<ViewData> for View {
-    fn event(&mut self, ctx: &mut EventCtx, event: &Event, _data: &mut ViewData, _env: &Env) {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut ViewData, _env: &Env) {
         match event {
             Event::AnimFrame(interval) => {
+                if data.suspended {
+                    // Let the animation loop stop, it's restarted when the window is back
+                    return;
+                }
+                // Don't try to catch up with the time spent suspended
+                let interval = if std::mem::take(&mut self.skip_interval) {
+                    0
+                } else {
+                    *interval
+                };
                 // TODO: Think about clamping it to zero -- comapre how it works.
                 //       There might be underflows with 0-delay GIFs.
-                self.current_delay -= *interval as i64;
+                if !data.paused {
+                    let speed = data.time_remap.speed(self.loop_progress());
+                    self.current_delay -= (interval as f64 * speed) as i64;
+                }
                 ctx.request_anim_frame();
                 ctx.request_paint();
 
@@ -188,62 +1160,363 @@ impl Widget<ViewData> for View {
     }
 
     fn update(&mut self, ctx: &mut UpdateCtx, old_data: &ViewData, data: &ViewData, _env: &Env) {
-        if data.zoom != old_data.zoom {
+        if data.zoom != old_data.zoom || data.physical != old_data.physical || data.rotation != old_data.rotation {
             ctx.request_layout();
         }
+        if data.offset != old_data.offset || data.color_key != old_data.color_key {
+            ctx.request_paint();
+        }
+        if data.timing != old_data.timing {
+            self.timing = if data.timing { Some(TimingLog::default()) } else { None };
+        }
+        if data.paused != old_data.paused || data.suspended != old_data.suspended {
+            if let Some(timing) = &mut self.timing {
+                timing.restart();
+            }
+        }
+        if old_data.diff && !data.diff {
+            // The frames themselves are still around, only the heatmap goes
+            self.diff_frame = None;
+        }
+        if data.diff != old_data.diff {
+            ctx.request_paint();
+        }
+        if old_data.suspended && !data.suspended {
+            self.skip_interval = true;
+            ctx.request_anim_frame();
+        }
     }
 
     fn layout(&mut self, _ctx: &mut LayoutCtx, bc: &BoxConstraints, data: &ViewData, _env: &Env) -> Size {
         bc.debug_check("Image");
         let size = match self.image_size {
-            Some(size) => size * data.zoom.scale_factor(),
+            Some(size) => size * self.scale_factor(data),
             None => {
                 self.need_legit_layout = true;
-                Size::new(100.0, 100.0) * data.zoom.scale_factor()
+                Size::new(100.0, 100.0) * self.scale_factor(data)
             }
         };
         // TODO: Should we ignore constraints to be able to return a non-integer HiDPI-aware size?
-        bc.constrain(size)
+        self.content_size = bc.constrain(size);
+        // A rotated view takes up the box around it, which may reach past the constraints
+        rotated_bounds(self.content_size, data.rotation)
     }
 
-    fn paint(&mut self, ctx: &mut PaintCtx, data: &ViewData, _env: &Env) {
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &ViewData, env: &Env) {
+        let transform = self.rotation_transform(data);
+        ctx.with_save(|ctx| {
+            ctx.render_ctx.transform(transform);
+            self.paint_content(ctx, data, env);
+        });
+    }
+}
+
+impl View {
+    /// Paint the view as if it weren't rotated.
+    fn paint_content(&mut self, ctx: &mut PaintCtx, data: &ViewData, env: &Env) {
         // TODO: Implement fancier resizing and cache the frames for recent scale factors.
         //       Think about scaling quality+speed here .. do we want to source from an already-scaled cached image instead?
 
-        let src_rect = self.image_size.unwrap_or_default().to_rect();
-        let dst_rect = ctx.size().to_rect();
+        let dst_rect = self.content_size.to_rect();
+        self.color_key = data.color_key;
+        if self.image_size.is_some() {
+            // Frames that are already uploaded follow along one at a time, as they come up to be shown.
+            // The view may only show part of the image, so its size doesn't tell the scale.
+            let display_scale = self.scale_factor(data) * ctx.scale().x();
+            self.resolution = upload_resolution(display_scale);
+        }
+        // The view may be too small for the whole image, in which case only the part at the offset is shown
+        let crop = self.crop(data, dst_rect.size());
+        // Turning by anything but quarter turns puts the pixels off the grid, where only bilinear sampling looks right
+        let interpolation = if data.smooth || !is_quarter_turn(data.rotation) {
+            InterpolationMode::Bilinear
+        } else {
+            InterpolationMode::NearestNeighbor
+        };
+
+        if data.checkerboard && !self.frames.is_empty() {
+            paint_checkerboard(ctx, dst_rect, env);
+        }
 
-        if self.current_delay > 0 {
+        if data.diff && data.paused && self.frames.len() > 1 {
+            if let Some(img) = self.current_diff(ctx) {
+                img.draw(ctx, crop, dst_rect, interpolation);
+            }
+        } else if self.current_delay > 0 || data.paused {
             // Still more waiting to do, just paint the current frame
             if let Some(img) = self.current_frame(ctx) {
-                ctx.render_ctx
-                    .draw_image_area(img, src_rect, dst_rect, InterpolationMode::Bilinear);
+                img.draw(ctx, crop, dst_rect, interpolation);
             }
         } else {
             // Paint until there's a delay specified
             let start_frame = self.current_frame;
+            let mut advanced = 0;
             while self.current_delay <= 0 {
+                advanced += 1;
                 // Paint the next frame
                 if let Some(img) = self.next_frame(ctx) {
-                    ctx.render_ctx
-                        .draw_image_area(img, src_rect, dst_rect, InterpolationMode::Bilinear);
+                    img.draw(ctx, crop, dst_rect, interpolation);
                 }
+                self.record_timing();
                 // Detect infinite loops due to GIFs with only 0-delay frames
                 if self.current_frame == start_frame {
                     break;
                 }
             }
+            // Having to skip ahead more than one frame means we were late
+            if advanced > 1 && self.frames.len() > 1 {
+                record_late_frame();
+            }
+        }
+
+        // Explain why there's nothing to see, or why there's less than the whole image
+        if let Some(failure) = &self.failure {
+            let layout = ctx
+                .text()
+                .new_text_layout(failure.clone())
+                .font(FontFamily::SYSTEM_UI, 12.0)
+                .text_color(env.get(theme::ERROR_TEXT))
+                .max_width(dst_rect.width())
+                .build()
+                .unwrap();
+            if self.frames.is_empty() {
+                ctx.render_ctx.fill(dst_rect, &env.get(theme::PANEL_BACKGROUND));
+                let origin = dst_rect.center() - layout.size().to_vec2() / 2.0;
+                ctx.render_ctx.draw_text(&layout, origin);
+            } else {
+                // A strip over the bottom edge, leaving the frames that did decode visible
+                let height = layout.size().height.min(dst_rect.height());
+                let strip = Rect::new(dst_rect.x0, dst_rect.y1 - height, dst_rect.x1, dst_rect.y1);
+                ctx.render_ctx
+                    .fill(strip, &env.get(theme::PANEL_BACKGROUND).with_alpha(0.8));
+                let origin = Point::new(strip.center().x - layout.size().width / 2.0, strip.y0);
+                ctx.with_save(|ctx| {
+                    ctx.clip(strip);
+                    ctx.render_ctx.draw_text(&layout, origin);
+                });
+            }
+        }
+
+        // Briefly flash the view after a reload or similar
+        if let Some(flash_start) = self.flash_start {
+            let elapsed = flash_start.elapsed();
+            if elapsed < View::FLASH_DURATION {
+                let alpha = 1.0 - elapsed.as_secs_f64() / View::FLASH_DURATION.as_secs_f64();
+                ctx.render_ctx
+                    .fill(dst_rect, &env.get(theme::FLASH).with_alpha(0.5 * alpha));
+            } else {
+                self.flash_start = None;
+            }
         }
 
         // If active, paint a border on top of the edge of the image
         // TODO: What if it's a 1px image?
         if data.selected {
-            let brush = ctx.render_ctx.solid_brush(Color::rgb8(245, 132, 66));
+            let brush = ctx.render_ctx.solid_brush(env.get(theme::SELECTION));
             let stroke_width = 1.0;
 
             // TODO: Double check the pixel perfect nature of this after HiDPI awareness is implemented
             let stroke_rect = dst_rect.inset(-stroke_width / 2.0);
             ctx.render_ctx.stroke(stroke_rect, &brush, stroke_width);
         }
+
+        // Mark pinned views with a small pin in the top right corner
+        if data.pinned {
+            let color = env.get(theme::SELECTION);
+            let head = Point::new(dst_rect.x1 - 8.0, dst_rect.y0 + 8.0);
+            let needle = Line::new(head, head + Vec2::new(-6.0, 6.0));
+            ctx.render_ctx.stroke(needle, &color, 1.5);
+            ctx.render_ctx.fill(Circle::new(head, 3.5), &color);
+        }
+
+        // Warn about a missing or changed file with a small triangle in the top left corner
+        if data.source != SourceStatus::Fresh {
+            let color = match data.source {
+                SourceStatus::Missing => env.get(theme::TOAST_ERROR),
+                _ => env.get(theme::TOAST_WARNING),
+            };
+            let mut triangle = BezPath::new();
+            triangle.move_to((dst_rect.x0 + 10.0, dst_rect.y0 + 3.0));
+            triangle.line_to((dst_rect.x0 + 17.0, dst_rect.y0 + 15.0));
+            triangle.line_to((dst_rect.x0 + 3.0, dst_rect.y0 + 15.0));
+            triangle.close_path();
+            ctx.render_ctx.fill(&triangle, &color);
+            let mark = Line::new(
+                (dst_rect.x0 + 10.0, dst_rect.y0 + 7.0),
+                (dst_rect.x0 + 10.0, dst_rect.y0 + 11.0),
+            );
+            ctx.render_ctx.stroke(mark, &env.get(theme::TOAST_TEXT), 1.5);
+            ctx.render_ctx.fill(
+                Circle::new((dst_rect.x0 + 10.0, dst_rect.y0 + 13.0), 0.75),
+                &env.get(theme::TOAST_TEXT),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An image where every pixel is different, so that any misplaced pixel shows up.
+    fn numbered(width: usize, height: usize) -> ImgVec<RGBA8> {
+        let buf = (0..width * height)
+            .map(|i| RGBA8::new((i % 256) as u8, (i / 256) as u8, 0, 255))
+            .collect();
+        ImgVec::new(buf, width, height)
+    }
+
+    /// What painting the `crop` of `pixels` into `dst_size` with nearest neighbor sampling shows.
+    fn paint_nearest(pixels: &ImgVec<RGBA8>, crop: Rect, dst_size: (usize, usize)) -> ImgVec<RGBA8> {
+        let src = crop_of(Size::new(pixels.width() as f64, pixels.height() as f64), crop);
+        let mut buf = Vec::with_capacity(dst_size.0 * dst_size.1);
+        for y in 0..dst_size.1 {
+            for x in 0..dst_size.0 {
+                let src_x = src.x0 + (x as f64 + 0.5) * src.width() / dst_size.0 as f64;
+                let src_y = src.y0 + (y as f64 + 0.5) * src.height() / dst_size.1 as f64;
+                buf.push(pixels[(src_x.floor() as usize, src_y.floor() as usize)]);
+            }
+        }
+        ImgVec::new(buf, dst_size.0, dst_size.1)
+    }
+
+    #[test]
+    fn export_matches_paint() {
+        let pixels = numbered(40, 30);
+        let image_size = Size::new(40.0, 30.0);
+        for &(scale, offset) in &[
+            (1.0, Point::new(0.0, 0.0)),
+            (1.0, Point::new(7.4, 3.6)),
+            (2.0, Point::new(12.5, 0.49)),
+            (3.0, Point::new(33.0, 25.0)),
+        ] {
+            let view_size = Size::new(18.0, 12.0);
+            let crop = shown_part(image_size, view_size, scale, offset);
+            let exported = crop_pixels(&pixels, crop);
+            let painted = paint_nearest(&pixels, crop, (18, 12));
+            for y in 0..painted.height() {
+                for x in 0..painted.width() {
+                    let native = (
+                        x * exported.width() / painted.width(),
+                        y * exported.height() / painted.height(),
+                    );
+                    assert_eq!(
+                        painted[(x, y)],
+                        exported[native],
+                        "at {}, {} with scale {}",
+                        x,
+                        y,
+                        scale
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn quarter_turns_stay_on_the_pixel_grid() {
+        let size = Size::new(40.0, 30.0);
+        for &(degrees, top_left, bounds) in &[
+            (0.0, Point::new(0.0, 0.0), Size::new(40.0, 30.0)),
+            (90.0, Point::new(30.0, 0.0), Size::new(30.0, 40.0)),
+            (180.0, Point::new(40.0, 30.0), Size::new(40.0, 30.0)),
+            (-90.0, Point::new(0.0, 40.0), Size::new(30.0, 40.0)),
+        ] {
+            assert_eq!(rotated_bounds(size, degrees), bounds);
+            let transform = rotation_transform(size, degrees);
+            assert_eq!(transform * Point::ZERO, top_left, "at {} degrees", degrees);
+            assert_eq!(transform.inverse() * top_left, Point::ZERO);
+        }
+    }
+
+    #[test]
+    fn rotation_stays_within_the_bounds() {
+        let size = Size::new(40.0, 30.0);
+        let bounds = rotated_bounds(size, 30.0).to_rect().inflate(1e-9, 1e-9);
+        let transform = rotation_transform(size, 30.0);
+        for corner in &[(0.0, 0.0), (40.0, 0.0), (0.0, 30.0), (40.0, 30.0)] {
+            assert!(bounds.contains(transform * Point::new(corner.0, corner.1)));
+        }
+        assert!((transform * Point::new(20.0, 15.0) - bounds.center()).hypot() < 1e-9);
+    }
+
+    #[test]
+    fn proxies_fit_the_limits() {
+        let huge = Size::new(20000.0, 20000.0);
+        let proxy = proxy_scale(huge, 64e6);
+        assert!((proxied(huge, proxy).area() - 64e6).abs() < 20000.0);
+        // Panoramas are tiled, so only the pixel count matters
+        let panorama = Size::new(40000.0, 1000.0);
+        assert_eq!(proxied(panorama, proxy_scale(panorama, 64e6)), panorama);
+        assert_eq!(proxy_scale(Size::new(4000.0, 3000.0), 64e6), 1.0);
+    }
+
+    #[test]
+    fn tiles_cover_the_frame() {
+        assert_eq!(tile_spans(100, 100), vec![(0, 100)]);
+        assert_eq!(tile_spans(101, 100), vec![(0, 50), (50, 101)]);
+        let tiles = tile_grid(250, 120, 100);
+        assert_eq!(tiles.len(), 6);
+        assert!(tiles.iter().all(|tile| tile.width() <= 100.0 && tile.height() <= 100.0));
+        assert_eq!(tiles.iter().map(|tile| tile.area()).sum::<f64>(), 250.0 * 120.0);
+    }
+
+    #[test]
+    fn tile_seams_meet_exactly() {
+        let tiles = tile_grid(1000, 700, 128);
+        // A crop that starts and ends between pixels, shown at a scale that doesn't divide evenly
+        let src = Rect::new(301.3, 57.9, 713.7, 655.1);
+        let dst = Rect::new(3.25, 7.5, 3.25 + src.width() * 0.37, 7.5 + src.height() * 1.73);
+        let draws = tile_draws(&tiles, src, dst);
+        // Only the tiles that the crop reaches are drawn
+        assert!(draws.len() < tiles.len());
+        for &(index, part, _) in &draws {
+            assert_eq!(tiles[index].intersect(part), part);
+        }
+        // Every seam is at exactly the same place for the tiles on both sides of it
+        let edges = |edge: fn(&Rect) -> f64, outer: f64| {
+            let mut edges: Vec<u64> = draws
+                .iter()
+                .map(|(_, _, to)| edge(to))
+                .filter(|&value| value != outer)
+                .map(f64::to_bits)
+                .collect();
+            edges.sort();
+            edges.dedup();
+            edges
+        };
+        assert_eq!(edges(|to| to.x0, dst.x0), edges(|to| to.x1, dst.x1));
+        assert_eq!(edges(|to| to.y0, dst.y0), edges(|to| to.y1, dst.y1));
+        for (i, (_, _, a)) in draws.iter().enumerate() {
+            for (_, _, b) in &draws[i + 1..] {
+                assert_eq!(a.intersect(*b).area(), 0.0);
+            }
+        }
+        let covered: f64 = draws.iter().map(|(_, _, to)| to.area()).sum();
+        assert!((covered - dst.area()).abs() < 1e-6);
+        let bounds = draws.iter().fold(
+            Rect::ZERO,
+            |bounds, (_, _, to)| {
+                if bounds == Rect::ZERO {
+                    *to
+                } else {
+                    bounds.union(*to)
+                }
+            },
+        );
+        assert_eq!(bounds, dst);
+    }
+
+    #[test]
+    fn crop_keeps_whole_pixels() {
+        let crop = shown_part(
+            Size::new(40.0, 30.0),
+            Size::new(18.0, 12.0),
+            2.0,
+            Point::new(12.5, 0.49),
+        );
+        let exported = crop_pixels(&numbered(40, 30), crop);
+        assert_eq!((exported.width(), exported.height()), (9, 6));
+        assert_eq!(exported[(0, 0)], numbered(40, 30)[(13, 0)]);
     }
 }
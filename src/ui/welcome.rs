@@ -0,0 +1,73 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use druid::widget::{Button, CrossAxisAlignment, Flex, Label};
+use druid::{Selector, Target, Widget, WidgetExt};
+
+use crate::i18n::{tr, tr_args};
+use crate::settings;
+use crate::ui::{theme, COMMAND_NEW_PROJECT, COMMAND_OPEN_PROJECT, COMMAND_SHOW_ADD_IMAGES, COMMAND_SHOW_OPEN_PROJECT};
+
+/// The hint panel that the surface shows while the project has no images.
+pub fn welcome() -> impl Widget<u64> {
+    let mut buttons = Flex::row();
+    // Offer to continue where the last session left off
+    if let Some(path) = settings::current().last_session() {
+        let label = if settings::is_scratch(path) {
+            tr("welcome-reopen-session")
+        } else {
+            let name = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+            tr_args("welcome-reopen", &[("name", name.into())])
+        };
+        let path = path.to_path_buf();
+        buttons.add_child(Button::new(label).on_click(move |ctx, _data, _env| {
+            ctx.submit_command(
+                COMMAND_OPEN_PROJECT
+                    .with(path.clone())
+                    .to(Target::Window(ctx.window_id())),
+            );
+        }));
+        buttons.add_spacer(8.0);
+    }
+    let buttons = buttons
+        .with_child(button(tr("welcome-add-images"), COMMAND_SHOW_ADD_IMAGES))
+        .with_spacer(8.0)
+        .with_child(button(tr("welcome-open-project"), COMMAND_SHOW_OPEN_PROJECT))
+        .with_spacer(8.0)
+        .with_child(button(tr("welcome-new-project"), COMMAND_NEW_PROJECT));
+    let mut col = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Center)
+        .with_child(Label::new(tr("welcome-hint")).with_text_color(theme::PANEL_TEXT));
+    // Explain why nothing moves, as the setting is easy to forget about
+    if settings::current().start_paused {
+        col.add_spacer(4.0);
+        col.add_child(Label::new(tr("welcome-start-paused")).with_text_color(theme::PANEL_TEXT));
+    }
+    col.with_spacer(12.0)
+        .with_child(buttons)
+        .padding(24.0)
+        .background(theme::PANEL_BACKGROUND)
+        .rounded(8.0)
+}
+
+fn button(label: String, selector: Selector) -> impl Widget<u64> {
+    Button::new(label).on_click(move |ctx, _data, _env| {
+        ctx.submit_command(selector.to(Target::Window(ctx.window_id())));
+    })
+}
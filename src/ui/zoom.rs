@@ -17,42 +17,201 @@
     along with this program.  If not, see <http://www.gnu.org/licenses/>.
 */
 
+use std::sync::Mutex;
+
 use druid::Data;
 use serde::{Deserialize, Serialize};
 
+/// The smallest scale factor, unless the settings say otherwise.
+pub const MIN_SCALE_FACTOR: f64 = 0.1;
+/// The largest scale factor, unless the settings say otherwise.
+pub const MAX_SCALE_FACTOR: f64 = 32.0;
+
+/// How much each turn of the knob scales by.
+const STEP: f64 = 1.1;
+
+static LIMITS: Mutex<(f64, f64)> = Mutex::new((MIN_SCALE_FACTOR, MAX_SCALE_FACTOR));
+
+/// Set the smallest and the largest scale factor. Limits that would exclude 1.0 fall back to the defaults.
+pub fn set_zoom_limits(min: f64, max: f64) {
+    let min = if min > 0.0 && min <= 1.0 { min } else { MIN_SCALE_FACTOR };
+    let max = if max >= 1.0 && max.is_finite() {
+        max
+    } else {
+        MAX_SCALE_FACTOR
+    };
+    *LIMITS.lock().unwrap() = (min, max);
+}
+
+/// The smallest and the largest scale factor.
+pub fn zoom_limits() -> (f64, f64) {
+    *LIMITS.lock().unwrap()
+}
+
 #[derive(Default, Data, Copy, Clone, Serialize, Deserialize)]
 pub struct Zoom {
     knob: i32, // 0 means no zoom, negative zooms out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    exact: Option<f64>, // A scale factor between the knob steps, until the knob is turned
 }
 
 impl PartialEq for Zoom {
     fn eq(&self, other: &Self) -> bool {
-        self.knob == other.knob
+        self.knob == other.knob && self.exact == other.exact
     }
 }
 
 impl Zoom {
     pub fn scale_factor(&self) -> f64 {
-        if self.knob < 0 {
-            let mut scale = 1.1f64.powi(self.knob);
-            if scale < 0.1 {
-                scale = 0.1
+        let (min, max) = zoom_limits();
+        self.scale_factor_within(min, max)
+    }
+
+    fn scale_factor_within(&self, min: f64, max: f64) -> f64 {
+        let scale = match self.exact {
+            Some(exact) => exact,
+            None => STEP.powi(self.knob),
+        };
+        scale.max(min).min(max)
+    }
+
+    /// The zoom with the scale factor closest to `scale`.
+    pub fn from_scale_factor(scale: f64) -> Zoom {
+        let (min, max) = zoom_limits();
+        let (min_knob, max_knob) = knob_range(min, max);
+        let knob = if scale > 0.0 {
+            (scale.ln() / STEP.ln()).round() as i32
+        } else {
+            0
+        };
+        Zoom {
+            knob: knob.max(min_knob).min(max_knob),
+            exact: None,
+        }
+    }
+
+    /// The zoom with exactly the scale factor `scale`, even if the knob can't reach it.
+    ///
+    /// The knob is set to the closest step, so turning it continues from there.
+    pub fn exact(scale: f64) -> Zoom {
+        let mut zoom = Zoom::from_scale_factor(scale);
+        if scale > 0.0 && zoom.scale_factor() != scale {
+            zoom.exact = Some(scale);
+        }
+        zoom
+    }
+
+    /// Zoom in by `delta` steps, or out with a negative `delta`.
+    ///
+    /// Returns `true` if the zoom ended up at the limit in the direction of the turn,
+    /// so that the user can be told why it doesn't go any further.
+    pub fn turn_the_knob(&mut self, delta: i32) -> bool {
+        let (min, max) = zoom_limits();
+        self.turn_the_knob_within(delta, min, max)
+    }
+
+    fn turn_the_knob_within(&mut self, delta: i32, min: f64, max: f64) -> bool {
+        let (min_knob, max_knob) = knob_range(min, max);
+        // Knobs from before the limits changed would otherwise take many turns to come back
+        self.knob = self.knob.max(min_knob).min(max_knob);
+        if let Some(exact) = self.exact.take() {
+            // Continue from the closest step in the direction of the turn
+            let step = delta.signum();
+            if (self.scale_factor_within(min, max) - exact) * step as f64 <= 0.0 {
+                self.knob += step;
             }
-            scale
-        } else if self.knob > 0 {
-            1.1f64.powi(self.knob)
+            self.knob = (self.knob + delta - step).max(min_knob).min(max_knob);
         } else {
-            1.0
+            let old_knob = self.knob;
+            let old_scale = self.scale_factor_within(min, max);
+            self.knob = (self.knob + delta).max(min_knob).min(max_knob);
+            // If the scale factor didn't change, revert the zoom change
+            if self.scale_factor_within(min, max) == old_scale {
+                self.knob = old_knob;
+            }
+        }
+        let scale = self.scale_factor_within(min, max);
+        (delta > 0 && scale == max) || (delta < 0 && scale == min)
+    }
+}
+
+/// The knob positions that reach the limits, the first steps at or past them.
+fn knob_range(min: f64, max: f64) -> (i32, i32) {
+    let min_knob = (min.ln() / STEP.ln()).floor() as i32;
+    let max_knob = (max.ln() / STEP.ln()).ceil() as i32;
+    (min_knob, max_knob)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIN: f64 = MIN_SCALE_FACTOR;
+    const MAX: f64 = MAX_SCALE_FACTOR;
+
+    fn knob(knob: i32) -> Zoom {
+        Zoom { knob, exact: None }
+    }
+
+    #[test]
+    fn clamped_at_both_ends() {
+        assert_eq!(knob(0).scale_factor_within(MIN, MAX), 1.0);
+        assert_eq!(knob(-1000).scale_factor_within(MIN, MAX), MIN);
+        assert_eq!(knob(1000).scale_factor_within(MIN, MAX), MAX);
+        let exact = Zoom {
+            knob: 0,
+            exact: Some(1000.0),
+        };
+        assert_eq!(exact.scale_factor_within(MIN, MAX), MAX);
+    }
+
+    #[test]
+    fn turning_reports_the_limits() {
+        let mut zoom = knob(0);
+        assert!(!zoom.turn_the_knob_within(1, MIN, MAX));
+        assert!(!zoom.turn_the_knob_within(-2, MIN, MAX));
+        let mut turns = 0;
+        while !zoom.turn_the_knob_within(1, MIN, MAX) {
+            turns += 1;
+            assert!(turns < 100);
+        }
+        assert_eq!(zoom.scale_factor_within(MIN, MAX), MAX);
+        while !zoom.turn_the_knob_within(-1, MIN, MAX) {
+            turns += 1;
+            assert!(turns < 200);
         }
+        assert_eq!(zoom.scale_factor_within(MIN, MAX), MIN);
     }
 
-    pub fn turn_the_knob(&mut self, delta: i32) {
-        let old_knob = self.knob;
-        let old_scale = self.scale_factor();
-        self.knob = self.knob + delta;
-        // If the scale factor didn't change, revert the zoom change
-        if self.scale_factor() == old_scale {
-            self.knob = old_knob;
+    #[test]
+    fn no_change_at_the_limits() {
+        for &(start, delta, limit) in [(-1000, -1, MIN), (1000, 1, MAX)].iter() {
+            let mut zoom = knob(start);
+            zoom.turn_the_knob_within(delta, MIN, MAX);
+            assert_eq!(zoom.scale_factor_within(MIN, MAX), limit);
+            let at_limit = zoom;
+            // Pushing against the limit changes nothing, so turning back responds right away
+            assert!(zoom.turn_the_knob_within(delta, MIN, MAX));
+            assert!(zoom == at_limit);
+            zoom.turn_the_knob_within(-delta, MIN, MAX);
+            assert!(zoom.scale_factor_within(MIN, MAX) != limit);
         }
     }
+
+    #[test]
+    fn exact_continues_from_the_closest_step() {
+        let mut zoom = Zoom {
+            knob: 7,
+            exact: Some(2.0),
+        };
+        assert!(!zoom.turn_the_knob_within(1, MIN, MAX));
+        assert_eq!(zoom.exact, None);
+        assert!(zoom.scale_factor_within(MIN, MAX) > 2.0);
+        let mut zoom = Zoom {
+            knob: 1000,
+            exact: Some(1000.0),
+        };
+        assert!(zoom.turn_the_knob_within(1, MIN, MAX));
+        assert_eq!(zoom.scale_factor_within(MIN, MAX), MAX);
+    }
 }
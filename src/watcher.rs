@@ -0,0 +1,220 @@
+/*
+    Copyright 2022 Kaur Kuut <admin@kaurkuut.com>
+
+    This file is part of Slark.
+
+    Slark is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as
+    published by the Free Software Foundation, either version 3 of the
+    License, or (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::channel;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use druid::{Data, ExtEventSink, Selector, Target};
+use log::error;
+use notify::{watcher, DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+
+/// Submitted with the canonical path of a watched file that has changed on disk.
+pub const COMMAND_FILE_CHANGED: Selector<PathBuf> = Selector::new("slark.file_changed");
+/// Submitted by `check_sources` with the status of every file it was asked about, next to the load time it was compared to.
+pub const COMMAND_SOURCES_CHECKED: Selector<Vec<(PathBuf, Option<SystemTime>, SourceStatus)>> =
+    Selector::new("slark.sources_checked");
+
+/// How a source file compares to what was loaded from it.
+#[derive(Data, Clone, Copy, Debug, PartialEq)]
+pub enum SourceStatus {
+    Fresh,
+    Missing,
+    Stale, // modified since it was loaded, without the watcher noticing
+}
+
+/// Watches image files for changes.
+///
+/// The parent directories get watched instead of the files themselves,
+/// so that files replaced via rename are still tracked and no file handles are kept open.
+pub struct FileWatcher {
+    watcher: Option<RecommendedWatcher>,
+    files: HashMap<PathBuf, usize>,       // watched file -> reference count
+    directories: HashMap<PathBuf, usize>, // watched directory -> reference count
+    shared_files: Arc<Mutex<HashSet<PathBuf>>>,
+}
+
+impl FileWatcher {
+    pub fn new(event_sink: ExtEventSink, target: Target) -> FileWatcher {
+        let (sender, receiver) = channel();
+        // Debouncing makes sure we don't start decoding a file that is still being written
+        let watcher = match watcher(sender, Duration::from_secs(1)) {
+            Ok(watcher) => Some(watcher),
+            Err(error) => {
+                error!("Failed to create a file watcher: {}", error);
+                None
+            }
+        };
+        let shared_files = Arc::new(Mutex::new(HashSet::new()));
+
+        let thread_files = shared_files.clone();
+        thread::spawn(move || {
+            // The loop ends when the watcher gets dropped
+            for event in receiver {
+                let path = match event {
+                    DebouncedEvent::Write(path) | DebouncedEvent::Create(path) | DebouncedEvent::Rename(_, path) => {
+                        path
+                    }
+                    _ => continue,
+                };
+                if thread_files.lock().unwrap().contains(&path) {
+                    if let Err(error) = event_sink.submit_command(COMMAND_FILE_CHANGED, path, target) {
+                        error!("Failed to submit file change: {}", error);
+                        break;
+                    }
+                }
+            }
+        });
+
+        FileWatcher {
+            watcher,
+            files: HashMap::new(),
+            directories: HashMap::new(),
+            shared_files,
+        }
+    }
+
+    pub fn watch(&mut self, path: &Path) {
+        let path = canonical(path);
+        let count = self.files.entry(path.clone()).or_insert(0);
+        *count += 1;
+        if *count > 1 {
+            return;
+        }
+        self.shared_files.lock().unwrap().insert(path.clone());
+
+        if let Some(dir) = path.parent() {
+            let count = self.directories.entry(dir.to_path_buf()).or_insert(0);
+            *count += 1;
+            if *count == 1 {
+                if let Some(watcher) = &mut self.watcher {
+                    if let Err(error) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+                        error!("Failed to watch {}: {}", dir.display(), error);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn unwatch(&mut self, path: &Path) {
+        let path = canonical(path);
+        match self.files.get_mut(&path) {
+            Some(count) if *count > 1 => {
+                *count -= 1;
+                return;
+            }
+            Some(_) => {
+                self.files.remove(&path);
+            }
+            None => return,
+        }
+        self.shared_files.lock().unwrap().remove(&path);
+
+        if let Some(dir) = path.parent() {
+            if let Some(count) = self.directories.get_mut(dir) {
+                *count -= 1;
+                if *count == 0 {
+                    self.directories.remove(dir);
+                    if let Some(watcher) = &mut self.watcher {
+                        // The directory may be gone already, in which case there's nothing to unwatch
+                        let _ = watcher.unwatch(dir);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        if let Some(watcher) = &mut self.watcher {
+            for dir in self.directories.keys() {
+                let _ = watcher.unwatch(dir);
+            }
+        }
+        self.files.clear();
+        self.directories.clear();
+        self.shared_files.lock().unwrap().clear();
+    }
+}
+
+/// When the file at `path` was last modified, if it exists and the file system tells.
+pub fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Compare the file at `path` with the version that was last modified at `loaded`.
+pub fn source_status(path: &Path, loaded: Option<SystemTime>) -> SourceStatus {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) if metadata.is_file() => metadata,
+        _ => return SourceStatus::Missing,
+    };
+    match (metadata.modified().ok(), loaded) {
+        (Some(modified), Some(loaded)) if modified != loaded => SourceStatus::Stale,
+        _ => SourceStatus::Fresh,
+    }
+}
+
+/// Check the `sources`, each with the modification time it was loaded at, on a thread of its own.
+///
+/// Files on a network drive can take a long time to answer, which only delays the findings.
+pub fn check_sources(sources: Vec<(PathBuf, Option<SystemTime>)>, event_sink: ExtEventSink, target: Target) {
+    thread::spawn(move || {
+        let findings = sources
+            .into_iter()
+            .map(|(path, loaded)| {
+                let status = source_status(&path, loaded);
+                (path, loaded, status)
+            })
+            .collect();
+        // The window may have been closed in the meantime, which is fine
+        let _ = event_sink.submit_command(COMMAND_SOURCES_CHECKED, findings, target);
+    });
+}
+
+/// The canonical form of `path`, or `path` itself if it can't be canonicalized.
+pub fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sources_are_compared_to_what_was_loaded() {
+        let dir = std::env::temp_dir().join(format!("slark-source-status-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("a.png");
+        fs::write(&path, b"pixels").unwrap();
+        let loaded = modified(&path);
+        assert!(loaded.is_some());
+        assert_eq!(source_status(&path, loaded), SourceStatus::Fresh);
+        // Without a known load time there's nothing to be stale against
+        assert_eq!(source_status(&path, None), SourceStatus::Fresh);
+        let earlier = loaded.unwrap() - Duration::from_secs(60);
+        assert_eq!(source_status(&path, Some(earlier)), SourceStatus::Stale);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(source_status(&path, loaded), SourceStatus::Missing);
+        assert_eq!(source_status(&dir, None), SourceStatus::Missing);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}